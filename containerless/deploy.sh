@@ -11,9 +11,8 @@ export CONTROLLER_LOG_PATH=$(dirname $(realpath $0))
 export DATABASE_PORT=7998
 
 echo "Clearing system state..."
-cp "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/template.txt" "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/mod.rs"
-rm "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/*.json" 2> /dev/null || true
-rm "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/*.rs" 2> /dev/null || true
+rm -rf "${RUST_SRC_PATH}"/decontainerized-functions/function_*
+rm -rf "${RUST_SRC_PATH}/target/decontainerized"
 
 echo "Deploying on k8s..."
 envsubst < containerless.yaml | microk8s.kubectl apply -f -
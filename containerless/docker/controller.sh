@@ -18,7 +18,7 @@ stop)
   killall controller-agent
 ;;
 clear)
-  git checkout ../rust/dispatcher-agent/src/decontainerized_functions/mod.rs
+  rm -rf ../rust/decontainerized-functions/function_* ../rust/target/decontainerized
 ;;
 *)
   echo "Usage:"
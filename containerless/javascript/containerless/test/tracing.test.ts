@@ -1,11 +1,15 @@
 import {
     while_, break_, label, block, let_, set, number, if_, callback,
-    identifier, string, binop, unknown, undefined_, clos, from, array, index
+    identifier, string, binop, unknown, undefined_, clos, from, array, index,
+    throw_, try_, located, lambda, call, get, obj, methodCall
 } from '../ts/exp';
 import {
     newTrace
 } from '../ts/tracing'
 import { Callbacks } from '../ts/callbacks';
+import * as state from '../ts/state';
+import * as fs from 'fs';
+import * as path from 'path';
 
 test('trivial, hand-constructed trace', () => {
     let t = newTrace();
@@ -832,4 +836,226 @@ test('arrays_1', () => {
                 index(identifier('arr'), number(1))
             )
         ]));
-});
\ No newline at end of file
+});
+
+test('throw caught by a catch clause', () => {
+    let t = newTrace();
+
+    t.traceTry('e', true);
+    try {
+        t.traceLet('x', number(1));
+        let x = 1;
+        let $cond = binop('>', identifier('x'), number(0));
+        if (x > 0) {
+            t.traceIfTrue($cond);
+            t.traceThrow(string('oops'));
+            throw 'oops';
+        } else {
+            t.traceIfFalse($cond);
+        }
+        t.exitBlock();
+        t.exitBlock();
+    } catch (e) {
+        t.traceCatch();
+        t.traceLet('y', identifier('e'));
+        let y = e;
+        t.exitBlock();
+    } finally {
+        t.traceFinally();
+        t.exitBlock();
+    }
+
+    t.traceLet('z', number(2));
+    let z = 2;
+    t.exitBlock();
+
+    expect(t.getTrace()).toMatchObject(block([
+        try_([
+            let_('x', number(1)),
+            if_(binop('>', identifier('x'), number(0)),
+                [throw_(string('oops'))],
+                [unknown()]),
+            unknown()
+        ], 'e', [let_('y', identifier('e'))], []),
+        let_('z', number(2))]));
+});
+
+test('re-tracing: try that completes, then throws', () => {
+    let t = newTrace();
+
+    function F(x: number) {
+        t.traceTry(undefined, true);
+        try {
+            let $cond = binop('>', identifier('x'), number(0));
+            if (x > 0) {
+                t.traceIfTrue($cond);
+                t.traceThrow(string('oops'));
+                throw 'oops';
+            } else {
+                t.traceIfFalse($cond);
+            }
+            t.exitBlock();
+            t.traceLet('y', number(1));
+            let y = 1;
+            t.exitBlock();
+        } catch {
+            t.traceCatch();
+            t.traceLet('y', number(2));
+            let y = 2;
+            t.exitBlock();
+        } finally {
+            t.traceFinally();
+            t.exitBlock();
+        }
+        t.exitBlock();
+    }
+
+    t.traceLet('x', number(0));
+    F(0);
+    t.newTrace();
+    t.traceLet('x', number(0));
+    F(1);
+
+    expect(t.getTrace()).toMatchObject(block([
+        let_('x', number(0)),
+        try_([
+            if_(binop('>', identifier('x'), number(0)),
+                [throw_(string('oops'))],
+                []),
+            let_('y', number(1))
+        ], undefined, [let_('y', number(2))], [])]));
+});
+
+test('break through a finally clause', () => {
+    let t = newTrace();
+
+    t.traceLabel('l');
+    l: {
+        t.traceTry(undefined, false);
+        try {
+            t.traceBreak('l', undefined_);
+            break l;
+            t.exitBlock();
+        } finally {
+            t.traceFinally();
+            t.traceLet('z', number(2));
+            let z = 2;
+            t.exitBlock();
+        }
+        t.exitBlock();
+    }
+
+    t.exitBlock();
+
+    expect(t.getTrace()).toMatchObject(block([
+        label('l', [
+            try_([break_('l', undefined_)], undefined, undefined, [
+                let_('z', number(2))]),
+            unknown()])]));
+});
+
+test('re-tracing: located expressions', () => {
+    let t = newTrace();
+    let loc = { file: 'index.js', line: 3, column: 9 };
+    for (let i = 0; i < 2; i++) {
+        t.newTrace();
+        t.traceLet('x', number(1));
+        t.traceLet('y', located(loc, binop('+', identifier('x'), number(1))));
+        t.exitBlock();
+    }
+
+    expect(t.getTrace()).toMatchObject(block([
+        let_('x', number(1)),
+        let_('y', located(loc, binop('+', identifier('x'), number(1))))]));
+});
+
+/**
+ * The trace of the following program, instrumented by hand as insertTracing
+ * would instrument it, must be traces/lambdas.json, which the trace compiler
+ * tests compile.
+ *
+ * ```
+ * let containerless = require('containerless');
+ * function fun0(req) {
+ *     let xs = req.body.xs;
+ *     let k = 2;
+ *     function scale(x) {
+ *         return x * k;
+ *     }
+ *     function add(acc, y) {
+ *         return acc + y;
+ *     }
+ *     let ys = xs.map(scale);
+ *     let sum = ys.reduce(add, 0);
+ *     let ten = scale(5);
+ *     let resp = { ys: ys, sum: sum, ten: ten };
+ *     let r = containerless.respond(resp);
+ * }
+ * let app0 = containerless.listen(fun0);
+ * ```
+ */
+test('lambdas.json: functions passed to higher-order methods', () => {
+    state.setListenPort('test');
+    let containerless = new Callbacks();
+    let cb = containerless;
+    function at(line: number, column: number) {
+        return { file: 'index.js', line, column };
+    }
+
+    cb.trace.traceLet('fun0', clos({}));
+    function fun0(req: any) {
+        let [$clos, $req] = cb.trace.traceFunctionBody('\'ret');
+        cb.trace.traceLet('req', $req);
+
+        cb.trace.traceLet('xs', located(at(3, 14), get(get(identifier('req'), 'body'), 'xs')));
+        let xs = req.body.xs;
+        cb.trace.traceLet('k', number(2));
+        let k = 2;
+        let scale = cb.lambda('scale', clos({ k: identifier('k') }), function(x: number) {
+            let [$clos, $x] = cb.trace.traceFunctionBody('\'ret');
+            cb.trace.traceLet('x', $x);
+            cb.trace.traceBreak('\'ret', binop('*', identifier('x'), from($clos, 'k')));
+            return x * k;
+            cb.trace.exitBlock();
+        });
+        let add = cb.lambda('add', clos({}), function(acc: number, y: number) {
+            let [$clos, $acc, $y] = cb.trace.traceFunctionBody('\'ret');
+            cb.trace.traceLet('acc', $acc);
+            cb.trace.traceLet('y', $y);
+            cb.trace.traceBreak('\'ret', binop('+', identifier('acc'), identifier('y')));
+            return acc + y;
+            cb.trace.exitBlock();
+        });
+        cb.trace.traceLet('ys', located(at(11, 14), methodCall(identifier('xs'), 'map', [identifier('scale')])));
+        let ys = xs.map(scale);
+        cb.trace.traceLet('sum', located(at(12, 15),
+            methodCall(identifier('ys'), 'reduce', [identifier('add'), number(0)])));
+        let sum = ys.reduce(add, 0);
+        cb.trace.traceLet('ten', located(at(13, 15), call(identifier('scale'), [number(5)])));
+        let ten = scale(5);
+        cb.trace.traceLet('resp', located(at(14, 16),
+            obj({ ys: identifier('ys'), sum: identifier('sum'), ten: identifier('ten') })));
+        let resp = { ys: ys, sum: sum, ten: ten };
+        cb.trace.traceFunctionCall('r', [from(identifier('containerless'), 'respond'), identifier('resp')]);
+        let r = containerless.respond(resp);
+        cb.trace.exitBlock();
+        cb.trace.exitBlock(); // exit the label
+    }
+
+    cb.trace.traceFunctionCall('app0', [from(identifier('containerless'), 'listen'), identifier('fun0')]);
+    let app0 = containerless.tracedListenCallback(fun0);
+    cb.trace.exitBlock();
+    cb.trace.exitBlock(); // end turn
+
+    app0({ path: '', query: {} as any, body: { xs: [1, 2, 3] } as any, requestID: '0' });
+    app0({ path: '', query: {} as any, body: { xs: [] } as any, requestID: '1' });
+
+    let lambdaLet = (cb.trace.getTrace() as any).body[1].named.body[0].body[0].body[3];
+    expect(lambdaLet).toMatchObject(let_('scale', lambda(['clos', 'arg0'], clos({ k: identifier('k') }), [
+        label('\'ret', [
+            let_('x', identifier('arg0')),
+            break_('\'ret', binop('*', identifier('x'), from(identifier('clos'), 'k')))])])));
+
+    let expected = fs.readFileSync(path.join(__dirname, '../traces/lambdas.json'), 'utf8');
+    expect(cb.trace.getTrace()).toEqual(JSON.parse(expected));
+});
@@ -5,7 +5,7 @@ import * as bodyParser from "body-parser";
 import * as state from './state';
 import { newTrace } from './tracing';
 import { newMockTrace } from './mockTracing';
-import { number, identifier, string } from './exp';
+import { number, identifier, string, Exp } from './exp';
 import { TracingInterface } from './types';
 
 const defaultEventArg = number(0);
@@ -39,7 +39,7 @@ export class Callbacks {
      * @param trace Callback trace.
      * @param body Callback body.
      */
-    private withTrace(trace: TracingInterface, responseID: string, body: () => void) {
+    private withTrace<T>(trace: TracingInterface, responseID: string | undefined, body: () => T): T {
         let outerTrace = this.trace;
         let outerResponseID = this.responseID;
         this.trace = trace;
@@ -53,6 +53,32 @@ export class Callbacks {
         return result;
     }
 
+    /**
+     * Traces the declaration of a function that the program passes to a
+     * higher-order method, such as 'map', as a lambda.
+     *
+     * 1. Creates a lambda trace.
+     * 2. Wraps the lambda trace with the function body.
+     * 3. Returns 2 as a function, which the program uses in place of f.
+     *
+     * @param name the name of the function
+     * @param clos the closure of the function
+     * @param f the function
+     */
+    lambda(name: string, clos: Exp, f: (...args: any[]) => any): ((...args: any[]) => any) {
+        let args = ['clos'];
+        for (let i = 0; i < f.length; i++) {
+            args.push(`arg${i}`);
+        }
+        let innerTrace = this.trace.traceLambda(name, args, clos);
+        return (...values: any[]) => {
+            return this.withTrace(innerTrace, this.responseID, () => {
+                innerTrace.pushArgs(args.map(x => identifier(x)));
+                return f(...values);
+            });
+        };
+    }
+
     /**
      * Mock callback function used for testing.
      *
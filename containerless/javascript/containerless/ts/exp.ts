@@ -1,5 +1,7 @@
-export type BinOp = '+' | '-' | '*' | '/' | '===' | '!==' | '>' | '<' | '>=' | '<=' | '&&' | '||';
-export type Op1 = "typeof" | "void" | '-'; // TODO(arjun): More required
+export type BinOp = '+' | '-' | '*' | '/' | '%' | '===' | '!==' | '==' | '!=' |
+    '>' | '<' | '>=' | '<=' | '&&' | '||' | '&' | '|' | '^' | '<<' | '>>' | '>>>' |
+    '??' | 'in';
+export type Op1 = "typeof" | "void" | '-' | '!' | '~';
 
 export type BlockExp = { kind: 'block', body: Exp[] };
 
@@ -20,6 +22,14 @@ type CallbackExp = {
     body: Exp[] // body of the callback
 };
 
+/**
+ * function(args) { body }, which captures the variables in clos. The body
+ * reaches them through the first argument, like the body of a callback.
+ */
+type LambdaExp = { kind: 'lambda', args: string[], clos: Exp, body: Exp[] };
+/** Calls a function that is the value of e. */
+type CallExp = { kind: 'call', e: Exp, callArgs: Exp[] };
+
 type PrimAppExp = {
     kind: 'primApp',
     event: string,
@@ -29,6 +39,29 @@ type PrimAppExp = {
 type LabelExp = { kind: 'label', name: string, body: Exp[] };
 type BreakExp = { kind: 'break', name: string, value: Exp };
 
+type ThrowExp = { kind: 'throw', e: Exp };
+/**
+ * try { body } catch (catchName) { handler } finally { finalizer }
+ *
+ * The handler is absent when there is no catch clause, and catchName is
+ * absent when the catch clause does not bind the exception.
+ */
+export type TryExp = {
+    kind: 'try',
+    body: Exp[],
+    catchName?: string,
+    handler?: Exp[],
+    finalizer: Exp[]
+};
+
+/** A position in the JavaScript source of the function. */
+export type Loc = { file: string, line: number, column: number };
+/**
+ * The expression e, which occurs at loc. Besides expressions, only set, throw,
+ * and primApp statements may be located.
+ */
+type LocatedExp = { kind: 'located', loc: Loc, e: Exp };
+
 type IdExp = { kind: 'identifier', name: string };
 
 /** A FromExp reads a value out of a closure, thus needs to be deferenced. */
@@ -69,6 +102,7 @@ export type Exp
     | GetExp
     | { kind: 'string', value: string }
     | { kind: 'undefined' }
+    | { kind: 'null' }
     | { kind: 'binop', op: BinOp, e1: Exp, e2: Exp }
     | { kind: 'op1', op: Op1, e: Exp }
     | IfExp
@@ -77,19 +111,26 @@ export type Exp
     | SetExp
     | BlockExp
     | CallbackExp
+    | LambdaExp
+    | CallExp
     | LabelExp
     | BreakExp
+    | ThrowExp
+    | TryExp
     | ObjExp
     | ClosExp
     | ArrayExp
     | IndexExp
     | PrimAppExp
-    | MethodCallExp ;
+    | MethodCallExp
+    | LocatedExp ;
 
 export type LVal = IdExp | FromExp | IndexExp
 
 export const undefined_ : Exp = { kind: 'undefined' };
 
+export const null_ : Exp = { kind: 'null' };
+
 export function identifier(name: string): IdExp {
     return { kind: 'identifier', name };
 }
@@ -126,6 +167,14 @@ export function callback(event: string, eventArg: Exp, callbackArgs: string[], c
     return { kind: 'callback', event, eventArg, callbackArgs, clos, body };
 }
 
+export function lambda(args: string[], clos: Exp, body: Exp[]): LambdaExp {
+    return { kind: 'lambda', args, clos, body };
+}
+
+export function call(e: Exp, callArgs: Exp[]): CallExp {
+    return { kind: 'call', e, callArgs };
+}
+
 export function let_(name: string, named: Exp): LetExp {
     return { kind: 'let', name, named };
 }
@@ -150,6 +199,19 @@ export function break_(name: string, value: Exp): BreakExp {
     return { kind: 'break', name: name, value };
 }
 
+export function throw_(e: Exp): ThrowExp {
+    return { kind: 'throw', e };
+}
+
+export function try_(body: Exp[], catchName: string | undefined,
+    handler: Exp[] | undefined, finalizer: Exp[]): TryExp {
+    return { kind: 'try', body, catchName, handler, finalizer };
+}
+
+export function located(loc: Loc, e: Exp): LocatedExp {
+    return { kind: 'located', loc, e };
+}
+
 export function obj(properties: TEnv): ObjExp {
     return { kind: 'object', properties: properties };
 }
@@ -65,6 +65,10 @@ class MockTrace implements TracingInterface {
         return new MockTrace();
     }
 
+    traceLambda(name: string, args: string[], clos: Exp): MockTrace {
+        return new MockTrace();
+    }
+
     tracePrimApp(event: string, eventArgs: Exp[]) {
     }
 
@@ -74,6 +78,18 @@ class MockTrace implements TracingInterface {
     traceBreak(name: string, value: Exp): void {
     }
 
+    traceThrow(e: Exp): void {
+    }
+
+    traceTry(catchName: string | undefined, hasHandler: boolean): void {
+    }
+
+    traceCatch(): void {
+    }
+
+    traceFinally(): void {
+    }
+
     traceReturn(e1: Exp): void {
     }
 
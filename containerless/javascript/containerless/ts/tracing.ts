@@ -16,21 +16,35 @@
  * - The 'traceCallback' method returns a new 'Trace' class to trace within the
  *   body of a callback function. The program must call 'exitBlock' at the end
  *   of a callback, since it is a block as well.
+ * - The 'traceLambda' method likewise returns a new 'Trace' class to trace
+ *   within the body of a function that the program passes to a higher-order
+ *   method, such as 'map'. Every call to the function enters the same body.
+ * - The 'traceTry' method enters the body of a 'try' statement. The program
+ *   must invoke 'traceCatch' at the start of the catch clause, and
+ *   'traceFinally' however the statement completes, even if it has no finally
+ *   clause.
  */
 
 import { TracingInterface } from './types';
 import {
-    while_, break_, label, block, let_, set, if_, callback,
-    Exp, BlockExp, LVal, primApp, unknown, methodCall
+    while_, break_, label, block, let_, set, if_, callback, lambda, throw_,
+    try_, Exp, BlockExp, LVal, TryExp, primApp, unknown, methodCall
 } from './exp';
 
 type Cursor = { body: Exp[], index: number };
 
+/**
+ * A 'try' statement whose body, or catch clause, is running. The cursor of
+ * the block that contains the statement is at 'depth' in the cursor stack.
+ */
+type ActiveTry = { exp: TryExp, depth: number };
+
 class Trace implements TracingInterface {
     private trace: BlockExp;
     private cursorStack: Cursor[];
     private cursor: Cursor | undefined;
     private argsBuf: Exp | undefined;
+    private tryStack: ActiveTry[];
 
     constructor(body: Exp[]) {
         let exp = block(body);
@@ -38,6 +52,7 @@ class Trace implements TracingInterface {
         this.cursor = { body: exp.body, index: 0 };
         this.cursorStack = [];
         this.argsBuf = undefined;
+        this.tryStack = [];
     }
 
     private getValidCursor(): Cursor {
@@ -137,6 +152,7 @@ class Trace implements TracingInterface {
     newTrace() {
         this.cursor = { body: this.trace.body, index: 0 };
         this.cursorStack = [];
+        this.tryStack = [];
     }
 
     exitBlock() {
@@ -337,6 +353,38 @@ class Trace implements TracingInterface {
         }
     }
 
+    /**
+     * Traces the declaration 'let name = lambda', and returns a new trace for
+     * the body of the lambda.
+     *
+     * @param name the name of the function
+     * @param args the names of the arguments of the lambda, starting with the
+     *             closure
+     * @param clos the closure of the function
+     */
+    traceLambda(name: string, args: string[], clos: Exp): Trace {
+        let exp = this.getCurrentExp();
+        if (exp.kind === 'unknown') {
+            let lambdaBody: Exp[] = [ unknown() ];
+            this.setExp(let_(name, lambda(args, clos, lambdaBody)));
+            return new Trace(lambdaBody);
+        }
+        else if (exp.kind === 'let' && exp.named.kind === 'lambda') {
+            let named = exp.named;
+            if (exp.name !== name || named.args.length !== args.length) {
+                throw new Error(`called traceLambda(${name}), but
+                   hole contains a lambda named ${exp.name}`);
+            }
+            named.clos = mergeExp(named.clos, clos);
+
+            this.mayIncrementCursor();
+            return new Trace(named.body);
+        }
+        else {
+            throw new Error(`expected let with a lambda, got ${exp.kind}`);
+        }
+    }
+
     tracePrimApp(event: string, eventArgs: Exp[]) {
         let exp = this.getCurrentExp();
         if (exp.kind === 'unknown') {
@@ -409,6 +457,82 @@ class Trace implements TracingInterface {
 
     }
 
+    traceThrow(e: Exp): void {
+        let exp = this.getCurrentExp();
+        if (exp.kind === 'unknown') {
+            this.setExp(throw_(e));
+        }
+        else if (exp.kind === 'throw') {
+            exp.e = mergeExp(exp.e, e);
+            this.mayIncrementCursor();
+        }
+        else {
+            throw new Error(`expected throw, got ${exp.kind}`);
+        }
+        // Nothing follows a throw in its block. If a catch clause handles
+        // the exception, traceCatch rewinds the rest of the way.
+        this.exitBlock();
+    }
+
+    /**
+     * Traces 'try { ... } catch (catchName) { ... } finally { ... }' and
+     * enters the block for the body. Every try statement has a finalizer,
+     * which is empty if it does not have a finally clause.
+     */
+    traceTry(catchName: string | undefined, hasHandler: boolean): void {
+        let exp = this.getCurrentExp();
+        let tryExp: TryExp;
+        if (exp.kind === 'unknown') {
+            tryExp = try_([unknown()], catchName,
+                hasHandler ? [unknown()] : undefined, [unknown()]);
+            this.setExp(tryExp);
+        }
+        else if (exp.kind === 'try') {
+            if (exp.catchName !== catchName ||
+                (exp.handler !== undefined) !== hasHandler) {
+                throw new Error(`Cannot merge try with catch clause ${catchName}
+                    into try with catch clause ${exp.catchName}`);
+            }
+            tryExp = exp;
+            this.mayIncrementCursor();
+        }
+        else {
+            throw new Error(`expected try, got ${exp.kind}`);
+        }
+        this.tryStack.push({ exp: tryExp, depth: this.cursorStack.length });
+        this.enterBlock({ body: tryExp.body, index: 0 });
+    }
+
+    /**
+     * Enters the catch clause of the innermost try statement. The exception
+     * may have left the body from any depth, so we rewind to the block that
+     * contains the try statement first.
+     */
+    traceCatch(): void {
+        let active = this.tryStack[this.tryStack.length - 1];
+        if (active === undefined || active.exp.handler === undefined) {
+            throw new Error('traceCatch outside of a try with a catch clause');
+        }
+        while (this.cursorStack.length > active.depth) {
+            this.quietExitBlock();
+        }
+        this.argsBuf = undefined;
+        this.enterBlock({ body: active.exp.handler, index: 0 });
+    }
+
+    /**
+     * Enters the finalizer of the innermost try statement. The finalizer runs
+     * wherever control left the try statement (e.g., after a break to an
+     * enclosing label), so exitBlock at its end returns there.
+     */
+    traceFinally(): void {
+        let active = this.tryStack.pop();
+        if (active === undefined) {
+            throw new Error('traceFinally outside of a try');
+        }
+        this.enterBlock({ body: active.exp.finalizer, index: 0 });
+    }
+
     traceReturn(e1: Exp): void {
         let e2 = this.getCurrentExp();
         if (e2.kind === 'unknown') {
@@ -454,6 +578,9 @@ function mergeExp(e1: Exp, e2: Exp): Exp {
     else if(e1.kind === 'undefined' && e2.kind === 'undefined') {
         return e1;
     }
+    else if(e1.kind === 'null' && e2.kind === 'null') {
+        return e1;
+    }
     else if (e1.kind === 'number' && e2.kind === 'number') {
         if (e1.value !== e2.value) {
             throw new Error(`Cannot merge numbers ${e1.value} and ${e2.value}`);
@@ -589,9 +716,22 @@ function mergeExp(e1: Exp, e2: Exp): Exp {
         e1.methodCallArgs = mergeExpArray(e1.methodCallArgs, e2.methodCallArgs);
         return e1;
     }
+    else if (e1.kind === 'call' && e2.kind === 'call') {
+        e1.e = mergeExp(e1.e, e2.e);
+        e1.callArgs = mergeExpArray(e1.callArgs, e2.callArgs);
+        return e1;
+    }
+    else if (e1.kind === 'located' && e2.kind === 'located') {
+        if (e1.loc.file !== e2.loc.file || e1.loc.line !== e2.loc.line ||
+            e1.loc.column !== e2.loc.column) {
+            throw new Error(`Mismatched locations in located(...) expressions`);
+        }
+        e1.e = mergeExp(e1.e, e2.e);
+        return e1;
+    }
     else {
-        // Note: We do not support merging callbacks here. traceCallback takes
-        // care of that.
+        // Note: We do not support merging callbacks or lambdas here.
+        // traceCallback and traceLambda take care of that.
         throw new Error(`Cannot merge expressions of kinds ${e1.kind} and
             ${e2.kind}`);
     }
@@ -19,9 +19,14 @@ export interface TracingInterface {
     traceWhile(cond: Exp): void;
     traceLoop(): void;
     traceCallback(event: string, eventArg: Exp, callbackArgs: string[], clos: Exp): TracingInterface;
+    traceLambda(name: string, args: string[], clos: Exp): TracingInterface;
     tracePrimApp(event: string, eventArgs: Exp[]): void;
     traceLabel(name: string): void;
     traceBreak(name: string, value: Exp): void;
+    traceThrow(e: Exp): void;
+    traceTry(catchName: string | undefined, hasHandler: boolean): void;
+    traceCatch(): void;
+    traceFinally(): void;
     traceReturn(e1: Exp): void;
     prettyPrint(): void;
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+import { transform } from '../ts/insertTracing';
+
+/**
+ * Instruments the body of a serverless function. The first statement of a
+ * serverless function must load the containerless library.
+ */
+function instrument(body: string): string {
+    return transform(`let containerless = require('containerless');\n${body}`);
+}
+
+test('throw', () => {
+    const code = instrument(`
+        let x = 1;
+        throw x;`);
+    expect(code).toMatch(/cb\.trace\.traceThrow\(exp\.identifier\(["']x["']\)\);\s*throw x;/);
+});
+
+test('try, catch, and finally', () => {
+    const code = instrument(`
+        let x = 1;
+        try {
+            x = 2;
+        } catch (e) {
+            x = 3;
+        } finally {
+            x = 4;
+        }`);
+    expect(code).toMatch(/cb\.trace\.traceTry\(["']e["'], true\);\s*try \{/);
+    expect(code).toMatch(/catch \(e\) \{\s*cb\.trace\.traceCatch\(\);/);
+    expect(code).toMatch(/finally \{\s*cb\.trace\.traceFinally\(\);/);
+});
+
+test('try without a catch clause still tells the trace that it is complete', () => {
+    const code = instrument(`
+        let x = 1;
+        l: {
+            try {
+                break l;
+            } finally {
+                x = 4;
+            }
+        }`);
+    expect(code).toMatch(/cb\.trace\.traceTry\(undefined, false\);/);
+    expect(code).toMatch(/finally \{\s*cb\.trace\.traceFinally\(\);/);
+});
+
+test('operations that may fail are located', () => {
+    const code = instrument(`
+        let x = 1;
+        let y = x + 1;
+        x = y * 2;`);
+    const loc = /exp\.located\(\{\s*file: ["']index\.js["'],\s*line: \d+,\s*column: \d+\s*\}/;
+    expect(code).toMatch(new RegExp(`traceLet\\(["']y["'], ${loc.source}, exp\\.binop`));
+    expect(code).toMatch(new RegExp(`traceSet\\(exp\\.identifier\\(["']x["']\\), ${loc.source}, exp\\.binop`));
+    expect(code).toMatch(/traceLet\(["']x["'], exp\.number\(1\)\)/);
+});
+
+test('functions passed to higher-order methods are lambdas', () => {
+    const code = instrument(`
+        let xs = [1, 2];
+        function double(x) {
+            return x * 2;
+        }
+        let ys = xs.map(double);
+        let z = double(3);`);
+    expect(code).toMatch(/double = cb\.lambda\(["']double["'], exp\.clos\(\{\}\), double\);/);
+    expect(code).not.toMatch(/traceLet\(["']double["']/);
+    expect(code).toMatch(/traceLet\(["']ys["'], exp\.located\([^]*?exp\.methodCall\(exp\.identifier\(["']xs["']\), ["']map["'], \[exp\.identifier\(["']double["']\)\]\)/);
+    expect(code).toMatch(/traceLet\(["']z["'], exp\.located\([^]*?exp\.call\(exp\.identifier\(["']double["']\), \[exp\.number\(3\)\]\)/);
+    expect(code).not.toMatch(/traceFunctionCall\(["']z["']/);
+});
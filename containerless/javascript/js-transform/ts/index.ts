@@ -1,5 +1,6 @@
 #!/usr/bin/env node
 import * as fs from 'fs';
+import * as path from 'path';
 import * as r from './insertTracing';
 
 function inputFile(): string | number {
@@ -17,5 +18,7 @@ function inputFile(): string | number {
     }
 }
 
-let inputCode = fs.readFileSync(inputFile(), { encoding: 'utf-8' });
-console.log(r.transform(inputCode));
\ No newline at end of file
+const input = inputFile();
+const file = typeof input === 'string' ? path.basename(input) : 'stdin';
+let inputCode = fs.readFileSync(input, { encoding: 'utf-8' });
+console.log(r.transform(inputCode, file));
\ No newline at end of file
@@ -133,6 +133,12 @@ const undefined_: b.MemberExpression =
         b.identifier('undefined_')
     );
 
+const null_: b.MemberExpression =
+    b.memberExpression(
+        b.identifier('exp'),
+        b.identifier('null_')
+    );
+
 function binop(op: string, e1: b.Expression, e2: b.Expression): b.CallExpression {
     const callee = b.memberExpression(
         b.identifier('exp'),
@@ -151,6 +157,128 @@ function methodCall(exp: b.Expression, method: string, methodCallArgs: b.Express
     return b.callExpression(callee, theArgs);
 }
 
+function call(e: b.Expression, callArgs: b.Expression[]): b.CallExpression {
+    const callee = b.memberExpression(
+        b.identifier('exp'),
+        b.identifier('call')
+    );
+    const theArgs = [e, b.arrayExpression(callArgs)];
+    return b.callExpression(callee, theArgs);
+}
+
+/**
+ * The name of the file that we are instrumenting, which the trace reports in
+ * the locations of runtime errors.
+ */
+let sourceFile = 'index.js';
+
+/**
+ * Marks e as the trace of the node. A runtime error in e reports the location
+ * of the node, which is a position in the normalized program. Nodes that
+ * normalization introduces do not have a location, and identifiers and
+ * literals cannot fail.
+ */
+function located(node: b.Node, e: b.Expression): b.Expression {
+    if(node.loc === null || node.loc === undefined || b.isIdentifier(node) || b.isLiteral(node)) {
+        return e;
+    }
+    const callee = b.memberExpression(
+        b.identifier('exp'),
+        b.identifier('located')
+    );
+    const loc = b.objectExpression([
+        b.objectProperty(b.identifier('file'), b.stringLiteral(sourceFile)),
+        b.objectProperty(b.identifier('line'), b.numericLiteral(node.loc.start.line)),
+        b.objectProperty(b.identifier('column'), b.numericLiteral(node.loc.start.column + 1))
+    ]);
+    return b.callExpression(callee, [loc, e]);
+}
+
+function primApp(event: string, eventArgs: b.Expression[]): b.CallExpression {
+    const callee = b.memberExpression(
+        b.identifier('exp'),
+        b.identifier('primApp')
+    );
+    const theArgs = [b.stringLiteral(event), b.arrayExpression(eventArgs)];
+    return b.callExpression(callee, theArgs);
+}
+
+/**
+ * The global functions that the trace calls with a primApp, which must agree
+ * with BUILTINS in shared::trace, which the trace compiler validates against.
+ */
+const builtins = [
+    'Math.abs', 'Math.ceil', 'Math.floor', 'Math.max', 'Math.min', 'Math.pow',
+    'Math.random', 'Math.round', 'Math.sqrt', 'JSON.parse', 'JSON.stringify',
+    'Number', 'parseFloat', 'parseInt', 'String'
+];
+
+/**
+ * The array methods that receive a function, which the trace represents as a
+ * lambda. These must agree with HIGHER_ORDER in shared::trace, which the
+ * trace compiler validates against.
+ */
+const higherOrder = ['filter', 'find', 'forEach', 'map', 'reduce', 'some', 'sort'];
+
+/**
+ * The names of the functions that the program passes to a higher-order
+ * method. The trace declares these functions as lambdas, and calls them with
+ * 'call', instead of inlining their bodies at every call.
+ */
+let lambdas: Set<string> = new Set();
+
+/**
+ * Finds the functions in s that the program passes to a higher-order method.
+ * After normalization, the function is always a variable.
+ */
+function findLambdas(s: b.Statement[], found: Set<string>): void {
+    s.forEach(s1 => {
+        switch(s1.type) {
+            case 'VariableDeclaration': {
+                s1.declarations.forEach(d => {
+                    const init = d.init;
+                    if(b.isCallExpression(init) && b.isMemberExpression(init.callee) &&
+                        b.isIdentifier(init.callee.property) &&
+                        higherOrder.includes(init.callee.property.name)) {
+                        const f = init.arguments[0];
+                        if(b.isIdentifier(f)) {
+                            found.add(f.name);
+                        }
+                    }
+                    else if(b.isFunctionExpression(init)) {
+                        findLambdas(init.body.body, found);
+                    }
+                });
+                break;
+            }
+            case 'BlockStatement': findLambdas(s1.body, found); break;
+            case 'WhileStatement': findLambdas([s1.body], found); break;
+            case 'LabeledStatement': findLambdas([s1.body], found); break;
+            case 'FunctionDeclaration': findLambdas(s1.body.body, found); break;
+            case 'IfStatement': {
+                findLambdas([s1.consequent], found);
+                if(s1.alternate !== null) {
+                    findLambdas([s1.alternate], found);
+                }
+                break;
+            }
+            case 'TryStatement': {
+                findLambdas([s1.block], found);
+                if(s1.handler !== null) {
+                    findLambdas([s1.handler.body], found);
+                }
+                if(s1.finalizer !== null) {
+                    findLambdas([s1.finalizer], found);
+                }
+                break;
+            }
+            default: {
+                break;
+            }
+        }
+    });
+}
+
 function op1(op: string, e: b.Expression): b.Expression {
     const callee = b.memberExpression(
         b.identifier('exp'),
@@ -246,6 +374,41 @@ function tracePrimApp(event: string, eventArgs: b.Expression[]): b.ExpressionSta
     return b.expressionStatement(callExpression);
 }
 
+function traceThrow(e: b.Expression): b.ExpressionStatement {
+    const memberExpression = b.memberExpression(t, b.identifier('traceThrow'));
+    const callExpression = b.callExpression(memberExpression, [e]);
+    return b.expressionStatement(callExpression);
+}
+
+function traceTry(catchName: string | undefined, hasHandler: boolean): b.ExpressionStatement {
+    const memberExpression = b.memberExpression(t, b.identifier('traceTry'));
+    const name = catchName === undefined ? b.identifier('undefined') : b.stringLiteral(catchName);
+    const callExpression = b.callExpression(memberExpression, [name, b.booleanLiteral(hasHandler)]);
+    return b.expressionStatement(callExpression);
+}
+
+const traceCatch: b.ExpressionStatement =
+    b.expressionStatement(
+        b.callExpression(
+            b.memberExpression(
+                t,
+                b.identifier('traceCatch')
+            ),
+            []
+        )
+    );
+
+const traceFinally: b.ExpressionStatement =
+    b.expressionStatement(
+        b.callExpression(
+            b.memberExpression(
+                t,
+                b.identifier('traceFinally')
+            ),
+            []
+        )
+    );
+
 const exitBlock: b.ExpressionStatement =
     b.expressionStatement(
         b.callExpression(
@@ -306,6 +469,7 @@ function transformExpression(e: b.Expression, st: State): [b.Expression, State]
         case 'NumericLiteral': return [number(e.value), st];
         case 'BooleanLiteral': return [boolean(e.value), st];
         case 'StringLiteral': return [string(e.value), st];
+        case 'NullLiteral': return [null_, st];
         case 'BinaryExpression': {
             const [left, st1] = transformExpression(e.left, st);
             const [right, st2] = transformExpression(e.right, st);
@@ -314,8 +478,15 @@ function transformExpression(e: b.Expression, st: State): [b.Expression, State]
         case 'AssignmentExpression': {
             const e2 = assertNormalized(e);
             const [right, st1] = transformExpression(e2.right, st);
-            const [left, st2] = transformExpression(e2.left, st);
-            return [traceSet(left, right), merge(st1, st2)];
+            const lval = e2.left;
+            // Traces do not have an l-value for fields, so an assignment to
+            // a field is an assignment to an index with a string key.
+            if(b.isMemberExpression(lval) && b.isIdentifier(lval.property) && lval.computed == false) {
+                const [obj, st2] = transformExpression(lval.object, st);
+                return [traceSet(index(obj, string(lval.property.name)), located(e2.right, right)), merge(st1, st2)];
+            }
+            const [left, st2] = transformExpression(lval, st);
+            return [traceSet(left, located(e2.right, right)), merge(st1, st2)];
         }
         case 'MemberExpression': {
             const obj = e.object;
@@ -425,9 +596,23 @@ function transformVariableDeclaration(s: b.VariableDeclaration, st: State): [b.S
                     case 'require': {
                         return [[ s ], nextSt];
                     }
+                    case 'Number':
+                    case 'parseFloat':
+                    case 'parseInt':
+                    case 'String': {
+                        const tPrimApp = primApp(init1.callee.name, theArgs);
+                        const tLet = traceLet(name, located(init1, tPrimApp));
+                        return [[ tLet, s ], nextSt.set(name, false)];
+                    }
                     default: {
                         const [callE, st2] = transformExpression(init1.callee, nextSt);
                         nextSt = merge(nextSt, st2);
+                        if(lambdas.has(init1.callee.name)) {
+                            // The wrapper that cb.lambda returns traces the
+                            // body of the lambda.
+                            const tLet = traceLet(name, located(init1, call(callE, theArgs)));
+                            return [[ tLet, s ], nextSt.set(name, false)];
+                        }
                         theArgs.unshift(callE);
                         break;
                     }
@@ -443,20 +628,63 @@ function transformVariableDeclaration(s: b.VariableDeclaration, st: State): [b.S
                         const tPrimApp = tracePrimApp('console.log', theArgs);
                         return [[ tPrimApp, s ], nextSt];
                     }
+                    case 'Array': {
+                        // The trace calls Array.isArray(x) as x.isArray().
+                        if(prop.name === 'isArray' && theArgs.length === 1) {
+                            const tMethod = methodCall(theArgs[0], 'isArray', []);
+                            const tLet = traceLet(name, tMethod);
+                            return [[ tLet, s ], nextSt.set(name, false)];
+                        }
+                        break;
+                    }
+                    case 'Math':
+                    case 'JSON': {
+                        const event = `${obj.name}.${prop.name}`;
+                        if(builtins.includes(event)) {
+                            const tLet = traceLet(name, located(init1, primApp(event, theArgs)));
+                            return [[ tLet, s ], nextSt.set(name, false)];
+                        }
+                        break;
+                    }
                     default: {
                         break;
                     }
                 }
                 switch(prop.name) {
                     // TODO(arjun): Total hack. Do better.
+                    case 'charAt':
+                    case 'charCodeAt':
+                    case 'concat':
+                    case 'endsWith':
+                    case 'includes':
+                    case 'indexOf':
+                    case 'padStart':
+                    case 'replace':
+                    case 'slice':
+                    case 'split':
+                    case 'substring':
+                    case 'toLowerCase':
+                    case 'toUpperCase':
+                    case 'trim':
                     case 'startsWith':
                     case 'shift':
                     case 'unshift':
                     case 'pop':
-                    case 'push': {
+                    case 'push':
+                    case 'fill':
+                    case 'join':
+                    case 'reverse':
+                    case 'splice':
+                    case 'filter':
+                    case 'find':
+                    case 'forEach':
+                    case 'map':
+                    case 'reduce':
+                    case 'some':
+                    case 'sort': {
                         const [obj2, st2] = transformExpression(obj, nextSt);
                         const tMethod = methodCall(obj2, prop.name, theArgs);
-                        const tLet = traceLet(name, tMethod);
+                        const tLet = traceLet(name, located(init1, tMethod));
                         return [[ tLet, s ], st2.set(name, false)];
                         break;
                     }
@@ -476,7 +704,7 @@ function transformVariableDeclaration(s: b.VariableDeclaration, st: State): [b.S
         }
         default: {
             const [init2, st1] = transformExpression(init, st);
-            const tLet = traceLet(name, init2);
+            const tLet = traceLet(name, located(init, init2));
             return [[tLet, s], st1.set(name, false)];
         }
     }
@@ -609,6 +837,15 @@ function transformBreakStatement(s: b.BreakStatement, st: State): [b.Statement[]
  *  b.exitBlock();
  * }
  * ```
+ *
+ * A function that the program passes to a higher-order method is a lambda:
+ *
+ * ```
+ * function F(x) {
+ *  ...
+ * }
+ * F = cb.lambda('F', clos({ a: identifier('a') }), F);
+ * ```
  */
 function transformFunctionDeclaration(s: b.FunctionDeclaration, st: State): [b.Statement[], State] {
     const id = s.id;
@@ -653,8 +890,14 @@ function transformFunctionDeclaration(s: b.FunctionDeclaration, st: State): [b.S
                 fvs.push(b.objectProperty(b.identifier(k!), from(b.identifier('$clos'), k!)));
             }
         });
-    const tClos = traceLet(lvaltoName(id), clos(fvs));
     const theFunction = b.functionDeclaration(id, params, b.blockStatement(body));
+    if(lambdas.has(id.name)) {
+        const cbLambda = b.callExpression(
+            b.memberExpression(b.identifier('cb'), b.identifier('lambda')),
+            [b.stringLiteral(id.name), clos(fvs), id]);
+        return [[theFunction, jsAssignment(id, cbLambda)], retSt];
+    }
+    const tClos = traceLet(lvaltoName(id), clos(fvs));
     return [[tClos, theFunction], retSt];
 }
 
@@ -671,6 +914,67 @@ function transformReturnStatement(s_: b.ReturnStatement, st: State): [b.Statemen
     return [[tBreak, s], st1];
 }
 
+/**
+ * ```
+ * b.traceThrow(identifier('e'));
+ * throw e;
+ * ```
+ */
+function transformThrowStatement(s: b.ThrowStatement, st: State): [b.Statement[], State] {
+    const [argument, st1] = transformExpression(s.argument, st);
+    return [[traceThrow(argument), s], st1];
+}
+
+/**
+ * Every try statement gets a finally clause, which tells the trace that the
+ * statement is complete.
+ *
+ * ```
+ * b.traceTry('e', true);
+ * try {
+ *  ...
+ *  b.exitBlock();
+ * } catch (e) {
+ *  b.traceCatch();
+ *  ...
+ *  b.exitBlock();
+ * } finally {
+ *  b.traceFinally();
+ *  ...
+ *  b.exitBlock();
+ * }
+ * ```
+ */
+function transformTryStatement(s: b.TryStatement, st: State): [b.Statement[], State] {
+    let [body, st1] = transformStatement(s.block, st);
+    body.push(exitBlock);
+    let catchName: string | undefined = undefined;
+    let handler: b.CatchClause | null = null;
+    let st2 = st;
+    if(s.handler !== null) {
+        const param = s.handler.param;
+        let handlerSt = st;
+        if(param !== null && param !== undefined) {
+            catchName = lvaltoName(param);
+            handlerSt = st.set(catchName, false);
+        }
+        let handlerBody: b.Statement[];
+        [handlerBody, st2] = transformStatement(s.handler.body, handlerSt);
+        handlerBody.unshift(traceCatch);
+        handlerBody.push(exitBlock);
+        handler = b.catchClause(param, b.blockStatement(handlerBody));
+    }
+    let [finalizer, st3]: [b.Statement[], State] = [[], st];
+    if(s.finalizer !== null) {
+        [finalizer, st3] = transformStatement(s.finalizer, st);
+    }
+    finalizer.unshift(traceFinally);
+    finalizer.push(exitBlock);
+    const tTry = traceTry(catchName, s.handler !== null);
+    const theTry = b.tryStatement(b.blockStatement(body), handler, b.blockStatement(finalizer));
+    return [[tTry, theTry], merge(merge(st1, st2), st3)];
+}
+
 function transformStatement(s: b.Statement, st: State): [b.Statement[], State] {
     switch(s.type) {
         case 'VariableDeclaration': return transformVariableDeclaration(s, st);
@@ -682,6 +986,8 @@ function transformStatement(s: b.Statement, st: State): [b.Statement[], State] {
         case 'BreakStatement': return transformBreakStatement(s, st);
         case 'FunctionDeclaration': return transformFunctionDeclaration(s, st);
         case 'ReturnStatement': return transformReturnStatement(s, st);
+        case 'ThrowStatement': return transformThrowStatement(s, st);
+        case 'TryStatement': return transformTryStatement(s, st);
         default: {
             throw new Error('TODO: ' + s.type);
         }
@@ -704,6 +1010,8 @@ function transformStatements(s: b.Statement[], st: State): [b.Statement[], State
 }
 
 function transformBody(s: b.Statement[]): b.Statement[] {
+    lambdas = new Set();
+    findLambdas(s, lambdas);
     const [prog, _] = transformStatements(s, Map());
     // TODO(emily): Fix. Need to detect require statements or something.
 
@@ -766,7 +1074,8 @@ function lvaltoName(lval: b.LVal): string {
 
 */
 
-export function transform(inputCode: string): string {
+export function transform(inputCode: string, file: string = 'index.js'): string {
+    sourceFile = file;
     let normalized = n.normalize(inputCode);
     let ast = parser.parse(normalized);
     ast.program.body = transformBody(ast.program.body);
@@ -23,6 +23,7 @@ enum SubCommand {
     RemoveContainers(RemoveContainers),
     RemoveTrace(RemoveTrace),
     Get(Get),
+    TraceStats(TraceStats),
     List(List),
     Invoke(Invoke),
     DispatcherVersion(DispatcherVersion),
@@ -41,6 +42,9 @@ struct Create {
     /// Execute this function in "containers only" mode
     #[clap(long)]
     containers_only: bool,
+    /// The gas limit of each decontainerized invocation
+    #[clap(long)]
+    gas_limit: Option<u64>,
 }
 
 /// Delete a function, removes its containers, and removes its compiled trace.
@@ -75,6 +79,14 @@ struct Get {
     name: String,
 }
 
+/// Summarizes the latest compiled trace of a function.
+#[derive(Clap)]
+struct TraceStats {
+    /// Name of the function
+    #[clap(short)]
+    name: String,
+}
+
 /// Lists all functions.
 #[derive(Clap)]
 struct List {}
@@ -106,9 +118,10 @@ async fn main() {
 
     match opts.subcmd {
         SubCommand::Create(t) => {
-            let output = controller::create_function(&t.name, &t.filename, t.containers_only)
-                .await
-                .unwrap();
+            let output =
+                controller::create_function(&t.name, &t.filename, t.containers_only, t.gas_limit)
+                    .await
+                    .unwrap();
             println!("{}", output);
         }
         SubCommand::Delete(t) => {
@@ -129,6 +142,10 @@ async fn main() {
             let output = controller::get_function(&t.name).await.unwrap();
             println!("{}", output);
         }
+        SubCommand::TraceStats(t) => {
+            let output = controller::trace_stats(&t.name).await.unwrap();
+            println!("{}", output);
+        }
         SubCommand::List(_) => {
             let output = controller::list_functions().await.unwrap();
             println!("{}", output);
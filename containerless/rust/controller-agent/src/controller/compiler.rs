@@ -1,20 +1,16 @@
 //! The compiler uses `cargo build` in the `/src` directory, so it must
 //! run as a single threaded task.
-use super::error::Error;
 use crate::trace_compiler;
 
 use shared::common::*;
+use shared::containerless::dispatcher;
 
 use futures::channel::mpsc;
 use k8s;
-use proc_macro2::Span;
-use quote::__private::TokenStream;
-use quote::quote;
 use std::fs;
 use std::io::{self, Write};
 use std::process::Stdio;
-use std::time::{Duration, Instant};
-use syn::Ident;
+use std::time::Duration;
 use tokio::process::Command;
 use tokio::task;
 
@@ -52,7 +48,8 @@ enum CompileStatus {
     Vanilla,
     Compiling,
     Compiled,
-    Error,
+    /// The reason why the function is in a broken state.
+    Error(String),
 }
 
 pub struct Compiler {
@@ -60,35 +57,165 @@ pub struct Compiler {
     is_compiling_now: AtomicBool,
 }
 
-fn gen_function_table_entry(name: &str) -> TokenStream {
-    let q_id = Ident::new(&format!("function_{}", name), Span::call_site());
-    return quote! {
-        ht.insert(#name, #q_id::containerless);
-    };
+/// The crate that compiles the trace of `name` to a shared library. These
+/// crates are members of the workspace, so they are built with the same
+/// dependencies as the dispatcher.
+fn function_crate_dir(name: &str) -> String {
+    return format!(
+        "{}/decontainerized-functions/function_{}",
+        ROOT.as_str(),
+        name
+    );
 }
 
-fn gen_function_mod(name: &str) -> TokenStream {
-    let q_id = Ident::new(&format!("function_{}", name), Span::call_site());
-    return quote! {
-        mod  #q_id;
-    };
+/// The latest trace of `name`, which is saved next to the code that the
+/// compiler generates from it.
+pub fn trace_path(name: &str) -> String {
+    return format!("{}/trace.json", function_crate_dir(name));
 }
 
-fn gen_function_table_file(table: &[String]) -> TokenStream {
-    let q_mods: Vec<_> = table.iter().map(|x| gen_function_mod(x)).collect();
-    let q_inserts: Vec<_> = table.iter().map(|x| gen_function_table_entry(x)).collect();
-    return quote! {
-        // This file is generated by the compiler in controller-agent.
-        use dispatcher_agent_lib::trace_runtime::Containerless;
-        use std::collections::HashMap;
-        #(#q_mods)*
-
-        pub fn init() -> HashMap<&'static str, Containerless> {
-            let mut ht: HashMap<&'static str, Containerless> = HashMap::new();
-            #(#q_inserts)*
-            return ht;
+/// The directory of shared libraries that the dispatcher downloads.
+pub fn shared_library_dir() -> String {
+    return format!("{}/target/decontainerized", ROOT.as_str());
+}
+
+fn shared_library_path(name: &str) -> String {
+    return format!("{}/libfunction_{}.so", shared_library_dir(), name);
+}
+
+/// The manifest of the crate of the function `name`. The version of bumpalo
+/// must be the one in dispatcher-agent-lib/Cargo.toml: the dispatcher passes
+/// its `Bump` to the library, and only loads a library whose dependencies
+/// match its own.
+fn gen_function_crate_manifest(name: &str) -> String {
+    return format!(
+        r#"# This file is generated by the compiler in controller-agent.
+[package]
+name = "function_{}"
+version = "0.1.0"
+edition = "2018"
+
+[lib]
+path = "lib.rs"
+crate-type = ["cdylib"]
+
+[dependencies]
+dispatcher-agent-lib = {{ path = "../../dispatcher-agent-lib" }}
+bumpalo = {{ version = "=3.20.3", features = ["collections", "allocator-api2"] }}
+"#,
+        name
+    );
+}
+
+/// The directory in which the compiler generates the crate of `name`. It is
+/// not a member of the workspace, so a crate that is only partly generated
+/// does not break `cargo build`.
+fn function_crate_tmp_dir(name: &str) -> String {
+    return format!("{}/target/function_crates/function_{}", ROOT.as_str(), name);
+}
+
+fn remove_dir_if_exists(dir: &str) -> io::Result<()> {
+    if let Err(err) = fs::remove_dir_all(dir) {
+        if err.kind() != io::ErrorKind::NotFound {
+            return Err(err);
+        }
+    }
+    return Ok(());
+}
+
+fn write_function_crate(name: &str, dir: &str, trace: &trace_compiler::Exp) -> Result<(), String> {
+    remove_dir_if_exists(dir)
+        .and_then(|()| fs::create_dir_all(dir))
+        .and_then(|()| {
+            fs::write(
+                format!("{}/Cargo.toml", dir),
+                gen_function_crate_manifest(name),
+            )
+        })
+        .and_then(|()| {
+            fs::write(
+                format!("{}/trace.json", dir),
+                serde_json::to_string(trace).expect("serializing trace"),
+            )
+        })
+        .map_err(|err| format!("creating crate: {}", err))?;
+    trace_compiler::compile(name.to_string(), &format!("{}/lib.rs", dir), trace, &[])
+        .map_err(|err| err.to_string())?;
+    return Ok(());
+}
+
+/// Generates the crate of `name` from `trace`, and replaces the crate of the
+/// previous trace of `name` with it only once the trace has compiled, so that
+/// the crate in the workspace always has a `lib.rs` that matches its
+/// `trace.json`.
+fn gen_function_crate(name: &str, trace: &trace_compiler::Exp) -> Result<(), String> {
+    let tmp_dir = function_crate_tmp_dir(name);
+    let result = write_function_crate(name, &tmp_dir, trace).and_then(|()| {
+        let crate_dir = function_crate_dir(name);
+        return remove_dir_if_exists(&crate_dir)
+            .and_then(|()| fs::rename(&tmp_dir, &crate_dir))
+            .map_err(|err| format!("replacing crate: {}", err));
+    });
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+    return result;
+}
+
+/// Copies the shared library that cargo built for `name` to the directory
+/// that the dispatcher downloads from. Cargo overwrites its own copy on the
+/// next build, which may happen during a download.
+fn publish_shared_library(name: &str) -> io::Result<()> {
+    fs::create_dir_all(shared_library_dir())?;
+    let tmp_path = format!("{}.tmp", shared_library_path(name));
+    fs::copy(
+        format!("{}/target/debug/libfunction_{}.so", ROOT.as_str(), name),
+        &tmp_path,
+    )?;
+    return fs::rename(tmp_path, shared_library_path(name));
+}
+
+/// The trace that we saved for `name`, if any. We merge every new trace
+/// with the previous one, which is on disk even when `traces` does not have
+/// it.
+fn load_trace(name: &str) -> Option<trace_compiler::Exp> {
+    let json = match fs::read_to_string(trace_path(name)) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            error!(target: "controller", "reading the saved trace for {}: {}", name, err);
+            return None;
         }
     };
+    match trace_compiler::parse(&json) {
+        Ok(trace) => return Some(trace),
+        Err(err) => {
+            error!(target: "controller", "parsing the saved trace for {}: {}", name, err);
+            return None;
+        }
+    }
+}
+
+/// Forgets the trace of `name`, so that the next trace that we receive for
+/// it is not merged with the traces before it.
+fn forget_trace(traces: &mut HashMap<String, trace_compiler::Exp>, name: &str) {
+    traces.remove(name);
+    if let Err(err) = fs::remove_file(trace_path(name)) {
+        if err.kind() != io::ErrorKind::NotFound {
+            error!(target: "controller", "removing the saved trace for {}: {}", name, err);
+        }
+    }
+}
+
+/// Removes the crate and the shared library of every function.
+fn remove_decontainerized_functions() -> io::Result<()> {
+    for entry in fs::read_dir(format!("{}/decontainerized-functions", ROOT.as_str()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        }
+    }
+    return remove_dir_if_exists(&shared_library_dir());
 }
 
 pub fn dispatcher_deployment_spec(version: usize) -> k8s::Deployment {
@@ -147,59 +274,28 @@ pub fn dispatcher_deployment_spec(version: usize) -> k8s::Deployment {
         .build();
 }
 
-fn generate_decontainerized_functions_mod(known_functions: &HashMap<String, CompileStatus>) {
-    let available_functions = known_functions
-        .iter()
-        .filter(|(_, v)| **v != CompileStatus::Error)
-        .map(|(k, _)| k.clone())
-        .collect::<Vec<_>>();
-
-    std::fs::write(
-        format!(
-            "{}/dispatcher-agent/src/decontainerized_functions/mod.rs",
-            ROOT.as_str()
-        ),
-        format!("{}", gen_function_table_file(&available_functions)),
-    )
-    .expect("cannot write function table file");
-}
-
-async fn wait_for_dispatcher_patch_to_complete(
-    k8s: &k8s::Client, desired_version: usize,
-) -> Result<(), Error> {
-    let interval = Duration::from_secs(1);
-    let timeout = Duration::from_secs(60);
-    let end_time = Instant::now() + timeout;
-    let label = format!("app=dispatcher,version={}", desired_version);
-    loop {
-        let dispatcher_pods = k8s
-            .list_pods_by_label_and_field(label.clone(), "status.phase=Running")
-            .await?;
-        if dispatcher_pods.len() == 1 {
-            return Ok(());
-        }
-        tokio::time::delay_for(interval).await;
-        if Instant::now() >= end_time {
-            break;
-        }
-    }
-    return Err(Error::Containerless(
-        "Could not patch dispatcher deployment.".to_string(),
-    ));
-}
-
 async fn compiler_task(compiler: Arc<Compiler>, mut recv_message: mpsc::Receiver<Message>) {
     let k8s = k8s::Client::from_kubeconfig_file(NAMESPACE)
         .await
         .expect("creating k8s::Client");
     let mut next_version = 1;
+    // Shared libraries from an earlier run of the controller are stale.
+    if let Err(err) = remove_decontainerized_functions() {
+        error!(target: "controller", "removing decontainerized functions: {}", err);
+    }
 
     let mut known_functions: HashMap<String, CompileStatus> = HashMap::new();
+    // Every trace that we receive for a function is merged into its trace,
+    // so the compiled function covers all the requests traced so far.
+    let mut traces: HashMap<String, trace_compiler::Exp> = HashMap::new();
 
     while let Some(message) = recv_message.next().await {
         match message {
             Message::RecompileDispatcher { started_compiling } => {
-                if !(compiler.cargo_build(Some(started_compiling)).await) {
+                if !(compiler
+                    .cargo_build("dispatcher-agent", Some(started_compiling))
+                    .await)
+                {
                     error!(target: "controller", "The code for dispatcher-agent is in a broken state. The system may not work.");
                     continue;
                 }
@@ -215,8 +311,14 @@ async fn compiler_task(compiler: Arc<Compiler>, mut recv_message: mpsc::Receiver
             Message::ResetDispatcher { started_compiling } => {
                 info!(target: "controller", "clearing Controller state");
                 known_functions.clear();
-                generate_decontainerized_functions_mod(&known_functions);
-                if !(compiler.cargo_build(Some(started_compiling)).await) {
+                traces.clear();
+                if let Err(err) = remove_decontainerized_functions() {
+                    error!(target: "controller", "removing decontainerized functions: {}", err);
+                }
+                if !(compiler
+                    .cargo_build("dispatcher-agent", Some(started_compiling))
+                    .await)
+                {
                     error!(target: "controller", "The code for dispatcher-agent is in a broken state. The system may not work.");
                     continue;
                 }
@@ -235,12 +337,19 @@ async fn compiler_task(compiler: Arc<Compiler>, mut recv_message: mpsc::Receiver
                 exclusive,
             } => {
                 if known_functions.contains_key(&name) {
-                    known_functions.insert(name.clone(), CompileStatus::Error);
+                    known_functions.insert(
+                        name.clone(),
+                        CompileStatus::Error("function created twice".to_string()),
+                    );
                     error!(target: "controller", "creating function {} twice", name);
                     continue;
                 }
                 if exclusive {
+                    for name in known_functions.keys() {
+                        forget_trace(&mut traces, name);
+                    }
                     known_functions.clear();
+                    traces.clear();
                 }
                 known_functions.insert(name.clone(), CompileStatus::Vanilla);
                 done.send(()).expect("sending done");
@@ -251,16 +360,7 @@ async fn compiler_task(compiler: Arc<Compiler>, mut recv_message: mpsc::Receiver
                 new_dispatcher_deployed,
             } => {
                 info!(target: "controller", "clearing compiled function {}", name);
-                let rs_path = format!(
-                    "{}/dispatcher-agent/src/decontainerized_functions/function_{}.rs",
-                    ROOT.as_str(),
-                    &name
-                );
-                let json_path = format!(
-                    "{}/dispatcher-agent/src/decontainerized_functions/function_{}.json",
-                    ROOT.as_str(),
-                    &name
-                );
+                forget_trace(&mut traces, &name);
                 match known_functions.remove(&name) {
                     None => {
                         error!(target: "controller", "clearing compiled function {}: did not find function in known_functions", name);
@@ -275,44 +375,31 @@ async fn compiler_task(compiler: Arc<Compiler>, mut recv_message: mpsc::Receiver
                         }
                         CompileStatus::Compiled => {
                             info!(target: "controller", "clearing compiled function {}: found function in known_functions", name);
-                            if let Err(err) = fs::remove_file(rs_path) {
-                                known_functions.insert(name.clone(), CompileStatus::Error);
-                                error!(target: "controller", "error reseting trace for {}: {}", &name, err);
-                                continue;
-                            }
-                            if let Err(err) = fs::remove_file(json_path) {
-                                known_functions.insert(name.clone(), CompileStatus::Error);
+                            if let Err(err) = fs::remove_dir_all(function_crate_dir(&name))
+                                .and_then(|()| fs::remove_file(shared_library_path(&name)))
+                            {
+                                known_functions.insert(
+                                    name.clone(),
+                                    CompileStatus::Error(format!("removing trace: {}", err)),
+                                );
                                 error!(target: "controller", "error reseting trace for {}: {}", &name, err);
                                 continue;
                             }
-                            generate_decontainerized_functions_mod(&known_functions);
-                            if !(compiler.cargo_build(Some(started_compiling)).await) {
-                                known_functions.insert(name.clone(), CompileStatus::Error);
-                                generate_decontainerized_functions_mod(&known_functions);
-                                error!(target: "controller", "The code for dispatcher-agent is in a broken state. The system may not work.");
-                                continue;
-                            }
-                            next_version += 1;
-                            k8s.patch_deployment(dispatcher_deployment_spec(next_version))
-                                .await
-                                .expect("patching dispatcher deployment");
-                            info!(target: "controller", "Patched dispatcher deployment");
-
-                            if let Err(err) =
-                                wait_for_dispatcher_patch_to_complete(&k8s, next_version).await
-                            {
-                                error!(target: "controller", "Error while waiting for dispatcher to patch: {:?}", err);
+                            started_compiling.send(()).expect("sending done");
+                            // The dispatcher keeps the shared library that it
+                            // loaded until we unload it.
+                            if let Err(err) = dispatcher::unload(&name).await {
+                                error!(target: "controller", "error unloading shared library for {}: {:?}", &name, err);
                                 continue;
                             }
-
                             new_dispatcher_deployed.send(()).expect("sending done");
                         }
                         CompileStatus::Compiling => {
                             error!(target: "controller", "trace not currently yet built for function {}", name);
                             continue;
                         }
-                        CompileStatus::Error => {
-                            error!(target: "controller", "calling reset on a function with an error: {}", name);
+                        CompileStatus::Error(reason) => {
+                            error!(target: "controller", "calling reset on a function with an error: {} ({})", name, reason);
                             continue;
                         }
                     },
@@ -320,47 +407,72 @@ async fn compiler_task(compiler: Arc<Compiler>, mut recv_message: mpsc::Receiver
             }
             Message::Compile { name, code } => {
                 info!(target: "controller", "compiler task received trace for {}", &name);
-                next_version += 1;
-                fs::write(
-                    format!(
-                        "{}/dispatcher-agent/src/decontainerized_functions/function_{}.json",
-                        ROOT.as_str(),
-                        &name
-                    ),
-                    &code,
-                )
-                .expect("failed to create trace (JSON) file");
-                let trace_compile_err = trace_compiler::compile(
-                    name.clone(),
-                    &format!(
-                        "{}/dispatcher-agent/src/decontainerized_functions/function_{}.rs",
-                        ROOT.as_str(),
-                        &name
-                    ),
-                    &String::from_utf8_lossy(&code),
-                );
-                if let Err(err) = trace_compile_err {
-                    known_functions.insert(name.clone(), CompileStatus::Error);
+                let trace = match trace_compiler::parse(&String::from_utf8_lossy(&code)) {
+                    Ok(trace) => trace,
+                    Err(err) => {
+                        error!(target: "controller", "error parsing trace for {}: {}", &name, err);
+                        known_functions.insert(name.clone(), CompileStatus::Error(err.to_string()));
+                        continue;
+                    }
+                };
+                let trace = match traces.remove(&name).or_else(|| load_trace(&name)) {
+                    None => trace,
+                    Some(old_trace) => {
+                        let mut merge = trace_compiler::Merge::new();
+                        let merged = merge.merge(&old_trace, &trace);
+                        // The traces disagree at a conflict, so at least one
+                        // of them is wrong there. The merged trace keeps the
+                        // node of the previous trace, which the function
+                        // already runs, and still gains the branches that
+                        // only the new trace covers.
+                        for conflict in merge.conflicts() {
+                            error!(target: "controller", "merging traces for {}: {}", &name, conflict);
+                        }
+                        merged
+                    }
+                };
+                let gen_result = gen_function_crate(&name, &trace);
+                let lifted = trace_compiler::lift(&trace, &[]);
+                traces.insert(name.clone(), trace);
+                if let Err(err) = gen_result {
                     error!(target: "controller", "error compiling trace for {}: {}", &name, err);
+                    known_functions.insert(name.clone(), CompileStatus::Error(err));
                     continue;
                 }
+                // The dispatcher interprets the trace while cargo build runs,
+                // and switches to the shared library once it is published. A
+                // function that is already compiled keeps its old library
+                // until then, since the dispatcher never goes back to the
+                // interpreter.
+                let compiled = known_functions.get(&name) == Some(&CompileStatus::Compiled);
+                if let (Ok(lifted), false) = (lifted, compiled) {
+                    let ir = serde_json::to_string(&lifted).expect("serializing lifted trace");
+                    if let Err(err) = dispatcher::interpret(&name, ir).await {
+                        error!(target: "controller", "error sending lifted trace for {}: {:?}", &name, err);
+                    }
+                }
 
                 known_functions.insert(name.clone(), CompileStatus::Compiling);
-                generate_decontainerized_functions_mod(&known_functions);
-
-                if !(compiler.cargo_build(None).await) {
-                    known_functions.insert(name.clone(), CompileStatus::Error);
-                    generate_decontainerized_functions_mod(&known_functions);
+                if !(compiler
+                    .cargo_build(&format!("function_{}", name), None)
+                    .await)
+                {
+                    known_functions.insert(
+                        name.clone(),
+                        CompileStatus::Error("cargo build failed".to_string()),
+                    );
+                    continue;
+                }
+                if let Err(err) = publish_shared_library(&name) {
+                    error!(target: "controller", "error publishing shared library for {}: {}", &name, err);
+                    known_functions.insert(name.clone(), CompileStatus::Error(err.to_string()));
                     continue;
                 }
                 known_functions.insert(name.clone(), CompileStatus::Compiled);
 
-                // TODO(arjun): If several traces are queued up, we should batch
-                // them together before updating the deployment.
-                k8s.patch_deployment(dispatcher_deployment_spec(next_version))
-                    .await
-                    .expect("patching dispatcher deployment");
-                info!(target: "controller", "Patched dispatcher deployment");
+                if let Err(err) = dispatcher::load(&name).await {
+                    error!(target: "controller", "error loading shared library for {}: {:?}", &name, err);
+                }
             }
             Message::Shutdown { done } => {
                 super::graceful_sigterm::delete_dynamic_resources(&k8s, true)
@@ -408,7 +520,11 @@ impl Compiler {
         return compiler;
     }
 
-    pub async fn cargo_build(&self, started_compiling: Option<oneshot::Sender<()>>) -> bool {
+    /// Builds `package`, which is either the dispatcher or the crate of a
+    /// decontainerized function.
+    pub async fn cargo_build(
+        &self, package: &str, started_compiling: Option<oneshot::Sender<()>>,
+    ) -> bool {
         let was_compiling = self.is_compiling_now.swap(true, SeqCst);
         if was_compiling {
             panic!("cargo was already running. This should not happen");
@@ -418,9 +534,9 @@ impl Compiler {
                 .send(())
                 .expect("could not send started_compiling message");
         }
-        info!(target: "controller", "Running cargo build on dispatcher-agent. Output is suppressed unless an error occurs.");
+        info!(target: "controller", "Running cargo build on {}. Output is suppressed unless an error occurs.", package);
         let cargo_result = Command::new("cargo")
-            .arg("build")
+            .args(["build", "-p", package])
             .current_dir(ROOT.as_str())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -429,8 +545,8 @@ impl Compiler {
             .expect("waiting for cargo to complete");
         self.is_compiling_now.store(false, SeqCst);
         if !cargo_result.status.success() {
-            io::stderr().write(&cargo_result.stderr).unwrap();
-            io::stdout().write(&cargo_result.stdout).unwrap();
+            io::stderr().write_all(&cargo_result.stderr).unwrap();
+            io::stdout().write_all(&cargo_result.stdout).unwrap();
             error!(target: "dispatcher", "cargo build failed");
             return false;
         }
@@ -1,5 +1,6 @@
-use super::compiler::Compiler;
+use super::compiler::{self, Compiler};
 use crate::controller::error::Error;
+use crate::trace_compiler;
 
 use shared::common::*;
 use shared::containerless::dispatcher;
@@ -184,6 +185,16 @@ pub async fn list_functions() -> Result<impl warp::Reply, warp::Rejection> {
     }
 }
 
+pub async fn trace_stats(name: String) -> Result<impl warp::Reply, warp::Rejection> {
+    match trace_stats_of(&name) {
+        Err(err) => {
+            error!(target:"controller", "TRACE_STATS: Error {:?} ", err);
+            error_response(err.info())
+        }
+        Ok(stats) => ok_response(stats),
+    }
+}
+
 async fn add_to_compiler(
     name: &str, compiler: Arc<Compiler>, exclusive: bool,
 ) -> Result<String, Error> {
@@ -203,6 +214,17 @@ async fn reset_function_via_compiler(name: &str, compiler: Arc<Compiler>) -> Res
     }
 }
 
+/// Analyzes the latest trace of a function, which the compiler saves next to
+/// the code that it generates, and returns the analysis as JSON.
+fn trace_stats_of(name: &str) -> Result<String, Error> {
+    let trace = std::fs::read_to_string(compiler::trace_path(name)).map_err(|_| {
+        Error::Containerless(format!("No trace has been compiled for function {}", name))
+    })?;
+    let exp = trace_compiler::parse(&trace).map_err(|err| Error::Parsing(err.to_string()))?;
+    let stats = trace_compiler::Stats::new().stats(&exp);
+    return Ok(serde_json::to_string(&stats).expect("serializing TraceStats"));
+}
+
 fn check_function_compatibility(code: &str) -> Result<String, Error> {
     let mut path_vec: Vec<&str> = env!("CARGO_MANIFEST_DIR").split("/").collect();
     path_vec.truncate(path_vec.len() - 2);
@@ -0,0 +1 @@
+pub mod trace_compiler;
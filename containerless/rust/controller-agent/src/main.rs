@@ -3,9 +3,10 @@ extern crate lazy_static;
 mod controller;
 mod handlers;
 mod routes;
-mod trace_compiler;
 
 use controller::compiler;
+// The modules of the controller use the trace compiler as `crate::trace_compiler`.
+use controller_agent::trace_compiler;
 use shared::common::*;
 use shared::logger;
 
@@ -23,7 +24,7 @@ async fn main() {
 
     let compiler = compiler::Compiler::new();
     assert!(
-        compiler.cargo_build(None).await,
+        compiler.cargo_build("dispatcher-agent", None).await,
         "initial cargo build failed"
     );
 
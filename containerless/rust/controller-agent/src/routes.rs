@@ -1,4 +1,4 @@
-use super::compiler::Compiler;
+use super::compiler::{self, Compiler};
 use super::handlers;
 
 use std::sync::Arc;
@@ -10,6 +10,7 @@ pub fn routes(
     ready_route()
         .or(system_ready_route())
         .or(download_dispatcher_route(root_str))
+        .or(download_shared_library_route())
         .or(recv_trace_route(compiler.clone()))
         .or(is_compiling_route(compiler.clone()))
         .or(restart_dispatcher_route(compiler.clone()))
@@ -20,6 +21,7 @@ pub fn routes(
         .or(reset_function_route(compiler.clone()))
         .or(get_function_route())
         .or(list_functions_route())
+        .or(trace_stats_route())
         .or(dispatcher_version_route(compiler.clone()))
 }
 
@@ -47,6 +49,13 @@ fn download_dispatcher_route(
         )))
 }
 
+fn download_shared_library_route(
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path("decontainerized")
+        .and(warp::get())
+        .and(warp::fs::dir(compiler::shared_library_dir()))
+}
+
 fn recv_trace_route(
     compiler: Arc<Compiler>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -133,6 +142,12 @@ fn list_functions_route() -> impl Filter<Extract = impl warp::Reply, Error = war
         .and_then(handlers::list_functions)
 }
 
+fn trace_stats_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("trace_stats" / String)
+        .and(warp::get())
+        .and_then(handlers::trace_stats)
+}
+
 fn dispatcher_version_route(
     compiler: Arc<Compiler>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
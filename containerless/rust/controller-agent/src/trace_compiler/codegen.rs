@@ -1,15 +1,21 @@
-use super::types::{Exp, LVal, Op1, Op2};
+use super::error::{unexpected, Error, Path};
+use super::types::{Exp, LVal, Loc, Op1, Op2, Typ};
 use duct::cmd;
 use proc_macro2::Span;
 use quote::__private::TokenStream;
 use quote::*;
+use shared::trace::{BUILTINS, HIGHER_ORDER};
 use syn::{Ident, Lifetime};
 
+const PASS: &str = "codegen";
+
 fn codegen_op1(op: &Op1) -> TokenStream {
     match op {
         Op1::Void => quote! { void },
         Op1::Typeof => quote! { typeof_ },
         Op1::Negative => quote! { neg },
+        Op1::Not => quote! { not },
+        Op1::BitNot => quote! { bit_not },
     }
 }
 
@@ -19,6 +25,7 @@ fn codegen_op(op: &Op2) -> TokenStream {
         Op2::Sub => quote! { sub },
         Op2::Mul => quote! { mul },
         Op2::Div => quote! { div },
+        Op2::Mod => quote! { rem },
         Op2::StrictEq => quote! { strict_eq },
         Op2::GT => quote! { gt },
         Op2::LT => quote! { lt },
@@ -27,252 +34,676 @@ fn codegen_op(op: &Op2) -> TokenStream {
         Op2::And => quote! { and },
         Op2::Or => quote! { or },
         Op2::StrictNotEq => quote! { strict_neq },
+        Op2::Eq => quote! { loose_eq },
+        Op2::NotEq => quote! { loose_neq },
+        Op2::BitAnd => quote! { bit_and },
+        Op2::BitOr => quote! { bit_or },
+        Op2::BitXor => quote! { bit_xor },
+        Op2::LeftShift => quote! { shl },
+        Op2::RightShift => quote! { shr },
+        Op2::UnsignedRightShift => quote! { ushr },
+        Op2::Nullish => quote! { nullish },
+        Op2::In => quote! { in_ },
     }
 }
 
-fn codegen_block(block: &[Exp], to_break: Option<Lifetime>) -> TokenStream {
-    let undef = Exp::Undefined {};
-    let (last, all_but_last) = match block.last() {
-        Some(Exp::Let { name: _, named: _ }) => (&undef, block),
-        Some(_) => block.split_last().unwrap(),
-        None => (&undef, block),
+/// Operators on unboxed values, which are built into Rust. `&` and `|` do not
+/// short-circuit, just like `Dyn::and` and `Dyn::or`. The bitwise operators
+/// are not defined on `f64`, so `TypeInference` never unboxes them.
+fn codegen_unboxed_op(op: &Op2) -> Option<TokenStream> {
+    let q_op = match op {
+        Op2::Add => quote! { + },
+        Op2::Sub => quote! { - },
+        Op2::Mul => quote! { * },
+        Op2::Div => quote! { / },
+        Op2::Mod => quote! { % },
+        Op2::StrictEq | Op2::Eq => quote! { == },
+        Op2::GT => quote! { > },
+        Op2::LT => quote! { < },
+        Op2::GTE => quote! { >= },
+        Op2::LTE => quote! { <= },
+        Op2::And => quote! { & },
+        Op2::Or => quote! { | },
+        Op2::StrictNotEq | Op2::NotEq => quote! { != },
+        _ => return None,
     };
+    return Some(q_op);
+}
+
+/// A construct that encloses the code being generated.
+enum Frame {
+    Label(Lifetime),
+    /// The body or the handler of a `try`, which an exception leaves by
+    /// breaking out of `label` with `Err(exn)`. If the `try` has a `finally`
+    /// clause, then `finally` indexes its `Finalizer`.
+    Try {
+        label: Lifetime,
+        finally: Option<usize>,
+    },
+}
+
+/// A `finally` clause has to run before a `break` leaves its `try`. So, the
+/// `break` is recorded in `pending` as the index of its label in `labels`
+/// and its value, and is re-issued after the finalizer.
+struct Finalizer {
+    pending: Ident,
+    labels: Vec<Lifetime>,
+}
+
+pub struct Codegen {
+    frames: Vec<Frame>,
+    finalizers: Vec<Finalizer>,
+    next_id: usize,
+    /// The innermost `Located` around the code being generated.
+    loc: Option<Loc>,
+    /// The number of calls to closures and builtins generated so far, which
+    /// borrow `ec`.
+    invokes: usize,
+}
 
-    let q_block_but_last = all_but_last.iter().map(|e| codegen_exp(e));
-    let q_last = codegen_exp(last);
-    match (to_break, last) {
-        // If the last element is already a break, then don't introduce another
-        // break.
-        // TODO(arjun): Perhaps this transformation should happen in rustify.rs.
-        (_, Exp::Break { name: _, value: _ }) => quote! {
-                #(#q_block_but_last)*
-                #q_last
-        },
-        (None, _) => {
-            quote! {
-                #(#q_block_but_last)*
-                #q_last
+fn lifetime(name: &str, path: &Path) -> Result<Lifetime, Error> {
+    return match name.chars().next() {
+        Some('\'') => Ok(Lifetime::new(name, Span::call_site())),
+        Some(_) => Ok(Lifetime::new(&format!("'{}", name), Span::call_site())),
+        None => unexpected(PASS, path, "a label with an empty name"),
+    };
+}
+
+impl Codegen {
+    pub fn new() -> Codegen {
+        return Codegen {
+            frames: vec![],
+            finalizers: vec![],
+            next_id: 0,
+            loc: None,
+            invokes: 0,
+        };
+    }
+
+    /// Runs the body of a closure, which `LiftCallbacks` lifted into the
+    /// generated function, with a recursive call. A call costs gas like a loop
+    /// iteration.
+    fn codegen_invoke(&mut self) -> TokenStream {
+        self.invokes += 1;
+        return quote! {
+            &mut |cbid, cbargs| {
+                ec.count()?;
+                containerless(arena, ec, cbid, cbargs)
             }
+        };
+    }
+
+    /// Calls a method of `ec`. A call to a closure or a builtin borrows `ec`
+    /// too, so when the arguments make such calls (i.e., `self.invokes` is
+    /// not `invokes` any more), they run before the call.
+    fn codegen_ec_call(&self, method: &str, args: Vec<TokenStream>, invokes: usize) -> TokenStream {
+        let q_method = Ident::new(method, Span::call_site());
+        if self.invokes == invokes {
+            return quote! { ec.#q_method(#(#args),*) };
         }
-        (Some(lifetime), _) => {
-            quote! {
-                #(#q_block_but_last)*
-                break #lifetime #q_last;
+        let q_indices = (0..args.len()).map(syn::Index::from);
+        return quote! {
+            {
+                let args = (#(#args,)*);
+                ec.#q_method(#(args.#q_indices),*)
             }
-        }
+        };
     }
-}
 
-fn codegen_exp(exp: &Exp) -> TokenStream {
-    match exp {
-        Exp::Clos { tenv: _ } => panic!("Exp::Clos should have been eliminated"),
-        Exp::Unknown {} => quote! { rt::unknown()? },
-        Exp::Integer { value } => quote! { Dyn::int(#value) },
-        Exp::Number { value } => quote! { Dyn::float(#value) },
-        Exp::Bool { value } => quote! { Dyn::bool(#value) },
-        Exp::Identifier { name } => {
-            // NOTE(arjun): Writing quote! { #x } would treat x as a string and
-            // generate the Rust code "x". This code is based on the following
-            // example:
-            // https://docs.rs/quote/0.6.12/quote/macro.quote.html#constructing-identifiers
-            let q_id = Ident::new(&name.to_string(), Span::call_site());
-            quote! { #q_id }
+    /// Calls a function in `rt::builtins`, which takes `ec` last. As in
+    /// `codegen_ec_call`, arguments that call closures run before the call.
+    fn codegen_builtin_call(
+        &mut self, f: &str, args: Vec<TokenStream>, invokes: usize,
+    ) -> TokenStream {
+        let q_f = Ident::new(f, Span::call_site());
+        let calls = self.invokes;
+        self.invokes += 1;
+        if calls == invokes {
+            return quote! { rt::builtins::#q_f(arena, &[#(#args),*], ec) };
         }
-        Exp::From { exp: _, field: _ } => panic!("Exp::From should be eliminated"),
-        Exp::Get { exp, field } => {
-            let q_exp = codegen_exp(exp);
-            quote! { #q_exp.get(#field)? }
-        }
-        Exp::Stringg { value } => quote! { Dyn::str(arena, #value) },
-        Exp::Undefined {} => quote! { Dyn::undef() },
-        Exp::Unit {} => quote! { () },
-        Exp::BinOp { op, e1, e2 } => {
-            let q_op = codegen_op(op);
-            let q_e1 = codegen_exp(e1);
-            let q_e2 = codegen_exp(e2);
-            match op {
-                Op2::Add => quote! { (#q_e1).#q_op(arena, #q_e2)? },
-                _ => quote! { (#q_e1).#q_op(#q_e2)? },
+        return quote! {
+            {
+                let args = [#(#args),*];
+                rt::builtins::#q_f(arena, &args, ec)
             }
-        }
-        Exp::Op1 { op, e } => {
-            let q_op = codegen_op1(op);
-            let q_e = codegen_exp(e);
-            match op {
-                Op1::Negative => quote! { (#q_e).#q_op()? },
-                _ => quote! { (#q_e).#q_op(arena)? },
+        };
+    }
+
+    fn fresh_id(&mut self) -> usize {
+        self.next_id += 1;
+        return self.next_id - 1;
+    }
+
+    /// Unwraps `q_result`, which produces a `Result`. An error returns from
+    /// the function, unless it is an exception inside a `try`, which breaks
+    /// out to the handler instead. A type error reports the current location.
+    fn propagate(&self, q_result: TokenStream) -> TokenStream {
+        let q_result = match &self.loc {
+            None => q_result,
+            Some(Loc { file, line, column }) => {
+                quote! { rt::at(#q_result, #file, #line, #column) }
             }
-        }
-        Exp::If {
-            cond,
-            true_part,
-            false_part,
-        } => {
-            let q_test = codegen_exp(cond);
-            let q_then_part = codegen_block(true_part, None);
-            let q_else_part = codegen_block(false_part, None);
-            quote! {
-                if #q_test.into() { #q_then_part } else { #q_else_part }
+        };
+        for frame in self.frames.iter().rev() {
+            if let Frame::Try { label, finally: _ } = frame {
+                return quote! {
+                    match rt::catchable(#q_result)? {
+                        Ok(value) => value,
+                        Err(exn) => break #label Err(exn),
+                    }
+                };
             }
         }
-        Exp::While { cond, body } => {
-            let q_cond = codegen_exp(cond);
-            let q_body = codegen_block(body, None);
-            quote! {
-                while (#q_cond).into() {
-                    ec.count()?;
-                    #q_body;
+        return quote! { #q_result? };
+    }
+
+    /// Breaks out of `label`, going through the `finally` clauses in between.
+    fn codegen_break(&mut self, label: &Lifetime, q_value: TokenStream) -> TokenStream {
+        for frame in self.frames.iter().rev() {
+            match frame {
+                Frame::Label(name) if name == label => break,
+                Frame::Try {
+                    label: try_label,
+                    finally: Some(ix),
+                } => {
+                    let finalizer = &mut self.finalizers[*ix];
+                    let k = match finalizer.labels.iter().position(|l| l == label) {
+                        Some(k) => k,
+                        None => {
+                            finalizer.labels.push(label.clone());
+                            finalizer.labels.len() - 1
+                        }
+                    };
+                    let q_pending = &finalizer.pending;
+                    return quote! {
+                        {
+                            #q_pending = Some((#k, #q_value));
+                            break #try_label Ok(Dyn::undef());
+                        }
+                    };
                 }
+                _ => (),
             }
         }
-        Exp::Let { name, named } => {
-            let q_name = Ident::new(&name.to_string(), Span::call_site());
-            let q_named = codegen_exp(named);
-            quote! {
-                let #q_name = #q_named;
+        return quote! {
+            break #label #q_value;
+        };
+    }
+
+    /// Generates code for the body of an `Unboxed` or a `Boxed`, which produces
+    /// an `f64` or a `bool` instead of a `Dyn`.
+    fn codegen_unboxed(&mut self, exp: &Exp, path: &Path) -> Result<TokenStream, Error> {
+        let q_exp = match exp {
+            Exp::Number { value } => quote! { #value },
+            Exp::Integer { value } => quote! { f64::from(#value) },
+            Exp::Bool { value } => quote! { #value },
+            Exp::Identifier { name } => {
+                let q_id = Ident::new(&name.to_string(), Span::call_site());
+                quote! { #q_id }
             }
-        }
-        Exp::Set {
-            name: LVal::Index { exp, index },
-            named,
-        } => {
-            let q_exp = codegen_exp(exp);
-            let q_index = codegen_exp(index);
-            let q_named = codegen_exp(named);
-            quote! { #q_exp.set(#q_index, #q_named)? }
-            //quote! { #q_exp.index(arena, #q_index)?.set(#q_named)? }
-        }
-        Exp::Set { name: _, named: _ } =>
-        // NOTE(arjun): This should have been turned into SetRef.
-        {
-            panic!("unexpected Exp::Set during code generation")
-        }
-        Exp::Block { body } => {
-            let q_body = codegen_block(body, None);
-            quote! {
-                {
-                    #q_body
+            Exp::BinOp { op, e1, e2 } => {
+                let q_op = match codegen_unboxed_op(op) {
+                    Some(q_op) => q_op,
+                    None => return unexpected(PASS, path, format!("{:?} on unboxed values", op)),
+                };
+                let q_e1 = self.codegen_unboxed(e1, &path.field("e1"))?;
+                let q_e2 = self.codegen_unboxed(e2, &path.field("e2"))?;
+                quote! { (#q_e1 #q_op #q_e2) }
+            }
+            Exp::Op1 {
+                op: Op1::Negative,
+                e,
+            } => {
+                let q_e = self.codegen_unboxed(e, &path.field("e"))?;
+                quote! { (-#q_e) }
+            }
+            Exp::Op1 { op: Op1::Not, e } => {
+                let q_e = self.codegen_unboxed(e, &path.field("e"))?;
+                quote! { (!#q_e) }
+            }
+            Exp::Guard { typ, e } => {
+                let q_e = self.codegen_exp(e, &path.field("e"))?;
+                match typ {
+                    Typ::F64 => self.propagate(quote! { rt::guard_f64(#q_e) }),
+                    Typ::Bool => self.propagate(quote! { rt::guard_bool(#q_e) }),
                 }
             }
+            _ => return unexpected(PASS, path, format!("{} in an unboxed expression", exp)),
+        };
+        return Ok(q_exp);
+    }
+
+    fn codegen_exps(
+        &mut self, exps: &[Exp], path: &Path, field: &'static str,
+    ) -> Result<Vec<TokenStream>, Error> {
+        exps.iter()
+            .enumerate()
+            .map(|(i, e)| self.codegen_exp(e, &path.index(field, i)))
+            .collect()
+    }
+
+    /// Generates a `bool` for the condition of an `If` or a `While`. A
+    /// condition that `TypeInference` did not unbox is a `Dyn`, which may
+    /// be any value, so it is converted as in JavaScript.
+    fn codegen_cond(&mut self, cond: &Exp, path: &Path) -> Result<TokenStream, Error> {
+        let q_cond = self.codegen_exp(cond, path)?;
+        match cond {
+            Exp::Unboxed {
+                typ: Typ::Bool,
+                e: _,
+            } => return Ok(q_cond),
+            _ => return Ok(quote! { (#q_cond).is_truthy() }),
         }
-        Exp::Callback {
-            event: _,
-            event_arg: _,
-            callback_args: _,
-            callback_clos: _,
-            body: _,
-        } =>
-        // NOTE(arjun): This should have been turned into Loopback.
-        {
-            panic!("unexpected Exp::Callback during code generation")
+    }
+
+    /// The value of a block is the value of its last expression, unless the
+    /// block ends with a `Let`.
+    fn codegen_block(
+        &mut self, block: &[Exp], path: &Path, field: &'static str,
+    ) -> Result<TokenStream, Error> {
+        let undef = Exp::Undefined {};
+        let (last, all_but_last) = match block.last() {
+            Some(Exp::Let { name: _, named: _ }) => (&undef, block),
+            Some(_) => block.split_last().unwrap(),
+            None => (&undef, block),
+        };
+
+        let q_block_but_last = self.codegen_exps(all_but_last, path, field)?;
+        let q_last = self.codegen_exp(last, &path.index(field, all_but_last.len()))?;
+        return Ok(quote! {
+            #(#q_block_but_last)*
+            #q_last
+        });
+    }
+
+    /// The `try` evaluates to `completion`, which is either the value of the
+    /// block or the exception that it throws. The handler and the finalizer
+    /// run in turn, then an exception is re-thrown to the enclosing handler.
+    fn codegen_try(
+        &mut self, body: &[Exp], catch_name: &Option<String>, handler: &Option<Vec<Exp>>,
+        finalizer: &[Exp], path: &Path,
+    ) -> Result<TokenStream, Error> {
+        let id = self.fresh_id();
+        let finally = if finalizer.is_empty() {
+            None
+        } else {
+            self.finalizers.push(Finalizer {
+                pending: Ident::new(&format!("finally{}", id), Span::call_site()),
+                labels: vec![],
+            });
+            Some(self.finalizers.len() - 1)
+        };
+
+        let q_try = Lifetime::new(&format!("'try{}", id), Span::call_site());
+        self.frames.push(Frame::Try {
+            label: q_try.clone(),
+            finally,
+        });
+        let q_body = self.codegen_block(body, path, "body")?;
+        self.frames.pop();
+        let mut q_stmts = vec![quote! {
+            let completion: Result<Dyn<'a>, Dyn<'a>> = #q_try: {
+                Ok({ #q_body })
+            };
+        }];
+
+        if let Some(handler) = handler {
+            let q_catch = Lifetime::new(&format!("'catch{}", id), Span::call_site());
+            self.frames.push(Frame::Try {
+                label: q_catch.clone(),
+                finally,
+            });
+            let q_handler = self.codegen_block(handler, path, "handler")?;
+            self.frames.pop();
+            let q_bind = match catch_name {
+                Some(name) => {
+                    let q_name = Ident::new(name, Span::call_site());
+                    quote! { let mut #q_name = Dyn::ref_(arena, exn); }
+                }
+                None => quote! {},
+            };
+            q_stmts.push(quote! {
+                let completion = match completion {
+                    Ok(value) => Ok(value),
+                    Err(exn) => #q_catch: {
+                        Ok({ #q_bind #q_handler })
+                    },
+                };
+            });
         }
-        Exp::Loopback {
-            event,
-            event_arg,
-            callback_clos,
-            id,
-        } => {
-            let q_event_arg = codegen_exp(event_arg);
-            let q_callback_clos = codegen_exp(callback_clos);
-            quote! {
-                ec.loopback(#event, #q_event_arg, #q_callback_clos, #id)?
+
+        if let Some(ix) = finally {
+            let q_finalizer = self.codegen_block(finalizer, path, "finalizer")?;
+            q_stmts.push(quote! {
+                { #q_finalizer };
+            });
+            let labels = std::mem::take(&mut self.finalizers[ix].labels);
+            if !labels.is_empty() {
+                let q_pending = self.finalizers[ix].pending.clone();
+                let mut q_arms = vec![];
+                for (k, label) in labels.iter().enumerate() {
+                    let q_break = self.codegen_break(label, quote! { value });
+                    q_arms.push(quote! { Some((#k, value)) => { #q_break } });
+                }
+                q_stmts.insert(
+                    0,
+                    quote! { let mut #q_pending: Option<(usize, Dyn<'a>)> = None; },
+                );
+                q_stmts.push(quote! {
+                    match #q_pending {
+                        #(#q_arms,)*
+                        _ => (),
+                    }
+                });
             }
         }
-        Exp::Label { name, body } => {
-            let q_name = match name.chars().next() {
-                Some(s) => {
-                    if s == '\'' {
-                        Lifetime::new(&format!("{}", name), Span::call_site())
-                    } else {
-                        Lifetime::new(&format!("'{}", name), Span::call_site())
+
+        let q_rethrow = self.propagate(quote! { completion.or_else(rt::throw) });
+        return Ok(quote! {
+            {
+                #(#q_stmts)*
+                #q_rethrow
+            }
+        });
+    }
+
+    fn codegen_exp(&mut self, exp: &Exp, path: &Path) -> Result<TokenStream, Error> {
+        let q_exp = match exp {
+            Exp::Clos { tenv: _ } => {
+                return unexpected(PASS, path, "Exp::Clos should have been eliminated");
+            }
+            Exp::Unknown {} => self.propagate(quote! { rt::unknown() }),
+            Exp::Integer { value } => quote! { Dyn::int(#value) },
+            Exp::Number { value } => quote! { Dyn::float(#value) },
+            Exp::Bool { value } => quote! { Dyn::bool(#value) },
+            Exp::Identifier { name } => {
+                // NOTE(arjun): Writing quote! { #x } would treat x as a string and
+                // generate the Rust code "x". This code is based on the following
+                // example:
+                // https://docs.rs/quote/0.6.12/quote/macro.quote.html#constructing-identifiers
+                let q_id = Ident::new(&name.to_string(), Span::call_site());
+                quote! { #q_id }
+            }
+            Exp::From { exp: _, field: _ } => {
+                return unexpected(PASS, path, "Exp::From should be eliminated");
+            }
+            Exp::Get { exp, field } => {
+                let q_exp = self.codegen_exp(exp, &path.field("exp"))?;
+                self.propagate(quote! { #q_exp.get(#field) })
+            }
+            Exp::Stringg { value } => quote! { Dyn::str(arena, #value) },
+            Exp::Undefined {} => quote! { Dyn::undef() },
+            Exp::Null {} => quote! { Dyn::null() },
+            Exp::Unit {} => quote! { () },
+            Exp::BinOp { op, e1, e2 } => {
+                let q_op = codegen_op(op);
+                let q_e1 = self.codegen_exp(e1, &path.field("e1"))?;
+                let q_e2 = self.codegen_exp(e2, &path.field("e2"))?;
+                match op {
+                    Op2::Add => self.propagate(quote! { (#q_e1).#q_op(arena, #q_e2) }),
+                    _ => self.propagate(quote! { (#q_e1).#q_op(#q_e2) }),
+                }
+            }
+            Exp::Op1 { op, e } => {
+                let q_op = codegen_op1(op);
+                let q_e = self.codegen_exp(e, &path.field("e"))?;
+                match op {
+                    Op1::Negative | Op1::Not | Op1::BitNot => {
+                        self.propagate(quote! { (#q_e).#q_op() })
                     }
+                    _ => self.propagate(quote! { (#q_e).#q_op(arena) }),
                 }
-                None => panic!("This should not happen."),
-            };
-            let q_body = codegen_block(body, Some(q_name.clone()));
-            quote! {
-                // NOTE(arjun): Unfortunately, labelled blocks are a nightly-only
-                // feature.
-                #q_name: loop {
-                    #q_body;
+            }
+            Exp::If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                let q_test = self.codegen_cond(cond, &path.field("cond"))?;
+                let q_then_part = self.codegen_block(true_part, path, "truePart")?;
+                let q_else_part = self.codegen_block(false_part, path, "falsePart")?;
+                quote! {
+                    if #q_test { #q_then_part } else { #q_else_part }
                 }
             }
-        }
-        Exp::Break { name, value } => {
-            let q_name = Lifetime::new(&name.to_string(), Span::call_site());
-            let q_value = codegen_exp(value);
-            quote! {
-                break #q_name #q_value;
+            Exp::While { cond, body } => {
+                let q_cond = self.codegen_cond(cond, &path.field("cond"))?;
+                let q_body = self.codegen_block(body, path, "body")?;
+                let q_count = self.propagate(quote! { ec.count() });
+                quote! {
+                    while #q_cond {
+                        #q_count;
+                        #q_body;
+                    }
+                }
             }
-        }
-        Exp::Object { properties } => {
-            let q_tenv = properties.iter().map(|(k, v)| {
-                let q_v = codegen_exp(v);
-                quote! { (#k, #q_v) }
-            });
-            quote! { Dyn::object_with(arena, vec![#(#q_tenv),*]) }
-        }
-        Exp::Array { exps } => {
-            let q_exps = exps.iter().map(|e| codegen_exp(e));
-            quote! { Dyn::vec_with(arena, vec![#(#q_exps),*]) }
-        }
-        Exp::Index { e1, e2 } => {
-            let q_e1 = codegen_exp(e1);
-            let q_e2 = codegen_exp(e2);
-            quote! { (#q_e1).index(arena, #q_e2)? }
-        }
-        Exp::Ref { e } => {
-            let q_e = codegen_exp(e);
-            quote! { Dyn::ref_(arena, #q_e) }
-        }
-        Exp::Deref { e } => {
-            let q_e = codegen_exp(e);
-            quote! { Dyn::deref(#q_e) }
-        }
-        Exp::SetRef { e1, e2 } => {
-            let q_e1 = codegen_exp(e1);
-            let q_e2 = codegen_exp(e2);
-            quote! { #q_e1.setref(#q_e2)? }
-        }
-        Exp::PrimApp { event, event_args } => {
-            let q_event_args = event_args.iter().map(|e| codegen_exp(e));
-            match event.as_str() {
-                "console.log" => {
+            Exp::Let { name, named } => {
+                let q_named = self.codegen_exp(named, &path.field("named"))?;
+                if name == "_" {
                     quote! {
-                        eprintln!("{:?}", #(#q_event_args),*)
+                        let _ = #q_named;
                     }
-                }
-                _ => {
-                    let q_event = Ident::new(&event.to_string(), Span::call_site());
+                } else {
+                    // Locals that EscapeAnalysis unboxes are assigned directly.
+                    let q_name = Ident::new(&name.to_string(), Span::call_site());
                     quote! {
-                        ec.#q_event(#(#q_event_args),*)?
+                        let mut #q_name = #q_named;
                     }
                 }
             }
-        }
-        Exp::MethodCall {
-            e,
-            method,
-            method_call_args,
-        } => {
-            let q_e = codegen_exp(e);
-            let q_method_call_args = method_call_args.iter().map(|e| codegen_exp(e));
-            let q_method = Ident::new(&method.to_string(), Span::call_site());
-            quote! {
-                #q_e.#q_method(#(#q_method_call_args),*)?
+            Exp::Set {
+                name: LVal::Identifier { name },
+                named,
+            } => {
+                let q_name = Ident::new(&name.to_string(), Span::call_site());
+                let q_named = self.codegen_exp(named, &path.field("named"))?;
+                quote! { { #q_name = #q_named; Dyn::undef() } }
             }
-        }
+            Exp::Set {
+                name: LVal::Index { exp, index },
+                named,
+            } => {
+                let q_exp = self.codegen_exp(exp, &path.field("name").field("exp"))?;
+                let q_index = self.codegen_exp(index, &path.field("name").field("index"))?;
+                let q_named = self.codegen_exp(named, &path.field("named"))?;
+                self.propagate(quote! { #q_exp.set(arena, #q_index, #q_named) })
+                //quote! { #q_exp.index(arena, #q_index)?.set(#q_named)? }
+            }
+            Exp::Set { name: _, named: _ } => {
+                // NOTE(arjun): This should have been turned into SetRef.
+                return unexpected(PASS, path, "Exp::Set should have been turned into SetRef");
+            }
+            Exp::Block { body } => {
+                let q_body = self.codegen_block(body, path, "body")?;
+                quote! {
+                    {
+                        #q_body
+                    }
+                }
+            }
+            Exp::Callback { .. } => {
+                // NOTE(arjun): This should have been turned into Loopback.
+                return unexpected(
+                    PASS,
+                    path,
+                    "Exp::Callback should have been turned into Loopback",
+                );
+            }
+            Exp::Loopback {
+                event,
+                event_arg,
+                callback_clos,
+                id,
+            } => {
+                let invokes = self.invokes;
+                let q_event_arg = self.codegen_exp(event_arg, &path.field("eventArg"))?;
+                let q_callback_clos = self.codegen_exp(callback_clos, &path.field("clos"))?;
+                let q_args = vec![
+                    quote! { #event },
+                    q_event_arg,
+                    q_callback_clos,
+                    quote! { #id },
+                ];
+                self.propagate(self.codegen_ec_call("loopback", q_args, invokes))
+            }
+            Exp::Lambda { .. } => {
+                return unexpected(
+                    PASS,
+                    path,
+                    "Exp::Lambda should have been turned into Closure",
+                );
+            }
+            Exp::Closure { clos, id } => {
+                let q_clos = self.codegen_exp(clos, &path.field("clos"))?;
+                quote! { Dyn::closure(arena, #id, #q_clos) }
+            }
+            Exp::Call { e, call_args } => {
+                let q_e = self.codegen_exp(e, &path.field("e"))?;
+                let q_call_args = self.codegen_exps(call_args, path, "callArgs")?;
+                let q_invoke = self.codegen_invoke();
+                self.propagate(quote! {
+                    #q_e.call(arena, &[#(#q_call_args),*], #q_invoke)
+                })
+            }
+            Exp::Label { name, body } => {
+                let q_name = lifetime(name, path)?;
+                self.frames.push(Frame::Label(q_name.clone()));
+                let q_body = self.codegen_block(body, path, "body")?;
+                self.frames.pop();
+                // A body that does not break out of the label produces the
+                // value of its last expression.
+                quote! {
+                    #q_name: {
+                        #q_body
+                    }
+                }
+            }
+            Exp::Break { name, value } => {
+                let q_name = lifetime(name, path)?;
+                let q_value = self.codegen_exp(value, &path.field("value"))?;
+                self.codegen_break(&q_name, q_value)
+            }
+            Exp::Throw { e } => {
+                let q_e = self.codegen_exp(e, &path.field("e"))?;
+                self.propagate(quote! { rt::throw(#q_e) })
+            }
+            Exp::Try {
+                body,
+                catch_name,
+                handler,
+                finalizer,
+            } => self.codegen_try(body, catch_name, handler, finalizer, path)?,
+            Exp::Object { properties } => {
+                let properties_path = path.field("properties");
+                let mut q_tenv = Vec::new();
+                for (k, v) in properties.iter() {
+                    let q_v = self.codegen_exp(v, &properties_path)?;
+                    q_tenv.push(quote! { (#k, #q_v) });
+                }
+                quote! { Dyn::object_with(arena, vec![#(#q_tenv),*]) }
+            }
+            Exp::Array { exps } => {
+                let q_exps = self.codegen_exps(exps, path, "exps")?;
+                quote! { Dyn::vec_with(arena, vec![#(#q_exps),*]) }
+            }
+            Exp::Index { e1, e2 } => {
+                let q_e1 = self.codegen_exp(e1, &path.field("exp"))?;
+                let q_e2 = self.codegen_exp(e2, &path.field("index"))?;
+                self.propagate(quote! { (#q_e1).index(arena, #q_e2) })
+            }
+            Exp::Ref { e } => {
+                let q_e = self.codegen_exp(e, &path.field("e"))?;
+                quote! { Dyn::ref_(arena, #q_e) }
+            }
+            Exp::Deref { e } => {
+                let q_e = self.codegen_exp(e, &path.field("e"))?;
+                quote! { Dyn::deref(#q_e) }
+            }
+            Exp::SetRef { e1, e2 } => {
+                let q_e1 = self.codegen_exp(e1, &path.field("e1"))?;
+                let q_e2 = self.codegen_exp(e2, &path.field("e2"))?;
+                self.propagate(quote! { #q_e1.setref(#q_e2) })
+            }
+            Exp::PrimApp { event, event_args } => {
+                let invokes = self.invokes;
+                let q_event_args = self.codegen_exps(event_args, path, "eventArgs")?;
+                match event.as_str() {
+                    // console.log returns undefined, and it may be the last
+                    // statement of a block whose value we use.
+                    "console.log" => {
+                        // Like console.log, we separate the arguments with
+                        // spaces.
+                        let format = vec!["{:?}"; q_event_args.len()].join(" ");
+                        quote! {
+                            {
+                                eprintln!(#format, #(#q_event_args),*);
+                                Dyn::undef()
+                            }
+                        }
+                    }
+                    _ => match BUILTINS.iter().find(|(name, _)| name == event) {
+                        Some((_, f)) => {
+                            let q_call = self.codegen_builtin_call(f, q_event_args, invokes);
+                            self.propagate(q_call)
+                        }
+                        None => self.propagate(self.codegen_ec_call(event, q_event_args, invokes)),
+                    },
+                }
+            }
+            Exp::Located { loc, e } => {
+                let outer = self.loc.replace(loc.clone());
+                let q_e = self.codegen_exp(e, &path.field("e"));
+                self.loc = outer;
+                q_e?
+            }
+            Exp::Unboxed { typ: _, e } => self.codegen_unboxed(e, &path.field("e"))?,
+            Exp::Boxed { typ, e } => {
+                let q_e = self.codegen_unboxed(e, &path.field("e"))?;
+                match typ {
+                    Typ::F64 => quote! { Dyn::float(#q_e) },
+                    Typ::Bool => quote! { Dyn::bool(#q_e) },
+                }
+            }
+            Exp::Guard { .. } => {
+                return unexpected(PASS, path, "Exp::Guard outside of an unboxed expression");
+            }
+            Exp::MethodCall {
+                e,
+                method,
+                method_call_args,
+            } => {
+                let q_e = self.codegen_exp(e, &path.field("e"))?;
+                let q_method_call_args =
+                    self.codegen_exps(method_call_args, path, "methodCallArgs")?;
+                let q_method = Ident::new(&method.to_string(), Span::call_site());
+                if HIGHER_ORDER.contains(&method.as_str()) {
+                    let q_invoke = self.codegen_invoke();
+                    self.propagate(quote! {
+                        #q_e.#q_method(arena, #(#q_method_call_args,)* #q_invoke)
+                    })
+                } else {
+                    self.propagate(quote! {
+                        #q_e.#q_method(arena, #(#q_method_call_args),*)
+                    })
+                }
+            }
+        };
+        return Ok(q_exp);
     }
 }
 
-pub fn codegen(e: &Exp, dest_file: &str) {
-    let q_e = codegen_exp(e);
+pub fn codegen(e: &Exp, dest_file: &str) -> Result<(), Error> {
+    let q_e = Codegen::new().codegen_exp(e, &Path::Root)?;
     let tokens = quote! {
         // We generate names from JavaScript, so camelCase names are inevitable.
         #![allow(non_snake_case)]
         #![allow(unused_variables)]
         #![forbid(unsafe_code)]
         #![allow(unused_imports)]
+        #![allow(unused_mut)]
+        #![allow(unused_assignments)]
+        #![allow(unreachable_code)]
+        #![allow(unused_labels)]
 
         use dispatcher_agent_lib::trace_runtime::{self as rt, ExecutionContext, Dyn, DynResult};
 
@@ -284,12 +715,47 @@ pub fn codegen(e: &Exp, dest_file: &str) {
         ) -> DynResult<'a> {
             Ok(#q_e)
         }
+
+        // The dispatcher loads this function from a shared library. See
+        // trace_runtime::shared_library.
+        dispatcher_agent_lib::export_containerless!(containerless);
     };
 
-    std::fs::write(dest_file, format!("{}", tokens))
-        .unwrap_or_else(|_| panic!("could not write to {}", dest_file));
+    std::fs::write(dest_file, format!("{}", tokens))?;
 
     // NOTE(arjun): If we ever measure compilation time, this line *must* be
     // removed.
-    cmd!("rustfmt", dest_file).run().expect("rustfmt failed");
+    cmd!("rustfmt", dest_file).run()?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::syntax::parse;
+    use super::*;
+
+    fn generate(text: &str) -> String {
+        let exp = parse(text).expect("parsing the trace");
+        let q_exp = Codegen::new()
+            .codegen_exp(&exp, &Path::Root)
+            .expect("generating code");
+        return q_exp.to_string();
+    }
+
+    #[test]
+    fn type_errors_report_the_innermost_location() {
+        let code = generate(r#"(located "f.js" 3 4 (get undefined x))"#);
+        assert!(code.contains(r#""f.js" , 3u32 , 4u32"#), "{}", code);
+        // The location of an inner expression does not apply after it.
+        let code = generate(
+            r#"(located "f.js" 1 2 (block [
+              (let y (located "f.js" 5 6 (+ 1.0 2.0)))
+              (get null y)
+            ]))"#,
+        );
+        assert!(code.contains(r#""f.js" , 5u32 , 6u32"#), "{}", code);
+        let get = r#"rt :: at (Dyn :: null () . get ("y") , "f.js" , 1u32 , 2u32)"#;
+        assert!(code.contains(get), "{}", code);
+        assert!(!generate("(get undefined x)").contains("rt :: at"));
+    }
 }
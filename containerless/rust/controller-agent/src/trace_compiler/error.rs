@@ -1,9 +1,79 @@
+use super::syntax::SyntaxError;
+use super::validator::Diagnostic;
 use serde_json::error::Error as JsonError;
 use std::convert::From;
+use std::fmt;
+
+/// The location of an `Exp` node, relative to the root of the trace. Passes
+/// build paths on the stack as they recurse, so a path only allocates when
+/// it is rendered into an `Error`.
+pub enum Path<'p> {
+    Root,
+    Field(&'p Path<'p>, &'static str),
+    Index(&'p Path<'p>, &'static str, usize),
+}
+
+impl<'p> Path<'p> {
+    pub fn field(&'p self, name: &'static str) -> Path<'p> {
+        Path::Field(self, name)
+    }
+
+    pub fn index(&'p self, name: &'static str, index: usize) -> Path<'p> {
+        Path::Index(self, name, index)
+    }
+}
+
+impl<'p> fmt::Display for Path<'p> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Path::Root => write!(f, "$"),
+            Path::Field(parent, name) => write!(f, "{}.{}", parent, name),
+            Path::Index(parent, name, index) => write!(f, "{}.{}[{}]", parent, name, index),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
     Json(JsonError),
+    /// An error in a trace in the text syntax.
+    Syntax(SyntaxError),
+    IO(std::io::Error),
+    /// A pass found a node that it does not expect at this point in the
+    /// pipeline, e.g., an `Exp::Clos` after `Transformer` eliminates them.
+    Unexpected {
+        pass: &'static str,
+        path: String,
+        message: String,
+    },
+    /// A pass found a node that it does not support yet.
+    Unsupported {
+        pass: &'static str,
+        path: String,
+        message: String,
+    },
+    /// The validator found problems with the trace.
+    Invalid(Vec<Diagnostic>),
+}
+
+pub fn unexpected<T>(
+    pass: &'static str, path: &Path, message: impl Into<String>,
+) -> Result<T, Error> {
+    return Err(Error::Unexpected {
+        pass,
+        path: path.to_string(),
+        message: message.into(),
+    });
+}
+
+pub fn unsupported<T>(
+    pass: &'static str, path: &Path, message: impl Into<String>,
+) -> Result<T, Error> {
+    return Err(Error::Unsupported {
+        pass,
+        path: path.to_string(),
+        message: message.into(),
+    });
 }
 
 impl std::fmt::Display for Error {
@@ -13,6 +83,32 @@ impl std::fmt::Display for Error {
                 fmt.write_str("JSON error: ")?;
                 return json_err.fmt(fmt);
             }
+            Error::Syntax(syntax_err) => write!(fmt, "syntax error at {}", syntax_err),
+            Error::IO(io_err) => {
+                fmt.write_str("I/O error: ")?;
+                return io_err.fmt(fmt);
+            }
+            Error::Unexpected {
+                pass,
+                path,
+                message,
+            } => write!(fmt, "{}: unexpected node at {}: {}", pass, path, message),
+            Error::Unsupported {
+                pass,
+                path,
+                message,
+            } => write!(fmt, "{}: unsupported node at {}: {}", pass, path, message),
+            Error::Invalid(diagnostics) => {
+                write!(
+                    fmt,
+                    "Validator: {} problem(s) with the trace",
+                    diagnostics.len()
+                )?;
+                for diagnostic in diagnostics.iter() {
+                    write!(fmt, "\n  {}", diagnostic)?;
+                }
+                return Ok(());
+            }
         }
     }
 }
@@ -24,3 +120,15 @@ impl From<JsonError> for Error {
         return Error::Json(e);
     }
 }
+
+impl From<SyntaxError> for Error {
+    fn from(e: SyntaxError) -> Self {
+        return Error::Syntax(e);
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        return Error::IO(e);
+    }
+}
@@ -0,0 +1,401 @@
+//! `Transformer` heap-allocates every local variable, since a local may be
+//! captured by a closure and outlive the callback that binds it. However,
+//! most locals are never captured, and going through `Dyn::Ref` for every
+//! read and write of a loop counter is expensive. This pass finds the locals
+//! that never escape and turns them back into ordinary Rust variables:
+//!
+//! 1. `Let(x, Ref(e))` becomes `Let(x, e)`,
+//! 2. `Deref(Identifier(x))` becomes `Identifier(x)`, and
+//! 3. `SetRef(Identifier(x), e)` becomes `Set(Identifier(x), e)`.
+//!
+//! After `Transformer`, a local appears as a bare `Identifier` (i.e., not
+//! under a `Deref` and not as the target of a `SetRef`) only when its
+//! reference is stored in a closure, thus those are exactly the locals that
+//! escape. Callback and lambda arguments also stay on the heap, because
+//! `LiftCallbacks` binds them with `Ref` after this pass runs, and so do the
+//! names that `catch` clauses bind, because they are not bound by a `Let`.
+use std::collections::HashSet;
+
+use super::types::{Exp, LVal};
+
+pub struct EscapeAnalysis {
+    locals: HashSet<String>,
+    escaping: HashSet<String>,
+}
+
+impl EscapeAnalysis {
+    pub fn new() -> EscapeAnalysis {
+        return EscapeAnalysis {
+            locals: HashSet::new(),
+            escaping: HashSet::new(),
+        };
+    }
+
+    fn scan_exps(&mut self, exps: &[Exp]) {
+        for e in exps.iter() {
+            self.scan(e);
+        }
+    }
+
+    fn scan(&mut self, exp: &Exp) {
+        match exp {
+            Exp::Unknown {}
+            | Exp::Integer { .. }
+            | Exp::Number { .. }
+            | Exp::Bool { .. }
+            | Exp::Stringg { .. }
+            | Exp::Undefined {}
+            | Exp::Null {}
+            | Exp::Unit {} => (),
+            Exp::Identifier { name } => {
+                self.escaping.insert(name.to_string());
+            }
+            Exp::Deref { e } => match &**e {
+                Exp::Identifier { name: _ } => (),
+                _ => self.scan(e),
+            },
+            Exp::SetRef { e1, e2 } => {
+                match &**e1 {
+                    Exp::Identifier { name: _ } => (),
+                    _ => self.scan(e1),
+                }
+                self.scan(e2);
+            }
+            Exp::Let { name, named } => {
+                self.locals.insert(name.to_string());
+                match &**named {
+                    Exp::Ref { e } => self.scan(e),
+                    // Not produced by Transformer, so we cannot unbox it.
+                    _ => {
+                        self.escaping.insert(name.to_string());
+                        self.scan(named);
+                    }
+                }
+            }
+            Exp::From { exp, field: _ } => self.scan(exp),
+            Exp::Get { exp, field: _ } => self.scan(exp),
+            Exp::Op1 { op: _, e } => self.scan(e),
+            Exp::BinOp { op: _, e1, e2 } => {
+                self.scan(e1);
+                self.scan(e2);
+            }
+            Exp::If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                self.scan(cond);
+                self.scan_exps(true_part);
+                self.scan_exps(false_part);
+            }
+            Exp::While { cond, body } => {
+                self.scan(cond);
+                self.scan_exps(body);
+            }
+            Exp::Set { name, named } => {
+                match name {
+                    LVal::Identifier { name } => {
+                        self.escaping.insert(name.to_string());
+                    }
+                    LVal::From { exp, field: _ } => self.scan(exp),
+                    LVal::Index { exp, index } => {
+                        self.scan(exp);
+                        self.scan(index);
+                    }
+                }
+                self.scan(named);
+            }
+            Exp::Block { body } => self.scan_exps(body),
+            Exp::Callback {
+                event: _,
+                event_arg,
+                callback_args,
+                callback_clos,
+                body,
+            } => {
+                for arg in callback_args.iter() {
+                    self.escaping.insert(arg.name.to_string());
+                }
+                self.scan(event_arg);
+                self.scan(callback_clos);
+                self.scan_exps(body);
+            }
+            Exp::Loopback {
+                event: _,
+                event_arg,
+                callback_clos,
+                id: _,
+            } => {
+                self.scan(event_arg);
+                self.scan(callback_clos);
+            }
+            Exp::Lambda { args, clos, body } => {
+                for arg in args.iter() {
+                    self.escaping.insert(arg.name.to_string());
+                }
+                self.scan(clos);
+                self.scan_exps(body);
+            }
+            Exp::Closure { clos, id: _ } => self.scan(clos),
+            Exp::Call { e, call_args } => {
+                self.scan(e);
+                self.scan_exps(call_args);
+            }
+            Exp::Label { name: _, body } => self.scan_exps(body),
+            Exp::Break { name: _, value } => self.scan(value),
+            Exp::Throw { e } => self.scan(e),
+            Exp::Try {
+                body,
+                catch_name: _,
+                handler,
+                finalizer,
+            } => {
+                self.scan_exps(body);
+                if let Some(handler) = handler {
+                    self.scan_exps(handler);
+                }
+                self.scan_exps(finalizer);
+            }
+            Exp::Object { properties } => properties.values().for_each(|e| self.scan(e)),
+            Exp::Clos { tenv } => tenv.values().for_each(|e| self.scan(e)),
+            Exp::Array { exps } => self.scan_exps(exps),
+            Exp::Index { e1, e2 } => {
+                self.scan(e1);
+                self.scan(e2);
+            }
+            Exp::Ref { e } | Exp::Located { loc: _, e } => self.scan(e),
+            Exp::Unboxed { typ: _, e } | Exp::Boxed { typ: _, e } | Exp::Guard { typ: _, e } => {
+                self.scan(e)
+            }
+            Exp::PrimApp {
+                event: _,
+                event_args,
+            } => self.scan_exps(event_args),
+            Exp::MethodCall {
+                e,
+                method: _,
+                method_call_args,
+            } => {
+                self.scan(e);
+                self.scan_exps(method_call_args);
+            }
+        }
+    }
+
+    fn is_unboxed(&self, name: &str) -> bool {
+        return self.locals.contains(name) && !self.escaping.contains(name);
+    }
+
+    fn unbox_exps(&self, exps: &mut [Exp]) {
+        for e in exps.iter_mut() {
+            self.unbox(e);
+        }
+    }
+
+    fn unbox(&self, exp: &mut Exp) {
+        match exp {
+            Exp::Unknown {}
+            | Exp::Integer { .. }
+            | Exp::Number { .. }
+            | Exp::Bool { .. }
+            | Exp::Stringg { .. }
+            | Exp::Undefined {}
+            | Exp::Null {}
+            | Exp::Unit {}
+            | Exp::Identifier { .. } => (),
+            Exp::Deref { e } => match &**e {
+                Exp::Identifier { name } if self.is_unboxed(name) => {
+                    let inner = std::mem::replace(&mut **e, Exp::Undefined {});
+                    *exp = inner;
+                }
+                _ => self.unbox(e),
+            },
+            Exp::SetRef { e1, e2 } => {
+                self.unbox(e2);
+                match &**e1 {
+                    Exp::Identifier { name } if self.is_unboxed(name) => {
+                        let name = name.to_string();
+                        let named = std::mem::replace(e2, Box::new(Exp::Undefined {}));
+                        *exp = Exp::Set {
+                            name: LVal::Identifier { name },
+                            named,
+                        };
+                    }
+                    _ => self.unbox(e1),
+                }
+            }
+            Exp::Let { name, named } => {
+                if let Exp::Ref { e } = &mut **named {
+                    self.unbox(e);
+                    if self.is_unboxed(name) {
+                        let inner = std::mem::replace(&mut **e, Exp::Undefined {});
+                        **named = inner;
+                    }
+                } else {
+                    self.unbox(named);
+                }
+            }
+            Exp::From { exp, field: _ } => self.unbox(exp),
+            Exp::Get { exp, field: _ } => self.unbox(exp),
+            Exp::Op1 { op: _, e } => self.unbox(e),
+            Exp::BinOp { op: _, e1, e2 } => {
+                self.unbox(e1);
+                self.unbox(e2);
+            }
+            Exp::If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                self.unbox(cond);
+                self.unbox_exps(true_part);
+                self.unbox_exps(false_part);
+            }
+            Exp::While { cond, body } => {
+                self.unbox(cond);
+                self.unbox_exps(body);
+            }
+            Exp::Set { name, named } => {
+                match name {
+                    LVal::Identifier { name: _ } => (),
+                    LVal::From { exp, field: _ } => self.unbox(exp),
+                    LVal::Index { exp, index } => {
+                        self.unbox(exp);
+                        self.unbox(index);
+                    }
+                }
+                self.unbox(named);
+            }
+            Exp::Block { body } => self.unbox_exps(body),
+            Exp::Callback {
+                event: _,
+                event_arg,
+                callback_args: _,
+                callback_clos,
+                body,
+            } => {
+                self.unbox(event_arg);
+                self.unbox(callback_clos);
+                self.unbox_exps(body);
+            }
+            Exp::Loopback {
+                event: _,
+                event_arg,
+                callback_clos,
+                id: _,
+            } => {
+                self.unbox(event_arg);
+                self.unbox(callback_clos);
+            }
+            Exp::Lambda {
+                args: _,
+                clos,
+                body,
+            } => {
+                self.unbox(clos);
+                self.unbox_exps(body);
+            }
+            Exp::Closure { clos, id: _ } => self.unbox(clos),
+            Exp::Call { e, call_args } => {
+                self.unbox(e);
+                self.unbox_exps(call_args);
+            }
+            Exp::Label { name: _, body } => self.unbox_exps(body),
+            Exp::Break { name: _, value } => self.unbox(value),
+            Exp::Throw { e } => self.unbox(e),
+            Exp::Try {
+                body,
+                catch_name: _,
+                handler,
+                finalizer,
+            } => {
+                self.unbox_exps(body);
+                if let Some(handler) = handler {
+                    self.unbox_exps(handler);
+                }
+                self.unbox_exps(finalizer);
+            }
+            Exp::Object { properties } => properties.values_mut().for_each(|e| self.unbox(e)),
+            Exp::Clos { tenv } => tenv.values_mut().for_each(|e| self.unbox(e)),
+            Exp::Array { exps } => self.unbox_exps(exps),
+            Exp::Index { e1, e2 } => {
+                self.unbox(e1);
+                self.unbox(e2);
+            }
+            Exp::Ref { e } | Exp::Located { loc: _, e } => self.unbox(e),
+            Exp::Unboxed { typ: _, e } | Exp::Boxed { typ: _, e } | Exp::Guard { typ: _, e } => {
+                self.unbox(e)
+            }
+            Exp::PrimApp {
+                event: _,
+                event_args,
+            } => self.unbox_exps(event_args),
+            Exp::MethodCall {
+                e,
+                method: _,
+                method_call_args,
+            } => {
+                self.unbox(e);
+                self.unbox_exps(method_call_args);
+            }
+        }
+    }
+
+    /// Unboxes every local in `exp` that does not escape.
+    pub fn analyze(&mut self, exp: &mut Exp) {
+        self.scan(exp);
+        self.unbox(exp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::syntax::parse;
+    use super::*;
+
+    fn analyze(text: &str) -> String {
+        let mut exp = parse(text).expect("parsing the trace");
+        EscapeAnalysis::new().analyze(&mut exp);
+        return exp.to_string();
+    }
+
+    #[test]
+    fn local_that_does_not_escape_is_unboxed() {
+        let text = analyze(
+            "(block [
+              (let i (ref 0.0))
+              (setRef i (+ (deref i) 1.0))
+              (primApp send (deref i))
+            ])",
+        );
+        assert_eq!(
+            text,
+            "(block [(let i 0.0) (set i (+ i 1.0)) (primApp send i)])"
+        );
+    }
+
+    #[test]
+    fn local_in_a_closure_escapes() {
+        let text = analyze(
+            "(block [
+              (let x (ref 0.0))
+              (let y (ref 1.0))
+              (let f (lambda [clos] (clos (x x)) []))
+              (setRef x (deref y))
+            ])",
+        );
+        assert!(text.contains("(let x (ref 0.0))"), "{}", text);
+        assert!(text.contains("(setRef x y)"), "{}", text);
+        assert!(text.contains("(let y 1.0)"), "{}", text);
+    }
+
+    #[test]
+    fn catch_name_stays_on_the_heap() {
+        let text = analyze(
+            "(block [
+              (try [(throw 1.0)] (catch e [(primApp send (deref e))]))
+            ])",
+        );
+        assert!(text.contains("(deref e)"), "{}", text);
+    }
+}
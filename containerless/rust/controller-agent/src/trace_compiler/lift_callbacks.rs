@@ -1,7 +1,10 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
+use super::error::{unexpected, unsupported, Error, Path};
 use super::types::{constructors::*, Arg, Exp, Exp::*, LVal, Op2::*};
 
+const PASS: &str = "LiftCallbacks";
+
 pub struct LiftCallbacks {
     cbid: String,
     cbargs: String,
@@ -24,153 +27,192 @@ impl LiftCallbacks {
         return self.next_id - 1;
     }
 
-    fn lift_callback(
-        &mut self, event: &str, event_arg: &Exp, callback_args: &[Arg], callback_clos: &Exp,
-        body: &[Exp],
-    ) -> Exp {
+    /// Lifts the body of a callback or a lambda, which binds its arguments
+    /// from `arg_cbargs`, and returns its id.
+    fn lift_body(&mut self, args: &[Arg], body: &[Exp], path: &Path) -> Result<i32, Error> {
         let x = self.fresh_id();
         let mut lifted_body: Vec<Exp> = vec![];
-        for (i, Arg { name }) in callback_args.iter().enumerate() {
+        for (i, Arg { name }) in args.iter().enumerate() {
             lifted_body.push(let_(
                 name,
                 ref_(index_(id(&self.cbargs), integer(i as i32))),
             ));
         }
-        lifted_body.append(&mut self.lift_exps(body));
+        lifted_body.append(&mut self.lift_exps(body, path, "body")?);
         self.callbacks.push((x, lifted_body));
+        return Ok(x);
+    }
+
+    fn lift_callback(
+        &mut self, event: &str, event_arg: &Exp, callback_args: &[Arg], callback_clos: &Exp,
+        body: &[Exp], path: &Path,
+    ) -> Result<Exp, Error> {
+        let x = self.lift_body(callback_args, body, path)?;
         let loopback_call = loopback(
             event,
-            self.lift_exp(event_arg),
-            self.lift_exp(callback_clos),
+            self.lift_exp(event_arg, &path.field("eventArg"))?,
+            self.lift_exp(callback_clos, &path.field("clos"))?,
             x,
         );
-        return loopback_call;
+        return Ok(loopback_call);
     }
 
-    fn lift_exps(&mut self, exps: &[Exp]) -> Vec<Exp> {
+    fn lift_exps(
+        &mut self, exps: &[Exp], path: &Path, field: &'static str,
+    ) -> Result<Vec<Exp>, Error> {
         let mut ret: Vec<Exp> = vec![];
         for (i, e) in exps.iter().enumerate() {
-            match e {
-                Callback {
-                    event,
-                    event_arg,
-                    callback_args,
-                    callback_clos,
-                    body,
-                } => {
-                    if i < exps.len() - 1 {
-                        let (_p, r) = exps.split_at(i + 1);
-                        let mut loopback = vec![self.lift_callback(
-                            event,
-                            event_arg,
-                            callback_args,
-                            callback_clos,
-                            body,
-                        )];
-                        loopback.append(&mut self.lift_exps(r));
-                        return loopback;
-                    } else {
-                        let loopback = vec![self.lift_callback(
-                            event,
-                            event_arg,
-                            callback_args,
-                            callback_clos,
-                            body,
-                        )];
-                        return loopback;
-                    }
-                }
-                _ => {
-                    let e = self.lift_exp(e);
-                    ret.push(e);
-                }
-            }
+            ret.push(self.lift_exp(e, &path.index(field, i))?);
         }
-        return ret;
+        return Ok(ret);
     }
 
-    fn lift_clos(&mut self, exps: &HashMap<String, Exp>) -> HashMap<String, Exp> {
-        let mut ret: HashMap<String, Exp> = HashMap::new();
+    fn lift_clos(
+        &mut self, exps: &IndexMap<String, Exp>, path: &Path,
+    ) -> Result<IndexMap<String, Exp>, Error> {
+        let mut ret: IndexMap<String, Exp> = IndexMap::new();
         for (key, value) in exps.iter() {
-            ret.insert(key.to_string(), self.lift_exp(value));
+            ret.insert(key.to_string(), self.lift_exp(value, path)?);
         }
-        return ret;
+        return Ok(ret);
     }
 
-    fn lift_exp(&mut self, exp: &Exp) -> Exp {
-        match exp {
-            Unknown {} => return unknown(),
-            Number { value } => return number(*value),
-            Bool { value } => return bool_(*value),
-            Identifier { name } => return id(name),
-            From { exp: _, field: _ } => panic!("Exp::From should be eliminated"),
-            Index { e1, e2 } => return index_(self.lift_exp(e1), self.lift_exp(e2)),
-            Get { exp, field } => return get(self.lift_exp(exp), field),
-            Stringg { value } => return string(value),
-            Undefined {} => return undefined(),
-            BinOp { op, e1, e2 } => return binop(op, self.lift_exp(e1), self.lift_exp(e2)),
-            Op1 { op, e } => return op1(op, self.lift_exp(e)),
+    fn lift_exp(&mut self, exp: &Exp, path: &Path) -> Result<Exp, Error> {
+        let e = match exp {
+            Unknown {} => unknown(),
+            Number { value } => number(*value),
+            Bool { value } => bool_(*value),
+            Identifier { name } => id(name),
+            Index { e1, e2 } => index_(
+                self.lift_exp(e1, &path.field("exp"))?,
+                self.lift_exp(e2, &path.field("index"))?,
+            ),
+            Get { exp, field } => get(self.lift_exp(exp, &path.field("exp"))?, field),
+            Stringg { value } => string(value),
+            Undefined {} => undefined(),
+            Null {} => null(),
+            BinOp { op, e1, e2 } => binop(
+                op,
+                self.lift_exp(e1, &path.field("e1"))?,
+                self.lift_exp(e2, &path.field("e2"))?,
+            ),
+            Op1 { op, e } => op1(op, self.lift_exp(e, &path.field("e"))?),
             If {
                 cond,
                 true_part,
                 false_part,
-            } => {
-                return if_(
-                    self.lift_exp(cond),
-                    self.lift_exps(true_part),
-                    self.lift_exps(false_part),
-                )
-            }
-            While { cond, body } => return while_(self.lift_exp(cond), self.lift_exps(body)),
-            Let { name, named } => return let_(name, self.lift_exp(named)),
-            Block { body } => return block(self.lift_exps(body)),
+            } => if_(
+                self.lift_exp(cond, &path.field("cond"))?,
+                self.lift_exps(true_part, path, "truePart")?,
+                self.lift_exps(false_part, path, "falsePart")?,
+            ),
+            While { cond, body } => while_(
+                self.lift_exp(cond, &path.field("cond"))?,
+                self.lift_exps(body, path, "body")?,
+            ),
+            Let { name, named } => let_(name, self.lift_exp(named, &path.field("named"))?),
+            Block { body } => block(self.lift_exps(body, path, "body")?),
             Callback {
                 event,
                 event_arg,
                 callback_args,
                 callback_clos,
                 body,
-            } => return self.lift_callback(event, event_arg, callback_args, callback_clos, body),
-            Label { name, body } => return label(name, self.lift_exps(body)),
-            Break { name, value } => return break_(name, self.lift_exp(value)),
-            Object { properties } => return obj(self.lift_clos(properties)),
-            Array { exps } => return array(self.lift_exps(exps)),
-            PrimApp { event, event_args } => return prim_app(event, self.lift_exps(event_args)),
-            Ref { e } => return ref_(self.lift_exp(e)),
-            Deref { e } => return deref(self.lift_exp(e)),
-            SetRef { e1, e2 } => return setref(self.lift_exp(e1), self.lift_exp(e2)),
+            } => self.lift_callback(event, event_arg, callback_args, callback_clos, body, path)?,
+            Lambda { args, clos, body } => {
+                let x = self.lift_body(args, body, path)?;
+                closure(self.lift_exp(clos, &path.field("clos"))?, x)
+            }
+            Call { e, call_args } => call(
+                self.lift_exp(e, &path.field("e"))?,
+                self.lift_exps(call_args, path, "callArgs")?,
+            ),
+            Label { name, body } => label(name, self.lift_exps(body, path, "body")?),
+            Break { name, value } => break_(name, self.lift_exp(value, &path.field("value"))?),
+            Throw { e } => throw_(self.lift_exp(e, &path.field("e"))?),
+            Try {
+                body,
+                catch_name,
+                handler,
+                finalizer,
+            } => try_(
+                self.lift_exps(body, path, "body")?,
+                catch_name.as_deref(),
+                match handler {
+                    None => None,
+                    Some(handler) => Some(self.lift_exps(handler, path, "handler")?),
+                },
+                self.lift_exps(finalizer, path, "finalizer")?,
+            ),
+            Object { properties } => obj(self.lift_clos(properties, &path.field("properties"))?),
+            Array { exps } => array(self.lift_exps(exps, path, "exps")?),
+            PrimApp { event, event_args } => {
+                prim_app(event, self.lift_exps(event_args, path, "eventArgs")?)
+            }
+            Ref { e } => ref_(self.lift_exp(e, &path.field("e"))?),
+            Deref { e } => deref(self.lift_exp(e, &path.field("e"))?),
+            SetRef { e1, e2 } => setref(
+                self.lift_exp(e1, &path.field("e1"))?,
+                self.lift_exp(e2, &path.field("e2"))?,
+            ),
             MethodCall {
                 e,
                 method,
                 method_call_args,
-            } => return method_call(self.lift_exp(e), method, self.lift_exps(method_call_args)),
+            } => method_call(
+                self.lift_exp(e, &path.field("e"))?,
+                method,
+                self.lift_exps(method_call_args, path, "methodCallArgs")?,
+            ),
+            Set {
+                name: LVal::Identifier { name },
+                named,
+            } => set(lval_id(name), self.lift_exp(named, &path.field("named"))?),
             Set {
                 name: LVal::Index { exp, index },
                 named,
             } => set(
-                lval_index(self.lift_exp(exp), self.lift_exp(index)),
-                self.lift_exp(named),
+                lval_index(
+                    self.lift_exp(exp, &path.field("name").field("exp"))?,
+                    self.lift_exp(index, &path.field("name").field("index"))?,
+                ),
+                self.lift_exp(named, &path.field("named"))?,
             ),
-            Set { name: _, named: _ } => panic!("Did not expect to find this here."),
-            Loopback {
-                event: _,
-                event_arg: _,
-                callback_clos: _,
-                id: _,
-            } => panic!("Did not expect to find this here."),
-            _ => {
-                panic!("Not implemented.");
+            Located { loc, e } => located(loc, self.lift_exp(e, &path.field("e"))?),
+            From { .. } | Clos { .. } | Set { .. } | Loopback { .. } | Closure { .. } => {
+                return unexpected(PASS, path, format!("{} after Transformer", exp));
             }
-        }
+            Unboxed { .. } | Boxed { .. } | Guard { .. } => {
+                return unexpected(PASS, path, format!("{} before TypeInference", exp));
+            }
+            Integer { .. } | Unit {} => {
+                return unsupported(PASS, path, format!("{}", exp));
+            }
+        };
+        return Ok(e);
     }
 
     /*
 
         1. Transforms callbacks to loopbacks, and lifts callback bodies to self.callbacks
 
+        A callback only registers its body, which runs in a later invocation,
+        so the code that follows a callback runs right after the loopback, in
+        the same invocation. Thus, a loopback replaces its callback in place,
+        wherever the callback occurs: in a branch, in a loop body (which
+        registers the same callback once per iteration), or in the event
+        argument or body of another callback. The body only refers to its
+        enclosing scope through the closure (the first callback argument),
+        which the loopback passes along.
+
+        2. Transforms lambdas to closures, and lifts their bodies the same way
+
+        A call to a closure runs its body synchronously, in a recursive call
+        of the generated function with the id of the body.
+
     */
-    pub fn lift(&mut self, exp: &Exp) -> Exp {
-        let base = self.lift_exp(exp);
+    pub fn lift(&mut self, exp: &Exp) -> Result<Exp, Error> {
+        let base = self.lift_exp(exp, &Path::Root)?;
         let t = self.callbacks.iter().fold(base, |acc, (k, v)| {
             if_(
                 binop(&StrictEq, id(&self.cbid), integer(*k)),
@@ -180,6 +222,84 @@ impl LiftCallbacks {
             )
         });
         //println!("{:?}", self.callbacks);
-        return t;
+        return Ok(t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::syntax::parse;
+    use super::*;
+
+    /// Lifts a trace in the text syntax, which must already be in the form
+    /// that `Transformer` produces, and checks the result.
+    fn assert_lifts_to(text: &str, expected: &str) {
+        let exp = parse(text).expect("parsing the trace");
+        let lifted = LiftCallbacks::new().lift(&exp).expect("lifting the trace");
+        let expected = parse(expected).expect("parsing the expected trace");
+        assert_eq!(lifted.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn callback_in_the_event_argument_of_a_callback() {
+        assert_lifts_to(
+            "(block [
+              (callback get (callback listen 0.0 [clos req] (object) [(primApp send 1.0)])
+                [clos resp] (object) [(primApp send 2.0)])
+            ])",
+            "(if (=== arg_cbid 2) [
+              (let clos (ref (index arg_cbargs 0)))
+              (let req (ref (index arg_cbargs 1)))
+              (primApp send 1.0)
+            ] [
+              (if (=== arg_cbid 1) [
+                (let clos (ref (index arg_cbargs 0)))
+                (let resp (ref (index arg_cbargs 1)))
+                (primApp send 2.0)
+              ] [
+                (block [
+                  (loopback get (loopback listen 0.0 (object) 2) (object) 1)
+                ])
+              ])
+            ])",
+        );
+    }
+
+    #[test]
+    fn statements_around_every_callback_stay_in_place() {
+        assert_lifts_to(
+            "(block [
+              (callback listen 0.0 [clos req] (object) [
+                (let x 1.0)
+                (callback get \"a\" [clos r] (object) [(primApp send 1.0)])
+                (let y 2.0)
+                (callback get \"b\" [clos r] (object) [(primApp send 3.0)])
+                (primApp send (+ x y))
+              ])
+            ])",
+            "(if (=== arg_cbid 1) [
+              (let clos (ref (index arg_cbargs 0)))
+              (let req (ref (index arg_cbargs 1)))
+              (let x 1.0)
+              (loopback get \"a\" (object) 2)
+              (let y 2.0)
+              (loopback get \"b\" (object) 3)
+              (primApp send (+ x y))
+            ] [
+              (if (=== arg_cbid 3) [
+                (let clos (ref (index arg_cbargs 0)))
+                (let r (ref (index arg_cbargs 1)))
+                (primApp send 3.0)
+              ] [
+                (if (=== arg_cbid 2) [
+                  (let clos (ref (index arg_cbargs 0)))
+                  (let r (ref (index arg_cbargs 1)))
+                  (primApp send 1.0)
+                ] [
+                  (block [(loopback listen 0.0 (object) 1)])
+                ])
+              ])
+            ])",
+        );
     }
 }
@@ -0,0 +1,454 @@
+//! Merges the traces that several tracing sessions produce for the same
+//! function. A trace only records the branches that its requests took, and
+//! marks the rest with `Exp::Unknown`. So, wherever one trace has an
+//! `Unknown` and the other has a concrete branch, the merged trace takes the
+//! concrete branch. Every other node must be the same in both traces. When it
+//! is not, the merged trace keeps the node from the first trace and the
+//! merge records a `Conflict`.
+use indexmap::IndexMap;
+use std::fmt;
+
+use super::error::Path;
+use super::types::{constructors::*, Exp, Exp::*};
+
+/// A node where the two traces disagree.
+#[derive(Debug)]
+pub struct Conflict {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "conflict at {}: {}", self.path, self.message)
+    }
+}
+
+/// Abbreviates an `Exp` for a `Conflict`, since the two subtrees may be
+/// arbitrarily large.
+fn summary(exp: &Exp) -> String {
+    let s = exp.to_string();
+    if s.chars().count() <= 60 {
+        return s;
+    }
+    let prefix: String = s.chars().take(60).collect();
+    return format!("{}...", prefix);
+}
+
+#[derive(Default)]
+pub struct Merge {
+    conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    pub fn new() -> Merge {
+        return Merge { conflicts: vec![] };
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        return &self.conflicts;
+    }
+
+    fn conflict(&mut self, path: &Path, message: String) {
+        self.conflicts.push(Conflict {
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    /// Merges two blocks. An `Unknown` can only appear as the last statement
+    /// of a block, so it stands for all the statements that the other block
+    /// has from that point on.
+    fn merge_block(
+        &mut self, block1: &[Exp], block2: &[Exp], path: &Path, field: &'static str,
+    ) -> Vec<Exp> {
+        let mut ret = vec![];
+        for i in 0..block1.len().max(block2.len()) {
+            match (block1.get(i), block2.get(i)) {
+                (Some(Unknown {}), _) if i == block1.len() - 1 => {
+                    ret.extend(block2[i..].iter().cloned());
+                    return ret;
+                }
+                (_, Some(Unknown {})) if i == block2.len() - 1 => {
+                    ret.extend(block1[i..].iter().cloned());
+                    return ret;
+                }
+                (Some(e1), Some(e2)) => ret.push(self.merge_exp(e1, e2, &path.index(field, i))),
+                _ => break,
+            }
+        }
+        if block1.len() != block2.len() {
+            self.conflict(
+                &path.field(field),
+                format!("blocks of length {} and {}", block1.len(), block2.len()),
+            );
+            return block1.to_vec();
+        }
+        return ret;
+    }
+
+    /// Merges two argument lists, which must have the same length.
+    fn merge_list(
+        &mut self, exps1: &[Exp], exps2: &[Exp], path: &Path, field: &'static str,
+    ) -> Vec<Exp> {
+        if exps1.len() != exps2.len() {
+            self.conflict(
+                &path.field(field),
+                format!("lists of length {} and {}", exps1.len(), exps2.len()),
+            );
+            return exps1.to_vec();
+        }
+        let mut ret = vec![];
+        for (i, (e1, e2)) in exps1.iter().zip(exps2.iter()).enumerate() {
+            ret.push(self.merge_exp(e1, e2, &path.index(field, i)));
+        }
+        return ret;
+    }
+
+    fn merge_tenv(
+        &mut self, tenv1: &IndexMap<String, Exp>, tenv2: &IndexMap<String, Exp>, path: &Path,
+        field: &'static str,
+    ) -> IndexMap<String, Exp> {
+        let path = path.field(field);
+        if tenv1.len() != tenv2.len() || tenv1.keys().any(|k| !tenv2.contains_key(k)) {
+            let mut keys1: Vec<&String> = tenv1.keys().collect();
+            let mut keys2: Vec<&String> = tenv2.keys().collect();
+            keys1.sort();
+            keys2.sort();
+            self.conflict(&path, format!("keys {:?} and {:?}", keys1, keys2));
+            return tenv1.clone();
+        }
+        let mut ret = IndexMap::new();
+        for (k, e1) in tenv1.iter() {
+            ret.insert(k.to_string(), self.merge_exp(e1, &tenv2[k], &path));
+        }
+        return ret;
+    }
+
+    fn merge_exp(&mut self, exp1: &Exp, exp2: &Exp, path: &Path) -> Exp {
+        let e = match (exp1, exp2) {
+            (Unknown {}, _) => exp2.clone(),
+            (_, Unknown {}) => exp1.clone(),
+            (
+                From {
+                    exp: e1,
+                    field: field1,
+                },
+                From {
+                    exp: e2,
+                    field: field2,
+                },
+            ) if field1 == field2 => from(self.merge_exp(e1, e2, &path.field("exp")), field1),
+            (
+                Get {
+                    exp: e1,
+                    field: field1,
+                },
+                Get {
+                    exp: e2,
+                    field: field2,
+                },
+            ) if field1 == field2 => get(self.merge_exp(e1, e2, &path.field("exp")), field1),
+            (
+                BinOp {
+                    op: op1,
+                    e1: e11,
+                    e2: e12,
+                },
+                BinOp {
+                    op: op2,
+                    e1: e21,
+                    e2: e22,
+                },
+            ) if op1 == op2 => binop(
+                op1,
+                self.merge_exp(e11, e21, &path.field("e1")),
+                self.merge_exp(e12, e22, &path.field("e2")),
+            ),
+            (Op1 { op: o1, e: e1 }, Op1 { op: o2, e: e2 }) if o1 == o2 => {
+                op1(o1, self.merge_exp(e1, e2, &path.field("e")))
+            }
+            (
+                If {
+                    cond: cond1,
+                    true_part: true_part1,
+                    false_part: false_part1,
+                },
+                If {
+                    cond: cond2,
+                    true_part: true_part2,
+                    false_part: false_part2,
+                },
+            ) => if_(
+                self.merge_exp(cond1, cond2, &path.field("cond")),
+                self.merge_block(true_part1, true_part2, path, "truePart"),
+                self.merge_block(false_part1, false_part2, path, "falsePart"),
+            ),
+            (
+                While {
+                    cond: cond1,
+                    body: body1,
+                },
+                While {
+                    cond: cond2,
+                    body: body2,
+                },
+            ) => while_(
+                self.merge_exp(cond1, cond2, &path.field("cond")),
+                self.merge_block(body1, body2, path, "body"),
+            ),
+            (
+                Let {
+                    name: name1,
+                    named: named1,
+                },
+                Let {
+                    name: name2,
+                    named: named2,
+                },
+            ) if name1 == name2 => {
+                let_(name1, self.merge_exp(named1, named2, &path.field("named")))
+            }
+            (
+                Set {
+                    name: name1,
+                    named: named1,
+                },
+                Set {
+                    name: name2,
+                    named: named2,
+                },
+            ) if name1 == name2 => set(
+                name1.clone(),
+                self.merge_exp(named1, named2, &path.field("named")),
+            ),
+            (Block { body: body1 }, Block { body: body2 }) => {
+                block(self.merge_block(body1, body2, path, "body"))
+            }
+            (
+                Callback {
+                    event: event1,
+                    event_arg: event_arg1,
+                    callback_args: callback_args1,
+                    callback_clos: callback_clos1,
+                    body: body1,
+                },
+                Callback {
+                    event: event2,
+                    event_arg: event_arg2,
+                    callback_args: callback_args2,
+                    callback_clos: callback_clos2,
+                    body: body2,
+                },
+            ) if event1 == event2 && callback_args1 == callback_args2 => callback(
+                event1,
+                self.merge_exp(event_arg1, event_arg2, &path.field("eventArg")),
+                callback_args1.to_vec(),
+                self.merge_exp(callback_clos1, callback_clos2, &path.field("clos")),
+                self.merge_block(body1, body2, path, "body"),
+            ),
+            (
+                Lambda {
+                    args: args1,
+                    clos: clos1,
+                    body: body1,
+                },
+                Lambda {
+                    args: args2,
+                    clos: clos2,
+                    body: body2,
+                },
+            ) if args1 == args2 => lambda(
+                args1.to_vec(),
+                self.merge_exp(clos1, clos2, &path.field("clos")),
+                self.merge_block(body1, body2, path, "body"),
+            ),
+            (
+                Call {
+                    e: e1,
+                    call_args: call_args1,
+                },
+                Call {
+                    e: e2,
+                    call_args: call_args2,
+                },
+            ) => call(
+                self.merge_exp(e1, e2, &path.field("e")),
+                self.merge_list(call_args1, call_args2, path, "callArgs"),
+            ),
+            (
+                Label {
+                    name: name1,
+                    body: body1,
+                },
+                Label {
+                    name: name2,
+                    body: body2,
+                },
+            ) if name1 == name2 => label(name1, self.merge_block(body1, body2, path, "body")),
+            (
+                Break {
+                    name: name1,
+                    value: value1,
+                },
+                Break {
+                    name: name2,
+                    value: value2,
+                },
+            ) if name1 == name2 => {
+                break_(name1, self.merge_exp(value1, value2, &path.field("value")))
+            }
+            (
+                Object {
+                    properties: properties1,
+                },
+                Object {
+                    properties: properties2,
+                },
+            ) => obj(self.merge_tenv(properties1, properties2, path, "properties")),
+            (Clos { tenv: tenv1 }, Clos { tenv: tenv2 }) => Clos {
+                tenv: self.merge_tenv(tenv1, tenv2, path, "tenv"),
+            },
+            (Array { exps: exps1 }, Array { exps: exps2 }) => {
+                array(self.merge_list(exps1, exps2, path, "exps"))
+            }
+            (Index { e1: e11, e2: e12 }, Index { e1: e21, e2: e22 }) => index_(
+                self.merge_exp(e11, e21, &path.field("exp")),
+                self.merge_exp(e12, e22, &path.field("index")),
+            ),
+            (
+                PrimApp {
+                    event: event1,
+                    event_args: event_args1,
+                },
+                PrimApp {
+                    event: event2,
+                    event_args: event_args2,
+                },
+            ) if event1 == event2 => prim_app(
+                event1,
+                self.merge_list(event_args1, event_args2, path, "eventArgs"),
+            ),
+            (
+                MethodCall {
+                    e: e1,
+                    method: method1,
+                    method_call_args: method_call_args1,
+                },
+                MethodCall {
+                    e: e2,
+                    method: method2,
+                    method_call_args: method_call_args2,
+                },
+            ) if method1 == method2 => method_call(
+                self.merge_exp(e1, e2, &path.field("e")),
+                method1,
+                self.merge_list(method_call_args1, method_call_args2, path, "methodCallArgs"),
+            ),
+            (Throw { e: e1 }, Throw { e: e2 }) => throw_(self.merge_exp(e1, e2, &path.field("e"))),
+            (Located { loc: loc1, e: e1 }, Located { loc: loc2, e: e2 }) if loc1 == loc2 => {
+                located(loc1, self.merge_exp(e1, e2, &path.field("e")))
+            }
+            (
+                Try {
+                    body: body1,
+                    catch_name: catch_name1,
+                    handler: handler1,
+                    finalizer: finalizer1,
+                },
+                Try {
+                    body: body2,
+                    catch_name: catch_name2,
+                    handler: handler2,
+                    finalizer: finalizer2,
+                },
+            ) if catch_name1 == catch_name2 && handler1.is_some() == handler2.is_some() => try_(
+                self.merge_block(body1, body2, path, "body"),
+                catch_name1.as_deref(),
+                match (handler1, handler2) {
+                    (Some(handler1), Some(handler2)) => {
+                        Some(self.merge_block(handler1, handler2, path, "handler"))
+                    }
+                    _ => None,
+                },
+                self.merge_block(finalizer1, finalizer2, path, "finalizer"),
+            ),
+            // The remaining nodes are either leaves or do not occur in traces.
+            _ if exp1 == exp2 => exp1.clone(),
+            _ => {
+                self.conflict(path, format!("{} and {}", summary(exp1), summary(exp2)));
+                exp1.clone()
+            }
+        };
+        return e;
+    }
+
+    /*
+
+        1. Fills the Unknown leaves of each trace with the branches of the other
+        2. Records a Conflict for every other node where they differ
+
+    */
+    pub fn merge(&mut self, exp1: &Exp, exp2: &Exp) -> Exp {
+        return self.merge_exp(exp1, exp2, &Path::Root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::syntax::parse;
+    use super::*;
+
+    /// Merges two traces in the text syntax, and returns the merged trace
+    /// and the number of conflicts.
+    fn merge(text1: &str, text2: &str) -> (String, usize) {
+        let exp1 = parse(text1).expect("parsing the first trace");
+        let exp2 = parse(text2).expect("parsing the second trace");
+        let mut merge = Merge::new();
+        let merged = merge.merge(&exp1, &exp2);
+        return (merged.to_string(), merge.conflicts().len());
+    }
+
+    #[test]
+    fn same_shape() {
+        let trace = "(block [(let x (get req n)) (if (> x 0) [(primApp send x)] [unknown])])";
+        assert_eq!(merge(trace, trace), (trace.to_string(), 0));
+    }
+
+    #[test]
+    fn newly_covered_branch() {
+        let (merged, conflicts) = merge(
+            "(block [(let x (get req n)) (if (> x 0) [(primApp send x)] [unknown])])",
+            "(block [(let x (get req n)) (if (> x 0) [unknown] [(primApp send 0)])])",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            merged,
+            "(block [(let x (get req n)) (if (> x 0) [(primApp send x)] [(primApp send 0)])])"
+        );
+    }
+
+    #[test]
+    fn newly_covered_statements() {
+        let (merged, conflicts) = merge(
+            "(block [(let x 1) unknown])",
+            "(block [(let x 1) (primApp send x)])",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(merged, "(block [(let x 1) (primApp send x)])");
+    }
+
+    #[test]
+    fn conflicting_branch() {
+        let (merged, conflicts) = merge(
+            "(block [(if (> x 0) [(primApp send 1)] [unknown])])",
+            "(block [(if (> x 0) [(primApp send 2)] [unknown])])",
+        );
+        assert_eq!(conflicts, 1);
+        // The merged trace keeps the first trace.
+        assert_eq!(
+            merged,
+            "(block [(if (> x 0) [(primApp send 1)] [unknown])])"
+        );
+    }
+}
@@ -1,30 +1,86 @@
-mod assertions;
 mod codegen;
 mod error;
+mod escape_analysis;
 mod lift_callbacks;
+mod merge;
 mod rustify;
+mod stats;
 mod transformer;
-mod types;
+mod type_inference;
+mod validator;
 
-use assertions::Assertions;
+use escape_analysis::EscapeAnalysis;
 use lift_callbacks::LiftCallbacks;
+pub use merge::Merge;
 use rustify::Rustify;
+use shared::trace::{syntax, types};
+pub use stats::Stats;
 use transformer::Transformer;
-use types::Exp;
+use type_inference::TypeInference;
+pub use types::Exp;
+use validator::Validator;
 
-pub fn compile(
-    _serverless_function_name: String, dest_file: &str, trace: &str,
-) -> Result<(), error::Error> {
-    let exp = serde_json::from_str::<Exp>(trace)?;
-    let mut assertions = Assertions::new();
-    assertions.assert_supposed_grammar(&exp);
-    assertions.assert_unique_names(&exp);
+/// The passes of the trace compiler, in the order in which they run. The
+/// controller never dumps IR, but the offline trace compiler does.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Pass {
+    Validator,
+    Transformer,
+    EscapeAnalysis,
+    LiftCallbacks,
+    TypeInference,
+    Rustify,
+}
+
+fn dump(dumps: &[Pass], pass: Pass, exp: &Exp) {
+    if dumps.contains(&pass) {
+        println!("; IR after {:?}\n{:#}\n", pass, exp);
+    }
+}
+
+/// Parses a trace, which is JSON that the tracing runtime produces.
+pub fn parse(trace: &str) -> Result<Exp, error::Error> {
+    return Ok(serde_json::from_str::<Exp>(trace)?);
+}
+
+/// Parses a trace in the text syntax of the `syntax` module. The controller
+/// only reads JSON, but the offline trace compiler reads both.
+pub fn parse_text(trace: &str) -> Result<Exp, error::Error> {
+    return Ok(syntax::parse(trace)?);
+}
+
+/// Runs the passes up to and including `LiftCallbacks`. The dispatcher can
+/// interpret the result until the compiled trace is deployed.
+pub fn lift(exp: &Exp, dumps: &[Pass]) -> Result<Exp, error::Error> {
+    let diagnostics = Validator::new().validate(exp);
+    if !diagnostics.is_empty() {
+        return Err(error::Error::Invalid(diagnostics));
+    }
+    dump(dumps, Pass::Validator, exp);
     let mut transformer = Transformer::new();
-    let exp2 = transformer.transform(&exp);
+    let mut exp2 = transformer.transform(exp)?;
+    dump(dumps, Pass::Transformer, &exp2);
+    let mut escape_analysis = EscapeAnalysis::new();
+    escape_analysis.analyze(&mut exp2);
+    dump(dumps, Pass::EscapeAnalysis, &exp2);
     let mut lift_callbacks = LiftCallbacks::new();
-    let mut exp3 = lift_callbacks.lift(&exp2);
+    let exp3 = lift_callbacks.lift(&exp2)?;
+    dump(dumps, Pass::LiftCallbacks, &exp3);
+    return Ok(exp3);
+}
+
+/// Compiles the trace `exp` to Rust and writes the result to `dest_file`. The
+/// IR is printed to standard output after every pass in `dumps`.
+pub fn compile(
+    _serverless_function_name: String, dest_file: &str, exp: &Exp, dumps: &[Pass],
+) -> Result<(), error::Error> {
+    let mut exp3 = lift(exp, dumps)?;
+    let mut type_inference = TypeInference::new();
+    type_inference.infer(&mut exp3);
+    dump(dumps, Pass::TypeInference, &exp3);
     let mut rustify = Rustify::new();
-    rustify.rustify(&mut exp3);
-    codegen::codegen(&exp3, dest_file);
+    rustify.rustify(&mut exp3)?;
+    dump(dumps, Pass::Rustify, &exp3);
+    codegen::codegen(&exp3, dest_file)?;
     Ok(())
 }
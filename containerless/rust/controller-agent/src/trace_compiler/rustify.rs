@@ -2,8 +2,11 @@
 /// but get in the way of generated code. Most of the pedantry manifests as
 /// warnings, which are merely irritating. However, some pedantry manifests
 /// as type errors that we have to address. This module handles both.
+use super::error::{unexpected, Error, Path};
 use super::types::Exp;
 
+const PASS: &str = "Rustify";
+
 pub struct Rustify {}
 
 impl Rustify {
@@ -11,10 +14,12 @@ impl Rustify {
         Rustify {}
     }
 
-    pub fn rustify_block(&mut self, block: &mut Vec<Exp>) {
+    fn rustify_block(
+        &mut self, block: &mut [Exp], path: &Path, field: &'static str,
+    ) -> Result<(), Error> {
         let len = block.len();
         for (ix, e) in block.iter_mut().enumerate() {
-            self.rustify(e);
+            self.rustify_exp(e, &path.index(field, ix))?;
             if ix < len - 1 {
                 if let Exp::Let { name: _, named: _ } = e {
                     // empty body
@@ -26,58 +31,44 @@ impl Rustify {
                     };
                 }
 
-            // We treat `if` as expressions in our IR. Conveniently, `if`
-            // in Rust is an expression too! However, when an `if` is in
-            // a block and *not* the last expression in the block, Rust
-            // requires both branches of the `if` to return `()`. This is
-            // a hack that transforms `if ...` into `let _ = if ...`, which
-            // is enough to satisfy the Rust type checker.
-            /*
-            if let Exp::If {
-                cond: _,
-                true_part: _,
-                false_part: _,
-            } = e
-            {
-                let inner = std::mem::replace(e, Exp::Undefined {});
-                *e = Exp::Let {
-                    name: "_".to_string(),
-                    typ: None,
-                    named: Box::new(inner),
-                };
-            } else if let Exp::Label {
-                name,
-                body: _
-            } = e
-            {
-                let inner = std::mem::replace(e, Exp::Undefined {});
-                *e = Exp::Let {
-                    name: "l".to_string(),
-                    typ: None,
-                    named: Box::new(inner)
-                };
-            }
-            */
-            } else {
-                if let Exp::Label { name: _, body: _ } = e {
+                // We treat `if` as expressions in our IR. Conveniently, `if`
+                // in Rust is an expression too! However, when an `if` is in
+                // a block and *not* the last expression in the block, Rust
+                // requires both branches of the `if` to return `()`. This is
+                // a hack that transforms `if ...` into `let _ = if ...`, which
+                // is enough to satisfy the Rust type checker.
+                /*
+                if let Exp::If {
+                    cond: _,
+                    true_part: _,
+                    false_part: _,
+                } = e
+                {
                     let inner = std::mem::replace(e, Exp::Undefined {});
-                    *e = Exp::Block {
-                        body: vec![
-                            Exp::Let {
-                                name: "l".to_string(),
-                                named: Box::new(inner),
-                            },
-                            Exp::Identifier {
-                                name: "l".to_string(),
-                            },
-                        ],
+                    *e = Exp::Let {
+                        name: "_".to_string(),
+                        typ: None,
+                        named: Box::new(inner),
+                    };
+                } else if let Exp::Label {
+                    name,
+                    body: _
+                } = e
+                {
+                    let inner = std::mem::replace(e, Exp::Undefined {});
+                    *e = Exp::Let {
+                        name: "l".to_string(),
+                        typ: None,
+                        named: Box::new(inner)
                     };
                 }
+                */
             }
         }
+        return Ok(());
     }
 
-    pub fn rustify(&mut self, exp: &mut Exp) {
+    fn rustify_exp(&mut self, exp: &mut Exp, path: &Path) -> Result<(), Error> {
         match exp {
             Exp::Unknown {} => (),
             Exp::Integer { value: _ } => (),
@@ -85,52 +76,59 @@ impl Rustify {
             Exp::Bool { value: _ } => (),
             Exp::Stringg { value: _ } => (),
             Exp::Undefined {} => (),
+            Exp::Null {} => (),
             Exp::Unit {} => (),
             Exp::Identifier { name: _ } => (),
-            Exp::From { exp, field: _ } => self.rustify(exp),
-            Exp::Get { exp, field: _ } => self.rustify(exp),
-            Exp::Op1 { op: _, e } => self.rustify(e),
+            Exp::From { exp, field: _ } => self.rustify_exp(exp, &path.field("exp"))?,
+            Exp::Get { exp, field: _ } => self.rustify_exp(exp, &path.field("exp"))?,
+            Exp::Op1 { op: _, e } => self.rustify_exp(e, &path.field("e"))?,
             Exp::BinOp { op: _, e1, e2 } => {
-                self.rustify(e1);
-                self.rustify(e2);
+                self.rustify_exp(e1, &path.field("e1"))?;
+                self.rustify_exp(e2, &path.field("e2"))?;
             }
             Exp::If {
                 cond,
                 true_part,
                 false_part,
             } => {
-                self.rustify(cond);
-                self.rustify_block(true_part);
-                self.rustify_block(false_part);
+                self.rustify_exp(cond, &path.field("cond"))?;
+                self.rustify_block(true_part, path, "truePart")?;
+                self.rustify_block(false_part, path, "falsePart")?;
             }
             Exp::While { cond, body } => {
-                self.rustify(cond);
-                self.rustify_block(body);
+                self.rustify_exp(cond, &path.field("cond"))?;
+                self.rustify_block(body, path, "body")?;
                 let inner = std::mem::replace(&mut *exp, Exp::Undefined {});
                 *exp = Exp::Block {
                     body: vec![inner, Exp::Undefined {}],
                 };
             }
-            Exp::Let { name: _, named } => self.rustify(named),
-            Exp::Set { name: _, named } => self.rustify(named),
-            Exp::Block { body } => self.rustify_block(body),
-            Exp::Callback {
-                event: _,
-                event_arg: _,
-                callback_args: _,
-                callback_clos: _,
-                body: _,
-            } => panic!("Exp::Callback should be eliminated"),
+            Exp::Let { name: _, named } => self.rustify_exp(named, &path.field("named"))?,
+            Exp::Set { name: _, named } => self.rustify_exp(named, &path.field("named"))?,
+            Exp::Block { body } => self.rustify_block(body, path, "body")?,
+            Exp::Callback { .. } => {
+                return unexpected(PASS, path, "Exp::Callback should be eliminated");
+            }
             Exp::Loopback {
                 event: _,
                 event_arg,
                 callback_clos,
                 id: _,
             } => {
-                self.rustify(event_arg);
-                self.rustify(callback_clos)
+                self.rustify_exp(event_arg, &path.field("eventArg"))?;
+                self.rustify_exp(callback_clos, &path.field("clos"))?;
+            }
+            Exp::Lambda { .. } => {
+                return unexpected(PASS, path, "Exp::Lambda should be eliminated");
+            }
+            Exp::Closure { clos, id: _ } => self.rustify_exp(clos, &path.field("clos"))?,
+            Exp::Call { e, call_args } => {
+                self.rustify_exp(e, &path.field("e"))?;
+                for (i, e) in call_args.iter_mut().enumerate() {
+                    self.rustify_exp(e, &path.index("callArgs", i))?;
+                }
             }
-            Exp::Label { name: _, body } => self.rustify_block(body),
+            Exp::Label { name: _, body } => self.rustify_block(body, path, "body")?,
             // Generating `break 'a break 'b e` produces an unreachable code
             // warning. This simplifies it to `break 'b e`.
             Exp::Break { name: _, value } => match **value {
@@ -138,33 +136,72 @@ impl Rustify {
                     let inner = std::mem::replace(&mut **value, Exp::Unit {});
                     *exp = inner;
                 }
-                _ => self.rustify(value),
+                _ => self.rustify_exp(value, &path.field("value"))?,
             },
-            Exp::Object { properties } => properties.values_mut().for_each(|e| self.rustify(e)),
-            Exp::Clos { tenv: _ } => panic!("Exp::Clos should be eliminated"),
-            Exp::Array { exps } => exps.iter_mut().for_each(|e| self.rustify(e)),
+            Exp::Throw { e } => self.rustify_exp(e, &path.field("e"))?,
+            Exp::Try {
+                body,
+                catch_name: _,
+                handler,
+                finalizer,
+            } => {
+                self.rustify_block(body, path, "body")?;
+                if let Some(handler) = handler {
+                    self.rustify_block(handler, path, "handler")?;
+                }
+                self.rustify_block(finalizer, path, "finalizer")?;
+            }
+            Exp::Object { properties } => {
+                let properties_path = path.field("properties");
+                for e in properties.values_mut() {
+                    self.rustify_exp(e, &properties_path)?;
+                }
+            }
+            Exp::Clos { tenv: _ } => {
+                return unexpected(PASS, path, "Exp::Clos should be eliminated");
+            }
+            Exp::Array { exps } => {
+                for (i, e) in exps.iter_mut().enumerate() {
+                    self.rustify_exp(e, &path.index("exps", i))?;
+                }
+            }
             Exp::Index { e1, e2 } => {
-                self.rustify(e1);
-                self.rustify(e2);
+                self.rustify_exp(e1, &path.field("exp"))?;
+                self.rustify_exp(e2, &path.field("index"))?;
             }
-            Exp::Ref { e } => self.rustify(e),
-            Exp::Deref { e } => self.rustify(e),
+            Exp::Ref { e } => self.rustify_exp(e, &path.field("e"))?,
+            Exp::Located { loc: _, e } => self.rustify_exp(e, &path.field("e"))?,
+            Exp::Unboxed { typ: _, e } | Exp::Boxed { typ: _, e } | Exp::Guard { typ: _, e } => {
+                self.rustify_exp(e, &path.field("e"))?
+            }
+            Exp::Deref { e } => self.rustify_exp(e, &path.field("e"))?,
             Exp::SetRef { e1, e2 } => {
-                self.rustify(e1);
-                self.rustify(e2);
+                self.rustify_exp(e1, &path.field("e1"))?;
+                self.rustify_exp(e2, &path.field("e2"))?;
             }
             Exp::PrimApp {
                 event: _,
                 event_args,
-            } => event_args.iter_mut().for_each(|e| self.rustify(e)),
+            } => {
+                for (i, e) in event_args.iter_mut().enumerate() {
+                    self.rustify_exp(e, &path.index("eventArgs", i))?;
+                }
+            }
             Exp::MethodCall {
                 e,
                 method: _,
                 method_call_args,
             } => {
-                self.rustify(e);
-                method_call_args.iter_mut().for_each(|e| self.rustify(e));
+                self.rustify_exp(e, &path.field("e"))?;
+                for (i, e) in method_call_args.iter_mut().enumerate() {
+                    self.rustify_exp(e, &path.index("methodCallArgs", i))?;
+                }
             }
         }
+        return Ok(());
+    }
+
+    pub fn rustify(&mut self, exp: &mut Exp) -> Result<(), Error> {
+        return self.rustify_exp(exp, &Path::Root);
     }
 }
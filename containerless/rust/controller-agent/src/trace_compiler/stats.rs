@@ -0,0 +1,246 @@
+//! Summarizes how much of a function a trace covers, which helps decide if
+//! the function is worth compiling. The analysis runs on the trace that the
+//! tracing runtime produces, before any pass transforms it.
+use serde::Serialize;
+
+use super::types::{Exp, LVal};
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TraceStats {
+    /// The number of `Exp` nodes in the trace.
+    pub nodes: usize,
+    /// The number of `Exp::Unknown` leaves, i.e., paths that no request took.
+    pub unknowns: usize,
+    /// The number of `If` nodes.
+    pub ifs: usize,
+    /// The number of `If` nodes whose true part or false part is unexplored.
+    pub partial_ifs: usize,
+    /// The number of callbacks.
+    pub callbacks: usize,
+    /// The depth of the most deeply nested `While` loop.
+    pub max_loop_depth: usize,
+}
+
+#[derive(Default)]
+pub struct Stats {
+    stats: TraceStats,
+    loop_depth: usize,
+}
+
+/// A block is unexplored when the trace never entered it.
+fn is_unexplored(block: &[Exp]) -> bool {
+    return matches!(block, [Exp::Unknown {}]);
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        return Stats {
+            stats: TraceStats::default(),
+            loop_depth: 0,
+        };
+    }
+
+    fn count_exps(&mut self, exps: &[Exp]) {
+        for e in exps.iter() {
+            self.count(e);
+        }
+    }
+
+    fn count(&mut self, exp: &Exp) {
+        self.stats.nodes += 1;
+        match exp {
+            Exp::Unknown {} => self.stats.unknowns += 1,
+            Exp::Integer { .. }
+            | Exp::Number { .. }
+            | Exp::Bool { .. }
+            | Exp::Identifier { .. }
+            | Exp::Stringg { .. }
+            | Exp::Undefined {}
+            | Exp::Null {}
+            | Exp::Unit {} => (),
+            Exp::From { exp, field: _ } | Exp::Get { exp, field: _ } => self.count(exp),
+            Exp::BinOp { op: _, e1, e2 } | Exp::Index { e1, e2 } | Exp::SetRef { e1, e2 } => {
+                self.count(e1);
+                self.count(e2);
+            }
+            Exp::Op1 { op: _, e }
+            | Exp::Ref { e }
+            | Exp::Deref { e }
+            | Exp::Throw { e }
+            | Exp::Located { loc: _, e }
+            | Exp::Unboxed { typ: _, e }
+            | Exp::Boxed { typ: _, e }
+            | Exp::Guard { typ: _, e } => self.count(e),
+            Exp::If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                self.stats.ifs += 1;
+                if is_unexplored(true_part) || is_unexplored(false_part) {
+                    self.stats.partial_ifs += 1;
+                }
+                self.count(cond);
+                self.count_exps(true_part);
+                self.count_exps(false_part);
+            }
+            Exp::While { cond, body } => {
+                self.loop_depth += 1;
+                if self.loop_depth > self.stats.max_loop_depth {
+                    self.stats.max_loop_depth = self.loop_depth;
+                }
+                self.count(cond);
+                self.count_exps(body);
+                self.loop_depth -= 1;
+            }
+            Exp::Let { name: _, named } => self.count(named),
+            Exp::Set { name, named } => {
+                match name {
+                    LVal::Identifier { name: _ } => (),
+                    LVal::From { exp, field: _ } => self.count(exp),
+                    LVal::Index { exp, index } => {
+                        self.count(exp);
+                        self.count(index);
+                    }
+                }
+                self.count(named);
+            }
+            Exp::Block { body } | Exp::Label { name: _, body } => self.count_exps(body),
+            Exp::Callback {
+                event: _,
+                event_arg,
+                callback_args: _,
+                callback_clos,
+                body,
+            } => {
+                self.stats.callbacks += 1;
+                self.count(event_arg);
+                self.count(callback_clos);
+                self.count_exps(body);
+            }
+            Exp::Loopback {
+                event: _,
+                event_arg,
+                callback_clos,
+                id: _,
+            } => {
+                self.count(event_arg);
+                self.count(callback_clos);
+            }
+            Exp::Lambda {
+                args: _,
+                clos,
+                body,
+            } => {
+                self.count(clos);
+                self.count_exps(body);
+            }
+            Exp::Closure { clos, id: _ } => self.count(clos),
+            Exp::Call { e, call_args } => {
+                self.count(e);
+                self.count_exps(call_args);
+            }
+            Exp::Break { name: _, value } => self.count(value),
+            Exp::Object { properties } => properties.values().for_each(|e| self.count(e)),
+            Exp::Clos { tenv } => tenv.values().for_each(|e| self.count(e)),
+            Exp::Array { exps } => self.count_exps(exps),
+            Exp::PrimApp {
+                event: _,
+                event_args,
+            } => self.count_exps(event_args),
+            Exp::MethodCall {
+                e,
+                method: _,
+                method_call_args,
+            } => {
+                self.count(e);
+                self.count_exps(method_call_args);
+            }
+            Exp::Try {
+                body,
+                catch_name: _,
+                handler,
+                finalizer,
+            } => {
+                self.count_exps(body);
+                if let Some(handler) = handler {
+                    self.count_exps(handler);
+                }
+                self.count_exps(finalizer);
+            }
+        }
+    }
+
+    pub fn stats(&mut self, exp: &Exp) -> TraceStats {
+        self.count(exp);
+        return self.stats.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::syntax::parse;
+    use super::*;
+
+    fn stats(text: &str) -> TraceStats {
+        let exp = parse(text).expect("parsing the trace");
+        return Stats::new().stats(&exp);
+    }
+
+    #[test]
+    fn nodes_count_every_expression() {
+        // block, let, 1, primApp, +, x and 2.
+        assert_eq!(stats("(block [(let x 1) (primApp send (+ x 2))])").nodes, 7);
+        assert_eq!(stats("unknown").nodes, 1);
+    }
+
+    #[test]
+    fn unknowns_and_partial_ifs() {
+        let stats = stats(
+            "(block [
+              (if (> x 0) [(primApp send x)] [unknown])
+              (if b [unknown] [unknown])
+              (if c [(let y 1)] [(let z 2)])
+              (if d [unknown (let w 1)] [])
+            ])",
+        );
+        assert_eq!(stats.ifs, 4);
+        // A part is unexplored only if it is exactly `[unknown]`.
+        assert_eq!(stats.partial_ifs, 2);
+        assert_eq!(stats.unknowns, 4);
+    }
+
+    #[test]
+    fn callbacks_in_callbacks() {
+        let stats = stats(
+            "(block [
+              (callback listen 0.0 [clos req] (clos) [
+                (callback get \"http://example.com\" [clos resp] (clos) [
+                  (primApp send resp)
+                ])
+                (callback get \"http://example.com/2\" [clos resp] (clos) [unknown])
+              ])
+            ])",
+        );
+        assert_eq!(stats.callbacks, 3);
+        assert_eq!(stats.unknowns, 1);
+        assert_eq!(stats.max_loop_depth, 0);
+    }
+
+    #[test]
+    fn max_loop_depth_of_nested_whiles() {
+        let stats = stats(
+            "(block [
+              (while a [
+                (while b [
+                  (if c [(while d [])] [])
+                ])
+              ])
+              (while e [])
+            ])",
+        );
+        assert_eq!(stats.max_loop_depth, 3);
+        assert_eq!(stats.ifs, 1);
+        assert_eq!(stats.partial_ifs, 0);
+    }
+}
@@ -1,6 +1,10 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
+use super::error::{unexpected, unsupported, Error, Path};
 use super::types::{constructors::*, Exp, Exp::*, LVal};
+use shared::trace::{METHODS, REST, VARIADIC};
+
+const PASS: &str = "Transformer";
 
 pub struct Transformer {}
 
@@ -9,133 +13,191 @@ impl Transformer {
         return Transformer {};
     }
 
-    fn transform_exps(&mut self, exps: &[Exp]) -> Vec<Exp> {
+    fn transform_exps(
+        &mut self, exps: &[Exp], path: &Path, field: &'static str,
+    ) -> Result<Vec<Exp>, Error> {
         let mut ret: Vec<Exp> = vec![];
-        for e in exps.iter() {
-            ret.push(self.transform_exp(e));
+        for (i, e) in exps.iter().enumerate() {
+            ret.push(self.transform_exp(e, &path.index(field, i))?);
         }
-        return ret;
+        return Ok(ret);
     }
 
-    fn transform_clos(&mut self, exps: &HashMap<String, Exp>) -> HashMap<String, Exp> {
-        let mut ret: HashMap<String, Exp> = HashMap::new();
+    fn transform_clos(
+        &mut self, exps: &IndexMap<String, Exp>, path: &Path,
+    ) -> Result<IndexMap<String, Exp>, Error> {
+        let path = path.field("tenv");
+        let mut ret: IndexMap<String, Exp> = IndexMap::new();
         for (key, value) in exps.iter() {
             let new_value = match value {
                 Identifier { name } => id(name),
-                From { exp, field } => get(self.transform_exp(exp), field),
-                _ => self.transform_exp(value),
+                From { exp, field } => get(self.transform_exp(exp, &path.field("exp"))?, field),
+                _ => self.transform_exp(value, &path)?,
             };
             ret.insert(key.to_string(), new_value);
         }
-        return ret;
+        return Ok(ret);
     }
 
-    fn transform_exp(&mut self, exp: &Exp) -> Exp {
-        match exp {
-            Unknown {} => return unknown(),
-            Number { value } => return number(*value),
-            Bool { value } => return bool_(*value),
-            Identifier { name } => return deref(id(name)),
-            Get { exp, field } => get(self.transform_exp(exp), field),
-            From { exp, field } => return deref(get(self.transform_exp(exp), field)),
-            Index { e1, e2 } => return index_(self.transform_exp(e1), self.transform_exp(e2)),
-            Stringg { value } => return string(value),
-            Undefined {} => return undefined(),
-            BinOp { op, e1, e2 } => {
-                return binop(op, self.transform_exp(e1), self.transform_exp(e2))
-            }
-            Op1 { op, e } => op1(op, self.transform_exp(e)),
+    fn transform_exp(&mut self, exp: &Exp, path: &Path) -> Result<Exp, Error> {
+        let e = match exp {
+            Unknown {} => unknown(),
+            Number { value } => number(*value),
+            Bool { value } => bool_(*value),
+            Identifier { name } => deref(id(name)),
+            Get { exp, field } => get(self.transform_exp(exp, &path.field("exp"))?, field),
+            From { exp, field } => deref(get(self.transform_exp(exp, &path.field("exp"))?, field)),
+            Index { e1, e2 } => index_(
+                self.transform_exp(e1, &path.field("exp"))?,
+                self.transform_exp(e2, &path.field("index"))?,
+            ),
+            Stringg { value } => string(value),
+            Undefined {} => undefined(),
+            Null {} => null(),
+            BinOp { op, e1, e2 } => binop(
+                op,
+                self.transform_exp(e1, &path.field("e1"))?,
+                self.transform_exp(e2, &path.field("e2"))?,
+            ),
+            Op1 { op, e } => op1(op, self.transform_exp(e, &path.field("e"))?),
             If {
                 cond,
                 true_part,
                 false_part,
-            } => {
-                return if_(
-                    self.transform_exp(cond),
-                    self.transform_exps(true_part),
-                    self.transform_exps(false_part),
-                );
-            }
-            While { cond, body } => {
-                return while_(self.transform_exp(cond), self.transform_exps(body))
+            } => if_(
+                self.transform_exp(cond, &path.field("cond"))?,
+                self.transform_exps(true_part, path, "truePart")?,
+                self.transform_exps(false_part, path, "falsePart")?,
+            ),
+            While { cond, body } => while_(
+                self.transform_exp(cond, &path.field("cond"))?,
+                self.transform_exps(body, path, "body")?,
+            ),
+            Let { name, named } => {
+                let_(name, ref_(self.transform_exp(named, &path.field("named"))?))
             }
-            Let { name, named } => return let_(name, ref_(self.transform_exp(named))),
             Set {
                 name: LVal::Identifier { name },
                 named,
-            } => return setref(id(name), self.transform_exp(named)),
+            } => setref(id(name), self.transform_exp(named, &path.field("named"))?),
             Set {
                 name: LVal::From { exp, field },
                 named,
-            } => {
-                return setref(
-                    get(self.transform_exp(exp), field),
-                    self.transform_exp(named),
-                );
-            }
+            } => setref(
+                get(
+                    self.transform_exp(exp, &path.field("name").field("exp"))?,
+                    field,
+                ),
+                self.transform_exp(named, &path.field("named"))?,
+            ),
             Set {
                 name: LVal::Index { exp, index },
                 named,
-            } => {
-                return set(
-                    lval_index(self.transform_exp(exp), self.transform_exp(index)),
-                    self.transform_exp(named),
-                );
-            }
-            Block { body } => return block(self.transform_exps(body)),
+            } => set(
+                lval_index(
+                    self.transform_exp(exp, &path.field("name").field("exp"))?,
+                    self.transform_exp(index, &path.field("name").field("index"))?,
+                ),
+                self.transform_exp(named, &path.field("named"))?,
+            ),
+            Block { body } => block(self.transform_exps(body, path, "body")?),
             Callback {
                 event,
                 event_arg,
                 callback_args,
                 callback_clos,
                 body,
-            } => {
-                return callback(
-                    event,
-                    self.transform_exp(event_arg),
-                    callback_args.to_vec(),
-                    self.transform_exp(callback_clos),
-                    self.transform_exps(body),
-                );
-            }
-            Label { name, body } => return label(name, self.transform_exps(body)),
-            Break { name, value } => return break_(name, self.transform_exp(value)),
-            Clos { tenv } => return obj(self.transform_clos(tenv)),
+            } => callback(
+                event,
+                self.transform_exp(event_arg, &path.field("eventArg"))?,
+                callback_args.to_vec(),
+                self.transform_exp(callback_clos, &path.field("clos"))?,
+                self.transform_exps(body, path, "body")?,
+            ),
+            Lambda { args, clos, body } => lambda(
+                args.to_vec(),
+                self.transform_exp(clos, &path.field("clos"))?,
+                self.transform_exps(body, path, "body")?,
+            ),
+            Call { e, call_args } => call(
+                self.transform_exp(e, &path.field("e"))?,
+                self.transform_exps(call_args, path, "callArgs")?,
+            ),
+            Label { name, body } => label(name, self.transform_exps(body, path, "body")?),
+            Break { name, value } => break_(name, self.transform_exp(value, &path.field("value"))?),
+            Throw { e } => throw_(self.transform_exp(e, &path.field("e"))?),
+            Try {
+                body,
+                catch_name,
+                handler,
+                finalizer,
+            } => try_(
+                self.transform_exps(body, path, "body")?,
+                catch_name.as_deref(),
+                match handler {
+                    None => None,
+                    Some(handler) => Some(self.transform_exps(handler, path, "handler")?),
+                },
+                self.transform_exps(finalizer, path, "finalizer")?,
+            ),
+            Clos { tenv } => obj(self.transform_clos(tenv, path)?),
             Object { properties } => {
-                let mut ret: HashMap<String, Exp> = HashMap::new();
+                let properties_path = path.field("properties");
+                let mut ret: IndexMap<String, Exp> = IndexMap::new();
                 for (key, value) in properties.iter() {
-                    ret.insert(key.to_string(), self.transform_exp(value));
+                    ret.insert(
+                        key.to_string(),
+                        self.transform_exp(value, &properties_path)?,
+                    );
                 }
-                return obj(ret);
+                obj(ret)
             }
-            Array { exps } => return array(self.transform_exps(exps)),
+            Array { exps } => array(self.transform_exps(exps, path, "exps")?),
             PrimApp { event, event_args } => {
-                return prim_app(event, self.transform_exps(event_args));
+                prim_app(event, self.transform_exps(event_args, path, "eventArgs")?)
             }
             MethodCall {
                 e,
                 method,
                 method_call_args,
             } => {
-                return method_call(
-                    self.transform_exp(e),
-                    method,
-                    self.transform_exps(method_call_args),
-                );
+                let e = self.transform_exp(e, &path.field("e"))?;
+                let mut args = self.transform_exps(method_call_args, path, "methodCallArgs")?;
+                if let Some((_, n)) = REST.iter().find(|(name, _)| name == method) {
+                    // e.splice(s, n, a, b) is e.splice(s, n, [a, b]).
+                    let rest = args.split_off(args.len().min(*n));
+                    args.resize(*n, undefined());
+                    args.push(array(rest));
+                    return Ok(method_call(e, method, args));
+                }
+                match METHODS.iter().find(|(name, _, _)| name == method) {
+                    // e.concat(a, b) is e.concat(a).concat(b).
+                    Some((_, _, VARIADIC)) => args
+                        .into_iter()
+                        .fold(e, |e, arg| method_call(e, method, vec![arg])),
+                    Some((_, _, max)) => {
+                        args.resize(*max, undefined());
+                        method_call(e, method, args)
+                    }
+                    None => return unexpected(PASS, path, format!("unknown method {}", method)),
+                }
             }
-            Loopback {
-                event: _,
-                event_arg: _,
-                callback_clos: _,
-                id: _,
-            } => panic!("Did not expect to find this here."),
-            Ref { e: _ } => panic!("Did not expect to find this here."),
-            Deref { e: _ } => panic!("Did not expect to find this here."),
-            SetRef { e1: _, e2: _ } => panic!("Did not expect to find this here."),
-            _ => {
-                panic!("Not implemented.");
+            Located { loc, e } => located(loc, self.transform_exp(e, &path.field("e"))?),
+            Loopback { .. }
+            | Closure { .. }
+            | Ref { .. }
+            | Deref { .. }
+            | SetRef { .. }
+            | Unboxed { .. }
+            | Boxed { .. }
+            | Guard { .. } => {
+                return unexpected(PASS, path, format!("{} before Transformer", exp));
             }
-        }
+            Integer { .. } | Unit {} => {
+                return unsupported(PASS, path, format!("{}", exp));
+            }
+        };
+        return Ok(e);
     }
 
     /*
@@ -145,7 +207,7 @@ impl Transformer {
         3. Transform Set to SetRef
 
     */
-    pub fn transform(&mut self, exp: &Exp) -> Exp {
-        return self.transform_exp(exp);
+    pub fn transform(&mut self, exp: &Exp) -> Result<Exp, Error> {
+        return self.transform_exp(exp, &Path::Root);
     }
 }
@@ -0,0 +1,693 @@
+//! Every value in the generated code is a `Dyn`, thus every operator is a
+//! dynamically checked method call. This pass finds the locals that only
+//! ever hold numbers (or only booleans) in the trace, e.g., loop counters, and
+//! specializes them to unboxed `f64` (or `bool`) Rust variables:
+//!
+//! 1. The initializer of a specialized `Let`, and the right-hand side of a
+//!    `Set` to a specialized local, are wrapped in `Unboxed`.
+//! 2. When an operator only involves typed operands, it is computed unboxed
+//!    and then `Boxed`, if a `Dyn` is needed.
+//! 3. Inside an `Unboxed` or a `Boxed`, any expression whose type we do not
+//!    know statically, e.g., a field of the request, is wrapped in a `Guard`.
+//!    At runtime, a guard fails with `Error::Unknown` if the value does not
+//!    have the expected type, just like a trace that reaches an unknown
+//!    branch.
+//!
+//! We pick types with a fixpoint over the assignments to each local. An
+//! assignment of a value that we know nothing about does not prevent a local
+//! from being specialized, since the guard will check it. However, a local
+//! that is assigned two different types, or a string, object, etc., stays a
+//! `Dyn`, and so does a local that is assigned the value of such a local.
+//!
+//! This pass must run after `EscapeAnalysis`, because only the locals that
+//! it unboxes are candidates, and after `LiftCallbacks`, so that it does not
+//! need to know about `Callback`.
+use std::collections::HashMap;
+
+use super::types::{constructors::*, Exp, LVal, Op1, Op2, Typ};
+
+/// The static type of an expression.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Ty {
+    /// No evidence yet. Only occurs during the fixpoint.
+    Bot,
+    /// Some value that we know nothing about, which may still be a number or
+    /// a boolean at runtime.
+    Unknown,
+    Num,
+    Bool,
+    /// A value that cannot be a number or a boolean, e.g., a string, or a
+    /// local that is assigned values of different types.
+    Other,
+}
+
+fn join(t1: Ty, t2: Ty) -> Ty {
+    match (t1, t2) {
+        (Ty::Bot, t) | (t, Ty::Bot) => t,
+        (Ty::Other, _) | (_, Ty::Other) => Ty::Other,
+        (Ty::Unknown, t) | (t, Ty::Unknown) => t,
+        (t1, t2) if t1 == t2 => t1,
+        _ => Ty::Other,
+    }
+}
+
+fn is_arith(op: &Op2) -> bool {
+    return matches!(op, Op2::Add | Op2::Sub | Op2::Mul | Op2::Div | Op2::Mod);
+}
+
+fn is_comparison(op: &Op2) -> bool {
+    return matches!(
+        op,
+        Op2::GT
+            | Op2::LT
+            | Op2::GTE
+            | Op2::LTE
+            | Op2::StrictEq
+            | Op2::StrictNotEq
+            | Op2::Eq
+            | Op2::NotEq
+    );
+}
+
+fn is_bitwise(op: &Op2) -> bool {
+    return matches!(
+        op,
+        Op2::BitAnd
+            | Op2::BitOr
+            | Op2::BitXor
+            | Op2::LeftShift
+            | Op2::RightShift
+            | Op2::UnsignedRightShift
+    );
+}
+
+fn is_equality(op: &Op2) -> bool {
+    return matches!(op, Op2::StrictEq | Op2::StrictNotEq | Op2::Eq | Op2::NotEq);
+}
+
+fn take(e: &mut Exp) -> Exp {
+    return std::mem::replace(e, Exp::Undefined {});
+}
+
+pub struct TypeInference {
+    vars: HashMap<String, Ty>,
+    changed: bool,
+}
+
+impl TypeInference {
+    pub fn new() -> TypeInference {
+        return TypeInference {
+            vars: HashMap::new(),
+            changed: false,
+        };
+    }
+
+    fn var_ty(&self, name: &str) -> Ty {
+        match self.vars.get(name) {
+            None => Ty::Unknown,
+            Some(t) => *t,
+        }
+    }
+
+    fn var_typ(&self, name: &str) -> Option<Typ> {
+        match self.vars.get(name) {
+            Some(Ty::Num) => Some(Typ::F64),
+            Some(Ty::Bool) => Some(Typ::Bool),
+            _ => None,
+        }
+    }
+
+    fn type_of(&self, exp: &Exp) -> Ty {
+        match exp {
+            Exp::Integer { .. } | Exp::Number { .. } => Ty::Num,
+            Exp::Bool { .. } => Ty::Bool,
+            Exp::Stringg { .. }
+            | Exp::Undefined {}
+            | Exp::Null {}
+            | Exp::Unit {}
+            | Exp::Object { .. }
+            | Exp::Array { .. }
+            | Exp::Closure { .. }
+            | Exp::Ref { .. }
+            | Exp::Let { .. }
+            | Exp::Set { .. }
+            | Exp::SetRef { .. } => Ty::Other,
+            Exp::Identifier { name } => self.var_ty(name),
+            Exp::Located { loc: _, e } => self.type_of(e),
+            Exp::Op1 {
+                op: Op1::Negative,
+                e,
+            } => match self.type_of(e) {
+                Ty::Bot => Ty::Bot,
+                Ty::Num => Ty::Num,
+                _ => Ty::Unknown,
+            },
+            Exp::Op1 { op: Op1::Not, .. } => Ty::Bool,
+            Exp::Op1 {
+                op: Op1::BitNot, ..
+            } => Ty::Num,
+            Exp::Op1 { .. } => Ty::Other,
+            Exp::BinOp { op, e1, e2 } if is_arith(op) => {
+                match (self.type_of(e1), self.type_of(e2)) {
+                    (Ty::Other, _) | (_, Ty::Other) if op == &Op2::Add => Ty::Other,
+                    (Ty::Bot, _) | (_, Ty::Bot) => Ty::Bot,
+                    (Ty::Num, Ty::Num) => Ty::Num,
+                    _ => Ty::Unknown,
+                }
+            }
+            Exp::BinOp {
+                op: Op2::Nullish, ..
+            } => Ty::Unknown,
+            // The bitwise operators produce 32-bit integers.
+            Exp::BinOp { op, .. } if is_bitwise(op) => Ty::Num,
+            Exp::BinOp { op, .. } if is_comparison(op) => Ty::Bool,
+            // In JavaScript, `&&` and `||` produce one of their operands.
+            Exp::BinOp {
+                op: Op2::And,
+                e1,
+                e2,
+            }
+            | Exp::BinOp {
+                op: Op2::Or,
+                e1,
+                e2,
+            } => match (self.type_of(e1), self.type_of(e2)) {
+                (Ty::Bot, _) | (_, Ty::Bot) => Ty::Bot,
+                (Ty::Bool, Ty::Bool) => Ty::Bool,
+                _ => Ty::Unknown,
+            },
+            _ => Ty::Unknown,
+        }
+    }
+
+    /// The type of the operands of `exp`, if it is a comparison that we can
+    /// compute unboxed.
+    fn compared(&self, exp: &Exp) -> Option<Typ> {
+        if let Exp::BinOp { op, e1, e2 } = exp {
+            match (op, self.type_of(e1), self.type_of(e2)) {
+                (op, Ty::Bool, Ty::Bool) if is_equality(op) => return Some(Typ::Bool),
+                (op, Ty::Num, Ty::Num) if is_comparison(op) => return Some(Typ::F64),
+                _ => (),
+            }
+        }
+        return None;
+    }
+
+    /// The type of `exp`, if we can compute it unboxed without a guard.
+    fn unboxes(&self, exp: &Exp) -> Option<Typ> {
+        match exp {
+            Exp::Identifier { name } => self.var_typ(name),
+            Exp::Located { loc: _, e } => self.unboxes(e),
+            Exp::Op1 {
+                op: Op1::Negative,
+                e: _,
+            } if self.type_of(exp) == Ty::Num => Some(Typ::F64),
+            Exp::Op1 { op: Op1::Not, e } if self.type_of(e) == Ty::Bool => Some(Typ::Bool),
+            Exp::BinOp { op, e1: _, e2: _ } if is_arith(op) && self.type_of(exp) == Ty::Num => {
+                Some(Typ::F64)
+            }
+            Exp::BinOp {
+                op: Op2::And,
+                e1,
+                e2,
+            }
+            | Exp::BinOp {
+                op: Op2::Or,
+                e1,
+                e2,
+            } if self.type_of(e1) == Ty::Bool && self.type_of(e2) == Ty::Bool => Some(Typ::Bool),
+            Exp::BinOp { .. } => self.compared(exp).map(|_| Typ::Bool),
+            _ => None,
+        }
+    }
+
+    fn assign(&mut self, name: &str, t: Ty) {
+        if let Some(t0) = self.vars.get(name) {
+            let t1 = join(*t0, t);
+            if t1 != *t0 {
+                self.vars.insert(name.to_string(), t1);
+                self.changed = true;
+            }
+        }
+    }
+
+    fn infer_exps(&mut self, exps: &[Exp]) {
+        for e in exps.iter() {
+            self.infer_exp(e);
+        }
+    }
+
+    fn infer_exp(&mut self, exp: &Exp) {
+        match exp {
+            Exp::Unknown {}
+            | Exp::Integer { .. }
+            | Exp::Number { .. }
+            | Exp::Bool { .. }
+            | Exp::Stringg { .. }
+            | Exp::Undefined {}
+            | Exp::Null {}
+            | Exp::Unit {}
+            | Exp::Identifier { .. } => (),
+            Exp::Let { name, named } => {
+                match &**named {
+                    Exp::Ref { .. } => (),
+                    _ => {
+                        if !self.vars.contains_key(name) {
+                            self.vars.insert(name.to_string(), Ty::Bot);
+                            self.changed = true;
+                        }
+                        self.assign(name, self.type_of(named));
+                    }
+                }
+                self.infer_exp(named);
+            }
+            Exp::Set { name, named } => {
+                match name {
+                    LVal::Identifier { name } => self.assign(name, self.type_of(named)),
+                    LVal::From { exp, field: _ } => self.infer_exp(exp),
+                    LVal::Index { exp, index } => {
+                        self.infer_exp(exp);
+                        self.infer_exp(index);
+                    }
+                }
+                self.infer_exp(named);
+            }
+            Exp::From { exp, field: _ } => self.infer_exp(exp),
+            Exp::Get { exp, field: _ } => self.infer_exp(exp),
+            Exp::Op1 { op: _, e } => self.infer_exp(e),
+            Exp::BinOp { op: _, e1, e2 } => {
+                self.infer_exp(e1);
+                self.infer_exp(e2);
+            }
+            Exp::If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                self.infer_exp(cond);
+                self.infer_exps(true_part);
+                self.infer_exps(false_part);
+            }
+            Exp::While { cond, body } => {
+                self.infer_exp(cond);
+                self.infer_exps(body);
+            }
+            Exp::Block { body } => self.infer_exps(body),
+            Exp::Callback {
+                event: _,
+                event_arg,
+                callback_args: _,
+                callback_clos,
+                body,
+            } => {
+                self.infer_exp(event_arg);
+                self.infer_exp(callback_clos);
+                self.infer_exps(body);
+            }
+            Exp::Loopback {
+                event: _,
+                event_arg,
+                callback_clos,
+                id: _,
+            } => {
+                self.infer_exp(event_arg);
+                self.infer_exp(callback_clos);
+            }
+            Exp::Lambda {
+                args: _,
+                clos,
+                body,
+            } => {
+                self.infer_exp(clos);
+                self.infer_exps(body);
+            }
+            Exp::Closure { clos, id: _ } => self.infer_exp(clos),
+            Exp::Call { e, call_args } => {
+                self.infer_exp(e);
+                self.infer_exps(call_args);
+            }
+            Exp::Label { name: _, body } => self.infer_exps(body),
+            Exp::Break { name: _, value } => self.infer_exp(value),
+            Exp::Throw { e } => self.infer_exp(e),
+            Exp::Try {
+                body,
+                catch_name: _,
+                handler,
+                finalizer,
+            } => {
+                self.infer_exps(body);
+                if let Some(handler) = handler {
+                    self.infer_exps(handler);
+                }
+                self.infer_exps(finalizer);
+            }
+            Exp::Object { properties } => properties.values().for_each(|e| self.infer_exp(e)),
+            Exp::Clos { tenv } => tenv.values().for_each(|e| self.infer_exp(e)),
+            Exp::Array { exps } => self.infer_exps(exps),
+            Exp::Index { e1, e2 } => {
+                self.infer_exp(e1);
+                self.infer_exp(e2);
+            }
+            Exp::Ref { e } | Exp::Deref { e } | Exp::Located { loc: _, e } => self.infer_exp(e),
+            Exp::SetRef { e1, e2 } => {
+                self.infer_exp(e1);
+                self.infer_exp(e2);
+            }
+            Exp::PrimApp {
+                event: _,
+                event_args,
+            } => self.infer_exps(event_args),
+            Exp::MethodCall {
+                e,
+                method: _,
+                method_call_args,
+            } => {
+                self.infer_exp(e);
+                self.infer_exps(method_call_args);
+            }
+            Exp::Unboxed { typ: _, e } | Exp::Boxed { typ: _, e } | Exp::Guard { typ: _, e } => {
+                self.infer_exp(e)
+            }
+        }
+    }
+
+    /// Produces the unboxed version of `exp`, which must have type `typ`.
+    fn to_unboxed(&self, typ: Typ, mut exp: Exp) -> Exp {
+        let ty = self.type_of(&exp);
+        let compared = self.compared(&exp);
+        match (typ, &mut exp) {
+            (Typ::F64, Exp::Number { .. })
+            | (Typ::F64, Exp::Integer { .. })
+            | (Typ::Bool, Exp::Bool { .. }) => exp,
+            (_, Exp::Identifier { name }) if self.var_typ(name) == Some(typ) => exp,
+            // Unboxed operators cannot fail, so they do not need a location.
+            (_, Exp::Located { loc: _, e }) if self.unboxes(e) == Some(typ) => {
+                self.to_unboxed(typ, take(e))
+            }
+            (
+                Typ::F64,
+                Exp::Op1 {
+                    op: Op1::Negative,
+                    e,
+                },
+            ) => op1(&Op1::Negative, self.to_unboxed(Typ::F64, take(e))),
+            (Typ::Bool, Exp::Op1 { op: Op1::Not, e }) if self.type_of(e) == Ty::Bool => {
+                op1(&Op1::Not, self.to_unboxed(Typ::Bool, take(e)))
+            }
+            (Typ::F64, Exp::BinOp { op, e1, e2 }) if is_arith(op) && ty != Ty::Other => binop(
+                op,
+                self.to_unboxed(Typ::F64, take(e1)),
+                self.to_unboxed(Typ::F64, take(e2)),
+            ),
+            (Typ::Bool, Exp::BinOp { op, e1, e2 }) if op == &Op2::And || op == &Op2::Or => binop(
+                op,
+                self.to_unboxed(Typ::Bool, take(e1)),
+                self.to_unboxed(Typ::Bool, take(e2)),
+            ),
+            (Typ::Bool, Exp::BinOp { op, e1, e2 }) if compared.is_some() => {
+                let operand_typ = compared.unwrap();
+                binop(
+                    op,
+                    self.to_unboxed(operand_typ, take(e1)),
+                    self.to_unboxed(operand_typ, take(e2)),
+                )
+            }
+            _ => {
+                self.specialize(&mut exp);
+                guard(typ, exp)
+            }
+        }
+    }
+
+    fn specialize_exps(&self, exps: &mut [Exp]) {
+        for e in exps.iter_mut() {
+            self.specialize(e);
+        }
+    }
+
+    /// Specializes `exp`, which produces a `Dyn`.
+    fn specialize(&self, exp: &mut Exp) {
+        if let Some(typ) = self.unboxes(exp) {
+            *exp = boxed(typ, self.to_unboxed(typ, take(exp)));
+            return;
+        }
+        match exp {
+            Exp::Unknown {}
+            | Exp::Integer { .. }
+            | Exp::Number { .. }
+            | Exp::Bool { .. }
+            | Exp::Stringg { .. }
+            | Exp::Undefined {}
+            | Exp::Null {}
+            | Exp::Unit {}
+            | Exp::Identifier { .. } => (),
+            Exp::Let { name, named } => match self.var_typ(name) {
+                Some(typ) => **named = unboxed(typ, self.to_unboxed(typ, take(named))),
+                None => self.specialize(named),
+            },
+            Exp::Set { name, named } => {
+                match name {
+                    LVal::Identifier { name } => {
+                        if let Some(typ) = self.var_typ(name) {
+                            **named = unboxed(typ, self.to_unboxed(typ, take(named)));
+                            return;
+                        }
+                    }
+                    LVal::From { exp, field: _ } => self.specialize(exp),
+                    LVal::Index { exp, index } => {
+                        self.specialize(exp);
+                        self.specialize(index);
+                    }
+                }
+                self.specialize(named);
+            }
+            Exp::From { exp, field: _ } => self.specialize(exp),
+            Exp::Get { exp, field: _ } => self.specialize(exp),
+            Exp::Op1 { op: _, e } => self.specialize(e),
+            Exp::BinOp { op: _, e1, e2 } => {
+                self.specialize(e1);
+                self.specialize(e2);
+            }
+            Exp::If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                self.specialize_cond(cond);
+                self.specialize_exps(true_part);
+                self.specialize_exps(false_part);
+            }
+            Exp::While { cond, body } => {
+                self.specialize_cond(cond);
+                self.specialize_exps(body);
+            }
+            Exp::Block { body } => self.specialize_exps(body),
+            Exp::Callback {
+                event: _,
+                event_arg,
+                callback_args: _,
+                callback_clos,
+                body,
+            } => {
+                self.specialize(event_arg);
+                self.specialize(callback_clos);
+                self.specialize_exps(body);
+            }
+            Exp::Loopback {
+                event: _,
+                event_arg,
+                callback_clos,
+                id: _,
+            } => {
+                self.specialize(event_arg);
+                self.specialize(callback_clos);
+            }
+            Exp::Lambda {
+                args: _,
+                clos,
+                body,
+            } => {
+                self.specialize(clos);
+                self.specialize_exps(body);
+            }
+            Exp::Closure { clos, id: _ } => self.specialize(clos),
+            Exp::Call { e, call_args } => {
+                self.specialize(e);
+                self.specialize_exps(call_args);
+            }
+            Exp::Label { name: _, body } => self.specialize_exps(body),
+            Exp::Break { name: _, value } => self.specialize(value),
+            Exp::Throw { e } => self.specialize(e),
+            Exp::Try {
+                body,
+                catch_name: _,
+                handler,
+                finalizer,
+            } => {
+                self.specialize_exps(body);
+                if let Some(handler) = handler {
+                    self.specialize_exps(handler);
+                }
+                self.specialize_exps(finalizer);
+            }
+            Exp::Object { properties } => properties.values_mut().for_each(|e| self.specialize(e)),
+            Exp::Clos { tenv } => tenv.values_mut().for_each(|e| self.specialize(e)),
+            Exp::Array { exps } => self.specialize_exps(exps),
+            Exp::Index { e1, e2 } => {
+                self.specialize(e1);
+                self.specialize(e2);
+            }
+            Exp::Ref { e } | Exp::Deref { e } | Exp::Located { loc: _, e } => self.specialize(e),
+            Exp::SetRef { e1, e2 } => {
+                self.specialize(e1);
+                self.specialize(e2);
+            }
+            Exp::PrimApp {
+                event: _,
+                event_args,
+            } => self.specialize_exps(event_args),
+            Exp::MethodCall {
+                e,
+                method: _,
+                method_call_args,
+            } => {
+                self.specialize(e);
+                self.specialize_exps(method_call_args);
+            }
+            Exp::Unboxed { .. } | Exp::Boxed { .. } | Exp::Guard { .. } => (),
+        }
+    }
+
+    /// Conditions that we can compute unboxed do not need a `Dyn` at all.
+    fn specialize_cond(&self, cond: &mut Exp) {
+        match self.unboxes(cond) {
+            Some(Typ::Bool) => *cond = unboxed(Typ::Bool, self.to_unboxed(Typ::Bool, take(cond))),
+            _ => self.specialize(cond),
+        }
+    }
+
+    /// Specializes every local in `exp` that only holds numbers, or only
+    /// holds booleans.
+    pub fn infer(&mut self, exp: &mut Exp) {
+        self.changed = true;
+        while self.changed {
+            self.changed = false;
+            self.infer_exp(exp);
+        }
+        self.specialize(exp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::syntax::parse;
+    use super::*;
+
+    fn infer(text: &str) -> (TypeInference, Exp) {
+        let mut exp = parse(text).expect("parsing the trace");
+        let mut type_inference = TypeInference::new();
+        type_inference.infer(&mut exp);
+        return (type_inference, exp);
+    }
+
+    #[test]
+    fn counter_is_a_number() {
+        let (inference, exp) = infer(
+            "(block [
+              (let i 0.0)
+              (while (< i 10.0) [(set i (+ i 1.0))])
+            ])",
+        );
+        assert_eq!(inference.vars["i"], Ty::Num);
+        let text = exp.to_string();
+        assert!(text.contains("(let i (unboxed f64 0.0))"), "{}", text);
+        assert!(text.contains("(set i (unboxed f64 (+ i 1.0)))"), "{}", text);
+    }
+
+    #[test]
+    fn unknown_value_is_guarded() {
+        let (inference, exp) = infer(
+            "(block [
+              (let n 1.0)
+              (set n (get req count))
+            ])",
+        );
+        assert_eq!(inference.vars["n"], Ty::Num);
+        assert!(exp.to_string().contains("(guard f64 (get req count))"));
+    }
+
+    #[test]
+    fn string_assigned_at_runtime_fails_the_guard() {
+        // `name` may be a string at runtime, so the guard fails with
+        // `Error::Unknown` and the container runs the request. See
+        // `trace_runtime::guard_f64`.
+        let (inference, exp) = infer(
+            "(block [
+              (let n 1.0)
+              (set n (get req name))
+              (primApp send (+ n 1.0))
+            ])",
+        );
+        assert_eq!(inference.vars["n"], Ty::Num);
+        let text = exp.to_string();
+        assert!(text.contains("(guard f64 (get req name))"), "{}", text);
+    }
+
+    #[test]
+    fn and_and_or_produce_an_operand() {
+        let (inference, _) = infer(
+            "(block [
+              (let b (< 1.0 2.0))
+              (set b (&& b true))
+              (let x (&& 1.0 2.0))
+              (let y (|| (get req a) false))
+            ])",
+        );
+        assert_eq!(inference.vars["b"], Ty::Bool);
+        assert_eq!(inference.vars["x"], Ty::Unknown);
+        assert_eq!(inference.vars["y"], Ty::Unknown);
+    }
+
+    #[test]
+    fn different_types_are_other() {
+        let (inference, _) = infer(
+            "(block [
+              (let x 1.0)
+              (set x true)
+              (let s \"a\")
+            ])",
+        );
+        assert_eq!(inference.vars["x"], Ty::Other);
+        assert_eq!(inference.vars["s"], Ty::Other);
+    }
+
+    #[test]
+    fn local_assigned_from_other_is_other() {
+        // Before `s` was joined as `Other`, `y` was a number whose guard
+        // always failed.
+        let (inference, exp) = infer(
+            "(block [
+              (let s \"a\")
+              (let y 1.0)
+              (set y s)
+              (let z y)
+            ])",
+        );
+        assert_eq!(inference.vars["y"], Ty::Other);
+        assert_eq!(inference.vars["z"], Ty::Other);
+        let text = exp.to_string();
+        assert!(!text.contains("guard"), "{}", text);
+        assert!(!text.contains("unboxed"), "{}", text);
+    }
+
+    #[test]
+    fn join_is_commutative() {
+        let tys = [Ty::Bot, Ty::Unknown, Ty::Num, Ty::Bool, Ty::Other];
+        for t1 in tys.iter() {
+            assert_eq!(join(*t1, Ty::Other), Ty::Other);
+            for t2 in tys.iter() {
+                assert_eq!(join(*t1, *t2), join(*t2, *t1));
+            }
+        }
+    }
+}
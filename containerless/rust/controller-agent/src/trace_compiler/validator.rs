@@ -0,0 +1,483 @@
+//! Checks that a trace is well-formed before the rest of the pipeline runs,
+//! so that the controller rejects a broken trace instead of generating code
+//! that fails to build. The validator does not stop at the first problem:
+//! it reports every problem in the trace as a `Diagnostic`. It checks that:
+//!
+//! 1. every node is in a position where it may occur (e.g., an `If` is a
+//!    statement and not an expression),
+//! 2. every `Let` (or `catch` clause) binds a unique name,
+//! 3. every `Identifier` refers to a name that is in scope,
+//! 4. every `Break` targets an enclosing `Label`,
+//! 5. every `Set` assigns to a variable that is in scope, a field, or an
+//!    index,
+//! 6. every `MethodCall` calls a method that `Dyn` supports, with the right
+//!    number of arguments, and
+//! 7. every `PrimApp` calls a builtin, or is a statement that calls one of
+//!    the `EVENTS`, with the right number of arguments.
+//!
+//! Scopes follow the Rust code that we generate. A `Let` is in scope for the
+//! rest of the block that contains it. The body of a callback or a lambda
+//! runs in another invocation of the function, so it only sees its own
+//! arguments (and it reaches the variables of its enclosing function through
+//! the closure, which is the first argument). Similarly, a `Break` cannot
+//! leave the body of a callback or a lambda.
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::error::Path;
+use super::types::{Arg, Exp, Exp::*, LVal};
+use shared::trace::{BUILTINS, EVENTS, METHODS, VARIADIC};
+
+/// The arguments of the generated `containerless` function, which are in
+/// scope everywhere.
+const ARGUMENTS: &[&str] = &["arg_cbid", "arg_cbargs"];
+
+/// A problem with a trace.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+pub struct Validator {
+    /// Every name that a `Let` binds, anywhere in the trace.
+    names: HashSet<String>,
+    /// The names in scope. We count the bindings of each name, so that a name
+    /// that is bound twice (which we report) stays in scope until both
+    /// bindings go out of scope.
+    scope: HashMap<String, usize>,
+    /// The names that `bind` added to `scope`, in order, so that we can
+    /// remove them when their block ends.
+    bound: Vec<String>,
+    /// The enclosing labels.
+    labels: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    pub fn new() -> Validator {
+        return Validator {
+            names: HashSet::new(),
+            scope: HashMap::new(),
+            bound: Vec::new(),
+            labels: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+    }
+
+    /// The kind of `exp`, as it appears in the JSON of a trace. The `Display`
+    /// of an `Exp` prints the whole subtree, which is too much for a
+    /// diagnostic.
+    fn kind(exp: &Exp) -> String {
+        return serde_json::to_value(exp)
+            .ok()
+            .and_then(|json| json["kind"].as_str().map(|kind| kind.to_string()))
+            .unwrap_or_else(|| "?".to_string());
+    }
+
+    fn report(&mut self, path: &Path, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn bind(&mut self, name: &str) {
+        *self.scope.entry(name.to_string()).or_insert(0) += 1;
+        self.bound.push(name.to_string());
+    }
+
+    /// Removes the names that were bound after `bound.len()` was `mark`.
+    fn unbind(&mut self, mark: usize) {
+        for name in self.bound.drain(mark..) {
+            let count = self.scope.get_mut(&name).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.scope.remove(&name);
+            }
+        }
+    }
+
+    fn in_scope(&self, name: &str) -> bool {
+        return self.scope.contains_key(name) || ARGUMENTS.contains(&name);
+    }
+
+    /// Binds a name with a `Let` or a `catch` clause.
+    fn bind_unique(&mut self, name: &str, path: &Path) {
+        // The tracing runtime may reuse the names that start with '$'.
+        if !name.starts_with('$') && !self.names.insert(name.to_string()) {
+            self.report(path, format!("non-unique name {}", name));
+        }
+        self.bind(name);
+    }
+
+    fn identifier(&mut self, name: &str, path: &Path) {
+        if !self.in_scope(name) {
+            self.report(path, format!("{} is not in scope", name));
+        }
+    }
+
+    /// Validates a block, whose bindings go out of scope when it ends.
+    fn block(&mut self, exps: &[Exp], path: &Path, field: &'static str) {
+        let mark = self.bound.len();
+        for (i, e) in exps.iter().enumerate() {
+            self.stmt(e, &path.index(field, i));
+        }
+        self.unbind(mark);
+    }
+
+    fn exps(&mut self, exps: &[Exp], path: &Path, field: &'static str) {
+        for (i, e) in exps.iter().enumerate() {
+            self.exp(e, &path.index(field, i));
+        }
+    }
+
+    fn tenv(&mut self, tenv: &IndexMap<String, Exp>, path: &Path, field: &'static str) {
+        let path = path.field(field);
+        for e in tenv.values() {
+            self.exp(e, &path);
+        }
+    }
+
+    /// Validates the body of a callback or a lambda, which starts with an
+    /// empty scope, except for the names of the arguments, which
+    /// `LiftCallbacks` binds.
+    fn body(&mut self, args: &[Arg], body: &[Exp], path: &Path) {
+        let scope = std::mem::take(&mut self.scope);
+        let bound = std::mem::take(&mut self.bound);
+        let labels = std::mem::take(&mut self.labels);
+        for arg in args.iter() {
+            self.bind(&arg.name);
+        }
+        self.block(body, path, "body");
+        self.scope = scope;
+        self.bound = bound;
+        self.labels = labels;
+    }
+
+    fn lval(&mut self, lval: &LVal, path: &Path) {
+        match lval {
+            LVal::Identifier { name } => {
+                if ARGUMENTS.contains(&name.as_str()) {
+                    self.report(path, format!("cannot assign to {}", name));
+                } else {
+                    self.identifier(name, path);
+                }
+            }
+            LVal::From { exp, field: _ } => self.exp(exp, &path.field("exp")),
+            LVal::Index { exp, index } => {
+                self.exp(exp, &path.field("exp"));
+                self.exp(index, &path.field("index"));
+            }
+        }
+    }
+
+    fn method_call(&mut self, method: &str, num_args: usize, path: &Path) {
+        match METHODS.iter().find(|(name, _, _)| *name == method) {
+            None => self.report(path, format!("unknown method {}", method)),
+            Some((_, min, max)) => self.arity("method", method, *min, *max, num_args, path),
+        }
+    }
+
+    fn event(&mut self, event: &str, num_args: usize, path: &Path) {
+        if BUILTINS.iter().any(|(name, _)| *name == event) {
+            return;
+        }
+        match EVENTS.iter().find(|(name, _, _)| *name == event) {
+            None => self.report(path, format!("unknown event {}", event)),
+            Some((_, min, max)) => self.arity("event", event, *min, *max, num_args, path),
+        }
+    }
+
+    /// Checks the number of arguments of a call to a method or an event.
+    fn arity(
+        &mut self, what: &str, name: &str, min: usize, max: usize, num_args: usize, path: &Path,
+    ) {
+        if num_args >= min && num_args <= max {
+            return;
+        }
+        let arity = if min == max {
+            min.to_string()
+        } else if max == VARIADIC {
+            format!("at least {}", min)
+        } else {
+            format!("{} to {}", min, max)
+        };
+        self.report(
+            path,
+            format!(
+                "{} {} takes {} argument(s), but got {}",
+                what, name, arity, num_args
+            ),
+        );
+    }
+
+    fn exp(&mut self, exp: &Exp, path: &Path) {
+        match exp {
+            Number { value: _ }
+            | Bool { value: _ }
+            | Stringg { value: _ }
+            | Undefined {}
+            | Null {} => (),
+            Identifier { name } => self.identifier(name, path),
+            From { exp, field: _ } | Get { exp, field: _ } => self.exp(exp, &path.field("exp")),
+            Index { e1, e2 } => {
+                self.exp(e1, &path.field("exp"));
+                self.exp(e2, &path.field("index"));
+            }
+            BinOp { op: _, e1, e2 } => {
+                self.exp(e1, &path.field("e1"));
+                self.exp(e2, &path.field("e2"));
+            }
+            Op1 { op: _, e } | Ref { e } | Deref { e } => self.exp(e, &path.field("e")),
+            Block { body } => self.block(body, path, "body"),
+            Object { properties } => self.tenv(properties, path, "properties"),
+            Clos { tenv } => self.tenv(tenv, path, "tenv"),
+            Array { exps } => self.exps(exps, path, "exps"),
+            MethodCall {
+                e,
+                method,
+                method_call_args,
+            } => {
+                self.exp(e, &path.field("e"));
+                self.exps(method_call_args, path, "methodCallArgs");
+                self.method_call(method, method_call_args.len(), path);
+            }
+            Lambda { args, clos, body } => {
+                self.exp(clos, &path.field("clos"));
+                self.body(args, body, path);
+            }
+            Closure { clos, id: _ } => self.exp(clos, &path.field("clos")),
+            Call { e, call_args } => {
+                self.exp(e, &path.field("e"));
+                self.exps(call_args, path, "callArgs");
+            }
+            PrimApp { event, event_args } if BUILTINS.iter().any(|(name, _)| name == event) => {
+                self.exps(event_args, path, "eventArgs")
+            }
+            Located { loc: _, e } => self.exp(e, &path.field("e")),
+            _ => self.report(path, format!("{} in expression position", Self::kind(exp))),
+        }
+    }
+
+    fn stmt(&mut self, exp: &Exp, path: &Path) {
+        match exp {
+            Unknown {} => (),
+            If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                self.exp(cond, &path.field("cond"));
+                self.block(true_part, path, "truePart");
+                self.block(false_part, path, "falsePart");
+            }
+            While { cond, body } => {
+                self.exp(cond, &path.field("cond"));
+                self.block(body, path, "body");
+            }
+            Let { name, named } => {
+                // The name is not in scope in its own initializer.
+                self.exp(named, &path.field("named"));
+                self.bind_unique(name, path);
+            }
+            Set { name, named } => {
+                self.lval(name, &path.field("name"));
+                self.exp(named, &path.field("named"));
+            }
+            SetRef { e1, e2 } => {
+                self.exp(e1, &path.field("e1"));
+                self.exp(e2, &path.field("e2"));
+            }
+            Block { body } => self.block(body, path, "body"),
+            Callback {
+                event: _,
+                event_arg,
+                callback_args,
+                callback_clos,
+                body,
+            } => {
+                self.exp(event_arg, &path.field("eventArg"));
+                self.exp(callback_clos, &path.field("clos"));
+                self.body(callback_args, body, path);
+            }
+            Label { name, body } => {
+                self.labels.push(name.to_string());
+                self.block(body, path, "body");
+                self.labels.pop();
+            }
+            Break { name, value } => {
+                if !self.labels.contains(name) {
+                    self.report(
+                        path,
+                        format!("break to {}, which is not an enclosing label", name),
+                    );
+                }
+                match **value {
+                    // A break out of a label that ends with a break.
+                    Break { .. } => self.stmt(value, &path.field("value")),
+                    _ => self.exp(value, &path.field("value")),
+                }
+            }
+            Throw { e } => self.exp(e, &path.field("e")),
+            Try {
+                body,
+                catch_name,
+                handler,
+                finalizer,
+            } => {
+                self.block(body, path, "body");
+                if let Some(handler) = handler {
+                    let mark = self.bound.len();
+                    if let Some(name) = catch_name {
+                        self.bind_unique(name, path);
+                    }
+                    self.block(handler, path, "handler");
+                    self.unbind(mark);
+                }
+                self.block(finalizer, path, "finalizer");
+            }
+            PrimApp { event, event_args } => {
+                self.exps(event_args, path, "eventArgs");
+                self.event(event, event_args.len(), path);
+            }
+            Loopback {
+                event: _,
+                event_arg,
+                callback_clos,
+                id: _,
+            } => {
+                self.exp(event_arg, &path.field("eventArg"));
+                self.exp(callback_clos, &path.field("clos"));
+            }
+            Located { loc: _, e } if matches!(**e, Set { .. } | Throw { .. } | PrimApp { .. }) => {
+                self.stmt(e, &path.field("e"));
+            }
+            _ => self.report(path, format!("{} in statement position", Self::kind(exp))),
+        }
+    }
+
+    /// Returns every problem in the trace `exp`.
+    pub fn validate(mut self, exp: &Exp) -> Vec<Diagnostic> {
+        self.exp(exp, &Path::Root);
+        return self.diagnostics;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::syntax::parse;
+    use super::*;
+
+    /// The messages of the diagnostics of the trace.
+    fn validate(text: &str) -> Vec<String> {
+        let exp = parse(text).expect("parsing the trace");
+        return Validator::new()
+            .validate(&exp)
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+    }
+
+    #[test]
+    fn accepts_a_valid_trace() {
+        let messages = validate(
+            "(block [
+              (let x 1.0)
+              (label l [
+                (if (> x 0.0) [(break l x)] [])
+                (set x (primApp \"Math.floor\" (get arg_cbargs n)))
+              ])
+              (primApp console.log)
+              (primApp console.log x \"x\")
+              (primApp send (methodCall x concat \"a\" \"b\"))
+            ])",
+        );
+        assert!(messages.is_empty(), "{:?}", messages);
+    }
+
+    #[test]
+    fn rejects_scope_errors() {
+        let messages = validate(
+            "(block [
+              (let x y)
+              (let x 1.0)
+              (set arg_cbid 1.0)
+              (callback listen 0.0 [clos req] x [(primApp send x)])
+            ])",
+        );
+        assert_eq!(
+            messages,
+            vec![
+                "y is not in scope",
+                "non-unique name x",
+                "cannot assign to arg_cbid",
+                "x is not in scope",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_break_out_of_a_callback() {
+        let messages = validate(
+            "(block [
+              (label l [
+                (callback listen 0.0 [clos] (clos) [(break l undefined)])
+                (break m undefined)
+              ])
+            ])",
+        );
+        assert_eq!(
+            messages,
+            vec![
+                "break to l, which is not an enclosing label",
+                "break to m, which is not an enclosing label",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_nodes_in_the_wrong_position() {
+        let messages = validate(
+            "(block [
+              (let x (if true [] []))
+              (+ 1.0 2.0)
+            ])",
+        );
+        assert_eq!(
+            messages,
+            vec!["if in expression position", "binop in statement position",]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_methods_and_events() {
+        let messages = validate(
+            "(block [
+              (let s \"a\")
+              (let t (methodCall s frobnicate))
+              (let u (methodCall s replace \"a\"))
+              (primApp launch s)
+              (primApp send s s)
+              (let v (primApp send s))
+            ])",
+        );
+        assert_eq!(
+            messages,
+            vec![
+                "unknown method frobnicate",
+                "method replace takes 2 argument(s), but got 1",
+                "unknown event launch",
+                "event send takes 1 argument(s), but got 2",
+                "primApp in expression position",
+            ]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Runs the trace compiler on a trace file, without a cluster. The controller
+//! runs exactly the same passes in `compiler_task`, so this is the easiest
+//! way to debug a trace that does not compile.
+use clap::Clap;
+use controller_agent::trace_compiler::{self, Exp, Merge, Pass, Stats};
+use std::path::Path;
+
+/// Compiles a trace to Rust.
+#[derive(Clap)]
+#[clap(
+    name = "trace-compiler",
+    version = "0.1",
+    author = "Emily Herbert <emilyherbert@cs.umass.edu>, Arjun Guha <a.guha@northeastern.edu>"
+)]
+struct Opts {
+    /// The trace to compile (JSON, or the text syntax if the name ends in .trace)
+    trace: String,
+    /// More traces of the same function to merge into the first one
+    #[clap(long)]
+    merge: Vec<String>,
+    /// Where to write the generated Rust code
+    #[clap(short, long)]
+    output: String,
+    /// Print a summary of the trace (JSON)
+    #[clap(long)]
+    stats: bool,
+    /// Where to write the IR that the dispatcher interprets (JSON)
+    #[clap(long)]
+    lifted: Option<String>,
+    /// Where to write the trace in the text syntax
+    #[clap(long)]
+    text: Option<String>,
+    /// Print the IR after Validator
+    #[clap(long)]
+    dump_validator: bool,
+    /// Print the IR after Transformer
+    #[clap(long)]
+    dump_transformer: bool,
+    /// Print the IR after EscapeAnalysis
+    #[clap(long)]
+    dump_escape_analysis: bool,
+    /// Print the IR after LiftCallbacks
+    #[clap(long)]
+    dump_lift_callbacks: bool,
+    /// Print the IR after TypeInference
+    #[clap(long)]
+    dump_type_inference: bool,
+    /// Print the IR after Rustify
+    #[clap(long)]
+    dump_rustify: bool,
+}
+
+fn read_trace(path: &str) -> Exp {
+    let trace = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("could not read {}: {}", path, err);
+        std::process::exit(1);
+    });
+    let parsed = if path.ends_with(".trace") {
+        trace_compiler::parse_text(&trace)
+    } else {
+        trace_compiler::parse(&trace)
+    };
+    return parsed.unwrap_or_else(|err| {
+        eprintln!("error parsing {}: {}", path, err);
+        std::process::exit(1);
+    });
+}
+
+fn write_file(path: &str, contents: String) {
+    if let Err(err) = std::fs::write(path, contents) {
+        eprintln!("could not write {}: {}", path, err);
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+
+    let mut dumps = Vec::new();
+    if opts.dump_validator {
+        dumps.push(Pass::Validator);
+    }
+    if opts.dump_transformer {
+        dumps.push(Pass::Transformer);
+    }
+    if opts.dump_escape_analysis {
+        dumps.push(Pass::EscapeAnalysis);
+    }
+    if opts.dump_lift_callbacks {
+        dumps.push(Pass::LiftCallbacks);
+    }
+    if opts.dump_type_inference {
+        dumps.push(Pass::TypeInference);
+    }
+    if opts.dump_rustify {
+        dumps.push(Pass::Rustify);
+    }
+
+    let mut trace = read_trace(&opts.trace);
+    for path in opts.merge.iter() {
+        let mut merge = Merge::new();
+        trace = merge.merge(&trace, &read_trace(path));
+        for conflict in merge.conflicts() {
+            eprintln!("merging {}: {}", path, conflict);
+        }
+    }
+    if opts.stats {
+        let stats = Stats::new().stats(&trace);
+        println!(
+            "{}",
+            serde_json::to_string(&stats).expect("serializing TraceStats")
+        );
+    }
+    let name = Path::new(&opts.trace)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("trace")
+        .to_string();
+
+    if let Some(path) = &opts.text {
+        write_file(path, format!("{:#}\n", trace));
+    }
+
+    if let Some(path) = &opts.lifted {
+        match trace_compiler::lift(&trace, &[]) {
+            Ok(lifted) => write_file(
+                path,
+                serde_json::to_string(&lifted).expect("serializing lifted IR"),
+            ),
+            Err(err) => {
+                eprintln!("error lifting {}: {}", &opts.trace, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Err(err) = trace_compiler::compile(name, &opts.output, &trace, &dumps) {
+        eprintln!("error compiling {}: {}", &opts.trace, err);
+        std::process::exit(1);
+    }
+}
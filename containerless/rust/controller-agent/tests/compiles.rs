@@ -0,0 +1,524 @@
+//! Generates random well-formed traces, compiles each one with the
+//! `trace-compiler` bin, and type-checks the generated code with `cargo
+//! check`, in a scratch crate that depends on `dispatcher-agent-lib` like the
+//! crates that the controller builds. Otherwise, we only find out that the
+//! compiler generates code that does not build when `cargo build` fails in
+//! the controller, and the function is stuck in `CompileStatus::Error`.
+//!
+//! The generator writes traces in the text syntax. When a batch of traces
+//! fails, proptest shrinks it to the smallest traces that still fail, and the
+//! test saves those in `tests/regressions`. The `regressions` test checks
+//! every trace in that directory.
+//!
+//! Both tests run cargo, and the first run builds `dispatcher-agent-lib` in
+//! its own target directory, so it takes a while.
+//!
+//! `CONTAINERLESS_CASES` sets the number of batches to generate (16 by
+//! default).
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::test_runner::{TestError, TestRunner};
+use shared::trace::{BUILTINS, METHODS};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const OP1: &[&str] = &["void", "typeof", "-", "!", "~"];
+
+const OP2: &[&str] = &[
+    "+", "-", "*", "/", "%", "===", "!==", "==", "!=", ">", "<", ">=", "<=", "&&", "||", "&", "|",
+    "^", "<<", ">>", ">>>", "??", "in",
+];
+
+const FIELDS: &[&str] = &["a", "b", "length"];
+
+/// An expression of a trace. A variable is an index into the variables in
+/// scope, so that every trace that we generate is well-formed.
+#[derive(Debug, Clone)]
+enum Exp {
+    Number(i32),
+    Bool(bool),
+    Str(String),
+    Undefined,
+    Null,
+    Var(usize),
+    Op1(usize, Box<Exp>),
+    BinOp(usize, Box<Exp>, Box<Exp>),
+    Get(Box<Exp>, usize),
+    Index(Box<Exp>, Box<Exp>),
+    Object(Vec<(usize, Exp)>),
+    Array(Vec<Exp>),
+    MethodCall(Box<Exp>, usize, Vec<Exp>),
+    Call(Box<Exp>, Vec<Exp>),
+    Builtin(usize, Vec<Exp>),
+    Located(Box<Exp>),
+}
+
+/// A statement of a trace. Like a variable, the target of a `Break` is an
+/// index into the enclosing labels.
+#[derive(Debug, Clone)]
+enum Stmt {
+    Let(Exp),
+    Set(usize, Exp),
+    SetIndex(Exp, Exp, Exp),
+    If(Exp, Vec<Stmt>, Vec<Stmt>),
+    While(Exp, Vec<Stmt>),
+    Label(Vec<Stmt>),
+    Break(usize, Exp),
+    Throw(Exp),
+    Try(Vec<Stmt>, Option<Vec<Stmt>>, Vec<Stmt>),
+    Send(Exp),
+    /// Logs any number of values, which `console.log` separates with spaces.
+    Log(Vec<Exp>),
+    Callback(Exp, Vec<Stmt>),
+    /// Binds a variable to a lambda with the given body.
+    Lambda(Vec<Stmt>),
+    Unknown,
+}
+
+fn exp() -> impl Strategy<Value = Exp> {
+    let leaf = prop_oneof![
+        (-1i32..10).prop_map(Exp::Number),
+        any::<bool>().prop_map(Exp::Bool),
+        "[a-z]{0,2}".prop_map(Exp::Str),
+        Just(Exp::Undefined),
+        Just(Exp::Null),
+        any::<usize>().prop_map(Exp::Var),
+    ];
+    return leaf.prop_recursive(3, 12, 3, |exp| {
+        prop_oneof![
+            (0..OP1.len(), exp.clone()).prop_map(|(op, e)| Exp::Op1(op, Box::new(e))),
+            (0..OP2.len(), exp.clone(), exp.clone()).prop_map(|(op, e1, e2)| Exp::BinOp(
+                op,
+                Box::new(e1),
+                Box::new(e2)
+            )),
+            (exp.clone(), 0..FIELDS.len()).prop_map(|(e, field)| Exp::Get(Box::new(e), field)),
+            (exp.clone(), exp.clone()).prop_map(|(e1, e2)| Exp::Index(Box::new(e1), Box::new(e2))),
+            vec((0..FIELDS.len(), exp.clone()), 0..3).prop_map(Exp::Object),
+            vec(exp.clone(), 0..3).prop_map(Exp::Array),
+            (exp.clone(), 0..METHODS.len(), vec(exp.clone(), 0..3))
+                .prop_map(|(e, method, args)| Exp::MethodCall(Box::new(e), method, args)),
+            (exp.clone(), vec(exp.clone(), 0..2))
+                .prop_map(|(e, args)| Exp::Call(Box::new(e), args)),
+            (0..BUILTINS.len(), vec(exp.clone(), 0..3))
+                .prop_map(|(builtin, args)| Exp::Builtin(builtin, args)),
+            exp.prop_map(|e| Exp::Located(Box::new(e))),
+        ]
+    });
+}
+
+fn stmt() -> impl Strategy<Value = Stmt> {
+    let leaf = prop_oneof![
+        exp().prop_map(Stmt::Let),
+        (any::<usize>(), exp()).prop_map(|(var, e)| Stmt::Set(var, e)),
+        (exp(), exp(), exp()).prop_map(|(e1, e2, e3)| Stmt::SetIndex(e1, e2, e3)),
+        (any::<usize>(), exp()).prop_map(|(label, e)| Stmt::Break(label, e)),
+        exp().prop_map(Stmt::Throw),
+        exp().prop_map(Stmt::Send),
+        vec(exp(), 0..4).prop_map(Stmt::Log),
+        Just(Stmt::Unknown),
+    ];
+    return leaf.prop_recursive(3, 24, 4, |stmt| {
+        let block = vec(stmt, 0..4);
+        prop_oneof![
+            (exp(), block.clone(), block.clone()).prop_map(|(c, t, f)| Stmt::If(c, t, f)),
+            (exp(), block.clone()).prop_map(|(c, body)| Stmt::While(c, body)),
+            block.clone().prop_map(Stmt::Label),
+            (
+                block.clone(),
+                proptest::option::of(block.clone()),
+                block.clone()
+            )
+                .prop_map(|(body, handler, finalizer)| Stmt::Try(body, handler, finalizer)),
+            (exp(), block.clone()).prop_map(|(e, body)| Stmt::Callback(e, body)),
+            block.prop_map(Stmt::Lambda),
+        ]
+    });
+}
+
+/// Renders statements in the text syntax.
+struct Render {
+    /// The variables in scope, and whether each one is a field of the
+    /// closure of the enclosing callback.
+    vars: Vec<(String, bool)>,
+    labels: Vec<String>,
+    next_name: usize,
+    indent: usize,
+}
+
+impl Render {
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next_name += 1;
+        return format!("{}{}", prefix, self.next_name);
+    }
+
+    fn newline(&self) -> String {
+        return format!("\n{}", "  ".repeat(self.indent));
+    }
+
+    fn var(&self, var: usize) -> String {
+        let (name, captured) = &self.vars[var % self.vars.len()];
+        if *captured {
+            return format!("(from clos {})", name);
+        }
+        return name.to_string();
+    }
+
+    fn exps(&self, exps: &[Exp]) -> String {
+        return exps
+            .iter()
+            .map(|e| format!(" {}", self.exp(e)))
+            .collect::<String>();
+    }
+
+    fn exp(&self, exp: &Exp) -> String {
+        match exp {
+            Exp::Number(n) => return format!("{:?}", *n as f64),
+            Exp::Bool(b) => return b.to_string(),
+            Exp::Str(s) => return format!("{:?}", s),
+            Exp::Undefined => return "undefined".to_string(),
+            Exp::Null => return "null".to_string(),
+            Exp::Var(var) => return self.var(*var),
+            Exp::Op1(op, e) => return format!("({} {})", OP1[*op], self.exp(e)),
+            Exp::BinOp(op, e1, e2) => {
+                return format!("({} {} {})", OP2[*op], self.exp(e1), self.exp(e2))
+            }
+            Exp::Get(e, field) => return format!("(get {} {})", self.exp(e), FIELDS[*field]),
+            Exp::Index(e1, e2) => return format!("(index {} {})", self.exp(e1), self.exp(e2)),
+            Exp::Object(properties) => {
+                let properties = properties
+                    .iter()
+                    .map(|(field, e)| format!(" ({} {})", FIELDS[*field], self.exp(e)))
+                    .collect::<String>();
+                return format!("(object{})", properties);
+            }
+            Exp::Array(exps) => return format!("(array{})", self.exps(exps)),
+            Exp::MethodCall(e, method, args) => {
+                let (name, min, max) = METHODS[*method];
+                // Pass as many of the arguments as the method takes, and
+                // undefined for the missing ones.
+                let args = (0..args.len().max(min).min(max))
+                    .map(|i| match args.get(i) {
+                        Some(arg) => format!(" {}", self.exp(arg)),
+                        None => " undefined".to_string(),
+                    })
+                    .collect::<String>();
+                return format!("(methodCall {} {}{})", self.exp(e), name, args);
+            }
+            Exp::Call(e, args) => return format!("(call {}{})", self.exp(e), self.exps(args)),
+            Exp::Builtin(builtin, args) => {
+                return format!("(primApp {:?}{})", BUILTINS[*builtin].0, self.exps(args))
+            }
+            Exp::Located(e) => return format!("(located \"index.js\" 1 1 {})", self.exp(e)),
+        }
+    }
+
+    /// Renders a block, whose variables go out of scope when it ends.
+    fn block(&mut self, body: &[Stmt]) -> String {
+        if body.is_empty() {
+            return "[]".to_string();
+        }
+        let mark = self.vars.len();
+        self.indent += 1;
+        let mut text = "[".to_string();
+        for s in body.iter() {
+            text.push_str(&self.newline());
+            let s = self.stmt(s);
+            text.push_str(&s);
+        }
+        self.indent -= 1;
+        text.push_str(&self.newline());
+        text.push(']');
+        self.vars.truncate(mark);
+        return text;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::Let(e) => {
+                let e = self.exp(e);
+                let name = self.fresh("v");
+                self.vars.push((name.clone(), false));
+                return format!("(let {} {})", name, e);
+            }
+            Stmt::Set(var, e) => return format!("(set {} {})", self.var(*var), self.exp(e)),
+            Stmt::SetIndex(e1, e2, e3) => {
+                return format!(
+                    "(set (index {} {}) {})",
+                    self.exp(e1),
+                    self.exp(e2),
+                    self.exp(e3)
+                )
+            }
+            Stmt::If(cond, true_part, false_part) => {
+                let cond = self.exp(cond);
+                let true_part = self.block(true_part);
+                let false_part = self.block(false_part);
+                return format!("(if {} {} {})", cond, true_part, false_part);
+            }
+            Stmt::While(cond, body) => {
+                let cond = self.exp(cond);
+                return format!("(while {} {})", cond, self.block(body));
+            }
+            Stmt::Label(body) => {
+                let name = self.fresh("l");
+                self.labels.push(name.clone());
+                let body = self.block(body);
+                self.labels.pop();
+                return format!("(label {} {})", name, body);
+            }
+            Stmt::Break(label, e) => {
+                let label = &self.labels[label % self.labels.len()];
+                return format!("(break {:?} {})", label, self.exp(e));
+            }
+            Stmt::Throw(e) => return format!("(throw {})", self.exp(e)),
+            Stmt::Try(body, handler, finalizer) => {
+                let mut text = format!("(try {}", self.block(body));
+                // A JavaScript try has a catch clause, a finally clause, or both.
+                if handler.is_some() || finalizer.is_empty() {
+                    let name = self.fresh("e");
+                    let mark = self.vars.len();
+                    self.vars.push((name.clone(), false));
+                    let handler = self.block(handler.as_deref().unwrap_or(&[]));
+                    self.vars.truncate(mark);
+                    text.push_str(&format!(" (catch {} {})", name, handler));
+                }
+                if !finalizer.is_empty() {
+                    text.push_str(&format!(" (finally {})", self.block(finalizer)));
+                }
+                text.push(')');
+                return text;
+            }
+            Stmt::Send(e) => return format!("(primApp send {})", self.exp(e)),
+            Stmt::Log(args) => return format!("(primApp \"console.log\"{})", self.exps(args)),
+            Stmt::Callback(e, body) => {
+                let e = self.exp(e);
+                return format!("(callback get {} {})", e, self.closure("response", body));
+            }
+            Stmt::Lambda(body) => {
+                let lambda = self.closure("x", body);
+                let name = self.fresh("f");
+                self.vars.push((name.clone(), false));
+                return format!("(let {} (lambda {}))", name, lambda);
+            }
+            Stmt::Unknown => return "unknown".to_string(),
+        }
+    }
+
+    /// Renders the arguments, the closure and the body of a callback or a
+    /// lambda, whose argument is `arg`. The closure has every variable in
+    /// scope, and the body reaches them through it.
+    fn closure(&mut self, arg: &str, body: &[Stmt]) -> String {
+        let mut tenv = String::new();
+        let mut captured = Vec::new();
+        for var in 0..self.vars.len() {
+            let name = self.vars[var].0.clone();
+            if !captured.contains(&(name.clone(), true)) {
+                tenv.push_str(&format!(" ({} {})", name, self.var(var)));
+                captured.push((name, true));
+            }
+        }
+        captured.push((arg.to_string(), false));
+        let vars = std::mem::replace(&mut self.vars, captured);
+        let labels = std::mem::replace(&mut self.labels, vec!["'ret".to_string()]);
+        self.indent += 1;
+        let label = self.newline();
+        let body = self.block(body);
+        self.indent -= 1;
+        self.vars = vars;
+        self.labels = labels;
+        return format!(
+            "[clos {}] (clos{}) [{}(label \"'ret\" {}){}]",
+            arg,
+            tenv,
+            label,
+            body,
+            self.newline()
+        );
+    }
+}
+
+/// Renders a trace whose body is the handler of a server, like the traces
+/// that the tracing runtime produces.
+fn render(body: &[Stmt]) -> String {
+    let mut render = Render {
+        vars: vec![("request".to_string(), false)],
+        labels: vec!["'ret".to_string()],
+        next_name: 0,
+        indent: 3,
+    };
+    let body = render.block(body);
+    return format!(
+        "(block [
+  (let fun0 (clos))
+  (let app0 (block [
+    (callback listen 0.0 [clos request] fun0 [
+      (label \"'ret\" {})
+    ])
+  ]))
+])
+",
+        body
+    );
+}
+
+fn trace() -> impl Strategy<Value = String> {
+    return vec(stmt(), 1..6).prop_map(|body| render(&body));
+}
+
+fn root() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR")).to_path_buf();
+}
+
+/// Creates a crate in the temporary directory that depends on
+/// `dispatcher-agent-lib`, like the crate that the controller generates for
+/// a function.
+fn scratch_crate(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).expect("creating scratch crate");
+    let manifest = format!(
+        r#"[package]
+name = "{}"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+dispatcher-agent-lib = {{ path = "{}" }}
+bumpalo = "*"
+
+# This crate is not a member of the workspace of the repository.
+[workspace]
+"#,
+        name,
+        root().join("../dispatcher-agent-lib").display()
+    );
+    std::fs::write(dir.join("Cargo.toml"), manifest).expect("writing Cargo.toml");
+    // Use the same versions of the dependencies as the workspace.
+    let lock = dir.join("Cargo.lock");
+    if !lock.exists() {
+        let _ = std::fs::copy(root().join("../Cargo.lock"), &lock);
+    }
+    return dir;
+}
+
+/// Compiles each trace to a module of the crate in `dir`, and type-checks
+/// the crate. Returns the error if the trace compiler rejects a trace, or if
+/// the code does not type-check.
+fn check(dir: &Path, traces: &[String]) -> Result<(), String> {
+    let src = dir.join("src");
+    let _ = std::fs::remove_dir_all(&src);
+    std::fs::create_dir_all(&src).expect("creating src");
+    let mut lib = String::new();
+    for (i, trace) in traces.iter().enumerate() {
+        let trace_path = src.join(format!("trace_{}.trace", i));
+        std::fs::write(&trace_path, trace).expect("writing trace");
+        let output = Command::new(env!("CARGO_BIN_EXE_trace-compiler"))
+            .arg(&trace_path)
+            .arg("-o")
+            .arg(src.join(format!("trace_{}.rs", i)))
+            .output()
+            .expect("running trace-compiler");
+        if !output.status.success() {
+            return Err(format!(
+                "trace-compiler failed on trace_{}:\n{}",
+                i,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        lib.push_str(&format!("pub mod trace_{};\n", i));
+    }
+    std::fs::write(src.join("lib.rs"), lib).expect("writing lib.rs");
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["check", "--quiet", "--message-format", "short"])
+        .current_dir(dir)
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("compiles-target"),
+        )
+        .output()
+        .expect("running cargo check");
+    if !output.status.success() {
+        return Err(format!(
+            "cargo check failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    return Ok(());
+}
+
+fn regressions_dir() -> PathBuf {
+    return root().join("tests/regressions");
+}
+
+/// Saves a trace that fails in `tests/regressions`, and returns its path.
+fn save_regression(trace: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    trace.hash(&mut hasher);
+    let path = regressions_dir().join(format!("{:016x}.trace", hasher.finish()));
+    std::fs::create_dir_all(regressions_dir()).expect("creating tests/regressions");
+    std::fs::write(&path, trace).expect("writing regression");
+    return path;
+}
+
+#[test]
+fn regressions() {
+    let mut paths = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(regressions_dir()) {
+        for entry in entries {
+            let path = entry.expect("reading directory entry").path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("trace") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    let traces = paths
+        .iter()
+        .map(|path| std::fs::read_to_string(path).expect("reading regression"))
+        .collect::<Vec<_>>();
+    if let Err(message) = check(&scratch_crate("regressions"), &traces) {
+        let names = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| format!("trace_{} is {}", i, path.display()))
+            .collect::<Vec<_>>();
+        panic!("{}\n{}", message, names.join("\n"));
+    }
+}
+
+#[test]
+fn generated_traces_compile() {
+    let cases = std::env::var("CONTAINERLESS_CASES")
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(16);
+    let config = ProptestConfig {
+        cases,
+        // Every case runs cargo check, so shrinking is slow.
+        max_shrink_iters: 512,
+        // We save the traces instead of the seeds of failing cases.
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    };
+    let dir = scratch_crate("generated");
+    let result = TestRunner::new(config).run(&vec(trace(), 1..8), |traces| {
+        return check(&dir, &traces).map_err(TestCaseError::fail);
+    });
+    match result {
+        Ok(()) => (),
+        Err(TestError::Fail(message, traces)) => {
+            let paths = traces
+                .iter()
+                .map(|trace| save_regression(trace).display().to_string())
+                .collect::<Vec<_>>();
+            panic!(
+                "{}\nsaved the smallest failing traces in:\n{}",
+                message,
+                paths.join("\n")
+            );
+        }
+        Err(TestError::Abort(message)) => panic!("{}", message),
+    }
+}
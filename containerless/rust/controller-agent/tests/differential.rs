@@ -0,0 +1,135 @@
+//! Runs every trace that `golden.rs` compiles through both tiers of the
+//! dispatcher: the code that the trace compiler generates, and the
+//! interpreter, which runs the lifted trace. Both must produce the same
+//! response, or the same error, for the same requests.
+//!
+//! The test builds a crate in the temporary directory that has a module for
+//! the generated code of every trace, and runs `differential/runner.rs` as
+//! its main function. Like `compiles.rs`, the first run builds
+//! `dispatcher-agent-lib`, so it takes a while.
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn root() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR")).to_path_buf();
+}
+
+fn traces_in(dir: &Path) -> Vec<PathBuf> {
+    let mut traces = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => panic!("reading {}: {}", dir.display(), err),
+    };
+    for entry in entries {
+        let path = entry.expect("reading directory entry").path();
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        if ext == Some("json") || ext == Some("trace") {
+            traces.push(path);
+        }
+    }
+    traces.sort();
+    return traces;
+}
+
+/// Creates the crate that runs the traces, which depends on
+/// `dispatcher-agent-lib` like the crate that the controller generates for a
+/// function.
+fn scratch_crate() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential");
+    std::fs::create_dir_all(&dir).expect("creating scratch crate");
+    let manifest = format!(
+        r#"[package]
+name = "differential"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+dispatcher-agent-lib = {{ path = "{}" }}
+bumpalo = "*"
+serde_json = "*"
+
+# This crate is not a member of the workspace of the repository.
+[workspace]
+"#,
+        root().join("../dispatcher-agent-lib").display()
+    );
+    std::fs::write(dir.join("Cargo.toml"), manifest).expect("writing Cargo.toml");
+    // Use the same versions of the dependencies as the workspace.
+    let lock = dir.join("Cargo.lock");
+    if !lock.exists() {
+        let _ = std::fs::copy(root().join("../Cargo.lock"), &lock);
+    }
+    return dir;
+}
+
+/// Compiles `trace` to `src/{module}.rs`, and writes the lifted trace to
+/// `src/{module}.json`.
+fn compile(src: &Path, trace: &Path, module: &str) {
+    let code = src.join(format!("{}.rs", module));
+    let status = Command::new(env!("CARGO_BIN_EXE_trace-compiler"))
+        .arg(trace)
+        .arg("-o")
+        .arg(&code)
+        .arg("--lifted")
+        .arg(src.join(format!("{}.json", module)))
+        .status()
+        .expect("running trace-compiler");
+    assert!(
+        status.success(),
+        "trace-compiler failed on {}",
+        trace.display()
+    );
+    // Every module would export the same symbols for the shared library.
+    let code = std::fs::read_to_string(&code).expect("reading generated code");
+    let code = code.replace(
+        "dispatcher_agent_lib::export_containerless!(containerless);",
+        "",
+    );
+    std::fs::write(src.join(format!("{}.rs", module)), code).expect("writing generated code");
+}
+
+#[test]
+fn generated_code_and_interpreter_agree() {
+    let mut traces = traces_in(&root().join("../../javascript/containerless/traces"));
+    traces.extend(traces_in(&root().join("tests/golden/text")));
+    let dir = scratch_crate();
+    let src = dir.join("src");
+    let _ = std::fs::remove_dir_all(&src);
+    std::fs::create_dir_all(&src).expect("creating src");
+    let mut main = String::new();
+    let mut table = String::new();
+    for (i, trace) in traces.iter().enumerate() {
+        let module = format!("trace_{}", i);
+        compile(&src, trace, &module);
+        main.push_str(&format!("mod {};\n", module));
+        table.push_str(&format!(
+            "    ({:?}, {}::containerless, include_str!(\"{}.json\")),\n",
+            trace.file_name().unwrap().to_str().unwrap(),
+            module,
+            module
+        ));
+    }
+    main.push_str(&format!(
+        "\nconst TRACES: &[(&str, Containerless, &str)] = &[\n{}];\n\n",
+        table
+    ));
+    main.push_str(include_str!("differential/runner.rs"));
+    std::fs::write(src.join("main.rs"), main).expect("writing main.rs");
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["run", "--quiet"])
+        .current_dir(&dir)
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential-target"),
+        )
+        .output()
+        .expect("running cargo run");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    println!("{}", stdout);
+    assert!(
+        output.status.success(),
+        "the tiers disagree, or the crate does not build:\n{}\n{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
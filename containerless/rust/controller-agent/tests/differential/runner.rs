@@ -0,0 +1,72 @@
+// The main function of the crate that `tests/differential.rs` builds. The
+// test puts the modules of the generated code and the table `TRACES` before
+// this file.
+use bumpalo::Bump;
+use dispatcher_agent_lib::trace_runtime::*;
+use std::collections::VecDeque;
+
+/// The bodies of the requests that every trace receives. The traces read
+/// these fields of the body.
+const BODIES: &[&str] = &[
+    r#"{"n": 2, "x": 1, "xs": [3, 1, 2], "arr": [1, 2, 3],
+        "text": "Hello, World", "words": ["a", "b", "a"]}"#,
+    r#"{"n": 0, "x": -1, "xs": [], "arr": [], "text": "", "words": []}"#,
+    r#"{}"#,
+];
+
+/// Runs an invocation like `run_decontainerized_function`, but without I/O:
+/// the response to a request is the URL of the request.
+fn run<F>(containerless: F, body: &str) -> String
+where
+    F: for<'a> Fn(&'a Bump, &mut ExecutionContext<'a>, Dyn<'a>, Dyn<'a>) -> DynResult<'a>,
+{
+    let arena = Bump::new();
+    let request = Dyn::object(&arena);
+    request.set_field("path", Dyn::str(&arena, "/")).unwrap();
+    let body = Dyn::from_json(&arena, serde_json::from_str(body).unwrap());
+    request.set_field("body", body).unwrap();
+    let mut ec = ExecutionContext::new(&arena, DEFAULT_GAS_LIMIT);
+    let mut pending = VecDeque::new();
+    pending.push_back(PendingOp::initial());
+    while let Some(op) = pending.pop_front() {
+        let outcome = match op.async_op {
+            AsyncOp::Preinitialize => AsyncOpOutcome::Initialize,
+            AsyncOp::Listen => AsyncOpOutcome::Connected,
+            AsyncOp::Get(url) | AsyncOp::Post(url, _) | AsyncOp::Put(url, _) => {
+                AsyncOpOutcome::MockGetResponse(serde_json::Value::String(url))
+            }
+            other => return format!("unexpected event {:?}", other),
+        };
+        let args = outcome.process(&arena, request, op.closure);
+        if let Err(err) = containerless(&arena, &mut ec, Dyn::int(op.indicator), args) {
+            return format!("error {}", err);
+        }
+        pending.extend(ec.new_ops.drain(..));
+    }
+    return format!("response {:?}", ec.response.map(|value| value.to_json()));
+}
+
+fn main() {
+    let mut differences = 0;
+    for (name, containerless, ir) in TRACES.iter() {
+        let program = Program::from_json(ir).expect("reading the lifted trace");
+        for body in BODIES.iter() {
+            let compiled = run(containerless, body);
+            let interpreted = run(
+                |arena, ec, arg_cbid, arg_cbargs| {
+                    program.containerless(arena, ec, arg_cbid, arg_cbargs)
+                },
+                body,
+            );
+            println!("{} with {}: {}", name, body, compiled);
+            if compiled != interpreted {
+                println!("  but the interpreter produces {}", interpreted);
+                differences += 1;
+            }
+        }
+    }
+    if differences > 0 {
+        println!("{} difference(s)", differences);
+        std::process::exit(1);
+    }
+}
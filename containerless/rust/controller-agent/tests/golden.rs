@@ -0,0 +1,175 @@
+//! Compiles every known trace with the `trace-compiler` bin and compares the
+//! generated code to a checked-in expected file. The traces are:
+//!
+//! - every trace in `javascript/containerless/traces`, whose expected code is
+//!   in `tests/golden/traces`, and
+//!
+//! - the trace of every example in `integration-tests/src/tests.rs`, which
+//!   are in `tests/golden/integration`, next to their expected code. Running
+//!   the integration tests with `CONTAINERLESS_BLESS=1` replaces these traces,
+//!   and
+//!
+//! - the hand-written traces in `tests/golden/text`, which are in the text
+//!   syntax, next to their expected code.
+//!
+//! In addition, every trace in `javascript/containerless/traces` must
+//! compile to its expected code after a round trip through the text syntax.
+//!
+//! To accept a change to the generated code, run the test with
+//! `CONTAINERLESS_BLESS=1`, which overwrites the expected files.
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn root() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR")).to_path_buf();
+}
+
+fn traces_in(dir: &Path) -> Vec<PathBuf> {
+    let mut traces = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => panic!("reading {}: {}", dir.display(), err),
+    };
+    for entry in entries {
+        let path = entry.expect("reading directory entry").path();
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        if ext == Some("json") || ext == Some("trace") {
+            traces.push(path);
+        }
+    }
+    traces.sort();
+    return traces;
+}
+
+/// A file in the temporary directory for the output of `trace`. Several tests
+/// may compile a trace called loops.json at the same time.
+fn output_path(trace: &Path, ext: &str) -> PathBuf {
+    let dir = trace
+        .parent()
+        .unwrap()
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let file = trace.file_name().unwrap().to_str().unwrap();
+    return Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}_{}.{}", dir, file, ext));
+}
+
+fn compile(trace: &Path) -> String {
+    let output = output_path(trace, "rs");
+    let status = Command::new(env!("CARGO_BIN_EXE_trace-compiler"))
+        .arg(trace)
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .expect("running trace-compiler");
+    assert!(
+        status.success(),
+        "trace-compiler failed on {}",
+        trace.display()
+    );
+    return std::fs::read_to_string(&output).expect("reading generated code");
+}
+
+/// Returns the names of the traces whose generated code does not match the
+/// expected code.
+fn check(traces: &[PathBuf], expected_dir: &Path) -> Vec<String> {
+    let bless = std::env::var("CONTAINERLESS_BLESS").is_ok();
+    let mut mismatches = Vec::new();
+    for trace in traces.iter() {
+        let actual = compile(trace);
+        let expected_path = expected_dir
+            .join(trace.file_stem().unwrap())
+            .with_extension("rs");
+        if bless {
+            std::fs::create_dir_all(expected_dir).expect("creating golden directory");
+            std::fs::write(&expected_path, &actual).expect("writing expected code");
+            continue;
+        }
+        match std::fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => (),
+            Ok(_) => mismatches.push(format!("{} (differs)", trace.display())),
+            Err(_) => mismatches.push(format!("{} (no expected code)", trace.display())),
+        }
+    }
+    return mismatches;
+}
+
+#[test]
+fn golden_traces() {
+    let traces_dir = root().join("../../javascript/containerless/traces");
+    let traces = traces_in(&traces_dir);
+    assert!(!traces.is_empty(), "no traces in {}", traces_dir.display());
+    let mismatches = check(&traces, &root().join("tests/golden/traces"));
+    assert!(
+        mismatches.is_empty(),
+        "generated code changed (rerun with CONTAINERLESS_BLESS=1 to accept):\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn golden_text() {
+    let dir = root().join("tests/golden/text");
+    let traces = traces_in(&dir);
+    assert!(!traces.is_empty(), "no traces in {}", dir.display());
+    let mismatches = check(&traces, &dir);
+    assert!(
+        mismatches.is_empty(),
+        "generated code changed (rerun with CONTAINERLESS_BLESS=1 to accept):\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn text_round_trip() {
+    let traces = traces_in(&root().join("../../javascript/containerless/traces"));
+    for trace in traces.iter() {
+        let text = output_path(trace, "trace");
+        // We only need the text, but the bin always writes code.
+        let status = Command::new(env!("CARGO_BIN_EXE_trace-compiler"))
+            .arg(trace)
+            .arg("-o")
+            .arg(output_path(trace, "text.rs"))
+            .arg("--text")
+            .arg(&text)
+            .status()
+            .expect("running trace-compiler");
+        assert!(
+            status.success(),
+            "trace-compiler failed on {}",
+            trace.display()
+        );
+        let expected_path = root()
+            .join("tests/golden/traces")
+            .join(trace.file_stem().unwrap())
+            .with_extension("rs");
+        let expected = std::fs::read_to_string(&expected_path).expect("reading expected code");
+        assert!(
+            compile(&text) == expected,
+            "{} compiles differently after a round trip through {}",
+            trace.display(),
+            text.display()
+        );
+    }
+}
+
+// The traces come from running the integration tests against a cluster, and
+// no one has blessed them yet. Remove the ignore once they are checked in.
+#[test]
+#[ignore]
+fn golden_integration() {
+    let dir = root().join("tests/golden/integration");
+    let traces = traces_in(&dir);
+    assert!(
+        !traces.is_empty(),
+        "no traces in {} (run ./test.sh with CONTAINERLESS_BLESS=1)",
+        dir.display()
+    );
+    let mismatches = check(&traces, &dir);
+    assert!(
+        mismatches.is_empty(),
+        "generated code changed (rerun with CONTAINERLESS_BLESS=1 to accept):\n{}",
+        mismatches.join("\n")
+    );
+}
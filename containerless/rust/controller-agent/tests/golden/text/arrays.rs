@@ -0,0 +1,103 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut words = req.get("body")?.get("words")?;
+            let mut top = words.slice(arena, Dyn::float(0f64), Dyn::float(3f64))?;
+            let mut last = words.slice(arena, Dyn::float(-1f64), Dyn::undef())?;
+            let mut more = top
+                .concat(arena, last)?
+                .concat(
+                    arena,
+                    Dyn::vec_with(arena, vec![Dyn::str(arena, "x"), Dyn::str(arena, "y")]),
+                )?
+                .concat(arena, Dyn::str(arena, "z"))?;
+            let mut removed = more.splice(
+                arena,
+                Dyn::float(1f64),
+                Dyn::float(2f64),
+                Dyn::vec_with(
+                    arena,
+                    vec![
+                        Dyn::str(arena, "a"),
+                        Dyn::str(arena, "b"),
+                        Dyn::str(arena, "c"),
+                    ],
+                ),
+            )?;
+            let mut length = more.unshift(
+                arena,
+                Dyn::vec_with(
+                    arena,
+                    vec![Dyn::str(arena, "first"), Dyn::str(arena, "second")],
+                ),
+            )?;
+            let mut reversed = more
+                .slice(arena, Dyn::undef(), Dyn::undef())?
+                .reverse(arena)?;
+            let mut filled = Dyn::vec_with(
+                arena,
+                vec![
+                    Dyn::float(1f64),
+                    Dyn::float(2f64),
+                    Dyn::float(3f64),
+                    Dyn::float(4f64),
+                ],
+            )
+            .fill(arena, Dyn::float(0f64), Dyn::float(1f64), Dyn::float(-1f64))?;
+            let mut found = more.indexOf(arena, Dyn::str(arena, "a"), Dyn::undef())?;
+            let mut has = Dyn::vec_with(arena, vec![Dyn::float(1f64), Dyn::float((0f64 / 0f64))])
+                .includes(arena, Dyn::float((0f64 / 0f64)), Dyn::undef())?;
+            let mut isArray = words.isArray(arena)?;
+            let mut notArray = Dyn::str(arena, "words").isArray(arena)?;
+            ec.send(Dyn::object_with(
+                arena,
+                vec![
+                    ("joined", more.join(arena, Dyn::str(arena, ", "))?),
+                    (
+                        "csv",
+                        Dyn::vec_with(
+                            arena,
+                            vec![
+                                Dyn::float(1f64),
+                                Dyn::undef(),
+                                Dyn::null(),
+                                Dyn::vec_with(arena, vec![Dyn::float(2f64), Dyn::float(3f64)]),
+                            ],
+                        )
+                        .join(arena, Dyn::undef())?,
+                    ),
+                    ("added", (top).add(arena, Dyn::str(arena, "!"))?),
+                    ("removed", removed),
+                    ("length", length),
+                    ("reversed", reversed),
+                    ("filled", filled),
+                    ("found", found),
+                    ("has", has),
+                    ("isArray", isArray),
+                    ("notArray", notArray),
+                ],
+            ))?
+        }
+    } else {
+        {
+            let mut fun0 = Dyn::object_with(arena, vec![]);
+            let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+            Dyn::undef()
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
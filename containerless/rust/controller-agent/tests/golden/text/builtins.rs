@@ -0,0 +1,162 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut data = rt::builtins::json_parse(arena, &[req.get("body")?.get("text")?], ec)?;
+            let mut xs = data.get("xs")?;
+            let mut lo = rt::builtins::math_min(
+                arena,
+                &[
+                    (xs).index(arena, Dyn::float(0f64))?,
+                    (xs).index(arena, Dyn::float(1f64))?,
+                    (xs).index(arena, Dyn::float(2f64))?,
+                ],
+                ec,
+            )?;
+            let mut hi = rt::builtins::math_max(
+                arena,
+                &[
+                    (xs).index(arena, Dyn::float(0f64))?,
+                    (xs).index(arena, Dyn::float(1f64))?,
+                    (xs).index(arena, Dyn::float(2f64))?,
+                ],
+                ec,
+            )?;
+            let mut half = Dyn::closure(arena, 2i32, Dyn::object_with(arena, vec![]));
+            let mut floor = {
+                let args = [half.call(arena, &[hi], &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                })?];
+                rt::builtins::math_floor(arena, &args, ec)
+            }?;
+            let mut rounded = rt::builtins::math_round(arena, &[Dyn::float(-2.5f64)], ec)?;
+            let mut power = {
+                let args = [rt::builtins::math_abs(arena, &[lo], ec)?, Dyn::float(3f64)];
+                rt::builtins::math_pow(arena, &args, ec)
+            }?;
+            let mut root = rt::builtins::math_sqrt(arena, &[Dyn::float(16f64)], ec)?;
+            let mut ceil = rt::builtins::math_ceil(arena, &[Dyn::float(1.2f64)], ec)?;
+            let mut random = rt::builtins::math_random(arena, &[], ec)?;
+            let mut inRange = (rt::guard_bool((random).gte(Dyn::float(0f64))?)?
+                & rt::guard_bool((random).lt(Dyn::float(1f64))?)?);
+            let mut hex = rt::builtins::parse_int(arena, &[Dyn::str(arena, "0x1f")], ec)?;
+            let mut binary = rt::builtins::parse_int(
+                arena,
+                &[Dyn::str(arena, "  -101xyz"), Dyn::float(2f64)],
+                ec,
+            )?;
+            let mut float = rt::builtins::parse_float(arena, &[Dyn::str(arena, "3.5e2px")], ec)?;
+            let mut number = rt::builtins::number(arena, &[Dyn::str(arena, "  42  ")], ec)?;
+            let mut notNumber = {
+                let args = [rt::builtins::number(arena, &[Dyn::str(arena, "4x")], ec)?];
+                rt::builtins::string(arena, &args, ec)
+            }?;
+            let mut string = rt::builtins::string(
+                arena,
+                &[Dyn::vec_with(
+                    arena,
+                    vec![
+                        Dyn::float(1f64),
+                        Dyn::vec_with(arena, vec![Dyn::float(2f64), Dyn::float(3f64)]),
+                    ],
+                )],
+                ec,
+            )?;
+            let mut error = Dyn::str(arena, "none");
+            let _ = {
+                let completion: Result<Dyn<'a>, Dyn<'a>> = 'try0: {
+                    Ok({
+                        let mut bad = match rt::catchable(rt::builtins::json_parse(
+                            arena,
+                            &[Dyn::str(arena, "{")],
+                            ec,
+                        ))? {
+                            Ok(value) => value,
+                            Err(exn) => break 'try0 Err(exn),
+                        };
+                        Dyn::undef()
+                    })
+                };
+                let completion = match completion {
+                    Ok(value) => Ok(value),
+                    Err(exn) => 'catch0: {
+                        Ok({
+                            let mut e = Dyn::ref_(arena, exn);
+                            {
+                                error = match rt::catchable(Dyn::deref(e).get("name"))? {
+                                    Ok(value) => value,
+                                    Err(exn) => break 'catch0 Err(exn),
+                                };
+                                Dyn::undef()
+                            }
+                        })
+                    }
+                };
+                completion.or_else(rt::throw)?
+            };
+            {
+                let args = (Dyn::object_with(
+                    arena,
+                    vec![
+                        ("lo", lo),
+                        ("hi", hi),
+                        ("floor", floor),
+                        ("rounded", rounded),
+                        ("power", power),
+                        ("root", root),
+                        ("ceil", ceil),
+                        ("inRange", Dyn::bool(inRange)),
+                        ("hex", hex),
+                        ("binary", binary),
+                        ("float", float),
+                        ("number", number),
+                        ("notNumber", notNumber),
+                        ("string", string),
+                        ("error", error),
+                        (
+                            "json",
+                            rt::builtins::json_stringify(
+                                arena,
+                                &[Dyn::object_with(
+                                    arena,
+                                    vec![("xs", xs), ("none", Dyn::undef())],
+                                )],
+                                ec,
+                            )?,
+                        ),
+                    ],
+                ),);
+                ec.send(args.0)
+            }?
+        }
+    } else {
+        if ((arg_cbid).strict_eq(Dyn::int(2i32))?).is_truthy() {
+            let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+            let mut x = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+            'ret: {
+                break 'ret (Dyn::deref(x)).div(Dyn::float(2f64))?;
+            }
+        } else {
+            {
+                let mut fun0 = Dyn::object_with(arena, vec![]);
+                let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+                Dyn::undef()
+            }
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
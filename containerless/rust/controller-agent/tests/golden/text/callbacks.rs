@@ -0,0 +1,82 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut n = Dyn::ref_(arena, req.get("body")?.get("n")?);
+            let mut total = Dyn::ref_(arena, Dyn::float(0f64));
+            let mut i = 0f64;
+            let _ = {
+                while ((Dyn::float(i)).lt(Dyn::deref(n))?).is_truthy() {
+                    ec.count()?;
+                    let _ = {
+                        i = (i + 1f64);
+                        Dyn::undef()
+                    };
+                    let mut app1 = {
+                        ec.loopback(
+                            "get",
+                            Dyn::str(arena, "http://example.com"),
+                            Dyn::object_with(arena, vec![("total", total), ("n", n)]),
+                            2i32,
+                        )?
+                    };
+                    Dyn::undef();
+                }
+                Dyn::undef()
+            };
+            let _ = if ((Dyn::deref(n)).gt(Dyn::float(1f64))?).is_truthy() {
+                let _ = total.setref(Dyn::float(5f64))?;
+                let _ = ec.loopback(
+                    "get",
+                    Dyn::str(arena, "http://example.com/n"),
+                    Dyn::object_with(arena, vec![("total", total), ("n", n)]),
+                    3i32,
+                )?;
+                total.setref((Dyn::deref(total)).add(arena, Dyn::float(1f64))?)?
+            } else {
+                Dyn::undef()
+            };
+            ec.send(Dyn::deref(total))?
+        }
+    } else {
+        if ((arg_cbid).strict_eq(Dyn::int(3i32))?).is_truthy() {
+            let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+            let mut response = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+            'ret: {
+                ec.send(Dyn::deref(Dyn::deref(clos).get("n")?))?
+            }
+        } else {
+            if ((arg_cbid).strict_eq(Dyn::int(2i32))?).is_truthy() {
+                let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+                let mut response = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+                'ret: {
+                    let _ = Dyn::deref(clos).get("total")?.setref(
+                        (Dyn::deref(Dyn::deref(clos).get("total")?))
+                            .add(arena, Dyn::float(1f64))?,
+                    )?;
+                    ec.send(Dyn::deref(Dyn::deref(clos).get("total")?))?
+                }
+            } else {
+                {
+                    let mut fun0 = Dyn::object_with(arena, vec![]);
+                    let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+                    Dyn::undef()
+                }
+            }
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
@@ -0,0 +1,184 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut xs = req.get("body")?.get("xs")?;
+            let mut k = Dyn::ref_(arena, Dyn::float(2f64));
+            let mut scale = Dyn::closure(arena, 2i32, Dyn::object_with(arena, vec![("k", k)]));
+            let mut total = Dyn::ref_(arena, Dyn::float(0f64));
+            let mut done = xs.forEach(
+                arena,
+                Dyn::closure(arena, 3i32, Dyn::object_with(arena, vec![("total", total)])),
+                &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                },
+            )?;
+            let mut scaled = xs.map(arena, scale, &mut |cbid, cbargs| {
+                ec.count()?;
+                containerless(arena, ec, cbid, cbargs)
+            })?;
+            let mut big = scaled.filter(
+                arena,
+                Dyn::closure(arena, 4i32, Dyn::object_with(arena, vec![("k", k)])),
+                &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                },
+            )?;
+            let mut sum = xs.reduce(
+                arena,
+                Dyn::closure(arena, 5i32, Dyn::object_with(arena, vec![])),
+                Dyn::float(0f64),
+                &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                },
+            )?;
+            let mut sorted = xs.sort(
+                arena,
+                Dyn::closure(arena, 6i32, Dyn::object_with(arena, vec![])),
+                &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                },
+            )?;
+            let mut first = xs.find(
+                arena,
+                Dyn::closure(arena, 7i32, Dyn::object_with(arena, vec![])),
+                &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                },
+            )?;
+            let mut any = xs.some(
+                arena,
+                Dyn::closure(arena, 8i32, Dyn::object_with(arena, vec![])),
+                &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                },
+            )?;
+            {
+                let args = (Dyn::object_with(
+                    arena,
+                    vec![
+                        ("total", Dyn::deref(total)),
+                        ("scaled", scaled),
+                        ("big", big),
+                        ("sum", sum),
+                        ("sorted", sorted),
+                        ("first", first),
+                        ("any", any),
+                        (
+                            "ten",
+                            scale.call(arena, &[Dyn::float(5f64)], &mut |cbid, cbargs| {
+                                ec.count()?;
+                                containerless(arena, ec, cbid, cbargs)
+                            })?,
+                        ),
+                    ],
+                ),);
+                ec.send(args.0)
+            }?
+        }
+    } else {
+        if ((arg_cbid).strict_eq(Dyn::int(8i32))?).is_truthy() {
+            let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+            let mut x = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+            'ret: {
+                break 'ret (Dyn::deref(x)).strict_eq(Dyn::float(0f64))?;
+            }
+        } else {
+            if ((arg_cbid).strict_eq(Dyn::int(7i32))?).is_truthy() {
+                let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+                let mut x = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+                'ret: {
+                    break 'ret (Dyn::deref(x)).gt(Dyn::float(2f64))?;
+                }
+            } else {
+                if ((arg_cbid).strict_eq(Dyn::int(6i32))?).is_truthy() {
+                    let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+                    let mut a = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+                    let mut b = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(2i32))?);
+                    'ret: {
+                        break 'ret (Dyn::deref(b)).sub(Dyn::deref(a))?;
+                    }
+                } else {
+                    if ((arg_cbid).strict_eq(Dyn::int(5i32))?).is_truthy() {
+                        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+                        let mut acc = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+                        let mut x = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(2i32))?);
+                        'ret: {
+                            break 'ret (Dyn::deref(acc)).add(arena, Dyn::deref(x))?;
+                        }
+                    } else {
+                        if ((arg_cbid).strict_eq(Dyn::int(4i32))?).is_truthy() {
+                            let mut clos =
+                                Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+                            let mut x =
+                                Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+                            let mut i =
+                                Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(2i32))?);
+                            'ret: {
+                                break 'ret (Dyn::deref(x))
+                                    .gt((Dyn::deref(Dyn::deref(clos).get("k")?))
+                                        .add(arena, Dyn::deref(i))?)?;
+                            }
+                        } else {
+                            if ((arg_cbid).strict_eq(Dyn::int(3i32))?).is_truthy() {
+                                let mut clos =
+                                    Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+                                let mut x =
+                                    Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+                                'ret: {
+                                    Dyn::deref(clos).get("total")?.setref(
+                                        (Dyn::deref(Dyn::deref(clos).get("total")?))
+                                            .add(arena, Dyn::deref(x))?,
+                                    )?
+                                }
+                            } else {
+                                if ((arg_cbid).strict_eq(Dyn::int(2i32))?).is_truthy() {
+                                    let mut clos = Dyn::ref_(
+                                        arena,
+                                        (arg_cbargs).index(arena, Dyn::int(0i32))?,
+                                    );
+                                    let mut x = Dyn::ref_(
+                                        arena,
+                                        (arg_cbargs).index(arena, Dyn::int(1i32))?,
+                                    );
+                                    'ret: {
+                                        break 'ret (Dyn::deref(x))
+                                            .mul(Dyn::deref(Dyn::deref(clos).get("k")?))?;
+                                    }
+                                } else {
+                                    {
+                                        let mut fun0 = Dyn::object_with(arena, vec![]);
+                                        let mut app0 = {
+                                            ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)?
+                                        };
+                                        Dyn::undef()
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
@@ -0,0 +1,97 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut x = req.get("body")?.get("x")?;
+            let mut r = 0f64;
+            let _ = 'l1: {
+                {
+                    r = (r + 1f64);
+                    Dyn::undef()
+                }
+            };
+            let _ = 'outer: {
+                let _ = 'inner: {
+                    if ((x).gt(Dyn::float(0f64))?).is_truthy() {
+                        break 'inner Dyn::undef();
+                    } else {
+                        {
+                            r = (r + 100f64);
+                            Dyn::undef()
+                        }
+                    }
+                };
+                {
+                    r = (r + 1000f64);
+                    Dyn::undef()
+                }
+            };
+            let _ = 'a: {
+                let _ = 'b: {
+                    let _ = if ((x).gt(Dyn::float(1f64))?).is_truthy() {
+                        break 'a Dyn::undef();
+                    } else {
+                        Dyn::undef()
+                    };
+                    {
+                        r = (r + 5f64);
+                        Dyn::undef()
+                    }
+                };
+                {
+                    r = (r + 7f64);
+                    Dyn::undef()
+                }
+            };
+            let mut i = 0f64;
+            let _ = {
+                while (i < 3f64) {
+                    ec.count()?;
+                    let _ = {
+                        i = (i + 1f64);
+                        Dyn::undef()
+                    };
+                    'l2: {
+                        let _ = if (i == 2f64) {
+                            break 'l2 Dyn::undef();
+                        } else {
+                            Dyn::undef()
+                        };
+                        {
+                            r = (r + 10f64);
+                            Dyn::undef()
+                        }
+                    };
+                }
+                Dyn::undef()
+            };
+            let mut app2 = { ec.send(Dyn::float(r))? };
+            'tail: {
+                {
+                    r = r;
+                    Dyn::undef()
+                }
+            }
+        }
+    } else {
+        {
+            let mut fun0 = Dyn::object_with(arena, vec![]);
+            let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+            Dyn::undef()
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
@@ -0,0 +1,41 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        let mut body = Dyn::deref(request).get("body")?;
+        let mut result = {
+            let mut n = rt::at(body.get("n"), "f.js", 5u32, 6u32)?;
+            let _ = if (rt::at((n).strict_eq(Dyn::float(0f64)), "f.js", 1u32, 2u32)?).is_truthy() {
+                let mut m = rt::at(Dyn::null().get("n"), "f.js", 7u32, 8u32)?;
+                Dyn::undef()
+            } else {
+                Dyn::undef()
+            };
+            if (rt::at((n).strict_eq(Dyn::float(2f64)), "f.js", 1u32, 2u32)?).is_truthy() {
+                rt::at(ec.send(n), "f.js", 1u32, 2u32)?
+            } else {
+                let mut e = rt::at(Dyn::undef().get("n"), "f.js", 1u32, 2u32)?;
+                Dyn::undef()
+            }
+        };
+        Dyn::undef()
+    } else {
+        {
+            let mut fun0 = Dyn::object_with(arena, vec![]);
+            let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+            Dyn::undef()
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
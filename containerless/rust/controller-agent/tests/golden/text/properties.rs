@@ -0,0 +1,72 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut words = req.get("body")?.get("words")?;
+            let mut counts = Dyn::object_with(arena, vec![]);
+            let mut i = 0f64;
+            let _ = {
+                while ((Dyn::float(i)).lt(words.get("length")?)?).is_truthy() {
+                    ec.count()?;
+                    let mut word = (words).index(arena, Dyn::float(i))?;
+                    let _ = counts.set(
+                        arena,
+                        word,
+                        (((counts).index(arena, word)?).nullish(Dyn::float(0f64))?)
+                            .add(arena, Dyn::float(1f64))?,
+                    )?;
+                    {
+                        i = (i + 1f64);
+                        Dyn::undef()
+                    };
+                }
+                Dyn::undef()
+            };
+            let mut letters = Dyn::vec_with(arena, vec![]);
+            let _ = letters.set(
+                arena,
+                Dyn::float(3f64),
+                (Dyn::str(arena, "abcd")).index(arena, Dyn::float(3f64))?,
+            )?;
+            let _ = letters.set(
+                arena,
+                Dyn::str(arena, "0"),
+                (Dyn::str(arena, "abcd")).index(arena, Dyn::float(0f64))?,
+            )?;
+            let mut grown = (letters).index(arena, Dyn::str(arena, "length"))?;
+            let _ = letters.set(arena, Dyn::str(arena, "length"), Dyn::float(2f64))?;
+            ec.send(Dyn::object_with(
+                arena,
+                vec![
+                    ("counts", counts),
+                    ("letters", letters),
+                    ("grown", grown),
+                    (
+                        "missing",
+                        (Dyn::str(arena, "abcd")).index(arena, Dyn::float(4f64))?,
+                    ),
+                ],
+            ))?
+        }
+    } else {
+        {
+            let mut fun0 = Dyn::object_with(arena, vec![]);
+            let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+            Dyn::undef()
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
@@ -0,0 +1,77 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut path = req.get("path")?;
+            let mut parts = path.slice(arena, Dyn::float(1f64), Dyn::undef())?.split(
+                arena,
+                Dyn::str(arena, "/"),
+                Dyn::undef(),
+            )?;
+            let mut name = (parts).index(arena, Dyn::float(0f64))?.toUpperCase(arena)?;
+            let mut query = path
+                .substring(
+                    arena,
+                    path.indexOf(arena, Dyn::str(arena, "?"), Dyn::undef())?,
+                    Dyn::undef(),
+                )?
+                .replace(arena, Dyn::str(arena, "?"), Dyn::str(arena, "$&$$"))?;
+            ec.send(Dyn::object_with(
+                arena,
+                vec![
+                    ("parts", parts),
+                    ("name", name),
+                    ("query", query),
+                    (
+                        "padded",
+                        Dyn::str(arena, "7").padStart(
+                            arena,
+                            Dyn::float(3f64),
+                            Dyn::str(arena, "0"),
+                        )?,
+                    ),
+                    (
+                        "trimmed",
+                        Dyn::str(arena, "  a-b  ")
+                            .trim(arena)?
+                            .concat(arena, Dyn::str(arena, "/"))?
+                            .concat(arena, Dyn::float(1f64))?
+                            .concat(arena, Dyn::bool(true))?,
+                    ),
+                    (
+                        "code",
+                        Dyn::str(arena, "€uro").charCodeAt(arena, Dyn::undef())?,
+                    ),
+                    (
+                        "tail",
+                        Dyn::str(arena, "héllo").slice(arena, Dyn::float(-3f64), Dyn::undef())?,
+                    ),
+                    (
+                        "found",
+                        path.includes(arena, Dyn::str(arena, "x"), Dyn::float(2f64))?,
+                    ),
+                ],
+            ))?
+        }
+    } else {
+        {
+            let mut fun0 = Dyn::object_with(arena, vec![]);
+            let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+            Dyn::undef()
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
@@ -0,0 +1,193 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut body = Dyn::deref(request).get("body")?;
+            let mut log = 0f64;
+            let mut r = 0f64;
+            let _ = {
+                let mut finally0: Option<(usize, Dyn<'a>)> = None;
+                let completion: Result<Dyn<'a>, Dyn<'a>> = 'try0: {
+                    Ok({
+                        {
+                            let mut finally1: Option<(usize, Dyn<'a>)> = None;
+                            let completion: Result<Dyn<'a>, Dyn<'a>> = 'try1: {
+                                Ok({
+                                    let _ = if (match rt::catchable(
+                                        (match rt::catchable(body.get("mode"))? {
+                                            Ok(value) => value,
+                                            Err(exn) => break 'try1 Err(exn),
+                                        })
+                                        .strict_eq(Dyn::float(1f64)),
+                                    )? {
+                                        Ok(value) => value,
+                                        Err(exn) => break 'try1 Err(exn),
+                                    })
+                                    .is_truthy()
+                                    {
+                                        match rt::catchable(rt::throw(Dyn::float(10f64)))? {
+                                            Ok(value) => value,
+                                            Err(exn) => break 'try1 Err(exn),
+                                        }
+                                    } else {
+                                        Dyn::undef()
+                                    };
+                                    let _ = if (match rt::catchable(
+                                        (match rt::catchable(body.get("mode"))? {
+                                            Ok(value) => value,
+                                            Err(exn) => break 'try1 Err(exn),
+                                        })
+                                        .strict_eq(Dyn::float(2f64)),
+                                    )? {
+                                        Ok(value) => value,
+                                        Err(exn) => break 'try1 Err(exn),
+                                    })
+                                    .is_truthy()
+                                    {
+                                        let _ = {
+                                            log = 5f64;
+                                            Dyn::undef()
+                                        };
+                                        {
+                                            finally1 = Some((0usize, Dyn::undef()));
+                                            break 'try1 Ok(Dyn::undef());
+                                        }
+                                    } else {
+                                        Dyn::undef()
+                                    };
+                                    {
+                                        r = 1f64;
+                                        Dyn::undef()
+                                    }
+                                })
+                            };
+                            {
+                                {
+                                    log = (log + 100f64);
+                                    Dyn::undef()
+                                }
+                            };
+                            match finally1 {
+                                Some((0usize, value)) => {
+                                    finally0 = Some((0usize, value));
+                                    break 'try0 Ok(Dyn::undef());
+                                }
+                                _ => (),
+                            }
+                            match rt::catchable(completion.or_else(rt::throw))? {
+                                Ok(value) => value,
+                                Err(exn) => break 'try0 Err(exn),
+                            }
+                        }
+                    })
+                };
+                let completion = match completion {
+                    Ok(value) => Ok(value),
+                    Err(exn) => 'catch0: {
+                        Ok({
+                            let mut e = Dyn::ref_(arena, exn);
+                            if (match rt::catchable((Dyn::deref(e)).strict_eq(Dyn::float(10f64)))? {
+                                Ok(value) => value,
+                                Err(exn) => break 'catch0 Err(exn),
+                            })
+                            .is_truthy()
+                            {
+                                {
+                                    r = (match rt::catchable(rt::guard_f64(Dyn::deref(e)))? {
+                                        Ok(value) => value,
+                                        Err(exn) => break 'catch0 Err(exn),
+                                    } + 1f64);
+                                    Dyn::undef()
+                                }
+                            } else {
+                                match rt::catchable(rt::throw(
+                                    match rt::catchable(
+                                        (Dyn::deref(e)).add(arena, Dyn::float(0f64)),
+                                    )? {
+                                        Ok(value) => value,
+                                        Err(exn) => break 'catch0 Err(exn),
+                                    },
+                                ))? {
+                                    Ok(value) => value,
+                                    Err(exn) => break 'catch0 Err(exn),
+                                }
+                            }
+                        })
+                    }
+                };
+                {
+                    let _ = {
+                        log = (log + 1000f64);
+                        Dyn::undef()
+                    };
+                    ec.send(Dyn::float(log))?
+                };
+                match finally0 {
+                    Some((0usize, value)) => {
+                        break 'ret value;
+                    }
+                    _ => (),
+                }
+                completion.or_else(rt::throw)?
+            };
+            let _ = {
+                let completion: Result<Dyn<'a>, Dyn<'a>> = 'try2: {
+                    Ok({
+                        if (match rt::catchable(
+                            (match rt::catchable(body.get("mode"))? {
+                                Ok(value) => value,
+                                Err(exn) => break 'try2 Err(exn),
+                            })
+                            .strict_eq(Dyn::float(3f64)),
+                        )? {
+                            Ok(value) => value,
+                            Err(exn) => break 'try2 Err(exn),
+                        })
+                        .is_truthy()
+                        {
+                            match rt::catchable(rt::throw(Dyn::float(7f64)))? {
+                                Ok(value) => value,
+                                Err(exn) => break 'try2 Err(exn),
+                            }
+                        } else {
+                            Dyn::undef()
+                        }
+                    })
+                };
+                let completion = match completion {
+                    Ok(value) => Ok(value),
+                    Err(exn) => 'catch2: {
+                        Ok({
+                            let mut e2 = Dyn::ref_(arena, exn);
+                            match rt::catchable(rt::throw(Dyn::float(99f64)))? {
+                                Ok(value) => value,
+                                Err(exn) => break 'catch2 Err(exn),
+                            }
+                        })
+                    }
+                };
+                completion.or_else(rt::throw)?
+            };
+            ec.send(Dyn::float((r + log)))?
+        }
+    } else {
+        {
+            let mut fun0 = Dyn::object_with(arena, vec![]);
+            let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+            Dyn::undef()
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
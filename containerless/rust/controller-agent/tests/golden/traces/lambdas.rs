@@ -0,0 +1,86 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut xs = rt::at(
+                rt::at(req.get("body"), "index.js", 3u32, 14u32)?.get("xs"),
+                "index.js",
+                3u32,
+                14u32,
+            )?;
+            let mut k = Dyn::ref_(arena, Dyn::float(2f64));
+            let mut scale = Dyn::closure(arena, 2i32, Dyn::object_with(arena, vec![("k", k)]));
+            let mut add = Dyn::closure(arena, 3i32, Dyn::object_with(arena, vec![]));
+            let mut ys = rt::at(
+                xs.map(arena, scale, &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                }),
+                "index.js",
+                11u32,
+                14u32,
+            )?;
+            let mut sum = rt::at(
+                ys.reduce(arena, add, Dyn::float(0f64), &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                }),
+                "index.js",
+                12u32,
+                15u32,
+            )?;
+            let mut ten = rt::at(
+                scale.call(arena, &[Dyn::float(5f64)], &mut |cbid, cbargs| {
+                    ec.count()?;
+                    containerless(arena, ec, cbid, cbargs)
+                }),
+                "index.js",
+                13u32,
+                15u32,
+            )?;
+            let mut resp = Dyn::object_with(arena, vec![("ys", ys), ("sum", sum), ("ten", ten)]);
+            let mut r = { ec.send(resp)? };
+            Dyn::undef()
+        }
+    } else {
+        if ((arg_cbid).strict_eq(Dyn::int(3i32))?).is_truthy() {
+            let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+            let mut arg0 = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+            let mut arg1 = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(2i32))?);
+            'ret: {
+                let mut acc = Dyn::deref(arg0);
+                let mut y = Dyn::deref(arg1);
+                break 'ret (acc).add(arena, y)?;
+            }
+        } else {
+            if ((arg_cbid).strict_eq(Dyn::int(2i32))?).is_truthy() {
+                let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+                let mut arg0 = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+                'ret: {
+                    let mut x = Dyn::deref(arg0);
+                    break 'ret (x).mul(Dyn::deref(Dyn::deref(clos).get("k")?))?;
+                }
+            } else {
+                {
+                    let mut fun0 = Dyn::object_with(arena, vec![]);
+                    let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+                    Dyn::undef()
+                }
+            }
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
@@ -0,0 +1,49 @@
+#![allow(non_snake_case)]
+#![allow(unused_variables)]
+#![forbid(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_mut)]
+#![allow(unused_assignments)]
+#![allow(unreachable_code)]
+#![allow(unused_labels)]
+use dispatcher_agent_lib::trace_runtime::{self as rt, Dyn, DynResult, ExecutionContext};
+pub fn containerless<'a>(
+    arena: &'a bumpalo::Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>,
+) -> DynResult<'a> {
+    Ok(if ((arg_cbid).strict_eq(Dyn::int(1i32))?).is_truthy() {
+        let mut clos = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(0i32))?);
+        let mut request = Dyn::ref_(arena, (arg_cbargs).index(arena, Dyn::int(1i32))?);
+        'ret: {
+            let mut req = Dyn::deref(request);
+            let mut arr00 = req.get("body")?.get("arr")?;
+            let mut i00 = 0f64;
+            let _ = 'loop_break0: {
+                {
+                    while ((Dyn::float(i00)).lt(arr00.get("length")?)?).is_truthy() {
+                        ec.count()?;
+                        let _ = 'loop_continue0: {
+                            {
+                                eprintln!("{:?}", (arr00).index(arena, Dyn::float(i00))?);
+                                Dyn::undef()
+                            }
+                        };
+                        {
+                            i00 = (i00 + 1f64);
+                            Dyn::undef()
+                        };
+                    }
+                    Dyn::undef()
+                }
+            };
+            let mut app2 = { ec.send(Dyn::str(arena, "bye"))? };
+            Dyn::undef()
+        }
+    } else {
+        {
+            let mut fun0 = Dyn::object_with(arena, vec![]);
+            let mut app0 = { ec.loopback("listen", Dyn::float(0f64), fun0, 1i32)? };
+            Dyn::undef()
+        }
+    })
+}
+dispatcher_agent_lib::export_containerless!(containerless);
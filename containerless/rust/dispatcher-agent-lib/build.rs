@@ -0,0 +1,148 @@
+//! Computes the parts of the fingerprint of the shared libraries of
+//! decontainerized functions that are known before compilation: the version
+//! of rustc, and a hash of the sources of this crate, of the entries of
+//! Cargo.lock that this crate depends on, and of its enabled features. See
+//! `trace_runtime::shared_library`.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn rustc_version() -> String {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .expect("running rustc --version");
+    let version = String::from_utf8(output.stdout).expect("reading rustc --version");
+    return version.trim().to_string();
+}
+
+fn source_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).expect("reading the sources") {
+        let path = entry.expect("reading the sources").path();
+        if path.is_dir() {
+            source_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// A `[[package]]` entry of Cargo.lock.
+struct Package {
+    name: String,
+    version: String,
+    lines: Vec<String>,
+    dependencies: Vec<String>,
+}
+
+fn parse_lock(lock: &str) -> Vec<Package> {
+    let mut packages = Vec::new();
+    for entry in lock.split("[[package]]").skip(1) {
+        let mut package = Package {
+            name: String::new(),
+            version: String::new(),
+            lines: Vec::new(),
+            dependencies: Vec::new(),
+        };
+        let mut in_dependencies = false;
+        for line in entry.lines().map(|line| line.trim()) {
+            if !in_dependencies && line.starts_with('[') {
+                // The next table, e.g. [metadata] in old lock files.
+                break;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            package.lines.push(line.to_string());
+            if in_dependencies {
+                if line == "]" {
+                    in_dependencies = false;
+                } else {
+                    let dep = line.trim_end_matches(',').trim_matches('"');
+                    package.dependencies.push(dep.to_string());
+                }
+            } else if line.starts_with("dependencies = [") {
+                in_dependencies = !line.ends_with(']');
+            } else if let Some(name) = line.strip_prefix("name = ") {
+                package.name = name.trim_matches('"').to_string();
+            } else if let Some(version) = line.strip_prefix("version = ") {
+                package.version = version.trim_matches('"').to_string();
+            }
+        }
+        packages.push(package);
+    }
+    return packages;
+}
+
+/// The entries of Cargo.lock that `root` depends on, transitively. An entry
+/// in a list of dependencies is a name, which is unique in the lock file, or
+/// a name and a version, which may be followed by the source.
+fn closure<'a>(packages: &'a [Package], root: &str) -> Vec<&'a Package> {
+    let mut by_name: HashMap<&str, Vec<&Package>> = HashMap::new();
+    for package in packages.iter() {
+        by_name.entry(&package.name).or_default().push(package);
+    }
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    let mut pending = vec![root.to_string()];
+    while let Some(dep) = pending.pop() {
+        let mut words = dep.split(' ');
+        let name = words.next().unwrap();
+        let version = words.next();
+        let candidates = by_name.get(name).map(|c| c.as_slice()).unwrap_or(&[]);
+        for package in candidates.iter() {
+            if (version.is_none() || version == Some(package.version.as_str()))
+                && seen.insert((&package.name, &package.version))
+            {
+                result.push(*package);
+                pending.extend(package.dependencies.iter().cloned());
+            }
+        }
+    }
+    result.sort_by(|p1, p2| (&p1.name, &p1.version).cmp(&(&p2.name, &p2.version)));
+    return result;
+}
+
+fn main() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut hasher = DefaultHasher::new();
+
+    let mut files = vec![manifest_dir.join("Cargo.toml")];
+    source_files(&manifest_dir.join("src"), &mut files);
+    files.sort();
+    for file in files.iter() {
+        file.strip_prefix(&manifest_dir).unwrap().hash(&mut hasher);
+        std::fs::read(file)
+            .expect("reading the sources")
+            .hash(&mut hasher);
+    }
+
+    // The lock file of the workspace, which cargo writes before it runs
+    // build scripts.
+    let lock_path = manifest_dir.join("../Cargo.lock");
+    let lock = std::fs::read_to_string(&lock_path).expect("reading Cargo.lock");
+    let packages = parse_lock(&lock);
+    for package in closure(&packages, env!("CARGO_PKG_NAME")) {
+        package.lines.hash(&mut hasher);
+    }
+
+    let mut features = std::env::vars()
+        .filter(|(key, _)| key.starts_with("CARGO_FEATURE_"))
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    features.sort();
+    features.hash(&mut hasher);
+
+    println!(
+        "cargo:rustc-env=CONTAINERLESS_BUILD_FINGERPRINT={} {:016x}",
+        rustc_version(),
+        hasher.finish()
+    );
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed={}", lock_path.display());
+}
@@ -3,6 +3,7 @@ use super::serverless_request::*;
 use super::state::{CreateMode, State};
 use super::types::*;
 use super::util;
+use crate::trace_runtime::Decontainerized;
 
 use futures::prelude::*;
 use tokio::task;
@@ -16,13 +17,19 @@ pub struct FunctionManager {
 impl FunctionManager {
     pub async fn new(
         k8s_client: K8sClient, http_client: HttpClient, short_deadline_http_client: HttpClient,
-        function_table: Weak<FunctionTable>, name: String, containers_only: bool,
-        create_mode: CreateMode, containerless: Option<Containerless>,
+        function_table: Weak<FunctionTable>, name: String, containers_only: bool, gas_limit: u64,
+        create_mode: CreateMode, decontainerized: Option<Decontainerized>,
         upgrade_pending: Arc<AtomicBool>,
     ) -> FunctionManager {
         let (send_requests, recv_requests) = mpsc::channel(1);
         let err_msg = format!("error raised by task for {}", &name);
-        let state = State::new(name, k8s_client, http_client, short_deadline_http_client);
+        let state = State::new(
+            name,
+            gas_limit,
+            k8s_client,
+            http_client,
+            short_deadline_http_client,
+        );
         task::spawn(util::log_error(
             State::function_manager_task(
                 Arc::clone(&state),
@@ -30,7 +37,7 @@ impl FunctionManager {
                 function_table,
                 create_mode,
                 containers_only,
-                containerless,
+                decontainerized,
                 upgrade_pending,
             ),
             err_msg,
@@ -150,4 +157,48 @@ impl FunctionManager {
             }
         }
     }
+
+    pub async fn decontainerize(&mut self, func: Decontainerized) -> Response {
+        let (send_resp, recv_resp) = oneshot::channel();
+        self.send_requests
+            .send(Message::Decontainerize(func, send_resp))
+            .await
+            .unwrap();
+        match recv_resp.await {
+            Ok(resp) => {
+                return resp;
+            }
+            Err(oneshot::Canceled) => {
+                return util::text_response(
+                    500,
+                    format!(
+                        "dispatcher shutdown before {} could be decontainerized",
+                        self.state.name
+                    ),
+                );
+            }
+        }
+    }
+
+    pub async fn unload(&mut self) -> Response {
+        let (send_resp, recv_resp) = oneshot::channel();
+        self.send_requests
+            .send(Message::Unload(send_resp))
+            .await
+            .unwrap();
+        match recv_resp.await {
+            Ok(resp) => {
+                return resp;
+            }
+            Err(oneshot::Canceled) => {
+                return util::text_response(
+                    500,
+                    format!(
+                        "dispatcher shutdown before {} could be unloaded",
+                        self.state.name
+                    ),
+                );
+            }
+        }
+    }
 }
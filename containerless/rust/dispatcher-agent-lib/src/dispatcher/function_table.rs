@@ -1,6 +1,7 @@
 use super::function_manager::FunctionManager;
 use super::types::*;
 use crate::error::Error;
+use crate::trace_runtime::{Decontainerized, SharedLibrary, DEFAULT_GAS_LIMIT};
 use hyper_timeout::TimeoutConnector;
 use std::time::Duration;
 
@@ -12,6 +13,71 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashMap;
 
+/// Downloads the shared library that the controller compiled for `name`, and
+/// loads it. Returns `None` if the controller has not compiled the function.
+pub async fn fetch_shared_library(name: &str) -> Result<Option<SharedLibrary>, Error> {
+    let resp = reqwest::get(&format!(
+        "http://controller/decontainerized/libfunction_{}.so",
+        name
+    ))
+    .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        return Error::controller(format!(
+            "downloading shared library for {}: {}",
+            name,
+            resp.status()
+        ));
+    }
+    // The dynamic loader caches libraries by path, so every version of a
+    // library needs a new path.
+    let path =
+        std::env::temp_dir().join(format!("libfunction_{}-{}.so", name, uuid::Uuid::new_v4()));
+    std::fs::write(&path, resp.bytes().await?)?;
+    let library = SharedLibrary::load(&path)?;
+    // The library stays mapped after its file is removed.
+    std::fs::remove_file(&path)?;
+    return Ok(Some(library));
+}
+
+/// The compiled version of `name`, if there is one. A function that cannot be
+/// loaded runs in containers.
+async fn load_compiled_function(name: &str) -> Option<Decontainerized> {
+    match fetch_shared_library(name).await {
+        Ok(library) => {
+            return library.map(|library| Decontainerized::Compiled(Arc::new(library)));
+        }
+        Err(err) => {
+            error!(target: "dispatcher", "loading compiled function {}: {}", name, err);
+            return None;
+        }
+    }
+}
+
+/// The gas limit in the metadata of a function, which storage sends with the
+/// function.
+fn gas_limit(headers: &reqwest::header::HeaderMap) -> u64 {
+    return headers
+        .get("x-containerless-gas-limit")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_GAS_LIMIT);
+}
+
+/// Reads the gas limit of `name` from storage. We only need this for functions
+/// that we adopt, since we read the rest of their metadata from Kubernetes.
+async fn fetch_gas_limit(name: &str) -> u64 {
+    match reqwest::get(&format!("http://storage:8080/get_function/{}", name)).await {
+        Ok(resp) => return gas_limit(resp.headers()),
+        Err(err) => {
+            error!(target: "dispatcher", "reading gas limit of {}: {}", name, err);
+            return DEFAULT_GAS_LIMIT;
+        }
+    }
+}
+
 struct FunctionTableImpl {
     functions: HashMap<String, FunctionManager>,
     http_client: HttpClient,
@@ -21,14 +87,11 @@ struct FunctionTableImpl {
 
 pub struct FunctionTable {
     inner: Mutex<FunctionTableImpl>,
-    decontainerized_functions: HashMap<&'static str, Containerless>,
     upgrade_pending: Arc<AtomicBool>,
 }
 
 impl FunctionTable {
-    pub async fn new(
-        decontainerized_functions: HashMap<&'static str, Containerless>,
-    ) -> Arc<FunctionTable> {
+    pub async fn new() -> Arc<FunctionTable> {
         let functions = HashMap::new();
         let k8s_client = Arc::new(
             k8s::client::Client::new("containerless")
@@ -56,7 +119,6 @@ impl FunctionTable {
         let upgrade_pending = Arc::new(AtomicBool::new(false));
         return Arc::new(FunctionTable {
             inner: Mutex::new(inner),
-            decontainerized_functions,
             upgrade_pending,
         });
     }
@@ -97,14 +159,12 @@ impl FunctionTable {
                         Arc::downgrade(self_),
                         name.to_string(),
                         containers_only,
+                        fetch_gas_limit(name).await,
                         super::state::CreateMode::Adopt {
                             num_replicas,
                             is_tracing,
                         },
-                        self_
-                            .decontainerized_functions
-                            .get(name)
-                            .map(|ptrptr| *ptrptr),
+                        load_compiled_function(name).await,
                         self_.upgrade_pending.clone(),
                     )
                     .await;
@@ -138,6 +198,7 @@ impl FunctionTable {
                 let headers = storage_resp.headers();
                 let containers_only = headers.contains_key("x-containerless-mode")
                     && headers["x-containerless-mode"] == "disable-tracing";
+                let gas_limit = gas_limit(headers);
                 if let Err(err) = response_into_result(
                     storage_resp.status().as_u16().clone(),
                     storage_resp.text().await?.clone(),
@@ -151,11 +212,9 @@ impl FunctionTable {
                     Arc::downgrade(self_),
                     name.to_string(),
                     containers_only,
+                    gas_limit,
                     super::state::CreateMode::New,
-                    self_
-                        .decontainerized_functions
-                        .get(name)
-                        .map(|ptrptr| *ptrptr),
+                    load_compiled_function(name).await,
                     self_.upgrade_pending.clone(),
                 )
                 .await;
@@ -1,16 +1,18 @@
 use super::types::*;
+use crate::trace_runtime::Decontainerized;
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub enum Mode {
     Tracing(usize),
     Vanilla,
-    Decontainerized(Containerless),
+    Decontainerized(Decontainerized),
 }
 
 impl std::fmt::Display for Mode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Mode::Decontainerized(_) => f.write_str("Decontainerized"),
+            Mode::Decontainerized(Decontainerized::Compiled(_)) => f.write_str("Decontainerized"),
+            Mode::Decontainerized(Decontainerized::Interpreted(_)) => f.write_str("Interpreted"),
             Mode::Tracing(_) => f.write_str("Tracing"),
             Mode::Vanilla => f.write_str("Vanilla"),
         }
@@ -32,5 +34,11 @@ pub enum Message {
     Request(ServerlessRequest),
     ExtractAndCompile(oneshot::Sender<Response>),
     GetMode(oneshot::Sender<Response>),
+    /// Runs the function without containers, either by interpreting its lifted
+    /// trace or by calling the shared library that the controller compiled.
+    Decontainerize(Decontainerized, oneshot::Sender<Response>),
+    /// Drops the decontainerized function, and runs the function in containers
+    /// again.
+    Unload(oneshot::Sender<Response>),
     Shutdown(oneshot::Sender<Result<(), crate::error::Error>>),
 }
@@ -4,6 +4,7 @@ use super::serverless_request::*;
 use super::types::*;
 use super::util;
 use crate::error::*;
+use crate::trace_runtime::Decontainerized;
 use std::time::{Duration, Instant};
 
 use futures::prelude::*;
@@ -19,6 +20,8 @@ pub enum CreateMode {
 
 pub struct State {
     pub name: String,
+    /// The gas limit of each decontainerized invocation.
+    pub gas_limit: u64,
     pub tracing_pod_name: String,
     pub vanilla_name: String,
     k8s_client: K8sClient,
@@ -31,7 +34,7 @@ pub struct State {
 
 impl State {
     pub fn new(
-        name: String, k8s_client: K8sClient, http_client: HttpClient,
+        name: String, gas_limit: u64, k8s_client: K8sClient, http_client: HttpClient,
         short_deadline_http_client: HttpClient,
     ) -> Arc<Self> {
         let tracing_pod_name = format!("function-tracing-{}", &name);
@@ -44,6 +47,7 @@ impl State {
 
         let state = State {
             name,
+            gas_limit,
             k8s_client,
             http_client,
             short_deadline_http_client,
@@ -278,9 +282,15 @@ impl State {
         .await;
     }
 
-    async fn invoke_decontainerized(self_: Arc<Self>, func: Containerless, req: ServerlessRequest) {
+    async fn invoke_decontainerized(
+        self_: Arc<Self>, func: Decontainerized, req: ServerlessRequest,
+    ) {
         // let data = req.payload.body.concat2();
         debug!(target: "dispatcher", "invoking decontainerized function {}", self_.name);
+        let containerless_mode_header = match func {
+            Decontainerized::Compiled(_) => "decontainerized",
+            Decontainerized::Interpreted(_) => "interpreted",
+        };
         let mut resp = match hyper::body::to_bytes(req.payload.body).await {
             Err(err) => hyper::Response::builder()
                 .status(500)
@@ -290,37 +300,29 @@ impl State {
                 )))
                 .unwrap(),
             Ok(body) => {
-                match crate::trace_runtime::run_decontainerized_function(
+                crate::trace_runtime::run_decontainerized_function(
                     func,
+                    &self_.name,
+                    self_.gas_limit,
                     self_.http_client.clone(),
                     &req.payload.path_and_query,
                     &body,
                 )
                 .await
-                {
-                    Err(err) => hyper::Response::builder()
-                        .status(500)
-                        .body(hyper::Body::from(format!(
-                            "error from serverless function {}",
-                            err
-                        )))
-                        .unwrap(),
-                    Ok(resp) => resp,
-                }
             }
         };
         resp.headers_mut().insert(
             "X-Containerless-Mode",
-            HeaderValue::from_static("decontainerized"),
+            HeaderValue::from_static(containerless_mode_header),
         );
         util::send_log_error(req.send, Ok(resp));
         // task::spawn(Self::invoke_decontainerized(Arc::clone(&self_), func, req));
     }
 
     async fn maybe_start_vanilla(
-        self_: Arc<State>, create_mode: &CreateMode, containerless: Option<Containerless>,
+        self_: Arc<State>, create_mode: &CreateMode, decontainerized: &Option<Decontainerized>,
     ) -> Result<(), Error> {
-        if *create_mode == CreateMode::New && containerless.is_none() {
+        if *create_mode == CreateMode::New && decontainerized.is_none() {
             return self_.start_vanilla_pod_and_service().await;
         }
         return Ok(());
@@ -328,12 +330,12 @@ impl State {
 
     async fn maybe_start_tracing(
         self_: Arc<State>, upgrade_pending: bool, create_mode: &CreateMode, containers_only: bool,
-        containerless: Option<Containerless>,
+        decontainerized: &Option<Decontainerized>,
     ) -> Result<(), Error> {
         if upgrade_pending || containers_only {
             return Ok(());
         }
-        if *create_mode == CreateMode::New && containerless.is_none() {
+        if *create_mode == CreateMode::New && decontainerized.is_none() {
             return self_.start_tracing_pod_and_service().await;
         }
         return Ok(());
@@ -392,7 +394,7 @@ impl State {
     pub async fn function_manager_task(
         self_: Arc<State>, mut recv_requests: mpsc::Receiver<Message>,
         function_table: Weak<FunctionTable>, create_mode: CreateMode, containers_only: bool,
-        containerless: Option<Containerless>, upgrade_pending: Arc<AtomicBool>,
+        decontainerized: Option<Decontainerized>, upgrade_pending: Arc<AtomicBool>,
     ) -> Result<(), Error> {
         try_join!(
             Self::maybe_start_tracing(
@@ -400,9 +402,9 @@ impl State {
                 upgrade_pending.load(SeqCst),
                 &create_mode,
                 containers_only,
-                containerless
+                &decontainerized
             ),
-            Self::maybe_start_vanilla(self_.clone(), &create_mode, containerless)
+            Self::maybe_start_vanilla(self_.clone(), &create_mode, &decontainerized)
         )?;
 
         let init_num_replicas = match create_mode {
@@ -421,14 +423,17 @@ impl State {
             self_.name.clone(),
         );
 
-        let mut mode = match (containers_only, containerless) {
+        // A function that starts decontainerized has no vanilla pods until
+        // it is unloaded.
+        let mut vanilla_started = create_mode != CreateMode::New || decontainerized.is_none();
+        let mut mode = match (containers_only, decontainerized) {
             (true, _) => Mode::Vanilla,
             (false, None) => Mode::Tracing(0),
-            (false, Some(f)) => Mode::Decontainerized(f),
+            (false, Some(func)) => Mode::Decontainerized(func),
         };
 
         while let Some(message) = recv_requests.next().await {
-            match (mode, message) {
+            match (mode.clone(), message) {
                 (_, Message::Shutdown(send_complete)) => {
                     if send_complete.is_canceled() {
                         error!(target: "dispatcher", "trying to send when the reciever is already dropped");
@@ -449,6 +454,61 @@ impl State {
                     let self_ = Arc::clone(&self_);
                     task::spawn(Self::invoke_decontainerized(self_, func, req));
                 }
+                (Mode::Tracing(_), Message::Decontainerize(_, send)) => {
+                    util::send_log_error(
+                        send,
+                        util::text_response(403, "function is tracing".to_string()),
+                    );
+                }
+                // A newer trace may replace the trace that we interpret, and a
+                // newer shared library may replace the one that we loaded, but
+                // we never go back from a shared library to the interpreter.
+                (
+                    Mode::Decontainerized(Decontainerized::Compiled(_)),
+                    Message::Decontainerize(Decontainerized::Interpreted(_), send),
+                ) => {
+                    util::send_log_error(
+                        send,
+                        util::text_response(403, "function is compiled".to_string()),
+                    );
+                }
+                (Mode::Vanilla, Message::Decontainerize(func, send))
+                | (Mode::Decontainerized(_), Message::Decontainerize(func, send)) => {
+                    mode = Mode::Decontainerized(func);
+                    info!(target: "dispatcher", "switched to {} mode for {}", mode, &self_.name);
+                    util::send_log_error(
+                        send,
+                        util::text_response(200, format!("switched to {} mode", mode)),
+                    );
+                }
+                (Mode::Decontainerized(_), Message::Unload(send)) => {
+                    if !vanilla_started {
+                        if let Err(err) = self_.start_vanilla_pod_and_service().await {
+                            error!(target: "dispatcher", "starting vanilla pods for {}: {}", &self_.name, err);
+                            util::send_log_error(
+                                send,
+                                util::text_response(
+                                    500,
+                                    format!("could not start vanilla pods: {}", err),
+                                ),
+                            );
+                            continue;
+                        }
+                        vanilla_started = true;
+                    }
+                    mode = Mode::Vanilla;
+                    info!(target: "dispatcher", "unloaded decontainerized function {}", &self_.name);
+                    util::send_log_error(
+                        send,
+                        util::text_response(200, "switched to Vanilla mode".to_string()),
+                    );
+                }
+                (_, Message::Unload(send)) => {
+                    util::send_log_error(
+                        send,
+                        util::text_response(200, format!("function is in {} mode", mode)),
+                    );
+                }
                 (_, Message::ExtractAndCompile(send)) => {
                     if let Mode::Tracing(_) = mode {
                         {
@@ -1,4 +1,3 @@
-pub use crate::trace_runtime::Containerless;
 pub use futures::channel::mpsc;
 pub use futures::channel::oneshot;
 pub use futures::try_join;
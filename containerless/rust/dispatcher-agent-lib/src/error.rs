@@ -9,6 +9,10 @@ pub enum Error {
     Reqwest(#[from] reqwest::Error),
     #[error("{0}")]
     Http(#[from] http::Error),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    SharedLibrary(#[from] crate::trace_runtime::LoadError),
     #[error("Error::Timeout")]
     Timeout,
     #[error("pod {0} is in phase {0}")]
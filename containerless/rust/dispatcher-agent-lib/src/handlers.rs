@@ -1,4 +1,5 @@
-use crate::dispatcher::function_table::FunctionTable;
+use crate::dispatcher::function_table::{self, FunctionTable};
+use crate::trace_runtime::{Decontainerized, Program};
 
 use shared::response::*;
 
@@ -83,6 +84,107 @@ pub async fn get_mode_handler(
     }
 }
 
+pub async fn interpret_handler(
+    function_name: String, body: bytes::Bytes, state: Arc<FunctionTable>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // The controller only sends traces that this dispatcher extracted.
+    if !FunctionTable::function_manager_exists(&state, &function_name).await {
+        return Ok(hyper::Response::builder()
+            .status(404)
+            .body(hyper::Body::from(format!(
+                "{} is not running in this dispatcher",
+                function_name
+            )))
+            .unwrap());
+    }
+    let program = match Program::from_json(&String::from_utf8_lossy(&body)) {
+        Ok(program) => Arc::new(program),
+        Err(err) => {
+            return Ok(hyper::Response::builder()
+                .status(400)
+                .body(hyper::Body::from(format!(
+                    "error parsing trace for {}: {}",
+                    function_name, err
+                )))
+                .unwrap());
+        }
+    };
+    match FunctionTable::get_function(&state, &function_name).await {
+        Ok(mut fm) => Ok(fm
+            .decontainerize(Decontainerized::Interpreted(program))
+            .await),
+        Err(err) => Ok(hyper::Response::builder()
+            .status(500)
+            .body(hyper::Body::from(format!("{:?}", err)))
+            .unwrap()),
+    }
+}
+
+pub async fn load_handler(
+    function_name: String, state: Arc<FunctionTable>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // A function manager that starts later loads the library itself.
+    if !FunctionTable::function_manager_exists(&state, &function_name).await {
+        return Ok(hyper::Response::builder()
+            .status(404)
+            .body(hyper::Body::from(format!(
+                "{} is not running in this dispatcher",
+                function_name
+            )))
+            .unwrap());
+    }
+    let library = match function_table::fetch_shared_library(&function_name).await {
+        Ok(Some(library)) => Arc::new(library),
+        Ok(None) => {
+            return Ok(hyper::Response::builder()
+                .status(404)
+                .body(hyper::Body::from(format!(
+                    "controller has no shared library for {}",
+                    function_name
+                )))
+                .unwrap());
+        }
+        Err(err) => {
+            return Ok(hyper::Response::builder()
+                .status(500)
+                .body(hyper::Body::from(format!(
+                    "error loading shared library for {}: {}",
+                    function_name, err
+                )))
+                .unwrap());
+        }
+    };
+    match FunctionTable::get_function(&state, &function_name).await {
+        Ok(mut fm) => Ok(fm.decontainerize(Decontainerized::Compiled(library)).await),
+        Err(err) => Ok(hyper::Response::builder()
+            .status(500)
+            .body(hyper::Body::from(format!("{:?}", err)))
+            .unwrap()),
+    }
+}
+
+pub async fn unload_handler(
+    function_name: String, state: Arc<FunctionTable>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // A function manager that starts later does not find the library.
+    if !FunctionTable::function_manager_exists(&state, &function_name).await {
+        return Ok(hyper::Response::builder()
+            .status(200)
+            .body(hyper::Body::from(format!(
+                "{} is not running in this dispatcher",
+                function_name
+            )))
+            .unwrap());
+    }
+    match FunctionTable::get_function(&state, &function_name).await {
+        Ok(mut fm) => Ok(fm.unload().await),
+        Err(err) => Ok(hyper::Response::builder()
+            .status(500)
+            .body(hyper::Body::from(format!("{:?}", err)))
+            .unwrap()),
+    }
+}
+
 pub async fn shutdown_function_instances_handler(
     function_name: String, state: Arc<FunctionTable>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
@@ -5,17 +5,14 @@ mod routes;
 pub mod trace_runtime;
 
 use dispatcher::function_table::FunctionTable;
-use dispatcher::{types, types::*};
 
 #[macro_use]
 extern crate log;
 
-pub type Containerless = types::Containerless;
-
-pub async fn main(decontainerized_functions: HashMap<&'static str, Containerless>) {
+pub async fn main() {
     shared::logger::init("http://controller-logger", 1);
     info!(target: "dispatcher", "UP");
-    let state = FunctionTable::new(decontainerized_functions).await;
+    let state = FunctionTable::new().await;
     if let Err(err) = FunctionTable::adopt_running_functions(&state).await {
         error!(target: "dispatcher", "adopting functions: {}", err);
         return;
@@ -10,6 +10,9 @@ pub fn routes(
     readiness_route()
         .or(extract_and_compile_route(state.clone()))
         .or(get_mode_route(state.clone()))
+        .or(interpret_route(state.clone()))
+        .or(load_route(state.clone()))
+        .or(unload_route(state.clone()))
         .or(shutdown_function_instances_route(state.clone()))
         .or(dispatcher_route(state.clone()))
         .or(dispatcher_route2(state.clone()))
@@ -39,6 +42,34 @@ fn get_mode_route(
         .and_then(handlers::get_mode_handler)
 }
 
+fn interpret_route(
+    state: Arc<FunctionTable>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("interpret" / String)
+        .and(warp::post())
+        .and(warp::filters::body::bytes())
+        .and(with_state(state))
+        .and_then(handlers::interpret_handler)
+}
+
+fn load_route(
+    state: Arc<FunctionTable>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("load" / String)
+        .and(warp::post())
+        .and(with_state(state))
+        .and_then(handlers::load_handler)
+}
+
+fn unload_route(
+    state: Arc<FunctionTable>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("unload" / String)
+        .and(warp::post())
+        .and(with_state(state))
+        .and_then(handlers::unload_handler)
+}
+
 fn shutdown_function_instances_route(
     state: Arc<FunctionTable>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -0,0 +1,470 @@
+//! The methods of `Array.prototype` that the generated code may call on a
+//! `Dyn::Vec`, besides `push`, `pop` and `shift`. Strings also have `slice`,
+//! `concat`, `indexOf` and `includes`, so the methods in `strings.rs` call the
+//! functions here when the receiver is an array.
+//!
+//! The higher-order methods take a function, which is a `Dyn::Closure` whose
+//! body is a lifted lambda. These methods call it synchronously through an
+//! `Invoke`, which runs the body with the generated code (or the interpreter)
+//! and charges gas for each call.
+//! As in JavaScript, a method visits the elements that the array has when the
+//! method starts, and the callback may modify the array while it runs.
+use super::error::{not_a_function, type_error, Error};
+use super::strings::{relative_position, to_integer, MAX_LENGTH};
+use super::type_dynamic::{Dyn, DynResult, DynVec, Invoke};
+use bumpalo::Bump;
+use std::cmp::Ordering;
+
+/// The array that receives a call to `method`.
+fn receiver<'a>(value: &Dyn<'a>, method: &str) -> Result<DynVec<'a>, Error<'a>> {
+    match value {
+        Dyn::Vec(v) => return Ok(*v),
+        _ => return not_a_function(method),
+    }
+}
+
+/// Checks that the argument of `method` is a function.
+fn function<'a>(value: Dyn<'a>, method: &str) -> Result<(), Error<'a>> {
+    match value {
+        Dyn::Closure(_) => return Ok(()),
+        _ => {
+            return type_error(format!(
+                "{} is not a function in Array.prototype.{}",
+                value.to_string(),
+                method
+            ))
+        }
+    }
+}
+
+/// The end of a range in an array of length `len`, which is the end of the
+/// array when the argument is omitted.
+fn relative_end(value: Dyn, len: usize) -> usize {
+    match value {
+        Dyn::Undefined => return len,
+        _ => return relative_position(value, len),
+    }
+}
+
+/// The arguments that the `Transformer` passes in an array, because the
+/// method takes any number of them.
+fn rest<'a>(items: Dyn<'a>, method: &str) -> Result<Vec<Dyn<'a>>, Error<'a>> {
+    match items {
+        Dyn::Vec(v) => return Ok(v.elems.borrow().to_vec()),
+        _ => return type_error(format!("{} expects its arguments in an array", method)),
+    }
+}
+
+/// Replaces `count` elements of `v` at `start` with `items`, and returns the
+/// elements that it removes.
+fn replace_range<'a>(v: DynVec<'a>, start: usize, count: usize, items: &[Dyn<'a>]) -> Vec<Dyn<'a>> {
+    let mut elems = v.elems.borrow_mut();
+    let removed = elems[start..start + count].to_vec();
+    let tail = elems[start + count..].to_vec();
+    elems.truncate(start);
+    elems.extend_from_slice(items);
+    elems.extend_from_slice(&tail);
+    return removed;
+}
+
+/// The first index of an element of `v` for which `eq` holds, starting at
+/// `from`, which counts from the end of the array when it is negative.
+fn position<'a>(v: DynVec<'a>, from: Dyn<'a>, eq: impl Fn(&Dyn<'a>) -> bool) -> Option<usize> {
+    let elems = v.elems.borrow();
+    let from = relative_position(from, elems.len());
+    return elems[from..].iter().position(eq).map(|i| from + i);
+}
+
+fn is_nan(value: &Dyn) -> bool {
+    return matches!(value, Dyn::Float(x) if x.is_nan());
+}
+
+pub(super) fn slice<'a>(arena: &'a Bump, v: DynVec<'a>, start: Dyn<'a>, end: Dyn<'a>) -> Dyn<'a> {
+    let elems = v.elems.borrow();
+    let start = relative_position(start, elems.len());
+    let end = relative_end(end, elems.len());
+    if start >= end {
+        return Dyn::vec(arena);
+    }
+    return Dyn::vec_with(arena, elems[start..end].to_vec());
+}
+
+/// An argument that is an array adds its elements, and any other argument
+/// is an element.
+pub(super) fn concat<'a>(arena: &'a Bump, v: DynVec<'a>, value: Dyn<'a>) -> Dyn<'a> {
+    let mut elems = v.elems.borrow().to_vec();
+    match value {
+        Dyn::Vec(w) => elems.extend_from_slice(&w.elems.borrow()),
+        _ => elems.push(value),
+    }
+    return Dyn::vec_with(arena, elems);
+}
+
+pub(super) fn index_of<'a>(v: DynVec<'a>, search: Dyn<'a>, from: Dyn<'a>) -> Dyn<'a> {
+    match position(v, from, |x| x.strictly_equals(&search)) {
+        Some(i) => return Dyn::float(i as f64),
+        None => return Dyn::float(-1.0),
+    }
+}
+
+/// Like `indexOf`, but it also finds `NaN`.
+pub(super) fn includes<'a>(v: DynVec<'a>, search: Dyn<'a>, from: Dyn<'a>) -> Dyn<'a> {
+    let found = position(v, from, |x| {
+        x.strictly_equals(&search) || (is_nan(x) && is_nan(&search))
+    });
+    return Dyn::Bool(found.is_some());
+}
+
+/// Calls `callback` with each element, its index, and the array, until `f`
+/// returns `false`. An element is read just before its call, so the callback
+/// sees the updates of earlier calls, and the iteration stops early when an
+/// earlier call shrinks the array.
+fn each<'a>(
+    arena: &'a Bump, array: Dyn<'a>, v: DynVec<'a>, callback: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    mut f: impl FnMut(usize, Dyn<'a>, Dyn<'a>) -> bool,
+) -> Result<(), Error<'a>> {
+    let len = v.len();
+    for i in 0..len {
+        if i >= v.len() {
+            break;
+        }
+        let elem = v.index(i);
+        let result = callback.call(arena, &[elem, Dyn::float(i as f64), array], invoke)?;
+        if !f(i, elem, result) {
+            break;
+        }
+    }
+    return Ok(());
+}
+
+/// The default order of `sort`, which compares the UTF-16 encodings of the
+/// string forms of the elements.
+fn compare_strings<'a>(x: Dyn<'a>, y: Dyn<'a>) -> Ordering {
+    let (x, y) = (x.to_string(), y.to_string());
+    return x.encode_utf16().cmp(y.encode_utf16());
+}
+
+/// A stable merge sort with a comparison that may fail. We cannot use
+/// `sort_by`, since a comparator that is not a total order may make it
+/// panic.
+fn merge_sort<'a>(
+    elems: &mut Vec<Dyn<'a>>,
+    compare: &mut impl FnMut(Dyn<'a>, Dyn<'a>) -> Result<Ordering, Error<'a>>,
+) -> Result<(), Error<'a>> {
+    if elems.len() <= 1 {
+        return Ok(());
+    }
+    let mut right = elems.split_off(elems.len() / 2);
+    merge_sort(elems, compare)?;
+    merge_sort(&mut right, compare)?;
+    let left = std::mem::take(elems);
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if compare(left[i], right[j])? == Ordering::Greater {
+            elems.push(right[j]);
+            j += 1;
+        } else {
+            elems.push(left[i]);
+            i += 1;
+        }
+    }
+    elems.extend_from_slice(&left[i..]);
+    elems.extend_from_slice(&right[j..]);
+    return Ok(());
+}
+
+impl<'a> Dyn<'a> {
+    /// `Array.isArray`, which the trace calls as a method of its argument.
+    #[allow(non_snake_case)]
+    pub fn isArray(&self, _arena: &'a Bump) -> DynResult<'a> {
+        return Ok(Dyn::Bool(matches!(self, Dyn::Vec(_))));
+    }
+
+    /// The separator is a comma when it is omitted.
+    pub fn join(&self, arena: &'a Bump, separator: Dyn<'a>) -> DynResult<'a> {
+        let v = receiver(self, "join")?;
+        let separator = match separator {
+            Dyn::Undefined => ",".to_string(),
+            _ => separator.to_string(),
+        };
+        let mut joined = String::new();
+        if !v.join_into(&separator, MAX_LENGTH, &mut joined, &mut vec![]) {
+            return type_error(format!(
+                "the result of join is longer than {} characters",
+                MAX_LENGTH
+            ));
+        }
+        return Ok(Dyn::str(arena, &joined));
+    }
+
+    /// Reverses the array in place and returns it.
+    pub fn reverse(&self, _arena: &'a Bump) -> DynResult<'a> {
+        let v = receiver(self, "reverse")?;
+        v.elems.borrow_mut().reverse();
+        return Ok(*self);
+    }
+
+    /// Sets the elements from `start` to `end` to `value`, and returns the
+    /// array.
+    pub fn fill(
+        &self, _arena: &'a Bump, value: Dyn<'a>, start: Dyn<'a>, end: Dyn<'a>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "fill")?;
+        let mut elems = v.elems.borrow_mut();
+        let start = relative_position(start, elems.len());
+        let end = relative_end(end, elems.len());
+        for x in elems.iter_mut().take(end).skip(start) {
+            *x = value;
+        }
+        return Ok(*self);
+    }
+
+    /// Removes `count` elements at `start`, inserts `items` in their place,
+    /// and returns the removed elements. The `Transformer` passes the
+    /// arguments after the second in the array `items`, and `undefined` for
+    /// an omitted count. So, a count of `undefined` counts as omitted, and
+    /// removes the rest of the array.
+    pub fn splice(
+        &self, arena: &'a Bump, start: Dyn<'a>, count: Dyn<'a>, items: Dyn<'a>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "splice")?;
+        let items = rest(items, "splice")?;
+        let len = v.len();
+        let start = relative_position(start, len);
+        let count = match count {
+            Dyn::Undefined => len - start,
+            _ => to_integer(count).max(0.0).min((len - start) as f64) as usize,
+        };
+        let removed = replace_range(v, start, count, &items);
+        return Ok(Dyn::vec_with(arena, removed));
+    }
+
+    /// Adds `items` to the start of the array, in order, and returns the new
+    /// length. The `Transformer` passes the arguments in the array `items`.
+    pub fn unshift(&self, _arena: &'a Bump, items: Dyn<'a>) -> DynResult<'a> {
+        let v = receiver(self, "unshift")?;
+        let items = rest(items, "unshift")?;
+        replace_range(v, 0, 0, &items);
+        return Ok(Dyn::float(v.len() as f64));
+    }
+
+    #[allow(non_snake_case)]
+    pub fn forEach(
+        &self, arena: &'a Bump, callback: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "forEach")?;
+        function(callback, "forEach")?;
+        each(arena, *self, v, callback, invoke, |_, _, _| true)?;
+        return Ok(Dyn::Undefined);
+    }
+
+    pub fn map(
+        &self, arena: &'a Bump, callback: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "map")?;
+        function(callback, "map")?;
+        // The result has the length of the array, even if the callback
+        // shrinks the array.
+        let mut results = vec![Dyn::Undefined; v.len()];
+        each(arena, *self, v, callback, invoke, |i, _, result| {
+            results[i] = result;
+            true
+        })?;
+        return Ok(Dyn::vec_with(arena, results));
+    }
+
+    pub fn filter(
+        &self, arena: &'a Bump, callback: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "filter")?;
+        function(callback, "filter")?;
+        let mut results = Vec::new();
+        each(arena, *self, v, callback, invoke, |_, elem, result| {
+            if result.is_truthy() {
+                results.push(elem);
+            }
+            true
+        })?;
+        return Ok(Dyn::vec_with(arena, results));
+    }
+
+    pub fn find(
+        &self, arena: &'a Bump, callback: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "find")?;
+        function(callback, "find")?;
+        let mut found = Dyn::Undefined;
+        each(arena, *self, v, callback, invoke, |_, elem, result| {
+            if result.is_truthy() {
+                found = elem;
+                return false;
+            }
+            true
+        })?;
+        return Ok(found);
+    }
+
+    pub fn some(
+        &self, arena: &'a Bump, callback: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "some")?;
+        function(callback, "some")?;
+        let mut found = false;
+        each(arena, *self, v, callback, invoke, |_, _, result| {
+            found = result.is_truthy();
+            !found
+        })?;
+        return Ok(Dyn::Bool(found));
+    }
+
+    /// The `Transformer` passes `undefined` when the initial value is
+    /// omitted, so an initial value of `undefined` counts as omitted. In
+    /// JavaScript, `[1].reduce(f, undefined)` would call `f` once.
+    pub fn reduce(
+        &self, arena: &'a Bump, callback: Dyn<'a>, initial: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "reduce")?;
+        function(callback, "reduce")?;
+        let len = v.len();
+        let (mut acc, start) = match initial {
+            Dyn::Undefined if len == 0 => {
+                return type_error("reduce of empty array with no initial value".to_string())
+            }
+            Dyn::Undefined => (v.index(0), 1),
+            _ => (initial, 0),
+        };
+        for i in start..len {
+            if i >= v.len() {
+                break;
+            }
+            let elem = v.index(i);
+            acc = callback.call(arena, &[acc, elem, Dyn::float(i as f64), *self], invoke)?;
+        }
+        return Ok(acc);
+    }
+
+    /// Sorts the array in place and returns it. The `undefined` elements
+    /// go last, and the comparison function does not see them. A comparison
+    /// that returns a positive number puts its first argument after the
+    /// second.
+    pub fn sort(
+        &self, arena: &'a Bump, compare: Dyn<'a>, invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        let v = receiver(self, "sort")?;
+        match compare {
+            Dyn::Undefined | Dyn::Closure(_) => (),
+            _ => {
+                return type_error(
+                    "the comparison function of Array.prototype.sort must be a function"
+                        .to_string(),
+                )
+            }
+        }
+        let len = v.len();
+        let mut elems = v
+            .elems
+            .borrow()
+            .iter()
+            .filter(|x| !matches!(x, Dyn::Undefined))
+            .copied()
+            .collect::<Vec<_>>();
+        merge_sort(&mut elems, &mut |x, y| match compare {
+            Dyn::Undefined => Ok(compare_strings(x, y)),
+            _ => {
+                let result = compare.call(arena, &[x, y], invoke)?.to_number();
+                if result > 0.0 {
+                    Ok(Ordering::Greater)
+                } else if result < 0.0 {
+                    Ok(Ordering::Less)
+                } else {
+                    Ok(Ordering::Equal)
+                }
+            }
+        })?;
+        elems.resize(len, Dyn::Undefined);
+        // The comparison function may have shrunk the array.
+        for (i, x) in elems.into_iter().enumerate().take(v.len()) {
+            v.set(i, x)?;
+        }
+        return Ok(*self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers<'a>(arena: &'a Bump, xs: &[f64]) -> Dyn<'a> {
+        return Dyn::vec_with(arena, xs.iter().map(|x| Dyn::float(*x)).collect());
+    }
+
+    /// Calls the closure with id 0, which subtracts its second argument from
+    /// its first, as in `(x, y) => x - y`.
+    fn subtract<'a>(_id: Dyn<'a>, args: Dyn<'a>) -> DynResult<'a> {
+        let args = match args {
+            Dyn::Vec(args) => args,
+            _ => panic!("the arguments of a call are an array"),
+        };
+        return args.index(1).sub(args.index(2));
+    }
+
+    #[test]
+    fn sort_compares_strings_by_default() {
+        let arena = Bump::new();
+        let xs = numbers(&arena, &[10.0, 9.0, 1.0]);
+        xs.push(&arena, Dyn::Undefined).unwrap();
+        xs.push(&arena, Dyn::float(2.0)).unwrap();
+        let sorted = xs.sort(&arena, Dyn::Undefined, &mut subtract).unwrap();
+        assert_eq!(sorted.to_string(), "1,10,2,9,");
+        assert_eq!(xs.to_string(), "1,10,2,9,");
+    }
+
+    #[test]
+    fn sort_calls_the_comparison_function() {
+        let arena = Bump::new();
+        let xs = numbers(&arena, &[10.0, 9.0, 1.0, 2.0]);
+        let compare = Dyn::closure(&arena, 0, Dyn::object(&arena));
+        xs.sort(&arena, compare, &mut subtract).unwrap();
+        assert_eq!(xs.to_string(), "1,2,9,10");
+        let err = xs.sort(&arena, Dyn::float(1.0), &mut subtract);
+        assert!(matches!(err, Err(Error::TypeError(_))));
+    }
+
+    #[test]
+    fn splice_removes_and_inserts() {
+        let arena = Bump::new();
+        let xs = numbers(&arena, &[1.0, 2.0, 3.0, 4.0, 5.0]);
+        let items = numbers(&arena, &[8.0, 9.0]);
+        let removed = xs.splice(&arena, Dyn::float(-4.0), Dyn::float(2.0), items);
+        assert_eq!(removed.unwrap().to_string(), "2,3");
+        assert_eq!(xs.to_string(), "1,8,9,4,5");
+        // A count of `undefined` removes the rest of the array.
+        let none = Dyn::vec(&arena);
+        let removed = xs.splice(&arena, Dyn::float(3.0), Dyn::Undefined, none);
+        assert_eq!(removed.unwrap().to_string(), "4,5");
+        assert_eq!(xs.to_string(), "1,8,9");
+        // A start past the end appends.
+        let items = numbers(&arena, &[7.0]);
+        let removed = xs.splice(&arena, Dyn::float(10.0), Dyn::float(1.0), items);
+        assert_eq!(removed.unwrap().to_string(), "");
+        assert_eq!(xs.to_string(), "1,8,9,7");
+    }
+
+    #[test]
+    fn indices_are_floats() {
+        let arena = Bump::new();
+        let xs = numbers(&arena, &[3.0, 4.0]);
+        let four = xs.indexOf(&arena, Dyn::float(4.0), Dyn::Undefined).unwrap();
+        assert!(matches!(four, Dyn::Float(x) if x == 1.0));
+        let callback = Dyn::closure(&arena, 0, Dyn::object(&arena));
+        let mut indices = Vec::new();
+        let mut record = |_id, args| {
+            if let Dyn::Vec(args) = args {
+                indices.push(matches!(args.index(2), Dyn::Float(_)));
+            }
+            return Ok(Dyn::Undefined);
+        };
+        xs.forEach(&arena, callback, &mut record).unwrap();
+        assert_eq!(indices, vec![true, true]);
+    }
+}
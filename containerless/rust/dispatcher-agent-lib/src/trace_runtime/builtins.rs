@@ -0,0 +1,345 @@
+//! The global functions of JavaScript that a trace may call with a `PrimApp`,
+//! such as `Math.floor` and `JSON.parse`.
+//!
+//! The generated code calls these functions directly, and the interpreter
+//! finds them in `BUILTINS`. Every builtin takes its arguments in a slice,
+//! so, as in JavaScript, a missing argument is `undefined` and an extra
+//! argument is ignored. The arguments come before the execution context, so
+//! that the code that computes them may use it too.
+use super::execution_context::ExecutionContext;
+use super::type_dynamic::{throw, Dyn, DynResult};
+use bumpalo::Bump;
+use serde_json::Value;
+
+pub type Builtin =
+    for<'a> fn(arena: &'a Bump, args: &[Dyn<'a>], ec: &mut ExecutionContext<'a>) -> DynResult<'a>;
+
+macro_rules! builtins {
+    ($($name:literal => $function:ident,)*) => {
+        /// The builtins, by the name of the event of the `PrimApp` that calls
+        /// them.
+        pub const BUILTINS: &[(&str, Builtin)] = &[$(($name, $function)),*];
+    };
+}
+
+shared::with_builtins!(builtins);
+
+fn arg<'a>(args: &[Dyn<'a>], index: usize) -> Dyn<'a> {
+    return args.get(index).copied().unwrap_or(Dyn::Undefined);
+}
+
+/// Converts the first argument to a number and applies `f` to it.
+fn math<'a>(args: &[Dyn<'a>], f: impl Fn(f64) -> f64) -> DynResult<'a> {
+    return Ok(Dyn::float(f(arg(args, 0).to_number())));
+}
+
+/// The whitespace that `trim` removes, which `parseInt` and `parseFloat`
+/// skip.
+fn is_space(c: char) -> bool {
+    return c.is_whitespace() || c == '\u{feff}';
+}
+
+/// The value that JavaScript throws for an error, e.g., a `SyntaxError`.
+fn error<'a>(arena: &'a Bump, name: &'a str, message: &str) -> DynResult<'a> {
+    let message = Dyn::str(arena, message);
+    return throw(Dyn::object_with(
+        arena,
+        vec![("name", Dyn::str(arena, name)), ("message", message)],
+    ));
+}
+
+pub fn math_abs<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    return math(args, f64::abs);
+}
+
+pub fn math_ceil<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    return math(args, f64::ceil);
+}
+
+pub fn math_floor<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    return math(args, f64::floor);
+}
+
+/// `Infinity` when there are no arguments, and `NaN` when any argument is
+/// not a number.
+pub fn math_max<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    let max = args.iter().fold(f64::NEG_INFINITY, |max, x| {
+        let x = x.to_number();
+        if max.is_nan() || x.is_nan() {
+            return f64::NAN;
+        }
+        return max.max(x);
+    });
+    return Ok(Dyn::float(max));
+}
+
+pub fn math_min<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    let min = args.iter().fold(f64::INFINITY, |min, x| {
+        let x = x.to_number();
+        if min.is_nan() || x.is_nan() {
+            return f64::NAN;
+        }
+        return min.min(x);
+    });
+    return Ok(Dyn::float(min));
+}
+
+pub fn math_pow<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    let (x, y) = (arg(args, 0).to_number(), arg(args, 1).to_number());
+    // Rust says that 1 to the power of NaN or of Infinity is 1.
+    if y.is_nan() || (x.abs() == 1.0 && y.is_infinite()) {
+        return Ok(Dyn::float(f64::NAN));
+    }
+    return Ok(Dyn::float(x.powf(y)));
+}
+
+/// A deterministic sequence, which `ExecutionContext::random` produces.
+pub fn math_random<'a>(
+    _arena: &'a Bump, _args: &[Dyn<'a>], ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    return Ok(Dyn::float(ec.random()));
+}
+
+/// Rounds half way cases up, unlike `f64::round`, which rounds them away
+/// from zero. A negative number that rounds to zero rounds to `-0`.
+pub fn math_round<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    return math(args, |x| {
+        let floor = x.floor();
+        if x - floor < 0.5 {
+            return floor;
+        }
+        if floor == -1.0 {
+            return -0.0;
+        }
+        return floor + 1.0;
+    });
+}
+
+pub fn math_sqrt<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    return math(args, f64::sqrt);
+}
+
+/// Throws a `SyntaxError` when the text is not JSON.
+pub fn json_parse<'a>(
+    arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    match serde_json::from_str(&arg(args, 0).to_string()) {
+        Ok(json) => return Ok(Dyn::from_json(arena, json)),
+        Err(err) => return error(arena, "SyntaxError", &err.to_string()),
+    }
+}
+
+/// JavaScript writes a number that is an integer without a fraction, e.g.,
+/// `1` instead of `1.0`.
+fn without_fractions(json: Value) -> Value {
+    match json {
+        Value::Number(n) => match n.as_f64() {
+            Some(x) if x.fract() == 0.0 && x.abs() < (1u64 << 53) as f64 => {
+                return Value::from(x as i64)
+            }
+            _ => return Value::Number(n),
+        },
+        Value::Array(elems) => {
+            return Value::Array(elems.into_iter().map(without_fractions).collect())
+        }
+        Value::Object(fields) => {
+            return Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, without_fractions(value)))
+                    .collect(),
+            )
+        }
+        _ => return json,
+    }
+}
+
+/// Produces `undefined` for a value that JSON cannot represent, such as
+/// `undefined` itself. It does not support a replacer or indentation.
+pub fn json_stringify<'a>(
+    arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    match arg(args, 0).to_json() {
+        None => return Ok(Dyn::Undefined),
+        Some(json) => return Ok(Dyn::str(arena, &without_fractions(json).to_string())),
+    }
+}
+
+/// `Number()` is `0`, but `Number(undefined)` is `NaN`.
+pub fn number<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    match args.first() {
+        None => return Ok(Dyn::float(0.0)),
+        Some(x) => return Ok(Dyn::float(x.to_number())),
+    }
+}
+
+/// Reads the longest prefix of the string that is a decimal number, after
+/// any whitespace.
+pub fn parse_float<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    let s = arg(args, 0).to_string();
+    let s = s.trim_start_matches(is_space);
+    let bytes = s.as_bytes();
+    let digits = |from: usize| {
+        return from
+            + bytes[from..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+    };
+    let mut end = match bytes.first() {
+        Some(b'+') | Some(b'-') => 1,
+        _ => 0,
+    };
+    if s[end..].starts_with("Infinity") {
+        let sign = if s.starts_with('-') { -1.0 } else { 1.0 };
+        return Ok(Dyn::float(sign * f64::INFINITY));
+    }
+    let integer_end = digits(end);
+    let mut has_digits = integer_end > end;
+    end = integer_end;
+    if bytes.get(end) == Some(&b'.') {
+        let fraction_end = digits(end + 1);
+        has_digits = has_digits || fraction_end > end + 1;
+        end = fraction_end;
+    }
+    if !has_digits {
+        return Ok(Dyn::float(f64::NAN));
+    }
+    // An exponent needs at least one digit.
+    if let Some(b'e') | Some(b'E') = bytes.get(end) {
+        let sign = match bytes.get(end + 1) {
+            Some(b'+') | Some(b'-') => 1,
+            _ => 0,
+        };
+        let exponent_end = digits(end + 1 + sign);
+        if exponent_end > end + 1 + sign {
+            end = exponent_end;
+        }
+    }
+    return Ok(Dyn::float(s[..end].parse::<f64>().unwrap_or(f64::NAN)));
+}
+
+/// Reads the longest prefix of the string that is an integer in the radix,
+/// after any whitespace and a sign. The radix is 16 when it is omitted and
+/// the string starts with `0x`, and 10 when it is omitted otherwise.
+pub fn parse_int<'a>(
+    _arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    let s = arg(args, 0).to_string();
+    let mut s = s.trim_start_matches(is_space);
+    let negative = s.starts_with('-');
+    if s.starts_with('-') || s.starts_with('+') {
+        s = &s[1..];
+    }
+    let mut radix = match arg(args, 1).to_int32() {
+        0 => 0,
+        radix if (2..=36).contains(&radix) => radix as u32,
+        _ => return Ok(Dyn::float(f64::NAN)),
+    };
+    // An explicit radix other than 16 does not skip the prefix.
+    if (radix == 0 || radix == 16) && (s.starts_with("0x") || s.starts_with("0X")) {
+        s = &s[2..];
+        radix = 16;
+    } else if radix == 0 {
+        radix = 10;
+    }
+    let digits = s
+        .chars()
+        .take_while(|c| c.is_digit(radix))
+        .map(|c| c.to_digit(radix).unwrap())
+        .collect::<Vec<u32>>();
+    if digits.is_empty() {
+        return Ok(Dyn::float(f64::NAN));
+    }
+    let n = digits
+        .iter()
+        .fold(0.0, |n, d| n * f64::from(radix) + f64::from(*d));
+    return Ok(Dyn::float(if negative { -n } else { n }));
+}
+
+/// `String()` is the empty string, but `String(undefined)` is
+/// `"undefined"`.
+pub fn string<'a>(
+    arena: &'a Bump, args: &[Dyn<'a>], _ec: &mut ExecutionContext<'a>,
+) -> DynResult<'a> {
+    match args.first() {
+        None => return Ok(Dyn::str(arena, "")),
+        Some(x) => return Ok(Dyn::str(arena, &x.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calls the builtin and returns the number that it produces.
+    fn call(f: Builtin, args: &[&str]) -> f64 {
+        let arena = Bump::new();
+        let mut ec = ExecutionContext::new(&arena, 1_000_000);
+        let args = args
+            .iter()
+            .map(|arg| match arg.parse::<f64>() {
+                Ok(x) if !arg.starts_with(' ') => Dyn::float(x),
+                _ => Dyn::str(&arena, arg),
+            })
+            .collect::<Vec<_>>();
+        match f(&arena, &args, &mut ec) {
+            Ok(Dyn::Float(x)) => return x,
+            other => panic!("expected a Float, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_int_reads_a_prefix_in_the_radix() {
+        assert_eq!(call(parse_int, &["0x1f"]), 31.0);
+        assert_eq!(call(parse_int, &["  -101xyz", "2"]), -5.0);
+        assert_eq!(call(parse_int, &["0x1f", "10"]), 0.0);
+        assert_eq!(call(parse_int, &["0x1f", "16"]), 31.0);
+        assert_eq!(call(parse_int, &["z", "36"]), 35.0);
+        assert!(call(parse_int, &["12", "37"]).is_nan());
+        assert!(call(parse_int, &[""]).is_nan());
+        assert!(call(parse_int, &[]).is_nan());
+    }
+
+    #[test]
+    fn parse_float_reads_a_decimal_prefix() {
+        assert_eq!(call(parse_float, &["3.5e2px"]), 350.0);
+        assert_eq!(call(parse_float, &["  -.5"]), -0.5);
+        assert_eq!(call(parse_float, &["1e"]), 1.0);
+        assert_eq!(call(parse_float, &["1e+x"]), 1.0);
+        assert_eq!(call(parse_float, &["-Infinityx"]), f64::NEG_INFINITY);
+        assert!(call(parse_float, &["."]).is_nan());
+        assert!(call(parse_float, &["x1"]).is_nan());
+    }
+
+    #[test]
+    fn math_round_rounds_half_way_cases_up() {
+        assert_eq!(call(math_round, &["2.5"]), 3.0);
+        assert_eq!(call(math_round, &["-2.5"]), -2.0);
+        assert_eq!(call(math_round, &["-2.6"]), -3.0);
+        assert_eq!(call(math_round, &["0.49999999999999994"]), 0.0);
+        let zero = call(math_round, &["-0.4"]);
+        assert!(zero == 0.0 && zero.is_sign_negative());
+        assert!(call(math_round, &["x"]).is_nan());
+    }
+}
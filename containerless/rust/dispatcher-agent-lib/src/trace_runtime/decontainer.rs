@@ -2,11 +2,37 @@ use super::affine_ref::AffineBoxFactory;
 use super::common::*;
 use super::error::*;
 use super::execution_context::*;
+use super::interpreter::Program;
+use super::shared_library::SharedLibrary;
 use super::type_dynamic::*;
-use super::Containerless;
+use hyper::header::HeaderValue;
 use std::convert::TryInto;
 use std::str;
 
+/// The code of a decontainerized function. A new trace is interpreted until
+/// the controller finishes compiling it to a shared library.
+#[derive(Clone)]
+pub enum Decontainerized {
+    Compiled(Arc<SharedLibrary>),
+    Interpreted(Arc<Program>),
+}
+
+impl Decontainerized {
+    fn call<'a>(
+        &self, arena: &'a Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>,
+        arg_cbargs: Dyn<'a>,
+    ) -> DynResult<'a> {
+        match self {
+            Decontainerized::Compiled(library) => {
+                library.containerless(arena, ec, arg_cbid, arg_cbargs)
+            }
+            Decontainerized::Interpreted(program) => {
+                program.containerless(arena, ec, arg_cbid, arg_cbargs)
+            }
+        }
+    }
+}
+
 // An `async fn` produces a future that may or may not be sendable:
 //
 //    https://rust-lang.github.io/async-book/07_workarounds/04_send_approximation.html
@@ -17,19 +43,44 @@ use std::str;
 // raises a type error if the future is not sendable. Having the type error
 // locally within this file makes it easier to debug.
 fn _statically_assert_sendable(
-    func: Containerless, client: HttpClient, url_path: &str, body: &[u8],
+    func: Decontainerized, name: &str, gas_limit: u64, client: HttpClient, url_path: &str,
+    body: &[u8],
 ) {
     fn _check_send<F>(_f: F)
     where
         F: std::future::Future + Send,
     {
     }
-    _check_send(run_decontainerized_function(func, client, url_path, body));
+    _check_send(run_decontainerized_function(
+        func, name, gas_limit, client, url_path, body,
+    ));
+}
+
+fn with_gas(mut resp: Response, gas_used: u64) -> Response {
+    resp.headers_mut()
+        .insert("X-Containerless-Gas", HeaderValue::from(gas_used));
+    return resp;
 }
 
+fn error_response(name: &str, gas_used: u64, gas_limit: u64, err: Error) -> Response {
+    info!(target: "dispatcher", "{} failed after using {} of {} gas: {}", name, gas_used, gas_limit, err);
+    let resp = hyper::Response::builder()
+        .status(500)
+        .body(hyper::Body::from(format!(
+            "error from serverless function {}",
+            err
+        )))
+        .unwrap();
+    return with_gas(resp, gas_used);
+}
+
+/// Runs an invocation of the function `name`, which may use at most
+/// `gas_limit` gas. The response reports the gas that the invocation used in
+/// the `X-Containerless-Gas` header, even when the invocation fails.
 pub async fn run_decontainerized_function(
-    func: Containerless, client: HttpClient, url_path: &str, body: &[u8],
-) -> Result<Response, Error> {
+    func: Decontainerized, name: &str, gas_limit: u64, client: HttpClient, url_path: &str,
+    body: &[u8],
+) -> Response {
     let arena = Bump::new();
     let affine_factory = AffineBoxFactory::new();
 
@@ -51,7 +102,7 @@ pub async fn run_decontainerized_function(
         request
     });
 
-    let mut ec = affine_factory.make_box(ExecutionContext::new());
+    let mut ec = affine_factory.make_box(ExecutionContext::new(&arena, gas_limit));
 
     let mut pending_futures = Vec::new();
     pending_futures.push(Box::pin(PendingOp::initial().to_future2(&client)));
@@ -62,12 +113,20 @@ pub async fn run_decontainerized_function(
         // Save the remaining operations in state.
         pending_futures = new_pending_futures;
         // Abort if the asynchronous operation failed.
-        let (outcome, indicator, closure) = outcome_result?;
-        // Build the arguments array from the result.
         affine_factory.begin_reads();
+        let (outcome, indicator, closure) = match outcome_result {
+            Ok(triple) => triple,
+            Err(err) => return error_response(name, ec.read().gas_used(), gas_limit, err),
+        };
+        // Build the arguments array from the result.
         let args = outcome.process(&arena, *request.read(), closure);
         // Run the serverless function.
-        let _result = func(&arena, ec.read_mut(), Dyn::int(indicator), args)?;
+        let result = func
+            .call(&arena, ec.read_mut(), Dyn::int(indicator), args)
+            .and_then(|_| ec.read_mut().charge_allocations());
+        if let Err(err) = result {
+            return error_response(name, ec.read().gas_used(), gas_limit, err);
+        }
         // Fetch newly constructed operations.
         let mut new_ops = Vec::new();
         std::mem::swap(&mut new_ops, &mut ec.read_mut().new_ops);
@@ -82,28 +141,82 @@ pub async fn run_decontainerized_function(
 
     affine_factory.begin_reads();
 
-    match ec.read().response {
+    let gas_used = ec.read().gas_used();
+    info!(target: "dispatcher", "{} used {} of {} gas", name, gas_used, gas_limit);
+    let resp = match ec.read().response {
         None => {
             // The follow error is not due to optimistic trace compilation.
             // It will re-occur if we re-execution in JavaScript, thus we
             // don't bother doing so.
-            return Ok(hyper::Response::builder()
+            hyper::Response::builder()
                 .status(500)
                 .body(hyper::Body::from("No response set"))
-                .unwrap());
+                .unwrap()
         }
         Some(resp) => {
             let resp: Result<String, ()> = resp.try_into();
-            if let Ok(body) = resp {
-                return Ok(hyper::Response::new(hyper::Body::from(body)));
+            match resp {
+                Ok(body) => hyper::Response::new(hyper::Body::from(body)),
+                Err(()) => hyper::Response::builder()
+                    .status(500)
+                    .body(hyper::Body::from(
+                        "Could not convert response to string (... from inside Rust...)",
+                    ))
+                    .unwrap(),
             }
-
-            return Ok(hyper::Response::builder()
-                .status(500)
-                .body(hyper::Body::from(
-                    "Could not convert response to string (... from inside Rust...)",
-                ))
-                .unwrap());
         }
+    };
+    return with_gas(resp, gas_used);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper_timeout::TimeoutConnector;
+    use shared::trace::syntax;
+
+    /// A trace that counts to 20,000 and then responds.
+    const LOOP: &str = "(block [
+      (let i 0.0)
+      (while (< i 20000.0) [
+        (set i (+ i 1.0))
+      ])
+      (primApp send \"done\")
+    ])";
+
+    async fn invoke(gas_limit: u64) -> (u16, u64, String) {
+        let exp = syntax::parse(LOOP).unwrap();
+        let program = Program::from_json(&serde_json::to_string(&exp).unwrap()).unwrap();
+        let connector = TimeoutConnector::new(hyper::client::HttpConnector::new());
+        let client = Arc::new(hyper::Client::builder().build(connector));
+        let func = Decontainerized::Interpreted(Arc::new(program));
+        let resp = run_decontainerized_function(func, "loop", gas_limit, client, "/", b"").await;
+        let status = resp.status().as_u16();
+        let gas = resp.headers()["X-Containerless-Gas"]
+            .to_str()
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        return (status, gas, String::from_utf8(body.to_vec()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn loop_runs_under_the_default_limit() {
+        let (status, gas, body) = invoke(DEFAULT_GAS_LIMIT).await;
+        assert_eq!((status, body.as_str()), (200, "done"));
+        assert!(
+            gas >= 20_000 && gas <= DEFAULT_GAS_LIMIT,
+            "used {} gas",
+            gas
+        );
+    }
+
+    #[tokio::test]
+    async fn loop_runs_out_of_gas_under_a_lower_limit() {
+        let (status, gas, body) = invoke(1_000).await;
+        assert_eq!(status, 500);
+        assert!(body.contains("Error::OutOfGas(1000)"), "{}", body);
+        assert!(gas >= 1_000, "used {} gas", gas);
     }
 }
@@ -1,28 +1,104 @@
+use super::type_dynamic::Dyn;
 use thiserror::Error;
 #[derive(Debug, Error)]
-pub enum Error {
+pub enum Error<'a> {
     /** Equivalent to a runtime type-error in JavaScript */
     #[error("Error::TypeError({0})")]
     TypeError(String),
     /** Result of reaching an unknown portion of the trace. */
     #[error("Error::Unknown")]
     Unknown,
-    /** Run out of gas. */
-    #[error("Error::OutOfGas")]
-    OutOfGas,
+    /** Run out of gas. Holds the gas limit of the invocation. */
+    #[error("Error::OutOfGas({0})")]
+    OutOfGas(u64),
     #[error("Error::Json({0})")]
     Json(#[from] serde_json::Error),
     #[error("Error::String({0})")]
     String(#[from] std::str::Utf8Error),
+    /** A value thrown by JavaScript code, which a `catch` clause may handle. */
+    #[error("Error::Thrown({0:?})")]
+    Thrown(Dyn<'a>),
 }
 
-pub fn type_error<T>(message: impl Into<String>) -> Result<T, Error> {
+impl<'a> Error<'a> {
+    /// A thrown value cannot outlive the arena that it is allocated in, so an
+    /// uncaught exception becomes a type error.
+    pub fn into_static(self) -> Error<'static> {
+        match self {
+            Error::TypeError(message) => Error::TypeError(message),
+            Error::Unknown => Error::Unknown,
+            Error::OutOfGas(limit) => Error::OutOfGas(limit),
+            Error::Json(err) => Error::Json(err),
+            Error::String(err) => Error::String(err),
+            Error::Thrown(value) => {
+                Error::TypeError(format!("uncaught exception: {}", value.to_string()))
+            }
+        }
+    }
+}
+
+pub fn type_error<'a, T>(message: impl Into<String>) -> Result<T, Error<'a>> {
     return Err(Error::TypeError(message.into()));
 }
 
-pub fn not_a_function<T>(fun: impl Into<String>) -> Result<T, Error> {
+pub fn not_a_function<'a, T>(fun: impl Into<String>) -> Result<T, Error<'a>> {
     return Err(Error::TypeError(format!(
         "{} is not a function.",
         fun.into()
     )));
 }
+
+/// Reports that the operation that produces `result` is at `line` and `column`
+/// of `file` in the JavaScript source, if it fails with a type error.
+pub fn at<'a, T>(
+    result: Result<T, Error<'a>>, file: &str, line: u32, column: u32,
+) -> Result<T, Error<'a>> {
+    match result {
+        Err(Error::TypeError(message)) => Err(Error::TypeError(format!(
+            "{}:{}:{}: {}",
+            file, line, column, message
+        ))),
+        result => result,
+    }
+}
+
+/// Separates the exceptions that a `catch` clause may handle from the errors
+/// that abort the invocation. Our type errors do not always coincide with the
+/// exceptions that JavaScript throws, so within a `try` block they abort the
+/// invocation (as `Error::Unknown`) instead of running the handler.
+pub fn catchable<'a, T>(result: Result<T, Error<'a>>) -> Result<Result<T, Dyn<'a>>, Error<'a>> {
+    match result {
+        Ok(value) => Ok(Ok(value)),
+        Err(Error::Thrown(value)) => Ok(Err(value)),
+        Err(Error::TypeError(_)) => Err(Error::Unknown),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn at_prefixes_only_type_errors() {
+        let result: Result<(), Error> = at(type_error("oops"), "f.js", 3, 4);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Error::TypeError(f.js:3:4: oops)"
+        );
+        let result: Result<(), Error> = at(Err(Error::Unknown), "f.js", 3, 4);
+        assert_eq!(result.unwrap_err().to_string(), "Error::Unknown");
+        assert_eq!(at(Ok(1), "f.js", 3, 4).unwrap(), 1);
+    }
+
+    #[test]
+    fn catch_handles_only_thrown_values() {
+        let result: Result<(), Error> = Err(Error::Thrown(Dyn::float(2.0)));
+        assert!(matches!(catchable(result), Ok(Err(Dyn::Float(_)))));
+        let result: Result<(), Error> = at(type_error("oops"), "f.js", 3, 4);
+        assert!(matches!(catchable(result), Err(Error::Unknown)));
+        let result: Result<(), Error> = Err(Error::OutOfGas(10));
+        assert!(matches!(catchable(result), Err(Error::OutOfGas(10))));
+        assert!(matches!(catchable::<i32>(Ok(1)), Ok(Ok(1))));
+    }
+}
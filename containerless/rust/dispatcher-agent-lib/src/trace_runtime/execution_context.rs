@@ -34,34 +34,27 @@ impl AsyncOpOutcome {
                 return clos;
             }
             AsyncOpOutcome::Connected => {
-                let args = Dyn::vec(arena);
-                args.push(clos).unwrap();
-                args.push(request).unwrap();
-                return args;
+                return Dyn::vec_with(arena, vec![clos, request]);
             }
             AsyncOpOutcome::MockGetResponse(json_value) => {
-                let args = Dyn::vec(arena);
-                args.push(clos).unwrap();
-                args.push(Dyn::from_json(arena, json_value)).unwrap();
-                return args;
+                return Dyn::vec_with(arena, vec![clos, Dyn::from_json(arena, json_value)]);
             }
             AsyncOpOutcome::GetResponse(body) => {
-                let args = Dyn::vec(arena);
-                args.push(clos).unwrap();
                 let resp = Dyn::from_json_string(arena, &String::from_utf8_lossy(&body))
                     .unwrap_or_else(|err| {
                         // TODO(arjun): Need DynResult as return type of this function
                         panic!("JSON error {} reading string {:?}", err, body);
                     });
-                args.push(resp).unwrap();
-                return args;
+                return Dyn::vec_with(arena, vec![clos, resp]);
             }
         }
     }
 }
 
 impl AsyncOp {
-    pub async fn to_future<'a>(self, client: &HttpClient) -> Result<AsyncOpOutcome, Error> {
+    pub async fn to_future(
+        self, client: &HttpClient,
+    ) -> Result<AsyncOpOutcome, Error<'static>> {
         match self {
             AsyncOp::Preinitialize => return Ok(AsyncOpOutcome::Initialize),
             AsyncOp::Listen => {
@@ -174,10 +167,13 @@ impl AsyncOp {
 
 use futures::prelude::*;
 
+/// An event that a callback sent. The fields are public so that a test can
+/// complete the event without I/O.
 pub struct PendingOp<'a> {
-    async_op: AsyncOp,
-    indicator: i32,
-    closure: Dyn<'a>,
+    pub async_op: AsyncOp,
+    /// Identifies the callback that handles the outcome.
+    pub indicator: i32,
+    pub closure: Dyn<'a>,
 }
 
 struct SendBox<T> {
@@ -188,7 +184,7 @@ unsafe impl<T> Send for SendBox<T> {}
 impl<'a> PendingOp<'a> {
     pub fn to_future2(
         self, client: &'a HttpClient,
-    ) -> impl Future<Output = Result<(AsyncOpOutcome, i32, Dyn<'a>), Error>> + Send {
+    ) -> impl Future<Output = Result<(AsyncOpOutcome, i32, Dyn<'a>), Error<'static>>> + Send {
         let indicator = self.indicator;
         let closure = SendBox {
             contents: self.closure,
@@ -209,24 +205,53 @@ impl<'a> PendingOp<'a> {
     }
 }
 
+/// The gas limit of an invocation, unless the function sets its own limit.
+pub const DEFAULT_GAS_LIMIT: u64 = 1_000_000;
+/// The gas that one iteration of a loop costs.
+const ITERATION_GAS: u64 = 1;
+/// The gas that sending a new event (i.e., registering a callback) costs.
+const CALLBACK_GAS: u64 = 100;
+/// Allocating this many bytes in the arena costs one unit of gas. This covers
+/// objects, arrays, and strings alike. The arena grows in chunks, so we may
+/// charge for up to twice the bytes that the function actually allocates.
+const BYTES_PER_GAS: usize = 64;
+/// The seed of `Math.random`. Every invocation starts from the same seed, so
+/// the generated code and the interpreter produce the same numbers.
+const RANDOM_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
 /// The execution context allows a callback to send new events. The lifetime
 /// `'a` is the lifetime of the arena in which the function may allocate
 /// heap values.
+///
+/// The execution context also meters the gas that an invocation uses, across
+/// all of its callbacks. An invocation that exceeds its gas limit fails with
+/// `Error::OutOfGas`. Allocations are charged at the next loop iteration or
+/// callback, and at the end of each callback.
 pub struct ExecutionContext<'a> {
     pub new_ops: Vec<PendingOp<'a>>,
     pub response: Option<Dyn<'a>>,
-    counter: usize,
+    arena: &'a Bump,
+    gas_limit: u64,
+    gas_used: u64,
+    charged_bytes: usize,
+    random_state: u64,
 }
 
 impl<'a> ExecutionContext<'a> {
-    pub fn new() -> Self {
+    pub fn new(arena: &'a Bump, gas_limit: u64) -> Self {
         let response = None;
         let new_ops = vec![];
-        let counter = 0;
+        // Do not charge for what the dispatcher allocated before the
+        // invocation started, e.g., the request.
+        let charged_bytes = arena.allocated_bytes();
         ExecutionContext {
             response,
             new_ops,
-            counter,
+            arena,
+            gas_limit,
+            gas_used: 0,
+            charged_bytes,
+            random_state: RANDOM_SEED,
         }
     }
 
@@ -243,9 +268,9 @@ impl<'a> ExecutionContext<'a> {
     }
 
     pub fn loopback(
-        &mut self, event_name: &'static str, event_arg: Dyn<'a>, event_clos: Dyn<'a>,
-        indicator: i32,
+        &mut self, event_name: &str, event_arg: Dyn<'a>, event_clos: Dyn<'a>, indicator: i32,
     ) -> DynResult<'a> {
+        self.charge(CALLBACK_GAS)?;
         if event_name == "listen" {
             self.loopback_int(AsyncOp::Listen, indicator, event_clos);
             return Ok(Dyn::int(0));
@@ -318,13 +343,41 @@ impl<'a> ExecutionContext<'a> {
         Ok(Dyn::int(0))
     }
 
-    #[allow(unused)]
-    pub fn count(&mut self) -> Result<(), Error> {
-        if self.counter == 10000 {
-            Err(Error::OutOfGas)
-        } else {
-            self.counter += 1;
-            Ok(())
+    fn charge(&mut self, gas: u64) -> Result<(), Error<'a>> {
+        let allocated_bytes = self.arena.allocated_bytes() - self.charged_bytes;
+        let allocation_gas = allocated_bytes / BYTES_PER_GAS;
+        // Charge the remainder the next time.
+        self.charged_bytes += allocation_gas * BYTES_PER_GAS;
+        self.gas_used += gas + allocation_gas as u64;
+        if self.gas_used > self.gas_limit {
+            return Err(Error::OutOfGas(self.gas_limit));
         }
+        return Ok(());
+    }
+
+    /// Charges for one iteration of a loop.
+    pub fn count(&mut self) -> Result<(), Error<'a>> {
+        return self.charge(ITERATION_GAS);
+    }
+
+    /// Charges for the allocations since the last charge.
+    pub fn charge_allocations(&mut self) -> Result<(), Error<'a>> {
+        return self.charge(0);
+    }
+
+    pub fn gas_used(&self) -> u64 {
+        return self.gas_used;
+    }
+
+    /// The next number in `[0, 1)` of `Math.random`, from the SplitMix64
+    /// generator.
+    pub fn random(&mut self) -> f64 {
+        self.random_state = self.random_state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.random_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // The high 53 bits fill the significand of an f64.
+        return (z >> 11) as f64 / (1u64 << 53) as f64;
     }
 }
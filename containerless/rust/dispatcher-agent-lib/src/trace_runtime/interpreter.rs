@@ -0,0 +1,610 @@
+//! Interprets the IR that the trace compiler produces after `LiftCallbacks`.
+//! The controller sends this IR to the dispatcher as soon as it receives a
+//! trace, so a decontainerized function is available before the dispatcher
+//! is rebuilt with the generated code. The interpreter must behave exactly
+//! like the code that `codegen.rs` generates from the same IR.
+//!
+//! The IR is `shared::trace::types`, which the trace compiler uses too. Only
+//! some of its nodes may occur after `LiftCallbacks`, and any other node is a
+//! type error.
+use super::builtins::BUILTINS;
+use super::common::*;
+use super::error::*;
+use super::execution_context::ExecutionContext;
+use super::type_dynamic::{throw, unknown, Dyn, DynResult, Invoke};
+use shared::trace::types::{Exp, LVal, Loc, Op1, Op2};
+use std::collections::HashMap;
+
+/// How an expression finishes when it does not produce a value.
+enum Exit<'a, 'p> {
+    /// A `break` to the label with the given name.
+    Break(&'p str, Dyn<'a>),
+    Error(Error<'a>),
+}
+
+impl<'a, 'p> From<Error<'a>> for Exit<'a, 'p> {
+    fn from(err: Error<'a>) -> Self {
+        return Exit::Error(err);
+    }
+}
+
+type Eval<'a, 'p> = Result<Dyn<'a>, Exit<'a, 'p>>;
+
+/// How the body or the handler of a `try` finishes, when it does not abort
+/// the trace.
+#[derive(Clone, Copy)]
+enum Completion<'a, 'p> {
+    Normal(Dyn<'a>),
+    Break(&'p str, Dyn<'a>),
+    Throw(Dyn<'a>),
+}
+
+/// Classifies the result of a block in a `try`. As in the generated code, a
+/// type error aborts the trace instead of running the handler, and it skips
+/// the finalizer too.
+fn complete<'a, 'p>(result: Eval<'a, 'p>) -> Result<Completion<'a, 'p>, Error<'a>> {
+    match result {
+        Ok(value) => Ok(Completion::Normal(value)),
+        Err(Exit::Break(name, value)) => Ok(Completion::Break(name, value)),
+        Err(Exit::Error(err)) => match catchable::<Dyn<'a>>(Err(err))? {
+            Ok(value) => Ok(Completion::Normal(value)),
+            Err(exn) => Ok(Completion::Throw(exn)),
+        },
+    }
+}
+
+/// The generated code turns labels into Rust lifetimes, which start with `'`.
+fn label_name(name: &str) -> &str {
+    return name.trim_start_matches('\'');
+}
+
+fn op1<'a>(arena: &'a Bump, op: &Op1, v: Dyn<'a>) -> DynResult<'a> {
+    match op {
+        Op1::Void => v.void(arena),
+        Op1::Typeof => v.typeof_(arena),
+        Op1::Negative => v.neg(),
+        Op1::Not => v.not(),
+        Op1::BitNot => v.bit_not(),
+    }
+}
+
+fn op2<'a>(arena: &'a Bump, op: &Op2, v1: Dyn<'a>, v2: Dyn<'a>) -> DynResult<'a> {
+    match op {
+        Op2::Add => v1.add(arena, v2),
+        Op2::Sub => v1.sub(v2),
+        Op2::Mul => v1.mul(v2),
+        Op2::Div => v1.div(v2),
+        Op2::Mod => v1.rem(v2),
+        Op2::StrictEq => v1.strict_eq(v2),
+        Op2::StrictNotEq => v1.strict_neq(v2),
+        Op2::Eq => v1.loose_eq(v2),
+        Op2::NotEq => v1.loose_neq(v2),
+        Op2::GT => v1.gt(v2),
+        Op2::LT => v1.lt(v2),
+        Op2::GTE => v1.gte(v2),
+        Op2::LTE => v1.lte(v2),
+        Op2::And => v1.and(v2),
+        Op2::Or => v1.or(v2),
+        Op2::BitAnd => v1.bit_and(v2),
+        Op2::BitOr => v1.bit_or(v2),
+        Op2::BitXor => v1.bit_xor(v2),
+        Op2::LeftShift => v1.shl(v2),
+        Op2::RightShift => v1.shr(v2),
+        Op2::UnsignedRightShift => v1.ushr(v2),
+        Op2::Nullish => v1.nullish(v2),
+        Op2::In => v1.in_(v2),
+    }
+}
+
+/// The methods that the generated code calls on a `Dyn`.
+/// The `Transformer` of the trace compiler passes every optional argument.
+fn method_call<'a>(
+    arena: &'a Bump, receiver: Dyn<'a>, method: &str, args: &[Dyn<'a>], invoke: &mut Invoke<'a, '_>,
+) -> DynResult<'a> {
+    match (method, args) {
+        ("filter", [callback]) => receiver.filter(arena, *callback, invoke),
+        ("find", [callback]) => receiver.find(arena, *callback, invoke),
+        ("forEach", [callback]) => receiver.forEach(arena, *callback, invoke),
+        ("map", [callback]) => receiver.map(arena, *callback, invoke),
+        ("reduce", [callback, initial]) => receiver.reduce(arena, *callback, *initial, invoke),
+        ("some", [callback]) => receiver.some(arena, *callback, invoke),
+        ("sort", [compare]) => receiver.sort(arena, *compare, invoke),
+        ("push", [value]) => receiver.push(arena, *value),
+        ("pop", []) => receiver.pop(arena),
+        ("shift", []) => receiver.shift(arena),
+        ("fill", [value, start, end]) => receiver.fill(arena, *value, *start, *end),
+        ("isArray", []) => receiver.isArray(arena),
+        ("join", [separator]) => receiver.join(arena, *separator),
+        ("reverse", []) => receiver.reverse(arena),
+        ("splice", [start, count, items]) => receiver.splice(arena, *start, *count, *items),
+        ("unshift", [items]) => receiver.unshift(arena, *items),
+        ("charAt", [pos]) => receiver.charAt(arena, *pos),
+        ("charCodeAt", [pos]) => receiver.charCodeAt(arena, *pos),
+        ("concat", [value]) => receiver.concat(arena, *value),
+        ("endsWith", [search, end]) => receiver.endsWith(arena, *search, *end),
+        ("includes", [search, from]) => receiver.includes(arena, *search, *from),
+        ("indexOf", [search, from]) => receiver.indexOf(arena, *search, *from),
+        ("padStart", [length, fill]) => receiver.padStart(arena, *length, *fill),
+        ("replace", [pattern, replacement]) => receiver.replace(arena, *pattern, *replacement),
+        ("slice", [start, end]) => receiver.slice(arena, *start, *end),
+        ("split", [separator, limit]) => receiver.split(arena, *separator, *limit),
+        ("startsWith", [search, from]) => receiver.startsWith(arena, *search, *from),
+        ("substring", [start, end]) => receiver.substring(arena, *start, *end),
+        ("toLowerCase", []) => receiver.toLowerCase(arena),
+        ("toUpperCase", []) => receiver.toUpperCase(arena),
+        ("trim", []) => receiver.trim(arena),
+        _ => not_a_function(method),
+    }
+}
+
+/// A lifted trace, which runs in place of the function that
+/// `codegen.rs` would generate from it.
+#[derive(Debug)]
+pub struct Program {
+    exp: Exp,
+}
+
+impl Program {
+    pub fn from_json(ir: &str) -> Result<Program, serde_json::Error> {
+        let exp = serde_json::from_str::<Exp>(ir)?;
+        return Ok(Program { exp });
+    }
+
+    /// Has the same signature as the generated `containerless` function.
+    pub fn containerless<'a>(
+        &self, arena: &'a Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>,
+        arg_cbargs: Dyn<'a>,
+    ) -> DynResult<'a> {
+        let mut interpreter = Interpreter {
+            arena,
+            ec,
+            program: &self.exp,
+            env: HashMap::new(),
+            loc: None,
+        };
+        return interpreter.run(arg_cbid, arg_cbargs);
+    }
+}
+
+struct Interpreter<'a, 'p, 'e> {
+    arena: &'a Bump,
+    ec: &'e mut ExecutionContext<'a>,
+    /// The whole program, which runs again to call a closure.
+    program: &'p Exp,
+    /// The trace compiler gives every variable a unique name, so a single
+    /// environment suffices.
+    env: HashMap<&'p str, Dyn<'a>>,
+    /// The innermost `Located` around the expression being evaluated.
+    loc: Option<&'p Loc>,
+}
+
+impl<'a, 'p, 'e> Interpreter<'a, 'p, 'e> {
+    /// Reports the current location in a type error, like `rt::at` in the
+    /// generated code.
+    fn at<T>(&self, result: Result<T, Error<'a>>) -> Result<T, Error<'a>> {
+        match self.loc {
+            None => result,
+            Some(Loc { file, line, column }) => at(result, file, *line, *column),
+        }
+    }
+
+    fn run(&mut self, arg_cbid: Dyn<'a>, arg_cbargs: Dyn<'a>) -> DynResult<'a> {
+        self.env.insert("arg_cbid", arg_cbid);
+        self.env.insert("arg_cbargs", arg_cbargs);
+        match self.eval(self.program) {
+            Ok(value) => Ok(value),
+            Err(Exit::Error(err)) => Err(err),
+            Err(Exit::Break(name, _)) => type_error(format!("break to unknown label {}", name)),
+        }
+    }
+
+    /// Calls the body of a closure, like the recursive call to `containerless`
+    /// in the generated code. The body gets a fresh environment, because the
+    /// variables that it captures are in the closure object.
+    fn invoke(&mut self, cbid: Dyn<'a>, cbargs: Dyn<'a>) -> DynResult<'a> {
+        self.ec.count()?;
+        let mut interpreter = Interpreter {
+            arena: self.arena,
+            ec: &mut *self.ec,
+            program: self.program,
+            env: HashMap::new(),
+            loc: None,
+        };
+        return interpreter.run(cbid, cbargs);
+    }
+
+    fn eval_exps(&mut self, exps: &'p [Exp]) -> Result<Vec<Dyn<'a>>, Exit<'a, 'p>> {
+        let mut values = Vec::with_capacity(exps.len());
+        for e in exps.iter() {
+            values.push(self.eval(e)?);
+        }
+        return Ok(values);
+    }
+
+    /// A block produces the value of its last expression. `Let` produces
+    /// `undefined`, so a block that ends with a `Let` does too.
+    fn eval_block(&mut self, block: &'p [Exp]) -> Eval<'a, 'p> {
+        let mut value = Dyn::undef();
+        for e in block.iter() {
+            value = self.eval(e)?;
+        }
+        return Ok(value);
+    }
+
+    /// The body and the handler run in turn, then the finalizer. A `break` or
+    /// an exception that leaves the body or the handler resumes after the
+    /// finalizer.
+    fn eval_try(
+        &mut self, body: &'p [Exp], catch_name: &'p Option<String>, handler: &'p Option<Vec<Exp>>,
+        finalizer: &'p [Exp],
+    ) -> Eval<'a, 'p> {
+        let mut completion = complete(self.eval_block(body))?;
+        if let (Completion::Throw(exn), Some(handler)) = (completion, handler) {
+            if let Some(name) = catch_name {
+                self.env.insert(name, Dyn::ref_(self.arena, exn));
+            }
+            completion = complete(self.eval_block(handler))?;
+        }
+        self.eval_block(finalizer)?;
+        match completion {
+            Completion::Normal(value) => Ok(value),
+            Completion::Break(name, value) => Err(Exit::Break(name, value)),
+            Completion::Throw(exn) => Ok(self.at(throw(exn))?),
+        }
+    }
+
+    fn eval(&mut self, exp: &'p Exp) -> Eval<'a, 'p> {
+        match exp {
+            Exp::Unknown {} => Ok(self.at(unknown())?),
+            Exp::Integer { value } => Ok(Dyn::int(*value)),
+            Exp::Number { value } => Ok(Dyn::float(*value)),
+            Exp::Bool { value } => Ok(Dyn::bool(*value)),
+            Exp::Identifier { name } => match self.env.get(name.as_str()) {
+                Some(value) => Ok(*value),
+                None => Ok(type_error(format!("{} is not defined", name))?),
+            },
+            Exp::Get { exp, field } => {
+                let value = self.eval(exp)?;
+                Ok(self.at(value.get(field))?)
+            }
+            Exp::Stringg { value } => Ok(Dyn::str(self.arena, value)),
+            Exp::Undefined {} | Exp::Unit {} => Ok(Dyn::undef()),
+            Exp::Null {} => Ok(Dyn::null()),
+            Exp::BinOp { op, e1, e2 } => {
+                let v1 = self.eval(e1)?;
+                let v2 = self.eval(e2)?;
+                Ok(self.at(op2(self.arena, op, v1, v2))?)
+            }
+            Exp::Op1 { op, e } => {
+                let v = self.eval(e)?;
+                Ok(self.at(op1(self.arena, op, v))?)
+            }
+            Exp::If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                if self.eval(cond)?.is_truthy() {
+                    self.eval_block(true_part)
+                } else {
+                    self.eval_block(false_part)
+                }
+            }
+            Exp::While { cond, body } => {
+                while self.eval(cond)?.is_truthy() {
+                    let count = self.ec.count();
+                    self.at(count)?;
+                    self.eval_block(body)?;
+                }
+                Ok(Dyn::undef())
+            }
+            Exp::Let { name, named } => {
+                let value = self.eval(named)?;
+                if name != "_" {
+                    self.env.insert(name, value);
+                }
+                Ok(Dyn::undef())
+            }
+            Exp::Set {
+                name: LVal::Identifier { name },
+                named,
+            } => {
+                let value = self.eval(named)?;
+                self.env.insert(name, value);
+                Ok(Dyn::undef())
+            }
+            Exp::Set {
+                name: LVal::Index { exp, index },
+                named,
+            } => {
+                let target = self.eval(exp)?;
+                let index = self.eval(index)?;
+                let value = self.eval(named)?;
+                Ok(self.at(target.set(self.arena, index, value))?)
+            }
+            Exp::Block { body } => self.eval_block(body),
+            Exp::Loopback {
+                event,
+                event_arg,
+                callback_clos,
+                id,
+            } => {
+                let event_arg = self.eval(event_arg)?;
+                let callback_clos = self.eval(callback_clos)?;
+                let result = self.ec.loopback(event, event_arg, callback_clos, *id);
+                Ok(self.at(result)?)
+            }
+            Exp::Closure { clos, id } => {
+                let clos = self.eval(clos)?;
+                Ok(Dyn::closure(self.arena, *id, clos))
+            }
+            Exp::Call { e, call_args } => {
+                let f = self.eval(e)?;
+                let args = self.eval_exps(call_args)?;
+                let arena = self.arena;
+                let result = f.call(arena, &args, &mut |cbid, cbargs| self.invoke(cbid, cbargs));
+                Ok(self.at(result)?)
+            }
+            Exp::Label { name, body } => match self.eval_block(body) {
+                Err(Exit::Break(target, value)) if label_name(target) == label_name(name) => {
+                    Ok(value)
+                }
+                result => result,
+            },
+            Exp::Break { name, value } => {
+                let value = self.eval(value)?;
+                Err(Exit::Break(name, value))
+            }
+            Exp::Object { properties } => {
+                let mut fields = Vec::with_capacity(properties.len());
+                for (k, v) in properties.iter() {
+                    let key: &'a str = self.arena.alloc_str(k);
+                    fields.push((key, self.eval(v)?));
+                }
+                Ok(Dyn::object_with(self.arena, fields))
+            }
+            Exp::Array { exps } => {
+                let values = self.eval_exps(exps)?;
+                Ok(Dyn::vec_with(self.arena, values))
+            }
+            Exp::Index { e1, e2 } => {
+                let v1 = self.eval(e1)?;
+                let v2 = self.eval(e2)?;
+                Ok(self.at(v1.index(self.arena, v2))?)
+            }
+            Exp::Ref { e } => {
+                let value = self.eval(e)?;
+                Ok(Dyn::ref_(self.arena, value))
+            }
+            Exp::Deref { e } => {
+                let value = self.eval(e)?;
+                Ok(Dyn::deref(value))
+            }
+            Exp::SetRef { e1, e2 } => {
+                let v1 = self.eval(e1)?;
+                let v2 = self.eval(e2)?;
+                Ok(self.at(v1.setref(v2))?)
+            }
+            Exp::PrimApp { event, event_args } => {
+                let args = self.eval_exps(event_args)?;
+                match (event.as_str(), args.as_slice()) {
+                    ("console.log", args) => {
+                        let args: Vec<String> = args.iter().map(|v| format!("{:?}", v)).collect();
+                        eprintln!("{}", args.join(" "));
+                        Ok(Dyn::undef())
+                    }
+                    ("send", [value]) => {
+                        let result = self.ec.send(*value);
+                        Ok(self.at(result)?)
+                    }
+                    (event, args) => match BUILTINS.iter().find(|(name, _)| *name == event) {
+                        Some((_, builtin)) => {
+                            let result = builtin(self.arena, args, self.ec);
+                            Ok(self.at(result)?)
+                        }
+                        None => Ok(self.at(not_a_function(event))?),
+                    },
+                }
+            }
+            Exp::MethodCall {
+                e,
+                method,
+                method_call_args,
+            } => {
+                let receiver = self.eval(e)?;
+                let args = self.eval_exps(method_call_args)?;
+                let arena = self.arena;
+                let result = method_call(arena, receiver, method, &args, &mut |cbid, cbargs| {
+                    self.invoke(cbid, cbargs)
+                });
+                Ok(self.at(result)?)
+            }
+            Exp::Throw { e } => {
+                let value = self.eval(e)?;
+                Ok(self.at(throw(value))?)
+            }
+            Exp::Try {
+                body,
+                catch_name,
+                handler,
+                finalizer,
+            } => self.eval_try(body, catch_name, handler, finalizer),
+            Exp::Located { loc, e } => {
+                let outer = self.loc.replace(loc);
+                let result = self.eval(e);
+                self.loc = outer;
+                result
+            }
+            Exp::From { .. }
+            | Exp::Set {
+                name: LVal::From { .. },
+                ..
+            }
+            | Exp::Callback { .. }
+            | Exp::Lambda { .. }
+            | Exp::Clos { .. }
+            | Exp::Unboxed { .. }
+            | Exp::Boxed { .. }
+            | Exp::Guard { .. } => Ok(type_error(format!(
+                "unexpected node in lifted IR: {:?}",
+                exp
+            ))?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::trace::syntax;
+
+    /// Runs the lifted IR `text` as the first callback, and returns the JSON
+    /// of the response, or the error.
+    fn run(text: &str) -> (Result<(), String>, Option<String>) {
+        let exp = syntax::parse(text).unwrap();
+        let program = Program::from_json(&serde_json::to_string(&exp).unwrap()).unwrap();
+        let arena = Bump::new();
+        let mut ec = ExecutionContext::new(&arena, 1_000_000);
+        let result = program
+            .containerless(&arena, &mut ec, Dyn::int(0), Dyn::undef())
+            .map(|_| ())
+            .map_err(|err| err.to_string());
+        let response = ec
+            .response
+            .map(|value| value.to_json().unwrap().to_string());
+        return (result, response);
+    }
+
+    fn response(text: &str) -> String {
+        match run(text) {
+            (Ok(()), Some(response)) => return response,
+            other => panic!("expected a response, got {:?}", other),
+        }
+    }
+
+    fn error(text: &str) -> String {
+        match run(text) {
+            (Err(err), _) => return err,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn break_leaves_the_label_with_its_value() {
+        let text = r#"(block [
+          (let x (label outer [
+            (label "'inner" [
+              (break outer 1.0)
+              (primApp send "unreachable")
+            ])
+            2.0
+          ]))
+          (let y (label "'ret" [
+            (break ret 3.0)
+          ]))
+          (let i 0.0)
+          (label loop [
+            (while true [
+              (if (=== i 3.0) [(break loop unit)] [])
+              (set i (+ i 1.0))
+            ])
+          ])
+          (primApp send (array x y i))
+        ])"#;
+        assert_eq!(response(text), "[1.0,3.0,3.0]");
+        assert_eq!(
+            error("(break nowhere 1.0)"),
+            "Error::TypeError(break to unknown label nowhere)"
+        );
+    }
+
+    #[test]
+    fn finally_runs_after_break_and_throw() {
+        let text = r#"(block [
+          (let log (array))
+          (let r (label l [
+            (try [(break l 1.0)] (finally [(methodCall log push "finally")]))
+            2.0
+          ]))
+          (try [(throw "boom")]
+            (catch e [(methodCall log push (deref e))])
+            (finally [(methodCall log push "finally 2")]))
+          (primApp send (array r log))
+        ])"#;
+        assert_eq!(response(text), r#"[1.0,["finally","boom","finally 2"]]"#);
+    }
+
+    #[test]
+    fn exceptions_and_type_errors_leave_try() {
+        // An exception in the handler propagates after the finalizer.
+        let text = r#"(try [(throw 1.0)]
+          (catch [(throw 2.0)])
+          (finally [(primApp send "finally")]))"#;
+        assert_eq!(
+            run(text),
+            (
+                Err("Error::Thrown(Float(2.0))".to_string()),
+                Some("\"finally\"".to_string())
+            )
+        );
+        // A type error aborts the trace, without running the handler or the
+        // finalizer.
+        let text = r#"(try [(get undefined x)]
+          (catch [(primApp send "caught")])
+          (finally [(primApp send "finally")]))"#;
+        assert_eq!(run(text), (Err("Error::Unknown".to_string()), None));
+    }
+
+    #[test]
+    fn calls_run_the_body_of_the_closure() {
+        let text = r#"(if (=== arg_cbid 1) [
+          (let clos (index arg_cbargs 0))
+          (let x (index arg_cbargs 1))
+          (label "'ret" [
+            (break "'ret" (* x (get clos k)))
+          ])
+        ] [
+          (let f (closure (object (k 3.0)) 1))
+          (primApp send (array (call f 5.0) (methodCall (array 1.0 2.0) map f)))
+        ])"#;
+        assert_eq!(response(text), "[15.0,[3.0,6.0]]");
+        assert_eq!(
+            error("(call 1.0 2.0)"),
+            "Error::TypeError(1 is not a function)"
+        );
+    }
+
+    #[test]
+    fn type_errors_report_the_innermost_location() {
+        assert_eq!(
+            error(r#"(located "f.js" 3 4 (get undefined x))"#),
+            "Error::TypeError(f.js:3:4: Undefined is not an object)"
+        );
+        // The location of an inner expression does not apply after it.
+        let text = r#"(located "f.js" 1 2 (block [
+          (let y (located "f.js" 5 6 (+ 1.0 2.0)))
+          (get null y)
+        ]))"#;
+        assert_eq!(
+            error(text),
+            "Error::TypeError(f.js:1:2: Null is not an object)"
+        );
+        assert_eq!(
+            error("(get undefined x)"),
+            "Error::TypeError(Undefined is not an object)"
+        );
+    }
+
+    #[test]
+    fn prim_apps_call_builtins() {
+        let text = r#"(primApp send (array
+          (primApp "Math.max" 1.0 5.0 3.0)
+          (primApp parseInt "42px")
+          (primApp "JSON.stringify" (object (a 1.0)))))"#;
+        assert_eq!(response(text), r#"[5.0,42.0,"{\"a\":1}"]"#);
+        assert_eq!(
+            error(r#"(located "f.js" 1 1 (primApp frob 1.0))"#),
+            "Error::TypeError(f.js:1:1: frob is not a function.)"
+        );
+    }
+}
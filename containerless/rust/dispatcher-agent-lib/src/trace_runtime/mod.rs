@@ -1,15 +1,22 @@
 //! The runtime system used by the generated Rust code.
 
 mod affine_ref;
+mod arrays;
+pub mod builtins;
 mod common;
 pub mod decontainer;
 pub mod error;
 pub mod execution_context;
+pub mod interpreter;
+pub mod shared_library;
+mod strings;
 pub mod type_dynamic;
 
 pub use decontainer::*;
 pub use error::*;
 pub use execution_context::*;
+pub use interpreter::*;
+pub use shared_library::*;
 pub use type_dynamic::*;
 
 pub type Containerless = for<'a> fn(
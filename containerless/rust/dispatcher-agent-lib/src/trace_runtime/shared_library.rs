@@ -0,0 +1,146 @@
+//! Loads decontainerized functions at runtime. The controller compiles the
+//! trace of each function to its own shared library, which exports
+//! `ENTRY_POINT` with the `ContainerlessEntry` signature. `Dyn` does not have
+//! a stable layout, so a library only works with a dispatcher that was built
+//! by the same toolchain, from the same sources of this crate and the same
+//! versions and features of its dependencies. The library also exports
+//! `FINGERPRINT_SYMBOL`, which returns the `fingerprint` of the copy of this
+//! crate that it links, and `SharedLibrary::load` refuses a library whose
+//! fingerprint differs from its own.
+//!
+//! The generated code forbids `unsafe_code`, which covers `no_mangle`, so it
+//! exports these symbols with `export_containerless!`.
+use super::error::*;
+use super::execution_context::ExecutionContext;
+use super::type_dynamic::*;
+use bumpalo::Bump;
+use lazy_static::lazy_static;
+use std::any::TypeId;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::Path;
+
+/// The name of the symbol that the generated code exports.
+pub const ENTRY_POINT: &[u8] = b"containerless_entry";
+
+/// The name of the function that returns the fingerprint of a library.
+pub const FINGERPRINT_SYMBOL: &[u8] = b"containerless_fingerprint";
+
+lazy_static! {
+    static ref FINGERPRINT: CString = {
+        // Cargo does not tell build.rs which features of the dependencies
+        // are enabled, and they depend on the other packages in the build,
+        // e.g. `serde_json/preserve_order`. But cargo hashes them into the
+        // metadata of this crate, which is part of every `TypeId` that it
+        // defines.
+        let types = TypeId::of::<(
+            ExecutionContext<'static>,
+            Dyn<'static>,
+            DynResult<'static>,
+            Bump,
+        )>();
+        let fingerprint = format!(
+            "{} {:?}",
+            env!("CONTAINERLESS_BUILD_FINGERPRINT"),
+            types
+        );
+        CString::new(fingerprint).unwrap()
+    };
+}
+
+/// Determines the layouts of the arguments of `ContainerlessEntry`: the
+/// version of rustc, a hash of the sources of this crate, of its entries in
+/// Cargo.lock and of its features (see build.rs), and the `TypeId` of the
+/// arguments, which covers the features of the dependencies.
+pub fn fingerprint() -> &'static CStr {
+    return FINGERPRINT.as_c_str();
+}
+
+/// The generated code wraps its `containerless` function in this signature.
+/// The entry point stores its result in the last argument, and turns panics
+/// into errors, since unwinding across an `extern "C"` function is undefined
+/// behavior.
+#[allow(improper_ctypes_definitions)]
+pub type ContainerlessEntry = for<'a> extern "C" fn(
+    arena: &'a Bump,
+    ec: &mut ExecutionContext<'a>,
+    arg_cbid: Dyn<'a>,
+    arg_cbargs: Dyn<'a>,
+    result: &mut DynResult<'a>,
+);
+
+type Fingerprint = extern "C" fn() -> *const c_char;
+
+/// Exports a function with the `Containerless` signature from the shared
+/// library of a decontainerized function, with the symbols that
+/// `SharedLibrary::load` expects.
+#[macro_export]
+macro_rules! export_containerless {
+    ($containerless:path) => {
+        #[no_mangle]
+        pub extern "C" fn containerless_fingerprint() -> *const std::os::raw::c_char {
+            return $crate::trace_runtime::fingerprint().as_ptr();
+        }
+
+        #[no_mangle]
+        #[allow(improper_ctypes_definitions)]
+        pub extern "C" fn containerless_entry<'a>(
+            arena: &'a bumpalo::Bump, ec: &mut $crate::trace_runtime::ExecutionContext<'a>,
+            arg_cbid: $crate::trace_runtime::Dyn<'a>, arg_cbargs: $crate::trace_runtime::Dyn<'a>,
+            result: &mut $crate::trace_runtime::DynResult<'a>,
+        ) {
+            *result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                $containerless(arena, ec, arg_cbid, arg_cbargs)
+            }))
+            .unwrap_or_else(|_| {
+                $crate::trace_runtime::type_error("panic in decontainerized function")
+            });
+        }
+    };
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("{0}")]
+    Library(#[from] libloading::Error),
+    #[error("library was built by {found}, but the dispatcher was built by {expected}")]
+    Fingerprint { expected: String, found: String },
+}
+
+pub struct SharedLibrary {
+    entry: ContainerlessEntry,
+    // `entry` points into the library, so the library must outlive it.
+    _library: libloading::Library,
+}
+
+impl SharedLibrary {
+    pub fn load(path: &Path) -> Result<SharedLibrary, LoadError> {
+        // Loading a library runs its initializers, and calling `entry` is only
+        // safe if the library was built against this crate (see above).
+        let library = unsafe { libloading::Library::new(path)? };
+        let library_fingerprint = unsafe { *library.get::<Fingerprint>(FINGERPRINT_SYMBOL)? };
+        // The fingerprint is a C string in the static data of the library.
+        let found = unsafe { CStr::from_ptr(library_fingerprint()) };
+        let expected = fingerprint();
+        if found != expected {
+            return Err(LoadError::Fingerprint {
+                expected: expected.to_string_lossy().into_owned(),
+                found: found.to_string_lossy().into_owned(),
+            });
+        }
+        let entry = unsafe { *library.get::<ContainerlessEntry>(ENTRY_POINT)? };
+        return Ok(SharedLibrary {
+            entry,
+            _library: library,
+        });
+    }
+
+    pub fn containerless<'a>(
+        &self, arena: &'a Bump, ec: &mut ExecutionContext<'a>, arg_cbid: Dyn<'a>,
+        arg_cbargs: Dyn<'a>,
+    ) -> DynResult<'a> {
+        let mut result = type_error("shared library did not produce a result");
+        (self.entry)(arena, ec, arg_cbid, arg_cbargs, &mut result);
+        return result;
+    }
+}
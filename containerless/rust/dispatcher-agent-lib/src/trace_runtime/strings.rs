@@ -0,0 +1,445 @@
+//! The methods of `String.prototype` that the generated code may call on a
+//! `Dyn::Str`.
+//!
+//! A JavaScript string is a sequence of UTF-16 code units, and its indices
+//! and its length count code units, whereas a Rust `String` is UTF-8. So,
+//! these methods index the UTF-16 encoding of a string. A substring that
+//! splits a surrogate pair holds the replacement character in place of the
+//! lone surrogate, which a `String` cannot hold.
+//!
+//! `slice`, `concat`, `indexOf` and `includes` are also methods of arrays,
+//! so they call the methods in `arrays.rs` when the receiver is a
+//! `Dyn::Vec`.
+use super::arrays;
+use super::error::{not_a_function, type_error, Error};
+use super::type_dynamic::{Dyn, DynResult};
+use bumpalo::Bump;
+
+/// The length of the longest string that a method may produce. JavaScript
+/// allows longer strings, but they could exhaust the memory of the
+/// dispatcher before we charge for them.
+pub(super) const MAX_LENGTH: usize = 1 << 24;
+
+/// The length of `s` in UTF-16 code units, which is `s.length` in JavaScript.
+pub fn utf16_len(s: &str) -> usize {
+    if s.is_ascii() {
+        return s.len();
+    }
+    return s.encode_utf16().count();
+}
+
+/// The UTF-16 code units of a string. The bytes of an ASCII string are its
+/// code units, so we index the string itself and only encode a string that
+/// is not ASCII. Otherwise, a loop that calls `charAt` on every index of a
+/// string would encode the whole string on every iteration.
+enum Units<'s> {
+    Ascii(&'s [u8]),
+    Utf16(Vec<u16>),
+}
+
+impl<'s> Units<'s> {
+    fn new(s: &'s str) -> Units<'s> {
+        if s.is_ascii() {
+            return Units::Ascii(s.as_bytes());
+        }
+        return Units::Utf16(s.encode_utf16().collect());
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Units::Ascii(bytes) => return bytes.len(),
+            Units::Utf16(units) => return units.len(),
+        }
+    }
+
+    fn get(&self, i: usize) -> u16 {
+        match self {
+            Units::Ascii(bytes) => return u16::from(bytes[i]),
+            Units::Utf16(units) => return units[i],
+        }
+    }
+
+    /// The substring from `start` up to, but not including, `end`.
+    fn substring<'a>(&self, arena: &'a Bump, start: usize, end: usize) -> Dyn<'a> {
+        match self {
+            // An ASCII string is valid UTF-8 at every index.
+            Units::Ascii(bytes) => {
+                return Dyn::str(arena, std::str::from_utf8(&bytes[start..end]).unwrap())
+            }
+            Units::Utf16(units) => {
+                return Dyn::str(arena, &String::from_utf16_lossy(&units[start..end]))
+            }
+        }
+    }
+
+    /// Whether `needle` occurs at index `i`, which must leave room for it.
+    fn matches_at(&self, needle: &Units, i: usize) -> bool {
+        match (self, needle) {
+            (Units::Ascii(h), Units::Ascii(n)) => return h[i..i + n.len()] == **n,
+            (Units::Utf16(h), Units::Utf16(n)) => return h[i..i + n.len()] == **n,
+            _ => return (0..needle.len()).all(|j| self.get(i + j) == needle.get(j)),
+        }
+    }
+
+    /// The first index at or after `from` where `needle` occurs.
+    fn find(&self, needle: &Units, from: usize) -> Option<usize> {
+        if needle.len() > self.len() {
+            return None;
+        }
+        return (from..=self.len() - needle.len()).find(|i| self.matches_at(needle, *i));
+    }
+}
+
+/// The string that receives a call to `method`.
+fn receiver<'a>(value: &Dyn<'a>, method: &str) -> Result<&'a str, Error<'a>> {
+    match value {
+        Dyn::Str(s) => return Ok(s.as_str()),
+        _ => return not_a_function(method),
+    }
+}
+
+/// Converts to an integer, like `ToIntegerOrInfinity` in JavaScript.
+pub(super) fn to_integer(value: Dyn) -> f64 {
+    let x = value.to_number();
+    if x.is_nan() {
+        return 0.0;
+    }
+    return x.trunc();
+}
+
+/// A position in a string of length `len`, clamped to the string.
+fn position(value: Dyn, len: usize) -> usize {
+    return to_integer(value).max(0.0).min(len as f64) as usize;
+}
+
+/// The end of a range in a string of length `len`, which is the end of the
+/// string when the argument is omitted.
+fn end_position(value: Dyn, len: usize) -> usize {
+    match value {
+        Dyn::Undefined => return len,
+        _ => return position(value, len),
+    }
+}
+
+/// A position for `slice`, which counts from the end of the string when it
+/// is negative.
+pub(super) fn relative_position(value: Dyn, len: usize) -> usize {
+    let x = to_integer(value);
+    if x < 0.0 {
+        return (len as f64 + x).max(0.0) as usize;
+    }
+    return x.min(len as f64) as usize;
+}
+
+/// The replacement for a match in `replace`, where `$$`, `$&`, `` $` `` and
+/// `$'` stand for `$`, the match, and the parts of the string before and
+/// after the match.
+fn substitute(replacement: &str, matched: &str, before: &str, after: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        let substitution = match (c, chars.peek()) {
+            ('$', Some('$')) => "$",
+            ('$', Some('&')) => matched,
+            ('$', Some('`')) => before,
+            ('$', Some('\'')) => after,
+            _ => {
+                result.push(c);
+                continue;
+            }
+        };
+        result.push_str(substitution);
+        chars.next();
+    }
+    return result;
+}
+
+impl<'a> Dyn<'a> {
+    #[allow(non_snake_case)]
+    pub fn charAt(&self, arena: &'a Bump, pos: Dyn<'a>) -> DynResult<'a> {
+        let units = Units::new(receiver(self, "charAt")?);
+        let i = to_integer(pos);
+        if i < 0.0 || i >= units.len() as f64 {
+            return Ok(Dyn::str(arena, ""));
+        }
+        return Ok(units.substring(arena, i as usize, i as usize + 1));
+    }
+
+    #[allow(non_snake_case)]
+    pub fn charCodeAt(&self, _arena: &'a Bump, pos: Dyn<'a>) -> DynResult<'a> {
+        let units = Units::new(receiver(self, "charCodeAt")?);
+        let i = to_integer(pos);
+        if i < 0.0 || i >= units.len() as f64 {
+            return Ok(Dyn::float(f64::NAN));
+        }
+        return Ok(Dyn::float(f64::from(units.get(i as usize))));
+    }
+
+    pub fn concat(&self, arena: &'a Bump, value: Dyn<'a>) -> DynResult<'a> {
+        if let Dyn::Vec(v) = self {
+            return Ok(arrays::concat(arena, *v, value));
+        }
+        let s = receiver(self, "concat")?;
+        return Ok(Dyn::str(arena, &(s.to_string() + &value.to_string())));
+    }
+
+    #[allow(non_snake_case)]
+    pub fn endsWith(&self, _arena: &'a Bump, search: Dyn<'a>, end: Dyn<'a>) -> DynResult<'a> {
+        let units = Units::new(receiver(self, "endsWith")?);
+        let end = end_position(end, units.len());
+        let search = search.to_string();
+        let search = Units::new(&search);
+        return Ok(Dyn::Bool(
+            search.len() <= end && units.matches_at(&search, end - search.len()),
+        ));
+    }
+
+    pub fn includes(&self, _arena: &'a Bump, search: Dyn<'a>, from: Dyn<'a>) -> DynResult<'a> {
+        if let Dyn::Vec(v) = self {
+            return Ok(arrays::includes(*v, search, from));
+        }
+        let units = Units::new(receiver(self, "includes")?);
+        let from = position(from, units.len());
+        let search = search.to_string();
+        return Ok(Dyn::Bool(units.find(&Units::new(&search), from).is_some()));
+    }
+
+    #[allow(non_snake_case)]
+    pub fn indexOf(&self, _arena: &'a Bump, search: Dyn<'a>, from: Dyn<'a>) -> DynResult<'a> {
+        if let Dyn::Vec(v) = self {
+            return Ok(arrays::index_of(*v, search, from));
+        }
+        let units = Units::new(receiver(self, "indexOf")?);
+        let from = position(from, units.len());
+        let search = search.to_string();
+        match units.find(&Units::new(&search), from) {
+            Some(i) => return Ok(Dyn::float(i as f64)),
+            None => return Ok(Dyn::float(-1.0)),
+        }
+    }
+
+    #[allow(non_snake_case)]
+    pub fn padStart(&self, arena: &'a Bump, length: Dyn<'a>, fill: Dyn<'a>) -> DynResult<'a> {
+        let s = receiver(self, "padStart")?;
+        let len = utf16_len(s);
+        let length = to_integer(length);
+        let fill = match fill {
+            Dyn::Undefined => vec![u16::from(b' ')],
+            _ => fill.to_string().encode_utf16().collect(),
+        };
+        if length <= len as f64 || fill.is_empty() {
+            return Ok(*self);
+        }
+        if length > MAX_LENGTH as f64 {
+            return type_error(format!("invalid string length {}", length));
+        }
+        let padding = fill
+            .iter()
+            .cycle()
+            .take(length as usize - len)
+            .copied()
+            .collect::<Vec<u16>>();
+        // The padding may end in half of a surrogate pair, but `s` cannot
+        // begin with the other half, so we may convert them separately.
+        return Ok(Dyn::str(arena, &(String::from_utf16_lossy(&padding) + s)));
+    }
+
+    /// Replaces the first occurrence of a string. The pattern cannot be a
+    /// regular expression, and the replacement cannot be a function.
+    pub fn replace(
+        &self, arena: &'a Bump, pattern: Dyn<'a>, replacement: Dyn<'a>,
+    ) -> DynResult<'a> {
+        let s = receiver(self, "replace")?;
+        let pattern = pattern.to_string();
+        match s.find(&pattern) {
+            None => return Ok(*self),
+            Some(i) => {
+                let (before, after) = (&s[..i], &s[i + pattern.len()..]);
+                let replacement = substitute(&replacement.to_string(), &pattern, before, after);
+                return Ok(Dyn::str(
+                    arena,
+                    &(before.to_string() + &replacement + after),
+                ));
+            }
+        }
+    }
+
+    pub fn slice(&self, arena: &'a Bump, start: Dyn<'a>, end: Dyn<'a>) -> DynResult<'a> {
+        if let Dyn::Vec(v) = self {
+            return Ok(arrays::slice(arena, *v, start, end));
+        }
+        let units = Units::new(receiver(self, "slice")?);
+        let start = relative_position(start, units.len());
+        let end = match end {
+            Dyn::Undefined => units.len(),
+            _ => relative_position(end, units.len()),
+        };
+        if start >= end {
+            return Ok(Dyn::str(arena, ""));
+        }
+        return Ok(units.substring(arena, start, end));
+    }
+
+    pub fn split(&self, arena: &'a Bump, separator: Dyn<'a>, limit: Dyn<'a>) -> DynResult<'a> {
+        let s = receiver(self, "split")?;
+        let limit = match limit {
+            Dyn::Undefined => u32::MAX as usize,
+            _ => limit.to_int32() as u32 as usize,
+        };
+        let parts: Vec<Dyn<'a>> = match separator {
+            Dyn::Undefined => std::iter::once(*self).take(limit).collect(),
+            _ => {
+                let separator = separator.to_string();
+                if separator.is_empty() {
+                    s.encode_utf16()
+                        .take(limit)
+                        .map(|unit| Dyn::str(arena, &String::from_utf16_lossy(&[unit])))
+                        .collect()
+                } else {
+                    s.split(separator.as_str())
+                        .take(limit)
+                        .map(|part| Dyn::str(arena, part))
+                        .collect()
+                }
+            }
+        };
+        return Ok(Dyn::vec_with(arena, parts));
+    }
+
+    #[allow(non_snake_case)]
+    pub fn startsWith(&self, _arena: &'a Bump, search: Dyn<'a>, from: Dyn<'a>) -> DynResult<'a> {
+        let units = Units::new(receiver(self, "startsWith")?);
+        let from = position(from, units.len());
+        let search = search.to_string();
+        let search = Units::new(&search);
+        return Ok(Dyn::Bool(
+            from + search.len() <= units.len() && units.matches_at(&search, from),
+        ));
+    }
+
+    pub fn substring(&self, arena: &'a Bump, start: Dyn<'a>, end: Dyn<'a>) -> DynResult<'a> {
+        let units = Units::new(receiver(self, "substring")?);
+        let start = position(start, units.len());
+        let end = end_position(end, units.len());
+        return Ok(units.substring(arena, start.min(end), start.max(end)));
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toLowerCase(&self, arena: &'a Bump) -> DynResult<'a> {
+        let s = receiver(self, "toLowerCase")?;
+        return Ok(Dyn::str(arena, &s.to_lowercase()));
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toUpperCase(&self, arena: &'a Bump) -> DynResult<'a> {
+        let s = receiver(self, "toUpperCase")?;
+        return Ok(Dyn::str(arena, &s.to_uppercase()));
+    }
+
+    pub fn trim(&self, arena: &'a Bump) -> DynResult<'a> {
+        let s = receiver(self, "trim")?;
+        // JavaScript also trims the byte order mark.
+        let trimmed = s.trim_matches(|c: char| c.is_whitespace() || c == '\u{feff}');
+        return Ok(Dyn::str(arena, trimmed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: DynResult) -> String {
+        return value.unwrap().to_string();
+    }
+
+    fn num(value: DynResult) -> f64 {
+        match value {
+            Ok(Dyn::Float(x)) => return x,
+            other => panic!("expected a Float, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn indices_count_utf16_code_units() {
+        let arena = Bump::new();
+        // The emoji is a surrogate pair, so "b" is at index 3.
+        let s = Dyn::str(&arena, "a😀b");
+        assert_eq!(num(s.get("length")), 4.0);
+        assert_eq!(num(s.charCodeAt(&arena, Dyn::float(1.0))), 55357.0);
+        assert_eq!(num(s.charCodeAt(&arena, Dyn::float(2.0))), 56832.0);
+        assert!(num(s.charCodeAt(&arena, Dyn::float(4.0))).is_nan());
+        assert_eq!(
+            num(s.indexOf(&arena, Dyn::str(&arena, "b"), Dyn::Undefined)),
+            3.0
+        );
+        assert_eq!(
+            num(s.indexOf(&arena, Dyn::str(&arena, "c"), Dyn::Undefined)),
+            -1.0
+        );
+        assert_eq!(text(s.charAt(&arena, Dyn::float(3.0))), "b");
+    }
+
+    #[test]
+    fn ascii_strings_and_searches_for_other_strings() {
+        let arena = Bump::new();
+        let s = Dyn::str(&arena, "hello");
+        assert_eq!(num(s.get("length")), 5.0);
+        assert_eq!(num(s.charCodeAt(&arena, Dyn::float(1.0))), 101.0);
+        assert_eq!(text(s.charAt(&arena, Dyn::float(4.0))), "o");
+        assert_eq!(
+            text(s.slice(&arena, Dyn::float(-3.0), Dyn::Undefined)),
+            "llo"
+        );
+        assert_eq!(
+            num(s.indexOf(&arena, Dyn::str(&arena, "l"), Dyn::float(3.0))),
+            3.0
+        );
+        // A string that is not ASCII cannot occur in one that is.
+        assert_eq!(
+            num(s.indexOf(&arena, Dyn::str(&arena, "é"), Dyn::Undefined)),
+            -1.0
+        );
+        let t = Dyn::str(&arena, "é-lo");
+        assert_eq!(
+            num(t.indexOf(&arena, Dyn::str(&arena, "lo"), Dyn::Undefined)),
+            2.0
+        );
+        let ends = t.endsWith(&arena, Dyn::str(&arena, "-"), Dyn::float(2.0));
+        assert_eq!(text(ends), "true");
+        let starts = s.startsWith(&arena, Dyn::str(&arena, "hello!"), Dyn::Undefined);
+        assert_eq!(text(starts), "false");
+    }
+
+    #[test]
+    fn substrings_that_split_a_surrogate_pair() {
+        let arena = Bump::new();
+        let s = Dyn::str(&arena, "a😀b");
+        assert_eq!(
+            text(s.slice(&arena, Dyn::float(1.0), Dyn::float(3.0))),
+            "😀"
+        );
+        assert_eq!(
+            text(s.slice(&arena, Dyn::float(-2.0), Dyn::Undefined)),
+            "\u{fffd}b"
+        );
+        assert_eq!(
+            text(s.substring(&arena, Dyn::float(2.0), Dyn::float(0.0))),
+            "a\u{fffd}"
+        );
+        assert_eq!(text(s.charAt(&arena, Dyn::float(1.0))), "\u{fffd}");
+        let parts = s.split(&arena, Dyn::str(&arena, ""), Dyn::Undefined);
+        assert_eq!(num(parts.unwrap().get("length")), 4.0);
+    }
+
+    #[test]
+    fn pad_start_and_search() {
+        let arena = Bump::new();
+        let s = Dyn::str(&arena, "😀");
+        let padded = s.padStart(&arena, Dyn::float(5.0), Dyn::str(&arena, "ab"));
+        assert_eq!(text(padded), "aba😀");
+        let ends = s.endsWith(&arena, Dyn::str(&arena, "😀"), Dyn::Undefined);
+        assert_eq!(text(ends), "true");
+        let starts = s.startsWith(&arena, Dyn::str(&arena, "😀"), Dyn::float(1.0));
+        assert_eq!(text(starts), "false");
+    }
+}
@@ -1,72 +1,204 @@
 //! Dynamic type wrapper.
 
 use super::error::{not_a_function, type_error, Error};
+use super::strings::utf16_len;
 use bumpalo::{
     collections::{String, Vec},
     Bump,
 };
+use hashbrown::{DefaultHashBuilder, HashMap};
 use std::cell::{Cell, RefCell};
 use std::convert::{From, TryFrom};
 
-#[allow(unused)]
 pub fn unknown<'a>() -> DynResult<'a> {
     Err(Error::Unknown)
 }
 
+/// Throws a JavaScript exception.
+pub fn throw<'a>(value: Dyn<'a>) -> DynResult<'a> {
+    Err(Error::Thrown(value))
+}
+
+/// Unboxes a number for the specialized code that the trace compiler
+/// generates. Any other value means that the trace does not cover this
+/// execution.
+pub fn guard_f64<'a>(value: Dyn<'a>) -> Result<f64, Error<'a>> {
+    match value {
+        Dyn::Int(n) => Ok(f64::from(n)),
+        Dyn::Float(x) => Ok(x),
+        _ => Err(Error::Unknown),
+    }
+}
+
+/// Unboxes a boolean. See `guard_f64`.
+pub fn guard_bool<'a>(value: Dyn<'a>) -> Result<bool, Error<'a>> {
+    match value {
+        Dyn::Bool(b) => Ok(b),
+        _ => Err(Error::Unknown),
+    }
+}
+
+/// The conversion from strings to numbers in JavaScript, which is stricter
+/// than `str::parse`. E.g., `Number("inf")` is `NaN`.
+fn string_to_number(s: &str) -> f64 {
+    let s = s.trim();
+    match s {
+        "" => return 0.0,
+        "Infinity" | "+Infinity" => return f64::INFINITY,
+        "-Infinity" => return f64::NEG_INFINITY,
+        _ => (),
+    }
+    if s.starts_with("0x") || s.starts_with("0X") {
+        return u64::from_str_radix(&s[2..], 16)
+            .map(|n| n as f64)
+            .unwrap_or(f64::NAN);
+    }
+    if !s
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-')
+    {
+        return f64::NAN;
+    }
+    return s.parse::<f64>().unwrap_or(f64::NAN);
+}
+
+/// Whether `key` is an array index, which JavaScript orders before the other
+/// keys of an object.
+fn is_array_index(key: &str) -> bool {
+    if key != "0" && (key.starts_with('0') || !key.chars().all(|c| c.is_ascii_digit())) {
+        return false;
+    }
+    return matches!(key.parse::<u32>(), Ok(n) if n != u32::MAX);
+}
+
+/// The number of holes that an assignment may add to the end of an array.
+/// JavaScript arrays may be sparse, but ours are not, and a larger array
+/// could exhaust the memory of the dispatcher before we charge for it.
+const MAX_HOLES: usize = 1 << 16;
+
+/// Converts a key to a property name, like `ToPropertyKey` in JavaScript,
+/// along with its value as an array index, if it is one. A key that is not
+/// a string is converted in the arena.
+fn property_key<'a>(arena: &'a Bump, key: Dyn<'a>) -> Result<(&'a str, Option<usize>), Error<'a>> {
+    let name: &'a str = match key {
+        Dyn::Str(s) => s.as_str(),
+        Dyn::Ref(_) => return type_error(format!("{:?} is not a property key", key)),
+        _ => arena.alloc_str(&key.to_string()),
+    };
+    if is_array_index(name) {
+        return Ok((name, Some(name.parse::<usize>().unwrap())));
+    }
+    return Ok((name, None));
+}
+
+/// The fields of an object. `entries` holds the fields in insertion order, and
+/// `index` maps each key to its position in `entries`. Both live in the arena,
+/// like every other value, and share the key, which the arena owns as well.
+///
+/// Keys are not interned. A key that comes from data is the string itself,
+/// which is not copied, and a key in the generated code is a literal, so an
+/// interner would save little memory. It would have to be reachable from
+/// every `set`, which only has the arena, and it would hash every key just as
+/// `index` does.
+struct Fields<'a> {
+    index: HashMap<&'a str, usize, DefaultHashBuilder, &'a Bump>,
+    entries: Vec<'a, (&'a str, Dyn<'a>)>,
+}
+
+impl<'a> std::fmt::Debug for Fields<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return f
+            .debug_map()
+            .entries(self.entries.iter().map(|(k, v)| (k, v)))
+            .finish();
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct DynObject<'a> {
-    fields: &'a RefCell<Vec<'a, (&'a str, Dyn<'a>)>>,
+    fields: &'a RefCell<Fields<'a>>,
 }
 
 impl<'a> DynObject<'a> {
     pub fn new(arena: &'a Bump) -> DynObject<'a> {
         DynObject {
-            fields: arena.alloc(RefCell::new(Vec::new_in(arena))),
+            fields: arena.alloc(RefCell::new(Fields {
+                index: HashMap::new_in(arena),
+                entries: Vec::new_in(arena),
+            })),
         }
     }
 
-    pub fn from(arena: &'a Bump, fields: std::vec::Vec<(&'static str, Dyn<'a>)>) -> DynObject<'a> {
+    pub fn from(arena: &'a Bump, fields: std::vec::Vec<(&'a str, Dyn<'a>)>) -> DynObject<'a> {
         let obj = Self::new(arena);
         for (k, v) in fields.into_iter() {
-            // TODO(arjun): very naive
             obj.set(k, v);
         }
         return obj;
     }
 
-    pub fn set(&self, key: &'static str, value: Dyn<'a>) {
-        // This is a pretty bad implementation. We are scanning a vector!
-        let mut vec = self.fields.borrow_mut();
-        for (k, v) in vec.iter_mut() {
-            if k.clone() == key.to_string() {
-                *v = value;
-                return;
+    /// Sets the field `key`. The key must live in the arena (or be static),
+    /// so that a key that comes from data, such as the string `word` in
+    /// `counts[word] = n`, is not copied.
+    pub fn set(&self, key: &'a str, value: Dyn<'a>) {
+        let mut fields = self.fields.borrow_mut();
+        let Fields { index, entries } = &mut *fields;
+        match index.get(key) {
+            Some(i) => entries[*i].1 = value,
+            None => {
+                index.insert(key, entries.len());
+                entries.push((key, value));
             }
         }
-        vec.push((key, value));
+    }
+
+    /// The field `key` of this object, without the prototype chain.
+    fn own(&self, key: &str) -> Option<Dyn<'a>> {
+        let fields = self.fields.borrow();
+        return fields.index.get(key).map(|i| fields.entries[*i].1);
     }
 
     pub fn get(&self, key: &str) -> Dyn<'a> {
-        let vec = self.fields.borrow();
-        let mut proto = None;
-        for (k, v) in vec.iter() {
-            if *k == key {
-                return *v;
-            }
-            if *k == "__proto__" {
-                proto = Some(*v);
-            }
+        if let Some(value) = self.own(key) {
+            return value;
         }
-        match proto {
+        match self.own("__proto__") {
             Some(Dyn::Object(p)) => return p.get(key),
             _ => return Dyn::Undefined,
         }
     }
 
+    /// The `in` operator, which follows the prototype chain like `get`.
+    pub fn has(&self, key: &str) -> bool {
+        if self.own(key).is_some() {
+            return true;
+        }
+        match self.own("__proto__") {
+            Some(Dyn::Object(p)) => return p.has(key),
+            _ => return false,
+        }
+    }
+
+    pub fn ptr_eq(&self, other: &DynObject<'a>) -> bool {
+        return std::ptr::eq(self.fields, other.fields);
+    }
+
+    /// The fields in the order of JavaScript: the array indices in ascending
+    /// order, followed by the other keys in insertion order.
+    pub fn entries(&self) -> std::vec::Vec<(&'a str, Dyn<'a>)> {
+        let mut entries = self.fields.borrow().entries.to_vec();
+        // The sort is stable, so the other keys stay in insertion order.
+        entries.sort_by_key(|(k, _)| match k.parse::<u32>() {
+            Ok(n) if is_array_index(k) => (false, n),
+            _ => (true, 0),
+        });
+        return entries;
+    }
+
     pub fn to_json(&self) -> serde_json::Value {
         use serde_json::Map;
         let mut map = Map::new();
-        for (k, v) in self.fields.borrow().iter() {
+        for (k, v) in self.entries().into_iter() {
             if let Some(v_json) = v.to_json() {
                 map.insert(k.to_string(), v_json);
             }
@@ -77,7 +209,7 @@ impl<'a> DynObject<'a> {
 
 #[derive(Debug, Copy, Clone)]
 pub struct DynVec<'a> {
-    elems: &'a RefCell<Vec<'a, Dyn<'a>>>,
+    pub(super) elems: &'a RefCell<Vec<'a, Dyn<'a>>>,
 }
 
 impl<'a> DynVec<'a> {
@@ -102,17 +234,61 @@ impl<'a> DynVec<'a> {
         }
     }
 
-    pub fn index(&self, index: i32) -> Dyn<'a> {
-        match usize::try_from(index) {
-            Err(_) => Dyn::Undefined,
-            Ok(index) => {
-                let vec = self.elems.borrow();
-                if index >= vec.len() {
-                    return Dyn::Undefined;
-                }
-                return vec[index];
-            }
+    pub(super) fn len(&self) -> usize {
+        return self.elems.borrow().len();
+    }
+
+    pub fn index(&self, index: usize) -> Dyn<'a> {
+        return self
+            .elems
+            .borrow()
+            .get(index)
+            .copied()
+            .unwrap_or(Dyn::Undefined);
+    }
+
+    /// Sets the element at `index`. Like JavaScript, an index past the end
+    /// grows the array, and the holes are `undefined`.
+    pub fn set(&self, index: usize, value: Dyn<'a>) -> Result<(), Error<'a>> {
+        if index >= self.elems.borrow().len() {
+            self.resize(index + 1)?;
+        }
+        self.elems.borrow_mut()[index] = value;
+        return Ok(());
+    }
+
+    /// Assigns to `length`, which truncates the array or adds holes to it.
+    pub fn set_length(&self, value: Dyn<'a>) -> Result<(), Error<'a>> {
+        let length = value.to_number();
+        if length < 0.0 || length.fract() != 0.0 || length > f64::from(u32::MAX) {
+            return type_error(format!("invalid array length {}", value.to_string()));
         }
+        return self.resize(length as usize);
+    }
+
+    fn resize(&self, length: usize) -> Result<(), Error<'a>> {
+        let mut elems = self.elems.borrow_mut();
+        if length > elems.len() + MAX_HOLES {
+            return type_error(format!(
+                "cannot grow an array of length {} to {}",
+                elems.len(),
+                length
+            ));
+        }
+        elems.resize(length, Dyn::Undefined);
+        return Ok(());
+    }
+
+    /// The `in` operator. The keys of an array are its indices and `length`.
+    pub fn has(&self, key: &str) -> bool {
+        if is_array_index(key) {
+            return key.parse::<usize>().unwrap() < self.elems.borrow().len();
+        }
+        return key == "length";
+    }
+
+    pub fn ptr_eq(&self, other: &DynVec<'a>) -> bool {
+        return std::ptr::eq(self.elems, other.elems);
     }
 
     pub fn push(self, value: Dyn<'a>) {
@@ -139,23 +315,79 @@ impl<'a> DynVec<'a> {
             self.elems
                 .borrow()
                 .iter()
-                .filter_map(|x| x.to_json())
+                // Like JSON.stringify, which writes holes and undefined
+                // elements as null.
+                .map(|x| x.to_json().unwrap_or(Value::Null))
                 .collect(),
         )
     }
 
+    /// Appends the elements to `out`, separated by `sep`, like `join` in
+    /// JavaScript. An element that is `undefined` or `null` is empty, and so
+    /// is an array that contains itself, which would never end. `visiting`
+    /// holds the arrays that enclose this one. Returns `false` and stops
+    /// early if `out` grows longer than `limit`.
+    pub(super) fn join_into(
+        &self, sep: &str, limit: usize, out: &mut std::string::String,
+        visiting: &mut std::vec::Vec<DynVec<'a>>,
+    ) -> bool {
+        if visiting.iter().any(|v| v.ptr_eq(self)) {
+            return true;
+        }
+        visiting.push(*self);
+        let len = self.len();
+        for i in 0..len {
+            if i > 0 {
+                out.push_str(sep);
+            }
+            match self.index(i) {
+                Dyn::Undefined | Dyn::Null => (),
+                Dyn::Vec(v) => {
+                    if !v.join_into(",", limit, out, visiting) {
+                        return false;
+                    }
+                }
+                x => out.push_str(&x.to_string()),
+            }
+            if out.len() > limit {
+                return false;
+            }
+        }
+        visiting.pop();
+        return true;
+    }
+
     pub fn to_string(&self) -> std::string::String {
-        // https://stackoverflow.com/a/42661287
-        return self
-            .elems
-            .borrow()
-            .iter()
-            .fold(std::string::String::new(), |acc, num| {
-                acc + &num.to_string() + ","
-            });
+        let mut out = std::string::String::new();
+        self.join_into(",", usize::MAX, &mut out, &mut vec![]);
+        return out;
     }
 }
 
+/// A function value. `LiftCallbacks` lifts the body of a lambda into the
+/// generated function, like the body of a callback, so a closure is the id of
+/// its body and the closure object, which holds the variables that the body
+/// captures.
+#[derive(Copy, Clone)]
+pub struct DynClosure<'a> {
+    id: i32,
+    clos: &'a Dyn<'a>,
+}
+
+impl<'a> std::fmt::Debug for DynClosure<'a> {
+    // A recursive function captures itself, so we do not print the closure
+    // object.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[Function {}]", self.id)
+    }
+}
+
+/// Runs the body of a closure, given the id of the body and the array of
+/// its arguments, like a loopback. The generated code calls the
+/// `containerless` function recursively, and the interpreter evaluates the
+/// program again.
+pub type Invoke<'a, 'b> = dyn FnMut(Dyn<'a>, Dyn<'a>) -> DynResult<'a> + 'b;
+
 /**
  * This is an implementation of "type dynamic" for traces.
  */
@@ -166,18 +398,24 @@ pub enum Dyn<'a> {
     Bool(bool),
     Str(&'a String<'a>),
     Undefined,
+    Null,
     Ref(&'a Cell<Dyn<'a>>),
     Vec(DynVec<'a>),
     Object(DynObject<'a>),
+    Closure(DynClosure<'a>),
 }
 
-pub type DynResult<'a> = Result<Dyn<'a>, Error>;
+pub type DynResult<'a> = Result<Dyn<'a>, Error<'a>>;
 
 impl<'a> Dyn<'a> {
     pub fn undef() -> Dyn<'a> {
         Dyn::Undefined
     }
 
+    pub fn null() -> Dyn<'a> {
+        Dyn::Null
+    }
+
     /** Wraps an integer in type `Dyn`. */
     pub fn int(n: i32) -> Dyn<'a> {
         Dyn::Int(n)
@@ -200,6 +438,28 @@ impl<'a> Dyn<'a> {
         Dyn::Ref(arena.alloc(Cell::new(value)))
     }
 
+    pub fn closure(arena: &'a Bump, id: i32, clos: Dyn<'a>) -> Dyn<'a> {
+        Dyn::Closure(DynClosure {
+            id,
+            clos: arena.alloc(clos),
+        })
+    }
+
+    /// Calls a function synchronously. The first argument of the body of a
+    /// closure is its closure object, which precedes `args`.
+    pub fn call(
+        &self, arena: &'a Bump, args: &[Dyn<'a>], invoke: &mut Invoke<'a, '_>,
+    ) -> DynResult<'a> {
+        match self {
+            Dyn::Closure(f) => {
+                let mut cbargs = vec![*f.clos];
+                cbargs.extend_from_slice(args);
+                return invoke(Dyn::int(f.id), Dyn::vec_with(arena, cbargs));
+            }
+            _ => return type_error(format!("{} is not a function", self.to_string())),
+        }
+    }
+
     pub fn setref(&self, new_value: Dyn<'a>) -> DynResult<'a> {
         if let Dyn::Ref(cell) = self {
             cell.set(new_value);
@@ -208,27 +468,39 @@ impl<'a> Dyn<'a> {
         return type_error(format!("setref on {:?}", self));
     }
 
-    pub fn set(&mut self, index: Dyn<'a>, new_value: Dyn<'a>) -> DynResult<'a> {
+    /// Assignment to a computed property (`self[key] = value`). Like sloppy
+    /// mode JavaScript, an assignment to a property of a string, number, or
+    /// boolean does nothing. An array has no fields besides its elements and
+    /// `length`, so the container runs an assignment to any other property
+    /// of an array.
+    pub fn set(&self, arena: &'a Bump, key: Dyn<'a>, value: Dyn<'a>) -> DynResult<'a> {
+        let (name, index) = property_key(arena, key)?;
         match (self, index) {
-            (Dyn::Vec(v), Dyn::Float(n)) => {
-                //std::mem::replace(&mut v.elems.borrow_mut()[n as usize], new_value);
-                v.elems.borrow_mut()[n as usize] = new_value;
-                return Ok(Dyn::Undefined);
+            (Dyn::Vec(v), Some(index)) => v.set(index, value)?,
+            (Dyn::Vec(v), None) if name == "length" => v.set_length(value)?,
+            (Dyn::Vec(_), None) => return unknown(),
+            (Dyn::Object(o), _) => o.set(name, value),
+            (Dyn::Undefined, _) | (Dyn::Null, _) | (Dyn::Ref(_), _) | (Dyn::Closure(_), _) => {
+                return type_error(format!(
+                    "cannot set property {} of {}",
+                    name,
+                    self.to_string()
+                ));
             }
-            _ => type_error("Should only use index on a vec!"),
+            (Dyn::Str(_), _) | (Dyn::Int(_), _) | (Dyn::Float(_), _) | (Dyn::Bool(_), _) => (),
         }
+        return Ok(Dyn::Undefined);
     }
 
     pub fn object(arena: &'a Bump) -> Dyn<'a> {
         Dyn::Object(DynObject::new(arena))
     }
 
-    pub fn object_with(arena: &'a Bump, fields: std::vec::Vec<(&'static str, Dyn<'a>)>) -> Dyn<'a> {
+    pub fn object_with(arena: &'a Bump, fields: std::vec::Vec<(&'a str, Dyn<'a>)>) -> Dyn<'a> {
         Dyn::Object(DynObject::from(arena, fields))
     }
 
-    pub fn set_field(&self, key: &'static str, value: Dyn<'a>) -> DynResult<'a> {
-        // This is a pretty bad implementation. We are scanning a vector!
+    pub fn set_field(&self, key: &'a str, value: Dyn<'a>) -> DynResult<'a> {
         if let Dyn::Object(o) = self {
             o.set(key, value);
             return Ok(Dyn::Undefined);
@@ -242,79 +514,147 @@ impl<'a> Dyn<'a> {
             Dyn::Object(o) => Ok(o.get(key)),
             Dyn::Vec(v) => Ok(v.get(key)),
             Dyn::Str(s) => match key {
-                "length" => Ok(Dyn::int(s.len() as i32)),
+                "length" => Ok(Dyn::float(utf16_len(s) as f64)),
                 k => not_a_function(k),
             },
             _ => type_error(format!("{:?} is not an object", self)),
         }
     }
 
+    /// Both operands, when they are numbers. The arithmetic operators work
+    /// on `f64` even when both operands are an `Int`, since JavaScript has no
+    /// integer division and its numbers do not overflow.
+    fn numbers(&self, other: Dyn<'a>) -> Option<(f64, f64)> {
+        match (*self, other) {
+            (Dyn::Int(_), Dyn::Int(_))
+            | (Dyn::Int(_), Dyn::Float(_))
+            | (Dyn::Float(_), Dyn::Int(_))
+            | (Dyn::Float(_), Dyn::Float(_)) => Some((self.to_number(), other.to_number())),
+            _ => None,
+        }
+    }
+
     pub fn add(&self, arena: &'a Bump, other: Dyn<'a>) -> DynResult<'a> {
+        if let Some((x, y)) = self.numbers(other) {
+            return Ok(Dyn::Float(x + y));
+        }
         match (*self, other) {
-            (Dyn::Int(m), Dyn::Int(n)) => Ok(Dyn::Int(m + n)),
-            (Dyn::Float(x), Dyn::Int(n)) => Ok(Dyn::Float(x + f64::from(n))),
-            (Dyn::Int(n), Dyn::Float(x)) => Ok(Dyn::Float(f64::from(n) + x)),
-            (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Float(x + y)),
             (Dyn::Float(x), Dyn::Str(s)) => Ok(Dyn::str(arena, &(x.to_string() + s))),
             (Dyn::Vec(v), Dyn::Str(s)) => Ok(Dyn::str(arena, &(v.to_string() + s))),
             (Dyn::Str(s), Dyn::Vec(v)) => Ok(Dyn::str(arena, &(s.to_string() + &v.to_string()))),
             (Dyn::Str(a), Dyn::Str(b)) => Ok(Dyn::str(arena, &(a.to_string() + b))),
-            (Dyn::Undefined, Dyn::Str(s)) => Ok(Dyn::str(arena, &(self.to_string() + s))),
-            _ => type_error(&format!("({:?}).add({:?})", &self, &other)),
+            (Dyn::Undefined, Dyn::Str(s)) | (Dyn::Null, Dyn::Str(s)) => {
+                Ok(Dyn::str(arena, &(self.to_string() + s)))
+            }
+            _ => type_error(format!("({:?}).add({:?})", self, other)),
         }
     }
 
     pub fn sub(&self, other: Dyn<'a>) -> DynResult<'a> {
-        match (*self, other) {
-            (Dyn::Int(m), Dyn::Int(n)) => Ok(Dyn::Int(m - n)),
-            (Dyn::Float(x), Dyn::Int(n)) => Ok(Dyn::Float(x - f64::from(n))),
-            (Dyn::Int(n), Dyn::Float(x)) => Ok(Dyn::Float(f64::from(n) - x)),
-            (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Float(x - y)),
-            _ => type_error(&format!("({:?}).sub({:?})", &self, &other)),
+        match self.numbers(other) {
+            Some((x, y)) => Ok(Dyn::Float(x - y)),
+            None => type_error(format!("({:?}).sub({:?})", self, other)),
         }
     }
 
     pub fn mul(&self, other: Dyn<'a>) -> DynResult<'a> {
-        match (*self, other) {
-            (Dyn::Int(m), Dyn::Int(n)) => Ok(Dyn::Int(m * n)),
-            (Dyn::Float(x), Dyn::Int(n)) => Ok(Dyn::Float(x * f64::from(n))),
-            (Dyn::Int(n), Dyn::Float(x)) => Ok(Dyn::Float(f64::from(n) * x)),
-            (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Float(x * y)),
-            _ => type_error(&format!("({:?}).mul({:?})", &self, &other)),
+        match self.numbers(other) {
+            Some((x, y)) => Ok(Dyn::Float(x * y)),
+            None => type_error(format!("({:?}).mul({:?})", self, other)),
         }
     }
 
     pub fn div(&self, other: Dyn<'a>) -> DynResult<'a> {
-        match (*self, other) {
-            (Dyn::Int(m), Dyn::Int(n)) => Ok(Dyn::Int(m / n)),
-            (Dyn::Float(x), Dyn::Int(n)) => Ok(Dyn::Float(x / f64::from(n))),
-            (Dyn::Int(n), Dyn::Float(x)) => Ok(Dyn::Float(f64::from(n) / x)),
-            (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Float(x / y)),
-            _ => type_error(&format!("({:?}).div({:?})", &self, &other)),
+        match self.numbers(other) {
+            Some((x, y)) => Ok(Dyn::Float(x / y)),
+            None => type_error(format!("({:?}).div({:?})", self, other)),
+        }
+    }
+
+    pub fn rem(&self, other: Dyn<'a>) -> DynResult<'a> {
+        match self.numbers(other) {
+            // Like JavaScript, Rust takes the sign of the remainder from the
+            // dividend, and the remainder of division by zero is NaN.
+            Some((x, y)) => Ok(Dyn::Float(x % y)),
+            None => type_error(format!("({:?}).rem({:?})", self, other)),
+        }
+    }
+
+    /// The strict equality comparison (`===`) of JavaScript, which
+    /// `indexOf` also uses. Values of different types are not equal, but an
+    /// `Int` and a `Float` are both numbers. Objects, arrays and functions
+    /// are equal only to themselves.
+    pub(super) fn strictly_equals(&self, other: &Dyn<'a>) -> bool {
+        match (*self, *other) {
+            (Dyn::Undefined, Dyn::Undefined) | (Dyn::Null, Dyn::Null) => true,
+            (Dyn::Int(m), Dyn::Int(n)) => m == n,
+            (Dyn::Int(_), Dyn::Float(_))
+            | (Dyn::Float(_), Dyn::Int(_))
+            | (Dyn::Float(_), Dyn::Float(_)) => self.to_number() == other.to_number(),
+            (Dyn::Str(s1), Dyn::Str(s2)) => s1 == s2,
+            (Dyn::Bool(b1), Dyn::Bool(b2)) => b1 == b2,
+            (Dyn::Object(o1), Dyn::Object(o2)) => o1.ptr_eq(&o2),
+            (Dyn::Vec(v1), Dyn::Vec(v2)) => v1.ptr_eq(&v2),
+            (Dyn::Closure(f1), Dyn::Closure(f2)) => std::ptr::eq(f1.clos, f2.clos),
+            _ => false,
         }
     }
 
     pub fn strict_eq(&self, other: Dyn<'a>) -> DynResult<'a> {
         match (*self, other) {
-            (Dyn::Undefined, Dyn::Undefined) => Ok(Dyn::Bool(true)),
-            (Dyn::Undefined, _) => Ok(Dyn::Bool(false)),
-            (_, Dyn::Undefined) => Ok(Dyn::Bool(false)),
-            (Dyn::Int(m), Dyn::Int(n)) => Ok(Dyn::Bool(m == n)),
-            (Dyn::Str(s1), Dyn::Str(s2)) => Ok(Dyn::Bool(s1 == s2)),
-            (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Bool(x == y)),
-            _ => type_error(&format!("({:?}).strict_eq({:?})", &self, &other)),
+            (Dyn::Ref(_), _) | (_, Dyn::Ref(_)) => {
+                type_error(format!("({:?}).strict_eq({:?})", self, other))
+            }
+            _ => Ok(Dyn::Bool(self.strictly_equals(&other))),
         }
     }
 
     pub fn strict_neq(&self, other: Dyn<'a>) -> DynResult<'a> {
         match (*self, other) {
-            (Dyn::Undefined, Dyn::Undefined) => Ok(Dyn::Bool(false)),
-            (Dyn::Undefined, _) => Ok(Dyn::Bool(true)),
-            (_, Dyn::Undefined) => Ok(Dyn::Bool(true)),
-            (Dyn::Int(m), Dyn::Int(n)) => Ok(Dyn::Bool(m != n)),
-            (Dyn::Str(s1), Dyn::Str(s2)) => Ok(Dyn::Bool(s1 != s2)),
-            (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Bool(x != y)),
-            _ => type_error(&format!("({:?}).strict_neq({:?})", &self, &other)),
+            (Dyn::Ref(_), _) | (_, Dyn::Ref(_)) => {
+                type_error(format!("({:?}).strict_neq({:?})", self, other))
+            }
+            _ => Ok(Dyn::Bool(!self.strictly_equals(&other))),
+        }
+    }
+
+    /// The abstract equality comparison (`==`) of JavaScript. `null` and
+    /// `undefined` are equal to each other and to nothing else. Objects and
+    /// arrays are equal only to themselves, or to a primitive that is equal
+    /// to their string representation. Any other pair of values of different
+    /// types is compared as numbers.
+    pub fn loose_eq(&self, other: Dyn<'a>) -> DynResult<'a> {
+        let result = match (*self, other) {
+            (Dyn::Undefined, Dyn::Undefined)
+            | (Dyn::Undefined, Dyn::Null)
+            | (Dyn::Null, Dyn::Undefined)
+            | (Dyn::Null, Dyn::Null) => true,
+            (Dyn::Undefined, _) | (Dyn::Null, _) => false,
+            (_, Dyn::Undefined) | (_, Dyn::Null) => false,
+            (Dyn::Str(s1), Dyn::Str(s2)) => s1 == s2,
+            (Dyn::Bool(b1), Dyn::Bool(b2)) => b1 == b2,
+            (Dyn::Object(o1), Dyn::Object(o2)) => o1.ptr_eq(&o2),
+            (Dyn::Vec(v1), Dyn::Vec(v2)) => v1.ptr_eq(&v2),
+            (Dyn::Closure(f1), Dyn::Closure(f2)) => std::ptr::eq(f1.clos, f2.clos),
+            (Dyn::Closure(_), _) | (_, Dyn::Closure(_)) => false,
+            (Dyn::Object(_), Dyn::Vec(_)) => false,
+            (Dyn::Vec(_), Dyn::Object(_)) => false,
+            (Dyn::Object(_), Dyn::Str(s)) => self.to_string() == s.as_str(),
+            (Dyn::Vec(_), Dyn::Str(s)) => self.to_string() == s.as_str(),
+            (Dyn::Str(s), Dyn::Object(_)) => s.as_str() == other.to_string(),
+            (Dyn::Str(s), Dyn::Vec(_)) => s.as_str() == other.to_string(),
+            (Dyn::Ref(_), _) | (_, Dyn::Ref(_)) => {
+                return type_error(format!("({:?}).loose_eq({:?})", self, other));
+            }
+            _ => self.to_number() == other.to_number(),
+        };
+        return Ok(Dyn::Bool(result));
+    }
+
+    pub fn loose_neq(&self, other: Dyn<'a>) -> DynResult<'a> {
+        match self.loose_eq(other)? {
+            Dyn::Bool(b) => Ok(Dyn::Bool(!b)),
+            _ => unreachable!(),
         }
     }
 
@@ -324,7 +664,7 @@ impl<'a> Dyn<'a> {
             (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Bool(x > y)),
             (Dyn::Int(m), Dyn::Float(n)) => Ok(Dyn::Bool((m as f64) > n)),
             (Dyn::Float(m), Dyn::Int(n)) => Ok(Dyn::Bool(m > (n as f64))),
-            _ => type_error(&format!("({:?}).gt({:?})", &self, &other)),
+            _ => type_error(format!("({:?}).gt({:?})", self, other)),
         }
     }
 
@@ -344,7 +684,7 @@ impl<'a> Dyn<'a> {
             (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Bool(x >= y)),
             (Dyn::Int(m), Dyn::Float(n)) => Ok(Dyn::Bool((m as f64) >= n)),
             (Dyn::Float(m), Dyn::Int(n)) => Ok(Dyn::Bool(m >= (n as f64))),
-            _ => type_error(&format!("({:?}).gte({:?})", &self, &other)),
+            _ => type_error(format!("({:?}).gte({:?})", self, other)),
         }
     }
 
@@ -354,34 +694,94 @@ impl<'a> Dyn<'a> {
             (Dyn::Float(x), Dyn::Float(y)) => Ok(Dyn::Bool(x <= y)),
             (Dyn::Int(m), Dyn::Float(n)) => Ok(Dyn::Bool((m as f64) <= n)),
             (Dyn::Float(m), Dyn::Int(n)) => Ok(Dyn::Bool(m <= (n as f64))),
-            _ => type_error(&format!("({:?}).lte({:?})", &self, &other)),
+            _ => type_error(format!("({:?}).lte({:?})", self, other)),
         }
     }
 
     pub fn and(&self, other: Dyn<'a>) -> DynResult<'a> {
         match (*self, other) {
             (Dyn::Bool(x), Dyn::Bool(y)) => Ok(Dyn::Bool(x && y)),
-            _ => type_error(&format!("({:?}).and({:?})", &self, &other)),
+            _ => type_error(format!("({:?}).and({:?})", self, other)),
         }
     }
 
     pub fn or(&self, other: Dyn<'a>) -> DynResult<'a> {
         match (*self, other) {
             (Dyn::Bool(x), Dyn::Bool(y)) => Ok(Dyn::Bool(x || y)),
-            _ => type_error(&format!("({:?}).or({:?})", &self, &other)),
+            _ => type_error(format!("({:?}).or({:?})", self, other)),
+        }
+    }
+
+    pub fn bit_and(&self, other: Dyn<'a>) -> DynResult<'a> {
+        return Ok(Dyn::float(f64::from(self.to_int32() & other.to_int32())));
+    }
+
+    pub fn bit_or(&self, other: Dyn<'a>) -> DynResult<'a> {
+        return Ok(Dyn::float(f64::from(self.to_int32() | other.to_int32())));
+    }
+
+    pub fn bit_xor(&self, other: Dyn<'a>) -> DynResult<'a> {
+        return Ok(Dyn::float(f64::from(self.to_int32() ^ other.to_int32())));
+    }
+
+    // NOTE(arjun): The shift operators only use the five lowest bits of the
+    // shift amount, which is exactly what wrapping_shl and wrapping_shr do.
+    pub fn shl(&self, other: Dyn<'a>) -> DynResult<'a> {
+        let n = self.to_int32().wrapping_shl(other.to_int32() as u32);
+        return Ok(Dyn::float(f64::from(n)));
+    }
+
+    pub fn shr(&self, other: Dyn<'a>) -> DynResult<'a> {
+        let n = self.to_int32().wrapping_shr(other.to_int32() as u32);
+        return Ok(Dyn::float(f64::from(n)));
+    }
+
+    pub fn ushr(&self, other: Dyn<'a>) -> DynResult<'a> {
+        let n = (self.to_int32() as u32).wrapping_shr(other.to_int32() as u32);
+        return Ok(Dyn::float(f64::from(n)));
+    }
+
+    /// The nullish coalescing operator (`??`). Both operands are evaluated.
+    pub fn nullish(&self, other: Dyn<'a>) -> DynResult<'a> {
+        match self {
+            Dyn::Undefined | Dyn::Null => Ok(other),
+            _ => Ok(*self),
+        }
+    }
+
+    /// The `in` operator: `self` is the key and `other` is the object.
+    pub fn in_(&self, other: Dyn<'a>) -> DynResult<'a> {
+        match other {
+            Dyn::Object(o) => Ok(Dyn::Bool(o.has(&self.to_string()))),
+            Dyn::Vec(v) => Ok(Dyn::Bool(v.has(&self.to_string()))),
+            _ => type_error(format!(
+                "cannot use 'in' operator to search for {:?} in {:?}",
+                self, other
+            )),
         }
     }
 
-    /** Array indexing. */
-    pub fn index(&self, arena: &'a Bump, index: Dyn<'a>) -> DynResult<'a> {
+    /// Reads a computed property (`self[key]`). Missing properties, including
+    /// indices past the end of an array or string, are `undefined`.
+    pub fn index(&self, arena: &'a Bump, key: Dyn<'a>) -> DynResult<'a> {
+        let (name, index) = property_key(arena, key)?;
         match (self, index) {
-            (Dyn::Vec(vec_cell), Dyn::Int(index)) => Ok(vec_cell.index(index)),
-            (Dyn::Vec(vec_cell), Dyn::Float(index)) => Ok(vec_cell.index(index as i32)),
-            (Dyn::Str(s), Dyn::Float(index)) => Ok(Dyn::str(
-                arena,
-                &s.chars().nth(index as usize).unwrap().to_string(),
+            (Dyn::Vec(v), Some(index)) => Ok(v.index(index)),
+            (Dyn::Vec(v), None) => Ok(v.get(name)),
+            (Dyn::Str(s), Some(index)) if index < utf16_len(s) => self.charAt(arena, key),
+            (Dyn::Str(_), Some(_)) => Ok(Dyn::Undefined),
+            (Dyn::Str(_), None) if name == "length" => self.get(name),
+            (Dyn::Object(o), _) => Ok(o.get(name)),
+            (Dyn::Undefined, _) | (Dyn::Null, _) | (Dyn::Ref(_), _) => type_error(format!(
+                "cannot read property {} of {}",
+                name,
+                self.to_string()
             )),
-            _ => type_error(&format!("({:?}).index({:?})", &self, &index)),
+            (Dyn::Str(_), None)
+            | (Dyn::Int(_), _)
+            | (Dyn::Float(_), _)
+            | (Dyn::Bool(_), _)
+            | (Dyn::Closure(_), _) => Ok(Dyn::Undefined),
         }
     }
 
@@ -395,7 +795,7 @@ impl<'a> Dyn<'a> {
     }
 
     /** push an element into a vector. */
-    pub fn push(self, value: Dyn<'a>) -> DynResult<'a> {
+    pub fn push(self, _arena: &'a Bump, value: Dyn<'a>) -> DynResult<'a> {
         match self {
             Dyn::Vec(vec_cell) => {
                 vec_cell.push(value);
@@ -405,22 +805,14 @@ impl<'a> Dyn<'a> {
         }
     }
 
-    pub fn pop(self) -> DynResult<'a> {
+    pub fn pop(self, _arena: &'a Bump) -> DynResult<'a> {
         match self {
             Dyn::Vec(vec_cell) => Ok(vec_cell.pop()),
             _ => not_a_function("pop"),
         }
     }
 
-    #[allow(non_snake_case)]
-    pub fn startsWith(&self, value: Dyn<'a>) -> DynResult<'a> {
-        match (self, value) {
-            (Dyn::Str(s), Dyn::Str(prefix)) => Ok(Dyn::Bool(s.starts_with(prefix.as_str()))),
-            _ => not_a_function("startsWith"),
-        }
-    }
-
-    pub fn shift(self) -> DynResult<'a> {
+    pub fn shift(self, _arena: &'a Bump) -> DynResult<'a> {
         match self {
             Dyn::Vec(v) => Ok(v.shift()),
             _ => not_a_function("shift"),
@@ -447,18 +839,67 @@ impl<'a> Dyn<'a> {
             Dyn::Int(_) => Ok(Dyn::str(arena, "number")),
             Dyn::Float(_) => Ok(Dyn::str(arena, "number")),
             Dyn::Undefined => Ok(Dyn::str(arena, "undefined")),
+            Dyn::Null => Ok(Dyn::str(arena, "object")),
             Dyn::Vec(_) => Ok(Dyn::str(arena, "object")),
             Dyn::Object(_) => Ok(Dyn::str(arena, "object")),
+            Dyn::Closure(_) => Ok(Dyn::str(arena, "function")),
             Dyn::Ref(_) => panic!("typeof_ applied to a ref"),
         }
     }
 
     pub fn neg(&self) -> DynResult<'a> {
         match self {
-            Dyn::Int(n) => Ok(Dyn::int(-n)),
+            Dyn::Int(n) => Ok(Dyn::float(-f64::from(*n))),
             Dyn::Float(n) => Ok(Dyn::float(-n)),
-            _ => type_error(&format!("({:?}).neg()", &self)),
+            _ => type_error(format!("({:?}).neg()", self)),
+        }
+    }
+
+    pub fn not(&self) -> DynResult<'a> {
+        return Ok(Dyn::Bool(!self.is_truthy()));
+    }
+
+    pub fn bit_not(&self) -> DynResult<'a> {
+        return Ok(Dyn::float(f64::from(!self.to_int32())));
+    }
+
+    /// Converts to a boolean, like `Boolean(x)` in JavaScript.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Dyn::Bool(b) => *b,
+            Dyn::Int(n) => *n != 0,
+            Dyn::Float(x) => *x != 0.0 && !x.is_nan(),
+            Dyn::Str(s) => !s.is_empty(),
+            Dyn::Undefined | Dyn::Null => false,
+            Dyn::Ref(_) | Dyn::Vec(_) | Dyn::Object(_) | Dyn::Closure(_) => true,
+        }
+    }
+
+    /// Converts to a number, like `Number(x)` in JavaScript. Objects and
+    /// arrays are converted through their string representation.
+    pub fn to_number(&self) -> f64 {
+        match self {
+            Dyn::Int(n) => f64::from(*n),
+            Dyn::Float(x) => *x,
+            Dyn::Bool(true) => 1.0,
+            Dyn::Bool(false) | Dyn::Null => 0.0,
+            Dyn::Str(s) => string_to_number(s),
+            Dyn::Undefined | Dyn::Ref(_) | Dyn::Closure(_) => f64::NAN,
+            Dyn::Vec(_) | Dyn::Object(_) => string_to_number(&self.to_string()),
+        }
+    }
+
+    /// Converts to a 32-bit integer, like the operands of the bitwise
+    /// operators in JavaScript.
+    pub fn to_int32(&self) -> i32 {
+        if let Dyn::Int(n) = self {
+            return *n;
+        }
+        let x = self.to_number();
+        if !x.is_finite() {
+            return 0;
         }
+        return x.trunc().rem_euclid(4294967296.0) as u32 as i32;
     }
 
     pub fn to_json(&self) -> Option<serde_json::Value> {
@@ -468,7 +909,9 @@ impl<'a> Dyn<'a> {
             Dyn::Bool(b) => Some(Value::Bool(*b)),
             Dyn::Int(n) => serde_json::Number::from_f64(*n as f64).map(Value::Number),
             Dyn::Float(x) => serde_json::Number::from_f64(*x).map(Value::Number),
-            Dyn::Undefined => None,
+            // Like JSON.stringify, which omits functions.
+            Dyn::Undefined | Dyn::Closure(_) => None,
+            Dyn::Null => Some(Value::Null),
             Dyn::Vec(vec_cell) => Some(vec_cell.to_json()),
             Dyn::Object(o) => Some(o.to_json()),
             Dyn::Ref(_) => panic!("typeof_ applied to a ref"),
@@ -484,7 +927,7 @@ impl<'a> Dyn<'a> {
                 Dyn::Float(n.as_f64().unwrap())
             }
             Value::Bool(b) => Dyn::Bool(b),
-            Value::Null => unimplemented!(),
+            Value::Null => Dyn::Null,
             Value::Array(vec) => {
                 let mut v = Vec::new_in(arena);
                 for item in vec.into_iter() {
@@ -496,16 +939,11 @@ impl<'a> Dyn<'a> {
                 })
             }
             Value::Object(key_value_pairs) => {
-                let mut obj = Vec::new_in(arena);
+                let obj = DynObject::new(arena);
                 for (k, v) in key_value_pairs.into_iter() {
-                    obj.push((
-                        String::from_str_in(&k, arena).into_bump_str(),
-                        Self::from_json(arena, v),
-                    ))
+                    obj.set(arena.alloc_str(&k), Self::from_json(arena, v));
                 }
-                Dyn::Object(DynObject {
-                    fields: arena.alloc(RefCell::new(obj)),
-                })
+                Dyn::Object(obj)
             }
         }
     }
@@ -519,25 +957,21 @@ impl<'a> Dyn<'a> {
     pub fn to_string(&self) -> std::string::String {
         match self {
             Dyn::Int(n) => n.to_string(),
+            Dyn::Float(n) if *n == f64::INFINITY => "Infinity".to_string(),
+            Dyn::Float(n) if *n == f64::NEG_INFINITY => "-Infinity".to_string(),
+            // Both zeros print as 0, so `-0` is the property key "0".
+            Dyn::Float(n) if *n == 0.0 => "0".to_string(),
             Dyn::Float(n) => n.to_string(),
             Dyn::Bool(b) => b.to_string(),
             Dyn::Str(s) => s.to_string(),
             Dyn::Undefined => "undefined".to_string(),
+            Dyn::Null => "null".to_string(),
             Dyn::Ref(cell) => format!("&{}", cell.get().to_string()),
             Dyn::Vec(v) => v.to_string(),
             Dyn::Object(_) => "[object Object]".to_string(),
-        }
-    }
-}
-
-impl<'a> From<Dyn<'a>> for bool {
-    fn from(value: Dyn<'a>) -> Self {
-        match value {
-            Dyn::Bool(b) => b,
-            Dyn::Int(0) => false,
-            Dyn::Int(_) => true,
-            Dyn::Undefined => false,
-            _ => panic!("Could not convert to bool."),
+            // JavaScript prints the source of the function, which we do not
+            // have.
+            Dyn::Closure(_) => "function () { [native code] }".to_string(),
         }
     }
 }
@@ -566,3 +1000,275 @@ impl<'a> TryFrom<Dyn<'a>> for DynObject<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: DynResult) -> f64 {
+        match value {
+            Ok(Dyn::Float(x)) => return x,
+            other => panic!("expected a Float, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bitwise_operators_produce_floats() {
+        let seven = Dyn::float(7.0).bit_or(Dyn::float(0.0)).unwrap();
+        let two = Dyn::float(2.0).bit_or(Dyn::float(0.0)).unwrap();
+        assert_eq!(num(seven.div(two)), 3.5);
+        assert_eq!(num(Dyn::float(1.0).shl(Dyn::float(31.0))), -2147483648.0);
+        assert_eq!(num(Dyn::float(-1.0).ushr(Dyn::float(0.0))), 4294967295.0);
+    }
+
+    #[test]
+    fn guards_fail_like_unknown_branches() {
+        let arena = Bump::new();
+        assert_eq!(guard_f64(Dyn::int(2)).unwrap(), 2.0);
+        assert_eq!(guard_f64(Dyn::float(0.5)).unwrap(), 0.5);
+        assert!(guard_bool(Dyn::bool(true)).unwrap());
+        // A local that the trace compiler unboxed may be assigned a string
+        // at runtime, which the container must run.
+        let s = Dyn::str(&arena, "1");
+        assert!(matches!(guard_f64(s), Err(Error::Unknown)));
+        assert!(matches!(guard_bool(s), Err(Error::Unknown)));
+        assert!(matches!(guard_f64(Dyn::bool(true)), Err(Error::Unknown)));
+        assert!(matches!(guard_bool(Dyn::float(1.0)), Err(Error::Unknown)));
+    }
+
+    #[test]
+    fn conditions_use_truthiness() {
+        let arena = Bump::new();
+        assert!(Dyn::bool(true).is_truthy());
+        assert!(!Dyn::Undefined.is_truthy());
+        assert!(!Dyn::null().is_truthy());
+        assert!(Dyn::int(1).is_truthy() && Dyn::float(1.0).is_truthy());
+        assert!(!Dyn::int(0).is_truthy() && !Dyn::float(0.0).is_truthy());
+        assert!(!Dyn::float(-0.0).is_truthy() && !Dyn::float(f64::NAN).is_truthy());
+        assert!(Dyn::str(&arena, "yes").is_truthy());
+        assert!(!Dyn::str(&arena, "").is_truthy());
+        assert!(Dyn::object(&arena).is_truthy());
+    }
+
+    #[test]
+    fn int_division_is_not_integer_division() {
+        assert_eq!(num(Dyn::int(7).div(Dyn::int(2))), 3.5);
+        assert_eq!(num(Dyn::int(1).div(Dyn::int(0))), f64::INFINITY);
+        assert_eq!(num(Dyn::int(-1).div(Dyn::int(0))), f64::NEG_INFINITY);
+        assert!(num(Dyn::int(0).div(Dyn::int(0))).is_nan());
+        assert!(num(Dyn::int(5).rem(Dyn::int(0))).is_nan());
+    }
+
+    #[test]
+    fn int_arithmetic_does_not_overflow() {
+        let min = Dyn::int(i32::MIN);
+        let max = Dyn::int(i32::MAX);
+        assert_eq!(num(min.rem(Dyn::int(-1))), 0.0);
+        let arena = Bump::new();
+        assert_eq!(num(max.add(&arena, Dyn::int(1))), 2147483648.0);
+        assert_eq!(num(min.sub(Dyn::int(1))), -2147483649.0);
+        assert_eq!(num(max.mul(Dyn::int(2))), 4294967294.0);
+        assert_eq!(num(min.neg()), 2147483648.0);
+    }
+
+    #[test]
+    fn string_hash_does_not_overflow() {
+        // The usual string hash, `h = (h << 5) - h + c`, without `| 0`.
+        let arena = Bump::new();
+        let mut h = Dyn::int(0);
+        for c in "a string that is long enough to overflow".chars() {
+            let shifted = h.shl(Dyn::int(5)).unwrap();
+            let diff = shifted.sub(h).unwrap();
+            h = diff.add(&arena, Dyn::int(c as i32)).unwrap();
+        }
+        // The result of Node.
+        assert_eq!(num(Ok(h)), 3293642542.0);
+    }
+
+    #[test]
+    fn objects_find_fields_by_runtime_keys() {
+        let arena = Bump::new();
+        let counts = Dyn::object(&arena);
+        for word in ["a", "b", "a"].iter() {
+            // Every key is a new string, as when it comes from the request.
+            let key = Dyn::str(&arena, word);
+            let n = match counts.index(&arena, key).unwrap() {
+                Dyn::Undefined => 1.0,
+                n => n.to_number() + 1.0,
+            };
+            counts.set(&arena, key, Dyn::float(n)).unwrap();
+        }
+        // Another copy of the key.
+        let key = Dyn::str(&arena, "a");
+        assert_eq!(num(counts.index(&arena, key)), 2.0);
+        assert_eq!(num(counts.get("b")), 1.0);
+        assert!(matches!(counts.get("c"), Ok(Dyn::Undefined)));
+    }
+
+    #[test]
+    fn overwriting_a_field_keeps_its_position() {
+        let arena = Bump::new();
+        let obj = Dyn::object_with(&arena, vec![("a", Dyn::float(1.0)), ("b", Dyn::float(2.0))]);
+        obj.set(&arena, Dyn::str(&arena, "a"), Dyn::float(3.0))
+            .unwrap();
+        obj.set_field("c", Dyn::float(4.0)).unwrap();
+        assert_eq!(
+            obj.to_json().unwrap().to_string(),
+            r#"{"a":3.0,"b":2.0,"c":4.0}"#
+        );
+    }
+
+    #[test]
+    fn integer_keys_come_first_in_ascending_order() {
+        let arena = Bump::new();
+        let obj = DynObject::new(&arena);
+        for key in ["b", "10", "a", "2", "01", "-1"].iter() {
+            obj.set(key, Dyn::bool(true));
+        }
+        let keys: std::vec::Vec<&str> = obj.entries().into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, ["2", "10", "b", "a", "01", "-1"]);
+        assert_eq!(
+            obj.to_json().to_string(),
+            r#"{"2":true,"10":true,"b":true,"a":true,"01":true,"-1":true}"#
+        );
+    }
+
+    #[test]
+    fn fields_are_looked_up_in_the_prototype() {
+        let arena = Bump::new();
+        let proto = Dyn::object_with(&arena, vec![("x", Dyn::float(1.0)), ("y", Dyn::float(2.0))]);
+        let obj = Dyn::object_with(&arena, vec![("__proto__", proto), ("y", Dyn::float(3.0))]);
+        assert_eq!(num(obj.get("x")), 1.0);
+        assert_eq!(num(obj.get("y")), 3.0);
+        assert!(matches!(obj.get("z"), Ok(Dyn::Undefined)));
+        assert!(matches!(
+            Dyn::str(&arena, "x").in_(obj),
+            Ok(Dyn::Bool(true))
+        ));
+        assert!(matches!(
+            Dyn::str(&arena, "z").in_(obj),
+            Ok(Dyn::Bool(false))
+        ));
+    }
+
+    #[test]
+    fn null_is_an_object_that_equals_only_undefined() {
+        let arena = Bump::new();
+        let null = Dyn::null();
+        assert_eq!(null.typeof_(&arena).unwrap().to_string(), "object");
+        assert!(matches!(null.loose_eq(Dyn::Undefined), Ok(Dyn::Bool(true))));
+        assert!(matches!(null.loose_eq(Dyn::null()), Ok(Dyn::Bool(true))));
+        assert!(matches!(
+            null.strict_eq(Dyn::Undefined),
+            Ok(Dyn::Bool(false))
+        ));
+        assert!(matches!(
+            null.strict_neq(Dyn::Undefined),
+            Ok(Dyn::Bool(true))
+        ));
+        assert!(matches!(null.strict_eq(Dyn::null()), Ok(Dyn::Bool(true))));
+        assert!(matches!(
+            null.loose_eq(Dyn::float(0.0)),
+            Ok(Dyn::Bool(false))
+        ));
+        assert!(!null.is_truthy());
+        assert!(matches!(null.not(), Ok(Dyn::Bool(true))));
+        assert_eq!(null.to_string(), "null");
+    }
+
+    #[test]
+    fn null_survives_json() {
+        let arena = Bump::new();
+        let json: serde_json::Value = serde_json::from_str(r#"{"a":null,"b":[null]}"#).unwrap();
+        let value = Dyn::from_json(&arena, json.clone());
+        assert!(matches!(value.get("a"), Ok(Dyn::Null)));
+        assert_eq!(value.to_json(), Some(json));
+        assert_eq!(Dyn::null().to_json(), Some(serde_json::Value::Null));
+    }
+
+    fn is_type_error<T: std::fmt::Debug>(result: Result<T, Error>) -> bool {
+        return matches!(result, Err(Error::TypeError(_)));
+    }
+
+    #[test]
+    fn computed_properties_of_objects_and_arrays() {
+        let arena = Bump::new();
+        let obj = Dyn::object(&arena);
+        obj.set(&arena, Dyn::float(1.0), Dyn::str(&arena, "one"))
+            .unwrap();
+        obj.set(&arena, Dyn::float(-0.0), Dyn::str(&arena, "zero"))
+            .unwrap();
+        let one = obj.index(&arena, Dyn::str(&arena, "1")).unwrap();
+        assert_eq!(one.to_string(), "one");
+        let zero = obj.index(&arena, Dyn::str(&arena, "0")).unwrap();
+        assert_eq!(zero.to_string(), "zero");
+        assert_eq!(
+            obj.to_json().unwrap().to_string(),
+            r#"{"0":"zero","1":"one"}"#
+        );
+
+        let arr = Dyn::vec_with(&arena, vec![Dyn::float(1.0)]);
+        arr.set(&arena, Dyn::float(-0.0), Dyn::float(2.0)).unwrap();
+        arr.set(&arena, Dyn::str(&arena, "2"), Dyn::float(3.0))
+            .unwrap();
+        assert_eq!(arr.to_json().unwrap().to_string(), "[2.0,null,3.0]");
+        assert_eq!(num(arr.index(&arena, Dyn::float(-0.0))), 2.0);
+        assert!(matches!(
+            arr.index(&arena, Dyn::float(1.0)),
+            Ok(Dyn::Undefined)
+        ));
+        assert!(matches!(
+            arr.index(&arena, Dyn::float(1.5)),
+            Ok(Dyn::Undefined)
+        ));
+        // An array has no other fields, so the container runs the assignment.
+        assert!(matches!(
+            arr.set(&arena, Dyn::float(1.5), Dyn::float(4.0)),
+            Err(Error::Unknown)
+        ));
+        assert!(is_type_error(Dyn::Undefined.index(&arena, Dyn::float(0.0))));
+        assert!(is_type_error(Dyn::null().set(
+            &arena,
+            Dyn::float(0.0),
+            Dyn::Undefined
+        )));
+    }
+
+    #[test]
+    fn arrays_grow_by_at_most_max_holes() {
+        let arena = Bump::new();
+        let arr = Dyn::vec(&arena);
+        let last = (MAX_HOLES - 1) as f64;
+        arr.set(&arena, Dyn::float(last), Dyn::bool(true)).unwrap();
+        assert_eq!(num(arr.get("length")), MAX_HOLES as f64);
+        assert!(matches!(
+            arr.index(&arena, Dyn::float(0.0)),
+            Ok(Dyn::Undefined)
+        ));
+        let too_far = (2 * MAX_HOLES) as f64;
+        assert!(is_type_error(arr.set(
+            &arena,
+            Dyn::float(too_far),
+            Dyn::bool(true)
+        )));
+        assert_eq!(num(arr.get("length")), MAX_HOLES as f64);
+    }
+
+    #[test]
+    fn assigning_the_length_of_an_array() {
+        let arena = Bump::new();
+        let arr = Dyn::vec_with(
+            &arena,
+            vec![Dyn::float(1.0), Dyn::float(2.0), Dyn::float(3.0)],
+        );
+        let length = Dyn::str(&arena, "length");
+        arr.set(&arena, length, Dyn::float(1.0)).unwrap();
+        assert_eq!(arr.to_json().unwrap().to_string(), "[1.0]");
+        arr.set(&arena, length, Dyn::str(&arena, "3")).unwrap();
+        assert_eq!(arr.to_json().unwrap().to_string(), "[1.0,null,null]");
+        for invalid in [-1.0, 1.5, f64::NAN, (3 + MAX_HOLES + 1) as f64].iter() {
+            assert!(is_type_error(arr.set(&arena, length, Dyn::float(*invalid))));
+        }
+        assert_eq!(num(arr.get("length")), 3.0);
+    }
+}
@@ -26,7 +26,7 @@ pub async fn get_function(
             } else {
                 "tracing"
             };
-            ok_response_with_containerless_mode(func.contents, mode.to_string())
+            ok_response_with_containerless_mode(func.contents, mode.to_string(), func.gas_limit)
         }
     }
 }
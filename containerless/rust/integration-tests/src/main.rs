@@ -24,16 +24,6 @@ fn child_pid(child: &Child) -> Pid {
 
 #[tokio::main]
 async fn main() {
-    Command::new("git")
-        .args(&[
-            "checkout",
-            "../dispatcher-agent/src/decontainerized_functions/mod.rs",
-        ])
-        .spawn()
-        .expect("spawning git")
-        .await
-        .expect("running git");
-
     let tests_handle = run_tests().await;
     let tests_pid = child_pid(&tests_handle);
 
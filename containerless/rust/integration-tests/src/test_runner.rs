@@ -1,32 +1,50 @@
 use crate::error::Error;
 
-use shared::containerless::cli;
+use shared::containerless::{cli, dispatcher};
 
 use serde_json::Value as JsonValue;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
-pub async fn poll_for_new_dispatcher(old_version: usize) -> Result<(), Error> {
+/// Waits until the dispatcher runs the shared library that the controller
+/// compiled for `name`.
+pub async fn poll_for_decontainerized(name: &str) -> Result<(), Error> {
     let interval = Duration::from_secs(1);
-    let timeout = Duration::from_secs(60);
+    let timeout = Duration::from_secs(120);
     let end_time = Instant::now() + timeout;
 
-    tokio::time::delay_for(interval).await;
     loop {
-        let output = cli::containerless_get_dispatcher_version().await?;
-        let status = output.status;
-        let stdout = String::from_utf8(output.stdout)?;
-        let _stderr = String::from_utf8(output.stderr)?;
-        let next_version = stdout.trim().parse::<usize>()?;
-        if status.success() && next_version == (old_version + 1) {
-            break;
-        }
         tokio::time::delay_for(interval).await;
+        if let Ok(mode) = dispatcher::mode(name).await {
+            if mode == "Decontainerized" {
+                return Ok(());
+            }
+        }
         if Instant::now() >= end_time {
             return Err(Error::Timeout);
         }
     }
-    tokio::time::delay_for(interval).await;
+}
 
+/// Copies the trace that the controller compiled for `name` to
+/// `target/integration-traces`. The controller runs on this machine, in the
+/// same workspace. With `CONTAINERLESS_BLESS` set, the trace also replaces the
+/// trace of `name` in the golden tests of the trace compiler
+/// (`controller-agent/tests/golden.rs`).
+fn save_trace(name: &str) -> Result<(), Error> {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let trace = workspace.join(format!(
+        "decontainerized-functions/function_{}/trace.json",
+        name
+    ));
+    let out_dir = workspace.join("target/integration-traces");
+    std::fs::create_dir_all(&out_dir)?;
+    std::fs::copy(&trace, out_dir.join(format!("{}.json", name)))?;
+    if std::env::var("CONTAINERLESS_BLESS").is_ok() {
+        let golden_dir = workspace.join("controller-agent/tests/golden/integration");
+        std::fs::create_dir_all(&golden_dir)?;
+        std::fs::copy(&trace, golden_dir.join(format!("{}.json", name)))?;
+    }
     return Ok(());
 }
 
@@ -44,18 +62,6 @@ pub async fn run_test_async(
     );
     let mut results = Vec::new();
 
-    // Retrieve the old dispatcher version
-    let dispatcher_version = cli::containerless_get_dispatcher_version().await;
-    if let Err(err) = dispatcher_version {
-        eprintln!("fail in the test runner: {:?}", err);
-        return results;
-    }
-    let old_dispatcher_version = String::from_utf8(dispatcher_version.unwrap().stdout)
-        .unwrap()
-        .trim()
-        .parse::<usize>()
-        .unwrap();
-
     // Create the function in Containerless
     cli::containerless_create(name, js_code, false)
         .await
@@ -69,15 +75,16 @@ pub async fn run_test_async(
         results.push(result);
     }
 
-    // Have containerless compile the function and redeploy the dispatcher
+    // Have containerless compile the function, which the dispatcher loads
     cli::containerless_compile(name)
         .await
         .expect("compiling to Rust");
 
-    // Poll for the new dispatcher
-    poll_for_new_dispatcher(old_dispatcher_version)
+    // Poll for the compiled function
+    poll_for_decontainerized(name)
         .await
-        .expect("waiting for new dispatcher");
+        .expect("waiting for compiled function");
+    save_trace(name).expect("saving trace");
 
     // Send requests to the decontainerized version
     for req in rs_requests.into_iter() {
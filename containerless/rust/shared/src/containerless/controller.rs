@@ -4,7 +4,7 @@ use serde_json::json;
 use std::fs;
 
 pub async fn create_function(
-    name: &str, filename: &str, containers_only: bool,
+    name: &str, filename: &str, containers_only: bool, gas_limit: Option<u64>,
 ) -> Result<String, Error> {
     Ok(reqwest::Client::new()
         .post(&format!(
@@ -14,6 +14,7 @@ pub async fn create_function(
         .json(&json!({
             "exclusive": true,
             "containers_only": containers_only,
+            "gas_limit": gas_limit,
             "contents": format!("{}", fs::read_to_string(filename)?.trim())
         }))
         .send()
@@ -62,6 +63,15 @@ pub async fn get_function(name: &str) -> Result<String, Error> {
     .await?)
 }
 
+pub async fn trace_stats(name: &str) -> Result<String, Error> {
+    Ok(
+        reqwest::get(&format!("http://localhost/controller/trace_stats/{}", name))
+            .await?
+            .text()
+            .await?,
+    )
+}
+
 pub async fn list_functions() -> Result<String, Error> {
     Ok(reqwest::get("http://localhost/controller/list_functions")
         .await?
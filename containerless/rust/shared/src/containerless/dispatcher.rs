@@ -36,6 +36,48 @@ pub async fn compile(name: &str) -> Result<String, Error> {
         .await?)
 }
 
+/// The mode of a function in the dispatcher, e.g., `Tracing` or
+/// `Decontainerized`.
+pub async fn mode(name: &str) -> Result<String, Error> {
+    let resp = reqwest::get(&format!("http://{}:8080/mode/{}", dispatcher_ip(), name)).await?;
+    response_into_result(resp.status().as_u16(), resp.text().await?).map_err(Error::Dispatcher)
+}
+
+/// Sends the lifted trace `ir` of a function to the dispatcher, which
+/// interprets it until the shared library for the trace is loaded.
+pub async fn interpret(name: &str, ir: String) -> Result<String, Error> {
+    let resp = reqwest::Client::new()
+        .post(&format!(
+            "http://{}:8080/interpret/{}",
+            dispatcher_ip(),
+            name
+        ))
+        .body(ir)
+        .send()
+        .await?;
+    response_into_result(resp.status().as_u16(), resp.text().await?).map_err(Error::Dispatcher)
+}
+
+/// Has the dispatcher load the shared library that the controller compiled
+/// for a function.
+pub async fn load(name: &str) -> Result<String, Error> {
+    let resp = reqwest::Client::new()
+        .post(&format!("http://{}:8080/load/{}", dispatcher_ip(), name))
+        .send()
+        .await?;
+    response_into_result(resp.status().as_u16(), resp.text().await?).map_err(Error::Dispatcher)
+}
+
+/// Has the dispatcher drop the shared library or lifted trace of a function,
+/// and run the function in containers again.
+pub async fn unload(name: &str) -> Result<String, Error> {
+    let resp = reqwest::Client::new()
+        .post(&format!("http://{}:8080/unload/{}", dispatcher_ip(), name))
+        .send()
+        .await?;
+    response_into_result(resp.status().as_u16(), resp.text().await?).map_err(Error::Dispatcher)
+}
+
 pub async fn shutdown_function_instances(name: &str) -> Result<String, Error> {
     let resp = reqwest::get(&format!(
         "http://{}:8080/shutdown_function_instances/{}",
@@ -6,4 +6,8 @@ pub struct Function {
     /// When set, all other functions get deleted
     pub exclusive: bool,
     pub containers_only: bool,
+    /// The gas limit of each decontainerized invocation. When not set, the
+    /// dispatcher uses its default limit.
+    #[serde(default)]
+    pub gas_limit: Option<u64>,
 }
@@ -7,3 +7,4 @@ pub mod function;
 pub mod logger;
 pub mod net;
 pub mod response;
+pub mod trace;
@@ -11,7 +11,7 @@ pub fn ok_response(
 }
 
 pub fn ok_response_with_containerless_mode(
-    body: String, mode: String,
+    body: String, mode: String, gas_limit: Option<u64>,
 ) -> Result<
     std::result::Result<http::response::Response<std::string::String>, http::Error>,
     warp::Rejection,
@@ -21,6 +21,10 @@ pub fn ok_response_with_containerless_mode(
         "X-Containerless-Mode",
         HeaderValue::from_str(&mode).unwrap(),
     );
+    if let Some(gas_limit) = gas_limit {
+        resp.headers_mut()
+            .insert("X-Containerless-Gas-Limit", HeaderValue::from(gas_limit));
+    }
     Ok(Ok(resp))
 }
 
@@ -0,0 +1,102 @@
+//! The parts of a trace that the trace compiler and the dispatcher must agree
+//! on: the IR (`types`), its text syntax (`syntax`), and the methods, the
+//! builtins, and the events that a trace may call. The validator of the trace
+//! compiler accepts exactly these, the generated code and the interpreter of
+//! the dispatcher implement them, and the tests of the trace compiler
+//! generate traces that call them.
+pub mod syntax;
+pub mod types;
+
+/// The methods of `Dyn` that a `MethodCall` may call, with the least and the
+/// greatest number of arguments that they take. The `Transformer` passes
+/// `undefined` for the optional arguments that a call omits, and splits a
+/// call to a method that takes any number of arguments (`VARIADIC`) into
+/// calls that take one, unless the method has a rest parameter (`REST`).
+/// `isArray` is `Array.isArray`, which the trace calls as a method of its
+/// argument. The interpreter of the dispatcher implements these in
+/// `method_call`.
+pub const METHODS: &[(&str, usize, usize)] = &[
+    ("push", 1, 1),
+    ("pop", 0, 0),
+    ("shift", 0, 0),
+    ("charAt", 0, 1),
+    ("charCodeAt", 0, 1),
+    ("concat", 1, VARIADIC),
+    ("endsWith", 1, 2),
+    ("includes", 1, 2),
+    ("indexOf", 1, 2),
+    ("padStart", 1, 2),
+    ("replace", 2, 2),
+    ("slice", 0, 2),
+    ("split", 0, 2),
+    ("startsWith", 1, 2),
+    ("substring", 1, 2),
+    ("toLowerCase", 0, 0),
+    ("toUpperCase", 0, 0),
+    ("trim", 0, 0),
+    ("filter", 1, 1),
+    ("find", 1, 1),
+    ("forEach", 1, 1),
+    ("map", 1, 1),
+    ("reduce", 1, 2),
+    ("some", 1, 1),
+    ("sort", 0, 1),
+    ("fill", 1, 3),
+    ("isArray", 0, 0),
+    ("join", 0, 1),
+    ("reverse", 0, 0),
+    ("splice", 0, VARIADIC),
+    ("unshift", 0, VARIADIC),
+];
+
+pub const VARIADIC: usize = usize::MAX;
+
+/// The variadic methods whose arguments after the given number form a rest
+/// parameter. The `Transformer` passes the arguments before it as usual, and
+/// the rest in an array.
+pub const REST: &[(&str, usize)] = &[("splice", 2), ("unshift", 0)];
+
+/// The methods that call a function argument. The generated code passes
+/// them a way to run the body of a closure.
+pub const HIGHER_ORDER: &[&str] = &["filter", "find", "forEach", "map", "reduce", "some", "sort"];
+
+/// Applies the macro `$table` to the global functions of JavaScript that a
+/// `PrimApp` may call in an expression, each with the name of the function
+/// in `trace_runtime::builtins` that implements it, as in
+/// `"Math.abs" => math_abs,`. The dispatcher builds its table of functions
+/// with this macro, so that it has a function for every builtin.
+#[macro_export]
+macro_rules! with_builtins {
+    ($table:ident) => {
+        $table! {
+            "Math.abs" => math_abs,
+            "Math.ceil" => math_ceil,
+            "Math.floor" => math_floor,
+            "Math.max" => math_max,
+            "Math.min" => math_min,
+            "Math.pow" => math_pow,
+            "Math.random" => math_random,
+            "Math.round" => math_round,
+            "Math.sqrt" => math_sqrt,
+            "JSON.parse" => json_parse,
+            "JSON.stringify" => json_stringify,
+            "Number" => number,
+            "parseFloat" => parse_float,
+            "parseInt" => parse_int,
+            "String" => string,
+        }
+    };
+}
+
+macro_rules! builtin_names {
+    ($($name:literal => $function:ident,)*) => {
+        /// The builtins, with the names of the functions that implement them.
+        pub const BUILTINS: &[(&str, &str)] = &[$(($name, stringify!($function))),*];
+    };
+}
+
+with_builtins!(builtin_names);
+
+/// The events that a `PrimApp` statement may call besides the builtins,
+/// with the least and the greatest number of arguments that they take.
+pub const EVENTS: &[(&str, usize, usize)] = &[("console.log", 0, VARIADIC), ("send", 1, 1)];
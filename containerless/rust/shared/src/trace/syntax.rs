@@ -0,0 +1,1007 @@
+//! A textual syntax for traces, which is easier to read and write by hand
+//! than the JSON that the tracing runtime produces. An `Exp` prints in this
+//! syntax (`{}` prints it on one line and `{:#}` indents blocks), and `parse`
+//! reads it back, so that `parse(&format!("{}", exp))` is `exp`.
+//!
+//! The syntax is a list of s-expressions:
+//!
+//! ```text
+//! ; Comments start with a semicolon.
+//! (block [
+//!   (let x 10)
+//!   (while (> x 0) [
+//!     (set x (- x 1))
+//!   ])
+//!   (primApp send (+ "done: " x))
+//! ])
+//! ```
+//!
+//! - The atoms `unknown`, `undefined`, `null`, `unit`, `true` and `false`
+//!   are the literals of the same name. An atom that is an integer is an
+//!   `Integer`, and an atom that is any other number (including `NaN` and
+//!   `inf`) is a `Number`. A string literal, which uses the escapes of JSON, is a
+//!   `Stringg`. Any other atom is an `Identifier`. `(id "name")` is an
+//!   `Identifier` whose name is not an atom, such as `(id "true")`.
+//!
+//! - `(op e1 e2)` is a `BinOp` and `(op e)` is an `Op1`, where `op` is the
+//!   JavaScript operator (e.g., `===` or `typeof`).
+//!
+//! - Every other node is `(kind ...)`, where `kind` is the `kind` of the node
+//!   in JSON and the fields follow in the order in which `Exp` declares them.
+//!   A list of statements, like the arguments of a `callback` or a
+//!   `lambda`, is in square brackets. A name (of a field, variable, label,
+//!   event or method) is an atom or a string literal. The properties of an
+//!   `object` or a `clos` are `(name e)` pairs. A `try` is
+//!   `(try [body] (catch name [handler]) (finally [finalizer]))`, where the
+//!   `catch` and `finally` clauses are optional, as is the name.
+//!
+//! The types of `unboxed`, `boxed` and `guard` are `f64` and `bool`.
+use indexmap::IndexMap;
+use std::fmt;
+
+use super::types::{constructors::*, Arg, Exp, Exp::*, LVal, Loc, Op1, Op2, Typ};
+
+const OP1: &[(&str, Op1)] = &[
+    ("void", Op1::Void),
+    ("typeof", Op1::Typeof),
+    ("-", Op1::Negative),
+    ("!", Op1::Not),
+    ("~", Op1::BitNot),
+];
+
+const OP2: &[(&str, Op2)] = &[
+    ("+", Op2::Add),
+    ("-", Op2::Sub),
+    ("*", Op2::Mul),
+    ("/", Op2::Div),
+    ("%", Op2::Mod),
+    ("===", Op2::StrictEq),
+    ("!==", Op2::StrictNotEq),
+    ("==", Op2::Eq),
+    ("!=", Op2::NotEq),
+    (">", Op2::GT),
+    ("<", Op2::LT),
+    (">=", Op2::GTE),
+    ("<=", Op2::LTE),
+    ("&&", Op2::And),
+    ("||", Op2::Or),
+    ("&", Op2::BitAnd),
+    ("|", Op2::BitOr),
+    ("^", Op2::BitXor),
+    ("<<", Op2::LeftShift),
+    (">>", Op2::RightShift),
+    (">>>", Op2::UnsignedRightShift),
+    ("??", Op2::Nullish),
+    ("in", Op2::In),
+];
+
+const TYPES: &[(&str, Typ)] = &[("f64", Typ::F64), ("bool", Typ::Bool)];
+
+/// The atoms that are literals, or that `parse` reads as numbers. An
+/// identifier with one of these names prints as `(id "name")`.
+const RESERVED: &[&str] = &[
+    "unknown",
+    "undefined",
+    "null",
+    "unit",
+    "true",
+    "false",
+    "NaN",
+    "inf",
+];
+
+fn name_of<T: PartialEq>(table: &[(&'static str, T)], value: &T) -> &'static str {
+    return table.iter().find(|(_, v)| v == value).unwrap().0;
+}
+
+/// Whether `name` can print as an atom.
+fn is_atom(name: &str) -> bool {
+    return !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '$');
+}
+
+/// Whether `parse` reads the atom `text` as a number.
+fn is_number(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    return digits.starts_with(|ch: char| ch.is_ascii_digit())
+        || digits == "NaN"
+        || digits == "inf";
+}
+
+fn string_literal(value: &str) -> String {
+    return serde_json::to_string(value).expect("serializing a string");
+}
+
+struct Printer {
+    out: String,
+    /// Whether to print each statement of a block on its own line.
+    pretty: bool,
+    indent: usize,
+}
+
+impl Printer {
+    fn new(pretty: bool) -> Printer {
+        return Printer {
+            out: String::new(),
+            pretty,
+            indent: 0,
+        };
+    }
+
+    fn text(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    fn name(&mut self, name: &str) {
+        if is_atom(name) {
+            self.text(name);
+        } else {
+            self.text(&string_literal(name));
+        }
+    }
+
+    fn identifier(&mut self, name: &str) {
+        if is_atom(name) && !is_number(name) && !RESERVED.contains(&name) {
+            self.text(name);
+        } else {
+            self.text("(id ");
+            self.text(&string_literal(name));
+            self.text(")");
+        }
+    }
+
+    /// Prints `(head e ...)`.
+    fn form(&mut self, head: &str, exps: &[&Exp]) {
+        self.text("(");
+        self.text(head);
+        for e in exps.iter() {
+            self.text(" ");
+            self.exp(e);
+        }
+        self.text(")");
+    }
+
+    fn exps(&mut self, exps: &[Exp]) {
+        for e in exps.iter() {
+            self.text(" ");
+            self.exp(e);
+        }
+    }
+
+    fn block(&mut self, body: &[Exp]) {
+        if body.is_empty() || !self.pretty {
+            self.text("[");
+            for (i, e) in body.iter().enumerate() {
+                if i > 0 {
+                    self.text(" ");
+                }
+                self.exp(e);
+            }
+            self.text("]");
+            return;
+        }
+        self.text("[");
+        self.indent += 1;
+        for e in body.iter() {
+            self.text("\n");
+            self.text(&"  ".repeat(self.indent));
+            self.exp(e);
+        }
+        self.indent -= 1;
+        self.text("\n");
+        self.text(&"  ".repeat(self.indent));
+        self.text("]");
+    }
+
+    fn properties(&mut self, head: &str, properties: &IndexMap<String, Exp>) {
+        self.text("(");
+        self.text(head);
+        for (name, e) in properties.iter() {
+            self.text(" (");
+            self.name(name);
+            self.text(" ");
+            self.exp(e);
+            self.text(")");
+        }
+        self.text(")");
+    }
+
+    fn field(&mut self, head: &str, exp: &Exp, field: &str) {
+        self.text("(");
+        self.text(head);
+        self.text(" ");
+        self.exp(exp);
+        self.text(" ");
+        self.name(field);
+        self.text(")");
+    }
+
+    /// The arguments of a callback or a lambda.
+    fn args(&mut self, args: &[Arg]) {
+        self.text("[");
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.text(" ");
+            }
+            self.name(&arg.name);
+        }
+        self.text("]");
+    }
+
+    fn lval(&mut self, lval: &LVal) {
+        match lval {
+            LVal::Identifier { name } => self.identifier(name),
+            LVal::From { exp, field } => self.field("from", exp, field),
+            LVal::Index { exp, index } => self.form("index", &[exp, index]),
+        }
+    }
+
+    fn exp(&mut self, exp: &Exp) {
+        match exp {
+            Unknown {} => self.text("unknown"),
+            Integer { value } => self.text(&value.to_string()),
+            // The Debug format of an f64 always has a decimal point or an
+            // exponent, and reads back as the same f64.
+            Number { value } => self.text(&format!("{:?}", value)),
+            Bool { value } => self.text(&value.to_string()),
+            Identifier { name } => self.identifier(name),
+            From { exp, field } => self.field("from", exp, field),
+            Get { exp, field } => self.field("get", exp, field),
+            Stringg { value } => self.text(&string_literal(value)),
+            Undefined {} => self.text("undefined"),
+            Null {} => self.text("null"),
+            Unit {} => self.text("unit"),
+            BinOp { op, e1, e2 } => self.form(name_of(OP2, op), &[e1, e2]),
+            Exp::Op1 { op, e } => self.form(name_of(OP1, op), &[e]),
+            If {
+                cond,
+                true_part,
+                false_part,
+            } => {
+                self.text("(if ");
+                self.exp(cond);
+                self.text(" ");
+                self.block(true_part);
+                self.text(" ");
+                self.block(false_part);
+                self.text(")");
+            }
+            While { cond, body } => {
+                self.text("(while ");
+                self.exp(cond);
+                self.text(" ");
+                self.block(body);
+                self.text(")");
+            }
+            Let { name, named } => {
+                self.text("(let ");
+                self.name(name);
+                self.text(" ");
+                self.exp(named);
+                self.text(")");
+            }
+            Set { name, named } => {
+                self.text("(set ");
+                self.lval(name);
+                self.text(" ");
+                self.exp(named);
+                self.text(")");
+            }
+            Block { body } => {
+                self.text("(block ");
+                self.block(body);
+                self.text(")");
+            }
+            Callback {
+                event,
+                event_arg,
+                callback_args,
+                callback_clos,
+                body,
+            } => {
+                self.text("(callback ");
+                self.name(event);
+                self.text(" ");
+                self.exp(event_arg);
+                self.text(" ");
+                self.args(callback_args);
+                self.text(" ");
+                self.exp(callback_clos);
+                self.text(" ");
+                self.block(body);
+                self.text(")");
+            }
+            Loopback {
+                event,
+                event_arg,
+                callback_clos,
+                id,
+            } => {
+                self.text("(loopback ");
+                self.name(event);
+                self.text(" ");
+                self.exp(event_arg);
+                self.text(" ");
+                self.exp(callback_clos);
+                self.text(&format!(" {})", id));
+            }
+            Lambda { args, clos, body } => {
+                self.text("(lambda ");
+                self.args(args);
+                self.text(" ");
+                self.exp(clos);
+                self.text(" ");
+                self.block(body);
+                self.text(")");
+            }
+            Closure { clos, id } => {
+                self.text("(closure ");
+                self.exp(clos);
+                self.text(&format!(" {})", id));
+            }
+            Call { e, call_args } => {
+                self.text("(call ");
+                self.exp(e);
+                self.exps(call_args);
+                self.text(")");
+            }
+            Label { name, body } => {
+                self.text("(label ");
+                self.name(name);
+                self.text(" ");
+                self.block(body);
+                self.text(")");
+            }
+            Break { name, value } => {
+                self.text("(break ");
+                self.name(name);
+                self.text(" ");
+                self.exp(value);
+                self.text(")");
+            }
+            Object { properties } => self.properties("object", properties),
+            Clos { tenv } => self.properties("clos", tenv),
+            Array { exps } => {
+                self.text("(array");
+                self.exps(exps);
+                self.text(")");
+            }
+            Index { e1, e2 } => self.form("index", &[e1, e2]),
+            Ref { e } => self.form("ref", &[e]),
+            Deref { e } => self.form("deref", &[e]),
+            SetRef { e1, e2 } => self.form("setRef", &[e1, e2]),
+            PrimApp { event, event_args } => {
+                self.text("(primApp ");
+                self.name(event);
+                self.exps(event_args);
+                self.text(")");
+            }
+            MethodCall {
+                e,
+                method,
+                method_call_args,
+            } => {
+                self.text("(methodCall ");
+                self.exp(e);
+                self.text(" ");
+                self.name(method);
+                self.exps(method_call_args);
+                self.text(")");
+            }
+            Throw { e } => self.form("throw", &[e]),
+            Try {
+                body,
+                catch_name,
+                handler,
+                finalizer,
+            } => {
+                self.text("(try ");
+                self.block(body);
+                if let Some(handler) = handler {
+                    self.text(" (catch ");
+                    if let Some(name) = catch_name {
+                        self.name(name);
+                        self.text(" ");
+                    }
+                    self.block(handler);
+                    self.text(")");
+                }
+                if !finalizer.is_empty() {
+                    self.text(" (finally ");
+                    self.block(finalizer);
+                    self.text(")");
+                }
+                self.text(")");
+            }
+            Located { loc, e } => {
+                self.text("(located ");
+                self.name(&loc.file);
+                self.text(&format!(" {} {} ", loc.line, loc.column));
+                self.exp(e);
+                self.text(")");
+            }
+            Unboxed { typ, e } => self.form(&format!("unboxed {}", name_of(TYPES, typ)), &[e]),
+            Boxed { typ, e } => self.form(&format!("boxed {}", name_of(TYPES, typ)), &[e]),
+            Guard { typ, e } => self.form(&format!("guard {}", name_of(TYPES, typ)), &[e]),
+        }
+    }
+}
+
+impl fmt::Display for Exp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut printer = Printer::new(f.alternate());
+        printer.exp(self);
+        return f.write_str(&printer.out);
+    }
+}
+
+/// An error in a trace in the text syntax.
+#[derive(Debug)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Token {
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Atom(String),
+    Str(String),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::OpenBracket => write!(f, "["),
+            Token::CloseBracket => write!(f, "]"),
+            Token::Atom(text) => write!(f, "{}", text),
+            Token::Str(value) => write!(f, "{}", string_literal(value)),
+            Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Splits `text` into tokens, each with its line and column.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize, usize)>, SyntaxError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let (mut line, mut column) = (1, 1);
+    while let Some((start, ch)) = chars.next() {
+        let (token_line, token_column) = (line, column);
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+            continue;
+        }
+        column += 1;
+        let token = match ch {
+            _ if ch.is_whitespace() => continue,
+            ';' => {
+                while let Some((_, ch)) = chars.peek() {
+                    if *ch == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+                continue;
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            '"' => {
+                let mut escaped = false;
+                let mut end = None;
+                for (i, ch) in chars.by_ref() {
+                    column += 1;
+                    if ch == '\n' {
+                        break;
+                    } else if escaped {
+                        escaped = false;
+                    } else if ch == '\\' {
+                        escaped = true;
+                    } else if ch == '"' {
+                        end = Some(i);
+                        break;
+                    }
+                }
+                let error = |message: &str| SyntaxError {
+                    line: token_line,
+                    column: token_column,
+                    message: message.to_string(),
+                };
+                let end = end.ok_or_else(|| error("unterminated string"))?;
+                let value = serde_json::from_str::<String>(&text[start..=end])
+                    .map_err(|_| error("invalid escape in string"))?;
+                Token::Str(value)
+            }
+            _ => {
+                let mut end = start + ch.len_utf8();
+                while let Some((i, ch)) = chars.peek() {
+                    if ch.is_whitespace() || "()[]\";".contains(*ch) {
+                        break;
+                    }
+                    end = i + ch.len_utf8();
+                    column += 1;
+                    chars.next();
+                }
+                Token::Atom(text[start..end].to_string())
+            }
+        };
+        tokens.push((token, token_line, token_column));
+    }
+    tokens.push((Token::End, line, column));
+    return Ok(tokens);
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Result<Parser, SyntaxError> {
+        return Ok(Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        });
+    }
+
+    fn peek(&self) -> &Token {
+        return &self.tokens[self.pos].0;
+    }
+
+    /// An error at the next token.
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SyntaxError> {
+        let (_, line, column) = &self.tokens[self.pos];
+        return Err(SyntaxError {
+            line: *line,
+            column: *column,
+            message: message.into(),
+        });
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, SyntaxError> {
+        return self.error(format!("expected {}, found {}", expected, self.peek()));
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        return token;
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), SyntaxError> {
+        if *self.peek() != token {
+            return self.unexpected(&token.to_string());
+        }
+        self.next();
+        return Ok(());
+    }
+
+    fn name(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Token::Atom(_) | Token::Str(_) => (),
+            _ => return self.unexpected("a name"),
+        }
+        match self.next() {
+            Token::Atom(name) | Token::Str(name) => return Ok(name),
+            _ => unreachable!(),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, SyntaxError> {
+        if let Token::Atom(text) = self.peek() {
+            if let Ok(n) = text.parse::<T>() {
+                self.next();
+                return Ok(n);
+            }
+        }
+        return self.unexpected("a number");
+    }
+
+    fn typ(&mut self) -> Result<Typ, SyntaxError> {
+        if let Token::Atom(text) = self.peek() {
+            if let Some((_, typ)) = TYPES.iter().find(|(name, _)| name == text) {
+                self.next();
+                return Ok(*typ);
+            }
+        }
+        return self.unexpected("f64 or bool");
+    }
+
+    /// Parses `[e ...]`.
+    fn block(&mut self) -> Result<Vec<Exp>, SyntaxError> {
+        self.expect(Token::OpenBracket)?;
+        let mut body = Vec::new();
+        while *self.peek() != Token::CloseBracket {
+            body.push(self.exp()?);
+        }
+        self.next();
+        return Ok(body);
+    }
+
+    /// Parses expressions up to the closing parenthesis of a form.
+    fn exps(&mut self) -> Result<Vec<Exp>, SyntaxError> {
+        let mut exps = Vec::new();
+        while *self.peek() != Token::Close {
+            exps.push(self.exp()?);
+        }
+        return Ok(exps);
+    }
+
+    /// Parses `(name e) ...` up to the closing parenthesis of a form.
+    fn properties(&mut self) -> Result<IndexMap<String, Exp>, SyntaxError> {
+        let mut properties = IndexMap::new();
+        while *self.peek() == Token::Open {
+            self.next();
+            let name = self.name()?;
+            let e = self.exp()?;
+            self.expect(Token::Close)?;
+            properties.insert(name, e);
+        }
+        return Ok(properties);
+    }
+
+    /// The arguments of a callback or a lambda, in square brackets.
+    fn args(&mut self) -> Result<Vec<Arg>, SyntaxError> {
+        self.expect(Token::OpenBracket)?;
+        let mut args = Vec::new();
+        while *self.peek() != Token::CloseBracket {
+            args.push(Arg { name: self.name()? });
+        }
+        self.next();
+        return Ok(args);
+    }
+
+    fn lval(&mut self) -> Result<LVal, SyntaxError> {
+        let (_, line, column) = &self.tokens[self.pos];
+        let (line, column) = (*line, *column);
+        match self.exp()? {
+            Identifier { name } => return Ok(LVal::Identifier { name }),
+            From { exp, field } => return Ok(LVal::From { exp, field }),
+            Index { e1, e2 } => return Ok(LVal::Index { exp: e1, index: e2 }),
+            _ => {
+                return Err(SyntaxError {
+                    line,
+                    column,
+                    message: "expected an identifier, from or index".to_string(),
+                })
+            }
+        }
+    }
+
+    fn atom(&mut self, text: String) -> Result<Exp, SyntaxError> {
+        match text.as_str() {
+            "unknown" => return Ok(unknown()),
+            "undefined" => return Ok(undefined()),
+            "null" => return Ok(null()),
+            "unit" => return Ok(Unit {}),
+            "true" => return Ok(bool_(true)),
+            "false" => return Ok(bool_(false)),
+            _ => (),
+        }
+        if is_number(&text) {
+            if let Ok(value) = text.parse::<i32>() {
+                return Ok(integer(value));
+            }
+            if let Ok(value) = text.parse::<f64>() {
+                return Ok(number(value));
+            }
+            self.pos -= 1;
+            return self.error(format!("invalid number {}", text));
+        }
+        if !is_atom(&text) {
+            self.pos -= 1;
+            return self.error(format!("unexpected {}", text));
+        }
+        return Ok(Identifier { name: text });
+    }
+
+    fn exp(&mut self) -> Result<Exp, SyntaxError> {
+        match self.peek() {
+            Token::Open => (),
+            Token::Atom(_) | Token::Str(_) => match self.next() {
+                Token::Atom(text) => return self.atom(text),
+                Token::Str(value) => return Ok(Stringg { value }),
+                _ => unreachable!(),
+            },
+            _ => return self.unexpected("an expression"),
+        }
+        self.next();
+        let head = match self.peek() {
+            Token::Atom(_) => self.name()?,
+            _ => return self.unexpected("the kind of a node"),
+        };
+        let exp = self.form(&head)?;
+        self.expect(Token::Close)?;
+        return Ok(exp);
+    }
+
+    /// Parses the rest of the form `(head ...)`, except for the closing
+    /// parenthesis.
+    fn form(&mut self, head: &str) -> Result<Exp, SyntaxError> {
+        let unary = OP1.iter().find(|(name, _)| *name == head);
+        let binary = OP2.iter().find(|(name, _)| *name == head);
+        if unary.is_some() || binary.is_some() {
+            let e1 = self.exp()?;
+            return match (unary, binary) {
+                // Only '-' is both, and it is unary if there is no e2.
+                (Some((_, op)), _) if binary.is_none() || *self.peek() == Token::Close => {
+                    Ok(op1(op, e1))
+                }
+                (_, Some((_, op))) => Ok(binop(op, e1, self.exp()?)),
+                _ => unreachable!(),
+            };
+        }
+        match head {
+            "id" => match self.peek() {
+                Token::Str(_) => return Ok(Identifier { name: self.name()? }),
+                _ => return self.unexpected("a string"),
+            },
+            "from" => {
+                let e = self.exp()?;
+                return Ok(from(e, &self.name()?));
+            }
+            "get" => {
+                let e = self.exp()?;
+                return Ok(get(e, &self.name()?));
+            }
+            "if" => {
+                let cond = self.exp()?;
+                let true_part = self.block()?;
+                return Ok(if_(cond, true_part, self.block()?));
+            }
+            "while" => {
+                let cond = self.exp()?;
+                return Ok(while_(cond, self.block()?));
+            }
+            "let" => {
+                let name = self.name()?;
+                return Ok(let_(&name, self.exp()?));
+            }
+            "set" => {
+                let lval = self.lval()?;
+                return Ok(set(lval, self.exp()?));
+            }
+            "block" => return Ok(block(self.block()?)),
+            "callback" => {
+                let event = self.name()?;
+                let event_arg = self.exp()?;
+                let callback_args = self.args()?;
+                let callback_clos = self.exp()?;
+                let body = self.block()?;
+                return Ok(callback(
+                    &event,
+                    event_arg,
+                    callback_args,
+                    callback_clos,
+                    body,
+                ));
+            }
+            "loopback" => {
+                let event = self.name()?;
+                let event_arg = self.exp()?;
+                let callback_clos = self.exp()?;
+                return Ok(loopback(&event, event_arg, callback_clos, self.number()?));
+            }
+            "lambda" => {
+                let args = self.args()?;
+                let clos = self.exp()?;
+                return Ok(lambda(args, clos, self.block()?));
+            }
+            "closure" => {
+                let clos = self.exp()?;
+                return Ok(closure(clos, self.number()?));
+            }
+            "call" => {
+                let e = self.exp()?;
+                return Ok(call(e, self.exps()?));
+            }
+            "label" => {
+                let name = self.name()?;
+                return Ok(label(&name, self.block()?));
+            }
+            "break" => {
+                let name = self.name()?;
+                return Ok(break_(&name, self.exp()?));
+            }
+            "object" => return Ok(obj(self.properties()?)),
+            "clos" => {
+                return Ok(Clos {
+                    tenv: self.properties()?,
+                })
+            }
+            "array" => return Ok(array(self.exps()?)),
+            "index" => {
+                let e1 = self.exp()?;
+                return Ok(index_(e1, self.exp()?));
+            }
+            "ref" => return Ok(ref_(self.exp()?)),
+            "deref" => return Ok(deref(self.exp()?)),
+            "setRef" => {
+                let e1 = self.exp()?;
+                return Ok(setref(e1, self.exp()?));
+            }
+            "primApp" => {
+                let event = self.name()?;
+                return Ok(prim_app(&event, self.exps()?));
+            }
+            "methodCall" => {
+                let e = self.exp()?;
+                let method = self.name()?;
+                return Ok(method_call(e, &method, self.exps()?));
+            }
+            "throw" => return Ok(throw_(self.exp()?)),
+            "try" => {
+                let body = self.block()?;
+                let (mut catch_name, mut handler, mut finalizer) = (None, None, Vec::new());
+                while *self.peek() == Token::Open {
+                    self.next();
+                    match self.name()?.as_str() {
+                        "catch" if handler.is_none() => {
+                            if *self.peek() != Token::OpenBracket {
+                                catch_name = Some(self.name()?);
+                            }
+                            handler = Some(self.block()?);
+                        }
+                        "finally" => finalizer = self.block()?,
+                        _ => {
+                            self.pos -= 1;
+                            return self.unexpected("catch or finally");
+                        }
+                    }
+                    self.expect(Token::Close)?;
+                }
+                return Ok(Try {
+                    body,
+                    catch_name,
+                    handler,
+                    finalizer,
+                });
+            }
+            "located" => {
+                let file = self.name()?;
+                let line = self.number()?;
+                let column = self.number()?;
+                let loc = Loc { file, line, column };
+                return Ok(located(&loc, self.exp()?));
+            }
+            "unboxed" => {
+                let typ = self.typ()?;
+                return Ok(unboxed(typ, self.exp()?));
+            }
+            "boxed" => {
+                let typ = self.typ()?;
+                return Ok(boxed(typ, self.exp()?));
+            }
+            "guard" => {
+                let typ = self.typ()?;
+                return Ok(guard(typ, self.exp()?));
+            }
+            _ => {
+                self.pos -= 1;
+                return self.error(format!("unknown kind of node {}", head));
+            }
+        }
+    }
+}
+
+/// Parses a trace in the text syntax.
+pub fn parse(text: &str) -> Result<Exp, SyntaxError> {
+    let mut parser = Parser::new(text)?;
+    let exp = parser.exp()?;
+    if *parser.peek() != Token::End {
+        return parser.unexpected("end of input");
+    }
+    return Ok(exp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `text` and prints it on one line.
+    fn reprint(text: &str) -> String {
+        return parse(text).expect("parsing the trace").to_string();
+    }
+
+    /// The position and the message of the error in `text`.
+    fn error(text: &str) -> (usize, usize, String) {
+        let err = parse(text).expect_err("parsing should fail");
+        return (err.line, err.column, err.message);
+    }
+
+    #[test]
+    fn atoms() {
+        assert_eq!(parse("1").unwrap(), integer(1));
+        assert_eq!(parse("1.5").unwrap(), number(1.5));
+        assert_eq!(parse("-inf").unwrap(), number(f64::NEG_INFINITY));
+        assert_eq!(parse("x").unwrap(), id("x"));
+        assert_eq!(parse("(id \"true\")").unwrap(), id("true"));
+        assert_eq!(parse("true").unwrap(), bool_(true));
+        assert_eq!(
+            parse("\"a\\nb\"").unwrap(),
+            Stringg {
+                value: "a\nb".to_string()
+            }
+        );
+        assert!(matches!(parse("NaN").unwrap(), Number { value } if value.is_nan()));
+    }
+
+    #[test]
+    fn minus_is_unary_without_a_second_operand() {
+        assert_eq!(parse("(- x)").unwrap(), op1(&Op1::Negative, id("x")));
+        assert_eq!(
+            parse("(- x 1)").unwrap(),
+            binop(&Op2::Sub, id("x"), integer(1))
+        );
+    }
+
+    #[test]
+    fn forms_print_as_they_parse() {
+        let forms = [
+            "(block [(let x 1) (set x (+ x 1)) (set (from x f) 2) (set (index x 0) 3)])",
+            "(if (< x 10) [(break l x)] [])",
+            "(while true [(primApp send (object (a 1) (\"b c\" x)))])",
+            "(callback listen 0.0 [clos req] (clos (x x)) [(primApp \"console.log\")])",
+            "(lambda [clos y] (clos) [(label \"'ret\" [(break \"'ret\" y)])])",
+            "(methodCall (array 1 2.5 \"s\") slice 0 1)",
+            "(try [(throw 1)] (catch [(primApp send 2)]) (finally [(primApp send 3)]))",
+            "(try [(throw 1)] (catch e [(primApp send e)]))",
+            "(located \"f.js\" 3 4 (get req body))",
+            "(boxed f64 (unboxed f64 (guard f64 x)))",
+            "(id \"null\")",
+        ];
+        for form in forms.iter() {
+            assert_eq!(reprint(form), *form);
+        }
+    }
+
+    #[test]
+    fn comments_and_whitespace() {
+        let text = "; a comment\n(block [\n  (let x 1) ; another\n])\n";
+        assert_eq!(reprint(text), "(block [(let x 1)])");
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        assert_eq!(
+            error("(block [\n  (frob x)])"),
+            (2, 4, "unknown kind of node frob".to_string())
+        );
+        assert_eq!(
+            error("(let x \"abc)"),
+            (1, 8, "unterminated string".to_string())
+        );
+        assert_eq!(
+            error("(set 1 2)"),
+            (1, 6, "expected an identifier, from or index".to_string())
+        );
+        assert_eq!(
+            error("x y"),
+            (1, 3, "expected end of input, found y".to_string())
+        );
+        assert_eq!(
+            error("(try [] (other []))"),
+            (1, 10, "expected catch or finally, found other".to_string())
+        );
+    }
+}
@@ -18,9 +18,8 @@
 //
 // - I could not find documentation for the #[serde(rename = "+")] directive.
 //   Instead, I guessed that it existed and it worked!
-use std::collections::HashMap;
-
-use serde::Deserialize;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 #[derive(PartialEq, Debug, Clone)]
@@ -58,6 +57,15 @@ impl<'de> ::serde::Deserialize<'de> for Arg {
     }
 }
 
+impl ::serde::Serialize for Arg {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        serializer.serialize_str(&self.name)
+    }
+}
+
 /// Deserializes a string or a sequence of strings into a vector of the target type.
 pub fn deserialize_args<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
 where
@@ -89,7 +97,7 @@ where
     deserializer.deserialize_any(Visitor(::std::marker::PhantomData))
 }
 
-#[derive(PartialEq, Debug, Deserialize, Clone)]
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 pub enum Op1 {
     #[serde(rename = "void")]
     Void,
@@ -97,9 +105,13 @@ pub enum Op1 {
     Typeof,
     #[serde(rename = "-")]
     Negative,
+    #[serde(rename = "!")]
+    Not,
+    #[serde(rename = "~")]
+    BitNot,
 }
 
-#[derive(PartialEq, Debug, Deserialize, Clone)]
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 pub enum Op2 {
     #[serde(rename = "+")]
     Add,
@@ -109,10 +121,16 @@ pub enum Op2 {
     Mul,
     #[serde(rename = "/")]
     Div,
+    #[serde(rename = "%")]
+    Mod,
     #[serde(rename = "===")]
     StrictEq,
     #[serde(rename = "!==")]
     StrictNotEq,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    NotEq,
     #[serde(rename = ">")]
     GT,
     #[serde(rename = "<")]
@@ -125,9 +143,46 @@ pub enum Op2 {
     And,
     #[serde(rename = "||")]
     Or,
+    #[serde(rename = "&")]
+    BitAnd,
+    #[serde(rename = "|")]
+    BitOr,
+    #[serde(rename = "^")]
+    BitXor,
+    #[serde(rename = "<<")]
+    LeftShift,
+    #[serde(rename = ">>")]
+    RightShift,
+    #[serde(rename = ">>>")]
+    UnsignedRightShift,
+    #[serde(rename = "??")]
+    Nullish,
+    #[serde(rename = "in")]
+    In,
+}
+
+/// A position in the JavaScript source of a function.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct Loc {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for Loc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
 }
 
-#[derive(PartialEq, Debug, Deserialize, Clone)]
+/// The Rust types that `TypeInference` can unbox a value to.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Typ {
+    F64,
+    Bool,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum Exp {
     Unknown {},
@@ -157,6 +212,7 @@ pub enum Exp {
         value: String,
     },
     Undefined {},
+    Null {},
     Unit {},
     #[serde(rename = "binop")]
     BinOp {
@@ -201,13 +257,38 @@ pub enum Exp {
         callback_clos: Box<Exp>,
         body: Vec<Exp>,
     },
-    #[serde(skip)]
+    /// A callback that `LiftCallbacks` has lifted to the top level. This node,
+    /// `Ref`, `Deref`, and `SetRef` do not occur in traces, but they are
+    /// serialized because the dispatcher interprets the lifted IR.
     Loopback {
         event: String,
+        #[serde(rename = "eventArg")]
         event_arg: Box<Exp>,
+        #[serde(rename = "clos")]
         callback_clos: Box<Exp>,
         id: i32,
     },
+    /// A function value, e.g., the function that a trace passes to `map`.
+    /// Like a callback, its body only reaches the variables of its enclosing
+    /// function through the closure, which is its first argument.
+    Lambda {
+        #[serde(deserialize_with = "deserialize_args")]
+        args: Vec<Arg>,
+        clos: Box<Exp>,
+        body: Vec<Exp>,
+    },
+    /// A lambda that `LiftCallbacks` has lifted to the top level, like a
+    /// `Loopback`. It evaluates to a `Dyn::Closure`.
+    Closure {
+        clos: Box<Exp>,
+        id: i32,
+    },
+    /// Calls the function `e`, which runs the body of a lambda synchronously.
+    Call {
+        e: Box<Exp>,
+        #[serde(rename = "callArgs")]
+        call_args: Vec<Exp>,
+    },
     Label {
         name: String,
         body: Vec<Exp>,
@@ -217,10 +298,10 @@ pub enum Exp {
         value: Box<Exp>,
     },
     Object {
-        properties: HashMap<String, Exp>,
+        properties: IndexMap<String, Exp>,
     },
     Clos {
-        tenv: HashMap<String, Exp>,
+        tenv: IndexMap<String, Exp>,
     },
     Array {
         exps: Vec<Exp>,
@@ -231,15 +312,12 @@ pub enum Exp {
         #[serde(rename = "index")]
         e2: Box<Exp>,
     },
-    #[serde(skip)]
     Ref {
         e: Box<Exp>,
     },
-    #[serde(skip)]
     Deref {
         e: Box<Exp>,
     },
-    #[serde(skip)]
     SetRef {
         e1: Box<Exp>,
         e2: Box<Exp>,
@@ -255,9 +333,53 @@ pub enum Exp {
         #[serde(rename = "methodCallArgs")]
         method_call_args: Vec<Exp>,
     },
+    Throw {
+        e: Box<Exp>,
+    },
+    /// `try { body } catch (catch_name) { handler } finally { finalizer }`.
+    /// A `try` without a `catch` clause has no `handler`, and one without a
+    /// `finally` clause has an empty `finalizer`.
+    Try {
+        body: Vec<Exp>,
+        #[serde(rename = "catchName", default)]
+        catch_name: Option<String>,
+        #[serde(default)]
+        handler: Option<Vec<Exp>>,
+        #[serde(default)]
+        finalizer: Vec<Exp>,
+    },
+    /// The expression `e`, which occurs at `loc` in the JavaScript source. A
+    /// runtime error in `e` reports the location of the innermost `Located`
+    /// that encloses it. Besides expressions, only `Set`, `Throw`, and
+    /// `PrimApp` statements may be located.
+    Located {
+        loc: Loc,
+        e: Box<Exp>,
+    },
+    /// An unboxed value of type `typ`. The body `e` is built from literals,
+    /// identifiers of type `typ`, operators, and `Guard`s.
+    #[serde(skip)]
+    Unboxed {
+        typ: Typ,
+        e: Box<Exp>,
+    },
+    /// Boxes the unboxed body `e` into a `Dyn`.
+    #[serde(skip)]
+    Boxed {
+        typ: Typ,
+        e: Box<Exp>,
+    },
+    /// Unboxes the `Dyn` expression `e`, or fails with `Error::Unknown` if
+    /// it does not have type `typ`. Only occurs in the body of an `Unboxed`
+    /// or a `Boxed`.
+    #[serde(skip)]
+    Guard {
+        typ: Typ,
+        e: Box<Exp>,
+    },
 }
 
-#[derive(PartialEq, Debug, Deserialize, Clone)]
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum LVal {
     Identifier { name: String },
@@ -265,89 +387,6 @@ pub enum LVal {
     Index { exp: Box<Exp>, index: Box<Exp> },
 }
 
-// https://stackoverflow.com/a/42661287
-fn vec_to_string(v: &[Exp]) -> String {
-    return v
-        .iter()
-        .fold(String::new(), |acc, num| acc + &num.to_string() + ",\n");
-}
-
-impl fmt::Display for Exp {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Exp::Unknown {} => write!(f, "Unknown"),
-            Exp::Integer { value } => write!(f, "Integer({})", value),
-            Exp::Number { value } => write!(f, "Number({})", value),
-            Exp::Bool { value } => write!(f, "Bool({})", value),
-            Exp::Identifier { name } => write!(f, "Identifier({})", name),
-            Exp::From { exp, field } => write!(f, "From({},{})", exp, field),
-            Exp::Get { exp, field } => write!(f, "Get({},{})", exp, field),
-            Exp::Stringg { value } => write!(f, "Stringg({})", value),
-            Exp::Undefined {} => write!(f, "Undefined"),
-            Exp::Unit {} => write!(f, "()"),
-            Exp::BinOp { op, e1, e2 } => write!(f, "BinOp({:?}, {}, {})", op, e1, e2),
-            Exp::Op1 { op, e } => write!(f, "Op1({:?}, {})", op, e),
-            Exp::If {
-                cond,
-                true_part,
-                false_part,
-            } => write!(
-                f,
-                "If({} \n[{}]\n else \n[{}]\n)",
-                cond,
-                vec_to_string(true_part),
-                vec_to_string(false_part)
-            ),
-            Exp::While { cond, body } => write!(f, "While({}, {:?})", cond, body),
-            Exp::Let { name, named } => write!(f, "Let({}, {})", name, named),
-            Exp::Set { name, named } => write!(f, "Set({:?}, {})", name, named),
-            Exp::Block { body } => write!(f, "Block(\n[{}])", vec_to_string(body)),
-            Exp::Callback {
-                event,
-                event_arg,
-                callback_args,
-                callback_clos,
-                body,
-            } => write!(
-                f,
-                "Callback({}, {}, {:?}, {}, {})",
-                event,
-                event_arg,
-                callback_args,
-                callback_clos,
-                vec_to_string(body)
-            ),
-            Exp::Loopback {
-                event,
-                event_arg,
-                callback_clos,
-                id,
-            } => write!(
-                f,
-                "Loopback({}, {}, {}, {})",
-                event, event_arg, callback_clos, id
-            ),
-            Exp::Label { name, body } => write!(f, "Label({}, {})", name, vec_to_string(body)),
-            Exp::Break { name, value } => write!(f, "Break({}, {})", name, value),
-            Exp::Object { properties } => write!(f, "Obj({:?}", properties),
-            Exp::Clos { tenv } => write!(f, "clos({:?}", tenv),
-            Exp::Array { exps } => write!(f, "Array({})", vec_to_string(exps)),
-            Exp::Index { e1, e2 } => write!(f, "Index({}, {})", e1, e2),
-            Exp::Ref { e } => write!(f, "Ref({})", e),
-            Exp::Deref { e } => write!(f, "Deref({})", e),
-            Exp::SetRef { e1, e2 } => write!(f, "SetRef({}, {})", e1, e2),
-            Exp::PrimApp { event, event_args } => {
-                write!(f, "PrimApp({}, {})", event, vec_to_string(event_args))
-            }
-            Exp::MethodCall {
-                e,
-                method,
-                method_call_args,
-            } => write!(f, "MethodCall({}, {}, {:?})", e, method, method_call_args),
-        }
-    }
-}
-
 pub mod constructors {
 
     // NOTE(arjun): I wish we could use this macro to avoid writing all these
@@ -359,8 +398,8 @@ pub mod constructors {
     // constructors to take care of allocating strings and boxes.
 
     use super::Exp::*;
-    use super::{Arg, Exp, LVal, Op1, Op2};
-    use std::collections::HashMap;
+    use super::{Arg, Exp, LVal, Loc, Op1, Op2, Typ};
+    use indexmap::IndexMap;
 
     pub fn unknown() -> Exp {
         Unknown {}
@@ -408,6 +447,10 @@ pub mod constructors {
         Undefined {}
     }
 
+    pub fn null() -> Exp {
+        Null {}
+    }
+
     pub fn binop(op: &Op2, e1: Exp, e2: Exp) -> Exp {
         BinOp {
             op: op.clone(),
@@ -500,6 +543,28 @@ pub mod constructors {
         }
     }
 
+    pub fn lambda(args: Vec<Arg>, clos: Exp, body: Vec<Exp>) -> Exp {
+        Lambda {
+            args,
+            clos: Box::new(clos),
+            body,
+        }
+    }
+
+    pub fn closure(clos: Exp, id: i32) -> Exp {
+        Closure {
+            clos: Box::new(clos),
+            id,
+        }
+    }
+
+    pub fn call(e: Exp, call_args: Vec<Exp>) -> Exp {
+        Call {
+            e: Box::new(e),
+            call_args,
+        }
+    }
+
     pub fn label(name: &str, body: Vec<Exp>) -> Exp {
         Label {
             name: name.to_string(),
@@ -540,18 +605,67 @@ pub mod constructors {
         }
     }
 
-    pub fn obj(tenv: HashMap<String, Exp>) -> Exp {
+    pub fn throw_(e: Exp) -> Exp {
+        Throw { e: Box::new(e) }
+    }
+
+    pub fn try_(
+        body: Vec<Exp>, catch_name: Option<&str>, handler: Option<Vec<Exp>>, finalizer: Vec<Exp>,
+    ) -> Exp {
+        Try {
+            body,
+            catch_name: catch_name.map(|name| name.to_string()),
+            handler,
+            finalizer,
+        }
+    }
+
+    pub fn obj(tenv: IndexMap<String, Exp>) -> Exp {
         Object { properties: tenv }
     }
 
     pub fn obj_2(tenv: &[(&str, Exp)]) -> Exp {
-        let mut hm = HashMap::new();
+        let mut hm = IndexMap::new();
         for (k, v) in tenv.iter() {
             hm.insert(k.to_string(), v.to_owned());
         }
         Object { properties: hm }
     }
 
+    pub fn located(loc: &Loc, e: Exp) -> Exp {
+        Located {
+            loc: loc.clone(),
+            e: Box::new(e),
+        }
+    }
+
+    pub fn unboxed(typ: Typ, e: Exp) -> Exp {
+        Unboxed {
+            typ,
+            e: Box::new(e),
+        }
+    }
+
+    pub fn boxed(typ: Typ, e: Exp) -> Exp {
+        Boxed {
+            typ,
+            e: Box::new(e),
+        }
+    }
+
+    pub fn guard(typ: Typ, e: Exp) -> Exp {
+        Guard {
+            typ,
+            e: Box::new(e),
+        }
+    }
+
+    pub fn lval_id(name: &str) -> LVal {
+        LVal::Identifier {
+            name: name.to_string(),
+        }
+    }
+
     pub fn lval_index(exp: Exp, index: Exp) -> LVal {
         LVal::Index {
             exp: Box::new(exp),
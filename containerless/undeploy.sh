@@ -9,7 +9,6 @@ envsubst < containerless.yaml | microk8s.kubectl delete --ignore-not-found -f -
 
 export RUST_SRC_PATH=$(dirname $(dirname $(realpath $0)))/rust
 echo "Clearing system state..."
-cp "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/template.txt" "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/mod.rs"
-rm "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/*.json" 2> /dev/null || true
-rm "${RUST_SRC_PATH}/dispatcher-agent/src/decontainerized_functions/*.rs" 2> /dev/null || true
+rm -rf "${RUST_SRC_PATH}"/decontainerized-functions/function_*
+rm -rf "${RUST_SRC_PATH}/target/decontainerized"
 echo "System state cleared.\n"